@@ -0,0 +1,270 @@
+#![allow(unused)]
+
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point, signature::Signature};
+use crate::hashes::sha256::tagged_hash;
+
+use super::schnorr::AdaptorError;
+
+// An ECDSA signature (r, s) with s = s'/t, where t is the adaptor secret.
+// ECDSA can't simply add t to s, so the nonce point is R = kT instead and r is
+// its x coordinate. R^ = kG is what verification sees, and the proof shows
+// that R and R^ share the same k.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcdsaPreSignature {
+    r: S256Point,
+    r_hat: S256Point,
+    s: BigUint,
+    proof: (BigUint, BigUint),
+}
+
+impl EcdsaPreSignature {
+    pub fn get_r(&self) -> S256Point {
+        self.r.clone()
+    }
+
+    pub fn get_r_hat(&self) -> S256Point {
+        self.r_hat.clone()
+    }
+
+    pub fn get_s(&self) -> BigUint {
+        self.s.clone()
+    }
+
+    // compressed R || compressed R^ || s' || the proof's c and response, 162
+    // bytes
+    pub fn serialize(&self) -> [u8; 162] {
+        let mut result = [0u8; 162];
+        result[..33].copy_from_slice(&self.r.sec(true));
+        result[33..66].copy_from_slice(&self.r_hat.sec(true));
+        result[66..98].copy_from_slice(&scalar::to_bytes(&self.s));
+        result[98..130].copy_from_slice(&scalar::to_bytes(&self.proof.0));
+        result[130..].copy_from_slice(&scalar::to_bytes(&self.proof.1));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, AdaptorError> {
+        if bytes.len() != 162 {
+            return Err(AdaptorError::InvalidPreSignature);
+        }
+        let r = S256Point::parse_sec(&bytes[..33]).map_err(|_| AdaptorError::InvalidPreSignature)?;
+        let r_hat = S256Point::parse_sec(&bytes[33..66]).map_err(|_| AdaptorError::InvalidPreSignature)?;
+        let n = S256Point::order();
+        let scalars: Vec<BigUint> = bytes[66..].chunks(32).map(BigUint::from_bytes_be).collect();
+        if scalars[0].is_zero() || scalars.iter().any(|x| *x >= n) {
+            return Err(AdaptorError::InvalidPreSignature);
+        }
+        let [s, c, response]: [BigUint; 3] = scalars.try_into().unwrap();
+        Ok(Self { r, r_hat, s, proof: (c, response) })
+    }
+}
+
+// c = H(R^ || T || R || A1 || A2) for the proof that log_G(R^) = log_T(R)
+fn dleq_challenge(r_hat: &S256Point, adaptor: &S256Point, r: &S256Point, a1: &S256Point, a2: &S256Point) -> BigUint {
+    let preimage: Vec<u8> = [r_hat, adaptor, r, a1, a2].iter().flat_map(|point| point.sec(true)).collect();
+    scalar::from_bytes_mod_order(&tagged_hash("EcdsaAdaptor/dleq", &preimage))
+}
+
+// Pre-signs the message hash z. The nonces are derived from the key, z, T
+// and aux_rand, which should be fresh randomness.
+pub fn pre_sign(sk: &PrivateKey, z: &BigUint, adaptor: &S256Point, aux_rand: &[u8; 32]) -> Result<EcdsaPreSignature, AdaptorError> {
+    let n = S256Point::order();
+    if *adaptor == S256Point::infinity_point() {
+        return Err(AdaptorError::PointAtInfinity);
+    }
+
+    let nonce = |i: u8| {
+        let mut preimage = aux_rand.to_vec();
+        preimage.extend_from_slice(&scalar::to_bytes(&sk.get_secret()));
+        preimage.extend_from_slice(&scalar::to_bytes(&(z % &n)));
+        preimage.extend(adaptor.sec(true));
+        preimage.push(i);
+        scalar::from_bytes_mod_order(&tagged_hash("EcdsaAdaptor/nonce", &preimage))
+    };
+    let (k, k_proof) = (nonce(0), nonce(1));
+    if k.is_zero() || k_proof.is_zero() {
+        panic!("Nonce is zero");
+    }
+
+    let g = S256Point::generator();
+    let r_hat = g.clone() * k.clone();
+    let r = adaptor.clone() * k.clone();
+    let r_x = r.get_x().unwrap().get_num() % &n;
+    if r_x.is_zero() {
+        panic!("r is zero");
+    }
+    let s = scalar::inverse(&k) * (z + &r_x * sk.get_secret()) % &n;
+
+    let (a1, a2) = (g * k_proof.clone(), adaptor.clone() * k_proof.clone());
+    let c = dleq_challenge(&r_hat, adaptor, &r, &a1, &a2);
+    let proof = (c.clone(), (k_proof + c * k) % &n);
+
+    Ok(EcdsaPreSignature { r, r_hat, s, proof })
+}
+
+// Checks the proof that R = kT for the k in R^ = kG, then that R^ is the
+// point an ECDSA verifier would compute: (z/s')G + (r/s')P.
+pub fn pre_verify(pubkey: &S256Point, z: &BigUint, adaptor: &S256Point, pre_sig: &EcdsaPreSignature) -> bool {
+    let n = S256Point::order();
+    let (c, response) = &pre_sig.proof;
+    if pre_sig.s.is_zero() || pre_sig.s >= n || *c >= n || *response >= n {
+        return false;
+    }
+    let infinity = S256Point::infinity_point();
+    if pre_sig.r == infinity || pre_sig.r_hat == infinity || *adaptor == infinity {
+        return false;
+    }
+
+    // a forger can land A1 or A2 on infinity, which has no encoding to hash
+    let g = S256Point::generator();
+    let a1 = g.clone() * response.clone() - pre_sig.r_hat.clone() * c.clone();
+    let a2 = adaptor.clone() * response.clone() - pre_sig.r.clone() * c.clone();
+    if a1 == infinity || a2 == infinity {
+        return false;
+    }
+    if dleq_challenge(&pre_sig.r_hat, adaptor, &pre_sig.r, &a1, &a2) != *c {
+        return false;
+    }
+
+    let r_x = pre_sig.r.get_x().unwrap().get_num() % &n;
+    let s_inv = scalar::inverse(&pre_sig.s);
+    let u = z * &s_inv % &n;
+    let v = r_x * s_inv % &n;
+    S256Point::multi_mul(&[(u, g), (v, pubkey.clone())]) == pre_sig.r_hat
+}
+
+// s = s'/t, normalized to the lower half as Core's standardness rules want
+pub fn adapt(pre_sig: &EcdsaPreSignature, secret: &BigUint) -> Signature {
+    let n = S256Point::order();
+    let s = &pre_sig.s * scalar::inverse(secret) % &n;
+    let s = if s > &n / 2u8 { scalar::negate(&s) } else { s };
+    Signature::new(pre_sig.r.get_x().unwrap().get_num() % n, s)
+}
+
+// t = s'/s up to the sign lost in low-s normalization, checked against the
+// adaptor point
+pub fn extract_secret(pre_sig: &EcdsaPreSignature, sig: &Signature, adaptor: &S256Point) -> Option<BigUint> {
+    let n = S256Point::order();
+    if sig.get_r() != pre_sig.r.get_x().unwrap().get_num() % &n || sig.get_s().is_zero() || sig.get_s() >= n {
+        return None;
+    }
+    let t = &pre_sig.s * scalar::inverse(&sig.get_s()) % &n;
+    let g = S256Point::generator();
+    if g.clone() * t.clone() == *adaptor {
+        return Some(t);
+    }
+    let t = scalar::negate(&t);
+    if g * t.clone() == *adaptor {
+        return Some(t);
+    }
+    None
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::adaptor::schnorr;
+    use crate::hashes::sha256::sha256;
+
+    use super::*;
+
+    fn z(msg: &[u8]) -> BigUint {
+        BigUint::from_bytes_be(&sha256(msg))
+    }
+
+    #[test]
+    fn pre_sign_works() {
+        let key = PrivateKey::new(BigUint::from(0xec0au64));
+        let t = BigUint::from(0x1234u64);
+        let adaptor = S256Point::generator() * t.clone();
+
+        for i in 0..4u8 {
+            let pre_sig = pre_sign(&key, &z(&[i]), &adaptor, &[i; 32]).unwrap();
+            assert!(pre_verify(&key.get_point(), &z(&[i]), &adaptor, &pre_sig));
+            assert!(!pre_verify(&key.get_point(), &z(&[i + 1]), &adaptor, &pre_sig));
+            assert!(!pre_verify(&key.get_point(), &z(&[i]), &S256Point::generator(), &pre_sig));
+
+            let sig = adapt(&pre_sig, &t);
+            assert!(key.get_point().verify(z(&[i]), &sig));
+            assert_eq!(extract_secret(&pre_sig, &sig, &adaptor), Some(t.clone()));
+            assert_eq!(EcdsaPreSignature::parse(&pre_sig.serialize()), Ok(pre_sig));
+        }
+    }
+
+    // R = kT with a k that doesn't match R^ must not pass
+    #[test]
+    fn pre_verify_checks_proof() {
+        let key = PrivateKey::new(BigUint::from(0xec0au64));
+        let adaptor = S256Point::generator() * BigUint::from(5u8);
+        let mut pre_sig = pre_sign(&key, &z(b"msg"), &adaptor, &[0; 32]).unwrap();
+        pre_sig.r = pre_sig.r.clone() + adaptor.clone();
+        assert!(!pre_verify(&key.get_point(), &z(b"msg"), &adaptor, &pre_sig));
+    }
+
+    // R^ = xG with response c x puts A1 at infinity
+    #[test]
+    fn pre_verify_rejects_infinity() {
+        let key = PrivateKey::new(BigUint::from(0xec0au64));
+        let adaptor = S256Point::generator() * BigUint::from(5u8);
+        let pre_sig = pre_sign(&key, &z(b"msg"), &adaptor, &[0; 32]).unwrap();
+
+        let x = BigUint::from(0x1234u64);
+        let c = BigUint::from(7u8);
+        let forged = EcdsaPreSignature {
+            r_hat: S256Point::generator() * x.clone(),
+            proof: (c.clone(), c * x % S256Point::order()),
+            ..pre_sig.clone()
+        };
+        assert!(!pre_verify(&key.get_point(), &z(b"msg"), &adaptor, &forged));
+
+        let at_infinity = EcdsaPreSignature { r_hat: S256Point::infinity_point(), ..pre_sig };
+        assert!(!pre_verify(&key.get_point(), &z(b"msg"), &adaptor, &at_infinity));
+    }
+
+    #[test]
+    fn parse_rejects_invalid() {
+        let key = PrivateKey::new(BigUint::from(0xec0au64));
+        let adaptor = S256Point::generator() * BigUint::from(5u8);
+        let bytes = pre_sign(&key, &z(b"msg"), &adaptor, &[0; 32]).unwrap().serialize();
+
+        assert_eq!(EcdsaPreSignature::parse(&bytes[..161]), Err(AdaptorError::InvalidPreSignature));
+        let mut bad_point = bytes;
+        bad_point[33] = 0x04;
+        assert_eq!(EcdsaPreSignature::parse(&bad_point), Err(AdaptorError::InvalidPreSignature));
+        let mut zero_s = bytes;
+        zero_s[66..98].fill(0);
+        assert_eq!(EcdsaPreSignature::parse(&zero_s), Err(AdaptorError::InvalidPreSignature));
+        let mut big_response = bytes;
+        big_response[130..].copy_from_slice(&scalar::to_bytes(&S256Point::order()));
+        assert_eq!(EcdsaPreSignature::parse(&big_response), Err(AdaptorError::InvalidPreSignature));
+    }
+
+    // A cross-chain swap: Alice's coins are on a chain with Schnorr and Bob's
+    // on one with only ECDSA. Alice picks t, and both pre-sign their payment
+    // to the other against T. Alice claims Bob's coins, which gives Bob t to
+    // claim hers.
+    #[test]
+    fn swap_handshake_works() {
+        let alice = PrivateKey::new(BigUint::from(0xa11ceu64));
+        let bob = PrivateKey::new(BigUint::from(0xb0bu64));
+        let t = BigUint::from(0xdeadbeefu64);
+        let adaptor = S256Point::generator() * t.clone();
+        let (to_bob, to_alice) = (b"alice pays bob on chain a".as_slice(), z(b"bob pays alice on chain b"));
+
+        // both sides hand over and check pre-signatures
+        let pre_alice = schnorr::pre_sign(&alice, to_bob, &adaptor, &[3; 32]).unwrap();
+        let pre_bob = pre_sign(&bob, &to_alice, &adaptor, &[4; 32]).unwrap();
+        assert!(schnorr::pre_verify(&alice.x_only_public_key(), to_bob, &adaptor, &pre_alice));
+        assert!(pre_verify(&bob.get_point(), &to_alice, &adaptor, &pre_bob));
+
+        // Alice completes and publishes Bob's payment to her
+        let sig_bob = adapt(&pre_bob, &t);
+        assert!(bob.get_point().verify(to_alice.clone(), &sig_bob));
+
+        // Bob sees it on chain and learns t
+        let learned = extract_secret(&pre_bob, &sig_bob, &adaptor).unwrap();
+        let sig_alice = schnorr::adapt(&pre_alice, &learned);
+        assert!(alice.x_only_public_key().verify(to_bob, &sig_alice));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod schnorr;
+pub mod ecdsa;
@@ -0,0 +1,200 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+use crate::schnorr::bip340::{challenge, SchnorrSignature, XOnlyPublicKey};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdaptorError {
+    // the adaptor point is the point at infinity
+    PointAtInfinity,
+    // the bytes are not a valid pre-signature
+    InvalidPreSignature,
+}
+
+impl fmt::Display for AdaptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdaptorError::PointAtInfinity => write!(f, "adaptor point is the point at infinity"),
+            AdaptorError::InvalidPreSignature => write!(f, "invalid pre-signature"),
+        }
+    }
+}
+
+impl std::error::Error for AdaptorError {}
+
+// A BIP340 signature with the adaptor secret t missing from s. R = kG + T
+// already includes the adaptor point and is kept whole, since its y parity
+// decides whether t gets added or subtracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchnorrPreSignature {
+    r: S256Point,
+    s: BigUint,
+}
+
+impl SchnorrPreSignature {
+    pub fn get_r(&self) -> S256Point {
+        self.r.clone()
+    }
+
+    pub fn get_s(&self) -> BigUint {
+        self.s.clone()
+    }
+
+    // compressed R || s, 65 bytes
+    pub fn serialize(&self) -> [u8; 65] {
+        let mut result = [0u8; 65];
+        result[..33].copy_from_slice(&self.r.sec(true));
+        result[33..].copy_from_slice(&scalar::to_bytes(&self.s));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, AdaptorError> {
+        if bytes.len() != 65 {
+            return Err(AdaptorError::InvalidPreSignature);
+        }
+        let r = S256Point::parse_sec(&bytes[..33]).map_err(|_| AdaptorError::InvalidPreSignature)?;
+        let s = BigUint::from_bytes_be(&bytes[33..]);
+        if s >= S256Point::order() {
+            return Err(AdaptorError::InvalidPreSignature);
+        }
+        Ok(Self { r, s })
+    }
+}
+
+// Signs msg with the nonce point shifted by the adaptor point T. The result
+// only becomes a valid signature once the discrete log t of T is added, and
+// the finished signature in turn reveals t to whoever holds the
+// pre-signature. Nonces are derived as in BIP340 with T mixed in.
+pub fn pre_sign(
+    sk: &PrivateKey,
+    msg: &[u8],
+    adaptor: &S256Point,
+    aux_rand: &[u8; 32],
+) -> Result<SchnorrPreSignature, AdaptorError> {
+    if *adaptor == S256Point::infinity_point() {
+        return Err(AdaptorError::PointAtInfinity);
+    }
+    let point = sk.get_point();
+    let d = if point.has_even_y() { sk.get_secret() } else { scalar::negate(&sk.get_secret()) };
+    let pubkey = XOnlyPublicKey::from_point(&point);
+
+    let mut t = scalar::to_bytes(&d);
+    for (byte, mask) in t.iter_mut().zip(tagged_hash("SchnorrAdaptor/aux", aux_rand)) {
+        *byte ^= mask;
+    }
+    let mut preimage = t.to_vec();
+    preimage.extend_from_slice(&pubkey.to_bytes());
+    preimage.extend(adaptor.sec(true));
+    preimage.extend_from_slice(msg);
+    let k = scalar::from_bytes_mod_order(&tagged_hash("SchnorrAdaptor/nonce", &preimage));
+    if k.is_zero() {
+        panic!("Nonce is zero");
+    }
+
+    let r = S256Point::generator() * k.clone() + adaptor.clone();
+    if r == S256Point::infinity_point() {
+        panic!("Nonce cancels the adaptor point");
+    }
+    // the final R must have an even y; when it doesn't the signature ends up
+    // with -R = -kG - T, so k is negated here and t is subtracted in adapt
+    let k = if r.has_even_y() { k } else { scalar::negate(&k) };
+    let e = challenge(&r.get_x().unwrap().to_bytes(), &pubkey, msg);
+    Ok(SchnorrPreSignature { r, s: (k + e * d) % S256Point::order() })
+}
+
+// s'G = R' + eP where R' = R - T, negated when R has an odd y. If this holds,
+// learning t is enough to finish the signature.
+pub fn pre_verify(pubkey: &XOnlyPublicKey, msg: &[u8], adaptor: &S256Point, pre_sig: &SchnorrPreSignature) -> bool {
+    if pre_sig.s >= S256Point::order() || pre_sig.r == S256Point::infinity_point() {
+        return false;
+    }
+    let e = challenge(&pre_sig.r.get_x().unwrap().to_bytes(), pubkey, msg);
+    let mut r = pre_sig.r.clone() - adaptor.clone();
+    if !pre_sig.r.has_even_y() {
+        r = -r;
+    }
+    S256Point::generator() * pre_sig.s.clone() == r + pubkey.get_point() * e
+}
+
+// s = s' + t, or s' - t when R has an odd y
+pub fn adapt(pre_sig: &SchnorrPreSignature, secret: &BigUint) -> SchnorrSignature {
+    let n = S256Point::order();
+    let t = if pre_sig.r.has_even_y() { secret % &n } else { scalar::negate(secret) };
+    SchnorrSignature::new(pre_sig.r.get_x().unwrap().get_num(), (&pre_sig.s + t) % n)
+}
+
+// t = s - s' (or s' - s), checked against the adaptor point. None if the
+// signature wasn't made from this pre-signature.
+pub fn extract_secret(pre_sig: &SchnorrPreSignature, sig: &SchnorrSignature, adaptor: &S256Point) -> Option<BigUint> {
+    if sig.get_r() != pre_sig.r.get_x().unwrap().get_num() {
+        return None;
+    }
+    let difference = (sig.get_s() + scalar::negate(&pre_sig.s)) % S256Point::order();
+    let t = if pre_sig.r.has_even_y() { difference } else { scalar::negate(&difference) };
+    if S256Point::generator() * t.clone() != *adaptor {
+        return None;
+    }
+    Some(t)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Alice wants t, Bob knows it. Bob pre-signs a payment to Alice and Alice
+    // pre-signs a payment to Bob, both locked to T. Bob claims his by adapting
+    // Alice's pre-signature, which hands t to Alice.
+    #[test]
+    fn swap_works() {
+        let alice = PrivateKey::new(BigUint::from(0xa11ceu64));
+        let bob = PrivateKey::new(BigUint::from(0xb0bu64));
+        let t = BigUint::from(0x5ec2e7u64);
+        let adaptor = S256Point::generator() * t.clone();
+        let (to_alice, to_bob) = (b"bob pays alice".as_slice(), b"alice pays bob".as_slice());
+
+        let pre_bob = pre_sign(&bob, to_alice, &adaptor, &[1; 32]).unwrap();
+        let pre_alice = pre_sign(&alice, to_bob, &adaptor, &[2; 32]).unwrap();
+        assert!(pre_verify(&bob.x_only_public_key(), to_alice, &adaptor, &pre_bob));
+        assert!(pre_verify(&alice.x_only_public_key(), to_bob, &adaptor, &pre_alice));
+        assert!(!pre_verify(&alice.x_only_public_key(), to_alice, &adaptor, &pre_alice));
+
+        // a pre-signature is not a signature
+        let unadapted = SchnorrSignature::new(pre_alice.get_r().get_x().unwrap().get_num(), pre_alice.get_s());
+        assert!(!alice.x_only_public_key().verify(to_bob, &unadapted));
+
+        let sig_alice = adapt(&pre_alice, &t);
+        assert!(alice.x_only_public_key().verify(to_bob, &sig_alice));
+
+        let learned = extract_secret(&pre_alice, &sig_alice, &adaptor).unwrap();
+        assert_eq!(learned, t);
+        let sig_bob = adapt(&pre_bob, &learned);
+        assert!(bob.x_only_public_key().verify(to_alice, &sig_bob));
+
+        assert_eq!(extract_secret(&pre_bob, &sig_alice, &adaptor), None);
+    }
+
+    // covers both parities of R
+    #[test]
+    fn adapt_works_for_any_nonce() {
+        let key = PrivateKey::new(BigUint::from(7u8));
+        let t = BigUint::from(11u8);
+        let adaptor = S256Point::generator() * t.clone();
+        let mut parities = [false, false];
+        for i in 0..8u8 {
+            let pre_sig = pre_sign(&key, &[i], &adaptor, &[i; 32]).unwrap();
+            parities[pre_sig.get_r().has_even_y() as usize] = true;
+            assert!(pre_verify(&key.x_only_public_key(), &[i], &adaptor, &pre_sig));
+            let sig = adapt(&pre_sig, &t);
+            assert!(key.x_only_public_key().verify(&[i], &sig));
+            assert_eq!(extract_secret(&pre_sig, &sig, &adaptor), Some(t.clone()));
+            assert_eq!(SchnorrPreSignature::parse(&pre_sig.serialize()), Ok(pre_sig));
+        }
+        assert_eq!(parities, [true, true]);
+        assert_eq!(pre_sign(&key, b"", &S256Point::infinity_point(), &[0; 32]), Err(AdaptorError::PointAtInfinity));
+    }
+}
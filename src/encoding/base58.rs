@@ -0,0 +1,173 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use crate::hashes::sha256::hash256;
+
+use super::hex::to_hex;
+
+// Base58, Bitcoin's alphabet without 0, O, I and l so addresses can't be
+// misread. Each leading zero byte becomes a leading '1'.
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+// Reasons a Base58 string can't be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base58Error {
+    // a character outside the alphabet, at its char index in the string
+    InvalidCharacter { character: char, position: usize },
+    // the last 4 bytes aren't the first 4 of hash256 of the rest
+    InvalidChecksum { expected: [u8; 4], found: [u8; 4] },
+    // Base58Check data must at least hold its 4 byte checksum
+    TooShort(usize),
+}
+
+impl fmt::Display for Base58Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Base58Error::InvalidCharacter { character, position } => {
+                write!(f, "invalid base58 character {:?} at position {}", character, position)
+            }
+            Base58Error::InvalidChecksum { expected, found } => write!(
+                f,
+                "invalid checksum: expected {}, found {}",
+                to_hex(expected),
+                to_hex(found)
+            ),
+            Base58Error::TooShort(len) => write!(f, "{} bytes is too short for a checksum", len),
+        }
+    }
+}
+
+impl std::error::Error for Base58Error {}
+
+pub fn encode(data: &[u8]) -> String {
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+
+    // base 256 to base 58, little endian digits
+    let mut digits: Vec<u8> = Vec::with_capacity(data.len() * 138 / 100 + 1);
+    for &byte in &data[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut result = "1".repeat(zeros);
+    result.extend(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char));
+    result
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>, Base58Error> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len());
+    let mut zeros = 0;
+    let mut leading = true;
+    for (position, character) in s.chars().enumerate() {
+        let value = ALPHABET
+            .iter()
+            .position(|&a| a as char == character)
+            .ok_or(Base58Error::InvalidCharacter { character, position })?;
+        if leading && value == 0 {
+            zeros += 1;
+            continue;
+        }
+        leading = false;
+
+        // base 58 to base 256, little endian bytes
+        let mut carry = value as u32;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut result = vec![0u8; zeros];
+    result.extend(bytes.iter().rev());
+    Ok(result)
+}
+
+// data followed by the first 4 bytes of hash256(data)
+pub fn encode_check(data: &[u8]) -> String {
+    let mut payload = data.to_vec();
+    payload.extend_from_slice(&hash256(data)[..4]);
+    encode(&payload)
+}
+
+// the data without its checksum, once the checksum is verified
+pub fn decode_check(s: &str) -> Result<Vec<u8>, Base58Error> {
+    let mut payload = decode(s)?;
+    if payload.len() < 4 {
+        return Err(Base58Error::TooShort(payload.len()));
+    }
+    let found: [u8; 4] = payload.split_off(payload.len() - 4).try_into().unwrap();
+    let expected: [u8; 4] = hash256(&payload)[..4].try_into().unwrap();
+    if found != expected {
+        return Err(Base58Error::InvalidChecksum { expected, found });
+    }
+    Ok(payload)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Bitcoin Core's base58_encode_decode.json
+    #[test]
+    fn encode_decode_works() {
+        let vectors = [
+            ("", ""),
+            ("61", "2g"),
+            ("626262", "a3gV"),
+            ("636363", "aPEr"),
+            ("73696d706c792061206c6f6e6720737472696e67", "2cFupjhnEsSn59qHXstmK2ffpLv2"),
+            ("00eb15231dfceb60925886b67d065299925915aeb172c06647", "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"),
+            ("516b6fcd0f", "ABnLTmg"),
+            ("bf4f89001e670274dd", "3SEo3LWLoPntC"),
+            ("572e4794", "3EFU7m"),
+            ("ecac89cad93923c02321", "EJDM8drfXA6uyA"),
+            ("10c8511e", "Rt5zm"),
+            ("00000000000000000000", "1111111111"),
+        ];
+        for (data, encoded) in vectors {
+            let data = hex::decode(data).unwrap();
+            assert_eq!(encode(&data), encoded);
+            assert_eq!(decode(encoded).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_invalid_characters() {
+        assert_eq!(
+            decode("3SEo3LW0oPntC"),
+            Err(Base58Error::InvalidCharacter { character: '0', position: 7 })
+        );
+        assert_eq!(decode("1l"), Err(Base58Error::InvalidCharacter { character: 'l', position: 1 }));
+        assert_eq!(decode("ñ1"), Err(Base58Error::InvalidCharacter { character: 'ñ', position: 0 }));
+    }
+
+    #[test]
+    fn check_works() {
+        // the P2PKH address of the compressed key for secret 1
+        let payload = hex::decode("00751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let address = encode_check(&payload);
+        assert_eq!(address, "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(decode_check(&address).unwrap(), payload);
+
+        // a typo in the last character
+        let err = decode_check("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMJ").unwrap_err();
+        assert!(matches!(err, Base58Error::InvalidChecksum { .. }));
+        assert_eq!(decode_check("2g"), Err(Base58Error::TooShort(1)));
+        assert_eq!(decode_check(""), Err(Base58Error::TooShort(0)));
+    }
+}
@@ -0,0 +1,355 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+use std::fmt;
+
+// BIP173 Bech32 and BIP350 Bech32m: a human readable part, a '1', then 5 bit
+// values as characters, the last 6 of which are a BCH checksum. The two
+// variants only differ in the constant the checksum is xored with.
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+const CHECKSUM_LEN: usize = 6;
+
+// the longest string BIP173 allows
+const MAX_LEN: usize = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    // BIP173, for segwit version 0
+    Bech32,
+    // BIP350, for segwit version 1 and up
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(&self) -> u32 {
+        match self {
+            Variant::Bech32 => 1,
+            Variant::Bech32m => 0x2bc830a3,
+        }
+    }
+}
+
+// Reasons a string can't be Bech32 encoded or decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bech32Error {
+    // strings are at most 90 characters, and need a separator and checksum
+    InvalidLength(usize),
+    // upper and lower case letters in the same string
+    MixedCase,
+    // no '1' separating the human readable part from the data
+    MissingSeparator,
+    // the human readable part is empty or has a character outside 33..=126
+    InvalidHrp,
+    // a data character outside the charset, at its char index in the string
+    InvalidCharacter { character: char, position: usize },
+    // a value to encode doesn't fit in 5 bits
+    InvalidValue(u8),
+    // regrouping bits left more than 4 bits of padding, or nonzero ones
+    InvalidPadding,
+    // The checksum is wrong for both variants. error_positions are the
+    // string indices of the substitutions that would fix it, when one or two
+    // do, and empty otherwise.
+    InvalidChecksum { error_positions: Vec<usize> },
+}
+
+impl fmt::Display for Bech32Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bech32Error::InvalidLength(len) => write!(f, "invalid bech32 length {}", len),
+            Bech32Error::MixedCase => write!(f, "mixed upper and lower case"),
+            Bech32Error::MissingSeparator => write!(f, "missing '1' separator"),
+            Bech32Error::InvalidHrp => write!(f, "invalid human readable part"),
+            Bech32Error::InvalidCharacter { character, position } => {
+                write!(f, "invalid bech32 character {:?} at position {}", character, position)
+            }
+            Bech32Error::InvalidValue(value) => write!(f, "value {} doesn't fit in 5 bits", value),
+            Bech32Error::InvalidPadding => write!(f, "invalid padding"),
+            Bech32Error::InvalidChecksum { error_positions } if error_positions.is_empty() => {
+                write!(f, "invalid checksum")
+            }
+            Bech32Error::InvalidChecksum { error_positions } => {
+                write!(f, "invalid checksum, likely errors at positions {:?}", error_positions)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Bech32Error {}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut chk: u32 = 1;
+    for &value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ value as u32;
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+// the high bits of each character, a zero, then the low bits
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    result.push(0);
+    result.extend(hrp.bytes().map(|b| b & 31));
+    result
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> [u8; CHECKSUM_LEN] {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0; CHECKSUM_LEN]);
+    let residue = polymod(&values) ^ variant.constant();
+    let mut result = [0u8; CHECKSUM_LEN];
+    for (i, value) in result.iter_mut().enumerate() {
+        *value = ((residue >> (5 * (5 - i))) & 31) as u8;
+    }
+    result
+}
+
+// Encodes 5 bit values, checksum included. The human readable part is
+// lowercased.
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> Result<String, Bech32Error> {
+    if hrp.is_empty() || hrp.bytes().any(|b| !(33..=126).contains(&b)) {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if let Some(&value) = data.iter().find(|&&v| v > 31) {
+        return Err(Bech32Error::InvalidValue(value));
+    }
+    let len = hrp.len() + 1 + data.len() + CHECKSUM_LEN;
+    if len > MAX_LEN {
+        return Err(Bech32Error::InvalidLength(len));
+    }
+    let hrp = hrp.to_lowercase();
+    let checksum = create_checksum(&hrp, data, variant);
+    let mut result = hrp.clone();
+    result.push('1');
+    result.extend(data.iter().chain(checksum.iter()).map(|&v| CHARSET[v as usize] as char));
+    Ok(result)
+}
+
+// The lowercased human readable part, the 5 bit values without the checksum
+// and which variant the checksum was made for.
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), Bech32Error> {
+    let len = s.chars().count();
+    if len > MAX_LEN {
+        return Err(Bech32Error::InvalidLength(len));
+    }
+    if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+    if let Some((position, character)) = s.chars().enumerate().find(|(_, c)| !(33..=126).contains(&(*c as u32))) {
+        return Err(Bech32Error::InvalidCharacter { character, position });
+    }
+    let s = s.to_lowercase();
+    let separator = s.rfind('1').ok_or(Bech32Error::MissingSeparator)?;
+    if separator == 0 {
+        return Err(Bech32Error::InvalidHrp);
+    }
+    if len - separator - 1 < CHECKSUM_LEN {
+        return Err(Bech32Error::InvalidLength(len));
+    }
+    let hrp = &s[..separator];
+    let mut values = Vec::with_capacity(len - separator - 1);
+    for (i, character) in s[separator + 1..].chars().enumerate() {
+        let value = CHARSET
+            .iter()
+            .position(|&c| c as char == character)
+            .ok_or(Bech32Error::InvalidCharacter { character, position: separator + 1 + i })?;
+        values.push(value as u8);
+    }
+
+    let mut expanded = hrp_expand(hrp);
+    expanded.extend_from_slice(&values);
+    let residue = polymod(&expanded);
+    let variant = match residue {
+        r if r == Variant::Bech32.constant() => Variant::Bech32,
+        r if r == Variant::Bech32m.constant() => Variant::Bech32m,
+        _ => {
+            let start = expanded.len() - values.len();
+            let error_positions = locate_errors(&expanded, start, residue)
+                .into_iter()
+                .map(|i| separator + 1 + i)
+                .collect();
+            return Err(Bech32Error::InvalidChecksum { error_positions });
+        }
+    };
+    values.truncate(values.len() - CHECKSUM_LEN);
+    Ok((hrp.to_string(), values, variant))
+}
+
+// Indices into the data values after start where one or two substitutions
+// would make the checksum valid for either variant, fewest errors first, or
+// nothing if it takes more. Any two substitutions change the checksum
+// differently, so the answer is unique when there really were at most two
+// errors.
+//
+// The checksum is linear: changing a value by d at index i xors the residue
+// with effect(i, d), whatever the other values are. So one error is a lookup
+// of residue ^ constant among all effects, and two are a lookup of
+// residue ^ constant ^ effect(j, d) for each j and d.
+fn locate_errors(expanded: &[u8], start: usize, residue: u32) -> Vec<usize> {
+    let zero = polymod(&vec![0; expanded.len()]);
+
+    // the effect of each single bit, then of each of the 31 changes
+    let mut effects: HashMap<u32, usize> = HashMap::new();
+    let mut rows: Vec<[u32; 32]> = Vec::with_capacity(expanded.len() - start);
+    for i in start..expanded.len() {
+        let mut bits = [0u32; 5];
+        for (bit, effect) in bits.iter_mut().enumerate() {
+            let mut values = vec![0u8; expanded.len()];
+            values[i] = 1 << bit;
+            *effect = polymod(&values) ^ zero;
+        }
+        let mut row = [0u32; 32];
+        for (d, effect) in row.iter_mut().enumerate().skip(1) {
+            *effect = (0..5).filter(|b| (d >> b) & 1 == 1).fold(0, |acc, b| acc ^ bits[b]);
+            effects.insert(*effect, i - start);
+        }
+        rows.push(row);
+    }
+
+    let targets = [Variant::Bech32, Variant::Bech32m].map(|v| residue ^ v.constant());
+    for target in targets {
+        if let Some(&i) = effects.get(&target) {
+            return vec![i];
+        }
+    }
+    for target in targets {
+        for (j, row) in rows.iter().enumerate() {
+            for effect in &row[1..] {
+                match effects.get(&(target ^ effect)) {
+                    Some(&i) if i != j => return vec![i.min(j), i.max(j)],
+                    _ => {}
+                }
+            }
+        }
+    }
+    vec![]
+}
+
+// Regroups from one bit width to another, e.g. bytes to the 5 bit values of
+// a segwit program and back. With pad the last group is filled with zeros,
+// without it the leftover bits must be fewer than from and all zero.
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Bech32Error> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max = (1u32 << to) - 1;
+    let mut result = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for &value in data {
+        if (value as u32) >> from != 0 {
+            return Err(Bech32Error::InvalidValue(value));
+        }
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max != 0 {
+        return Err(Bech32Error::InvalidPadding);
+    }
+    Ok(result)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_valid_strings_works() {
+        let bech32 = [
+            "A12UEL5L",
+            "a12uel5l",
+            "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+            "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+            "?1ezyfcl",
+        ];
+        let bech32m = [
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "an83characterlonghumanreadablepartthatcontainsthetheexcludedcharactersbioandnumber11sg7hg6",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ];
+        for (strings, variant) in [(bech32, Variant::Bech32), (bech32m, Variant::Bech32m)] {
+            for s in strings {
+                let (hrp, data, decoded) = decode(s).unwrap();
+                assert_eq!(decoded, variant);
+                assert_eq!(encode(&hrp, &data, variant).unwrap(), s.to_lowercase());
+            }
+        }
+    }
+
+    #[test]
+    fn decode_rejects_invalid_strings() {
+        assert_eq!(decode("pzry9x0s0muk"), Err(Bech32Error::MissingSeparator));
+        assert_eq!(decode("1pzry9x0s0muk"), Err(Bech32Error::InvalidHrp));
+        assert_eq!(
+            decode("x1b4n0q5v"),
+            Err(Bech32Error::InvalidCharacter { character: 'b', position: 2 })
+        );
+        assert_eq!(decode("li1dgmt3"), Err(Bech32Error::InvalidLength(8)));
+        assert_eq!(decode("A12uEL5L"), Err(Bech32Error::MixedCase));
+        assert_eq!(
+            decode("\u{7f}1axkwrx"),
+            Err(Bech32Error::InvalidCharacter { character: '\u{7f}', position: 0 })
+        );
+        let long = format!("{}1{}", "a".repeat(84), "q".repeat(6));
+        assert_eq!(decode(&long), Err(Bech32Error::InvalidLength(91)));
+        assert_eq!(encode("", &[], Variant::Bech32), Err(Bech32Error::InvalidHrp));
+        assert_eq!(encode("a", &[32], Variant::Bech32), Err(Bech32Error::InvalidValue(32)));
+    }
+
+    #[test]
+    fn error_location_works() {
+        let valid = "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw";
+        let substitute = |s: &str, positions: &[usize]| -> String {
+            s.chars()
+                .enumerate()
+                .map(|(i, c)| if positions.contains(&i) { if c == 'q' { 'p' } else { 'q' } } else { c })
+                .collect()
+        };
+
+        for positions in [vec![7], vec![20], vec![44], vec![8, 9], vec![10, 40], vec![38, 43]] {
+            let typo = substitute(valid, &positions);
+            assert_eq!(decode(&typo), Err(Bech32Error::InvalidChecksum { error_positions: positions }));
+        }
+
+        // in a Bech32m string too
+        let typo = substitute("abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx", &[12, 30]);
+        assert_eq!(decode(&typo), Err(Bech32Error::InvalidChecksum { error_positions: vec![12, 30] }));
+
+        // three errors are still caught, though they may look like two others
+        let typo = substitute(valid, &[8, 20, 30]);
+        assert!(matches!(decode(&typo), Err(Bech32Error::InvalidChecksum { .. })));
+    }
+
+    #[test]
+    fn convert_bits_works() {
+        let program = hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let values = convert_bits(&program, 8, 5, true).unwrap();
+        assert_eq!(values.len(), 32);
+        assert_eq!(convert_bits(&values, 5, 8, false).unwrap(), program);
+
+        assert_eq!(convert_bits(&[0xff], 8, 5, false), Err(Bech32Error::InvalidPadding));
+        assert_eq!(convert_bits(&[0], 5, 8, false), Err(Bech32Error::InvalidPadding));
+        assert_eq!(convert_bits(&[0, 1], 5, 8, false), Err(Bech32Error::InvalidPadding));
+        assert_eq!(convert_bits(&[32], 5, 8, true), Err(Bech32Error::InvalidValue(32)));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod base58;
+pub mod bech32;
+pub mod hex;
+pub mod wire;
@@ -0,0 +1,838 @@
+#![allow(unused)]
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use num::BigUint;
+
+use crate::adaptor::ecdsa::EcdsaPreSignature;
+use crate::adaptor::schnorr::{AdaptorError, SchnorrPreSignature};
+use crate::finite_field::{scalar, secp_ec::{S256Point, SecError}};
+use crate::finite_field::signature::{DerError, RecoverableSignature, RecoveryError, Signature};
+use crate::frost::dkg::Round1Package;
+use crate::frost::sign::SigningCommitments;
+use crate::musig::nonce::{AggNonce, PubNonce};
+use crate::ring::aos::{LinkableRingSignature, RingSignature};
+use crate::ring::borromean::BorromeanSignature;
+use crate::schnorr::bip340::{SchnorrError, SchnorrSignature, XOnlyPublicKey};
+use crate::taproot::tree::{ControlBlock, TapLeaf, TaprootError};
+use crate::vrf::ecvrf::{self, VrfError, PROOF_LEN};
+use crate::wallet::bip32::{Bip32Error, DerivationPath, ExtendedPrivKey, ExtendedPubKey, SERIALIZED_LEN};
+use crate::zkp::bulletproofs::{BulletproofError, RangeProof};
+use crate::zkp::pedersen::Commitment;
+use crate::zkp::sigma::{DleqProof, DlogProof, OrProof, SigmaError};
+
+// the most items a length prefix may announce, as in Bitcoin Core, so a
+// bogus length can't make the decoder loop forever
+pub const MAX_SIZE: u64 = 0x02000000;
+
+// Reasons bytes can't be read back as a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    // reading or writing failed, usually UnexpectedEof for truncated input
+    Io(io::ErrorKind),
+    // a CompactSize using more bytes than its value needs
+    NonCanonicalCompactSize(u64),
+    // a length prefix over MAX_SIZE
+    OversizedLength(u64),
+    // bytes were left over after the value
+    TrailingBytes(usize),
+    // leaf versions are even, the low bit is the control block's parity
+    InvalidLeafVersion(u8),
+    // the point at infinity has no SEC encoding
+    PointAtInfinity,
+    // a scalar that isn't below the group order n
+    InvalidScalar,
+    // a MuSig2 nonce with a point that doesn't parse
+    InvalidNonce,
+    Sec(SecError),
+    Der(DerError),
+    Recovery(RecoveryError),
+    Schnorr(SchnorrError),
+    Adaptor(AdaptorError),
+    Taproot(TaprootError),
+    Bip32(Bip32Error),
+    Sigma(SigmaError),
+    Bulletproof(BulletproofError),
+    Vrf(VrfError),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Io(kind) => write!(f, "io error: {}", kind),
+            WireError::NonCanonicalCompactSize(n) => write!(f, "non-canonical CompactSize for {}", n),
+            WireError::OversizedLength(n) => write!(f, "length {} is over {}", n, MAX_SIZE),
+            WireError::TrailingBytes(count) => write!(f, "{} trailing bytes", count),
+            WireError::InvalidLeafVersion(version) => write!(f, "invalid leaf version {:#04x}", version),
+            WireError::PointAtInfinity => write!(f, "the point at infinity can't be encoded"),
+            WireError::InvalidScalar => write!(f, "scalar is not below the curve order"),
+            WireError::InvalidNonce => write!(f, "invalid MuSig2 nonce"),
+            WireError::Sec(e) => write!(f, "invalid SEC point: {}", e),
+            WireError::Der(e) => write!(f, "invalid DER signature: {}", e),
+            WireError::Recovery(e) => write!(f, "invalid compact signature: {}", e),
+            WireError::Schnorr(e) => write!(f, "invalid schnorr encoding: {}", e),
+            WireError::Adaptor(e) => write!(f, "invalid adaptor signature: {}", e),
+            WireError::Taproot(e) => write!(f, "invalid taproot encoding: {}", e),
+            WireError::Bip32(e) => write!(f, "invalid extended key: {}", e),
+            WireError::Sigma(e) => write!(f, "invalid sigma proof: {}", e),
+            WireError::Bulletproof(e) => write!(f, "invalid range proof: {}", e),
+            WireError::Vrf(e) => write!(f, "invalid VRF proof: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+fn io_error(e: io::Error) -> WireError {
+    WireError::Io(e.kind())
+}
+
+// Values with a byte encoding for transactions and the p2p protocol
+pub trait Encodable {
+    // writes self and returns how many bytes that took
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError>;
+
+    // Panics for the few values with no encoding, such as the point at
+    // infinity; consensus_encode returns those as errors instead. Writing to
+    // a Vec can't fail otherwise.
+    fn to_wire(&self) -> Vec<u8> {
+        let mut result = vec![];
+        self.consensus_encode(&mut result).unwrap();
+        result
+    }
+}
+
+pub trait Decodable: Sized {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError>;
+
+    // the whole of bytes has to be the one value
+    fn from_wire(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut rest = bytes;
+        let value = Self::consensus_decode(&mut rest)?;
+        if !rest.is_empty() {
+            return Err(WireError::TrailingBytes(rest.len()));
+        }
+        Ok(value)
+    }
+}
+
+fn write_all<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<usize, WireError> {
+    writer.write_all(bytes).map_err(io_error)?;
+    Ok(bytes.len())
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], WireError> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes).map_err(io_error)?;
+    Ok(bytes)
+}
+
+// integers are little endian whatever their size
+macro_rules! impl_int {
+    ($($int:ty),*) => {$(
+        impl Encodable for $int {
+            fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+                write_all(writer, &self.to_le_bytes())
+            }
+        }
+
+        impl Decodable for $int {
+            fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+                Ok(<$int>::from_le_bytes(read_array(reader)?))
+            }
+        }
+    )*};
+}
+
+impl_int!(u8, u16, u32, u64, i32, i64);
+
+// Bitcoin's variable length integer: one byte below 0xfd, otherwise a marker
+// byte followed by a 2, 4 or 8 byte little endian number
+pub fn write_compact_size<W: Write>(writer: &mut W, n: u64) -> Result<usize, WireError> {
+    match n {
+        0..=0xfc => (n as u8).consensus_encode(writer),
+        0xfd..=0xffff => Ok(0xfdu8.consensus_encode(writer)? + (n as u16).consensus_encode(writer)?),
+        0x10000..=0xffffffff => Ok(0xfeu8.consensus_encode(writer)? + (n as u32).consensus_encode(writer)?),
+        _ => Ok(0xffu8.consensus_encode(writer)? + n.consensus_encode(writer)?),
+    }
+}
+
+// Only the shortest encoding of each number is accepted, so every number
+// has one encoding and transactions can't be changed by re-encoding them
+pub fn read_compact_size<R: Read>(reader: &mut R) -> Result<u64, WireError> {
+    let (n, min) = match u8::consensus_decode(reader)? {
+        0xfd => (u16::consensus_decode(reader)? as u64, 0xfd),
+        0xfe => (u32::consensus_decode(reader)? as u64, 0x10000),
+        0xff => (u64::consensus_decode(reader)?, 0x100000000),
+        n => return Ok(n as u64),
+    };
+    if n < min {
+        return Err(WireError::NonCanonicalCompactSize(n));
+    }
+    Ok(n)
+}
+
+// the length, checked against MAX_SIZE
+fn read_length<R: Read>(reader: &mut R) -> Result<usize, WireError> {
+    match read_compact_size(reader)? {
+        n if n > MAX_SIZE => Err(WireError::OversizedLength(n)),
+        n => Ok(n as usize),
+    }
+}
+
+// hashes and other fixed size values are written as they are
+impl<const N: usize> Encodable for [u8; N] {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        write_all(writer, self)
+    }
+}
+
+impl<const N: usize> Decodable for [u8; N] {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        read_array(reader)
+    }
+}
+
+// a CompactSize count then the items, which for Vec<u8> makes the usual
+// length prefixed bytes of scripts and witness elements
+impl<T: Encodable> Encodable for Vec<T> {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        let mut len = write_compact_size(writer, self.len() as u64)?;
+        for item in self {
+            len += item.consensus_encode(writer)?;
+        }
+        Ok(len)
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        // grown as items arrive rather than trusting the count up front
+        let count = read_length(reader)?;
+        let mut result = vec![];
+        for _ in 0..count {
+            result.push(T::consensus_decode(reader)?);
+        }
+        Ok(result)
+    }
+}
+
+// compressed SEC, though uncompressed keys decode too
+impl Encodable for S256Point {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        if *self == S256Point::infinity_point() {
+            return Err(WireError::PointAtInfinity);
+        }
+        write_all(writer, &self.sec(true))
+    }
+}
+
+impl Decodable for S256Point {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let mut sec = vec![u8::consensus_decode(reader)?];
+        if sec[0] == 0x04 {
+            sec.extend(read_array::<R, 64>(reader)?);
+        } else {
+            sec.extend(read_array::<R, 32>(reader)?);
+        }
+        S256Point::parse_sec(&sec).map_err(WireError::Sec)
+    }
+}
+
+// Scalars are 32 big endian bytes. They aren't Encodable themselves, as not
+// every BigUint is a scalar, so the types holding them use these.
+fn write_scalar<W: Write>(writer: &mut W, scalar: &BigUint) -> Result<usize, WireError> {
+    if *scalar >= S256Point::order() {
+        return Err(WireError::InvalidScalar);
+    }
+    write_all(writer, &scalar::to_bytes(scalar))
+}
+
+fn read_scalar<R: Read>(reader: &mut R) -> Result<BigUint, WireError> {
+    let scalar = BigUint::from_bytes_be(&read_array::<R, 32>(reader)?);
+    if scalar >= S256Point::order() {
+        return Err(WireError::InvalidScalar);
+    }
+    Ok(scalar)
+}
+
+// a CompactSize count then the scalars, like Vec<T>
+fn write_scalars<W: Write>(writer: &mut W, scalars: &[BigUint]) -> Result<usize, WireError> {
+    let mut len = write_compact_size(writer, scalars.len() as u64)?;
+    for scalar in scalars {
+        len += write_scalar(writer, scalar)?;
+    }
+    Ok(len)
+}
+
+fn read_scalars<R: Read>(reader: &mut R) -> Result<Vec<BigUint>, WireError> {
+    let count = read_length(reader)?;
+    let mut result = vec![];
+    for _ in 0..count {
+        result.push(read_scalar(reader)?);
+    }
+    Ok(result)
+}
+
+// length prefixed DER, strictly parsed
+impl Encodable for Signature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_der().consensus_encode(writer)
+    }
+}
+
+impl Decodable for Signature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        Signature::parse_der(&Vec::<u8>::consensus_decode(reader)?).map_err(WireError::Der)
+    }
+}
+
+// the 65 byte compact form of signed messages, along with whether the key
+// it recovers to is compressed since the header byte carries that too
+impl Encodable for (RecoverableSignature, bool) {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.0.to_compact(self.1).consensus_encode(writer)
+    }
+}
+
+impl Decodable for (RecoverableSignature, bool) {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        RecoverableSignature::from_compact(&read_array::<R, 65>(reader)?).map_err(WireError::Recovery)
+    }
+}
+
+impl Encodable for XOnlyPublicKey {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_bytes().consensus_encode(writer)
+    }
+}
+
+impl Decodable for XOnlyPublicKey {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        XOnlyPublicKey::from_bytes(&read_array::<R, 32>(reader)?).map_err(WireError::Schnorr)
+    }
+}
+
+impl Encodable for SchnorrSignature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_bytes().consensus_encode(writer)
+    }
+}
+
+impl Decodable for SchnorrSignature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        SchnorrSignature::from_bytes(&read_array::<R, 64>(reader)?).map_err(WireError::Schnorr)
+    }
+}
+
+// compressed R || s
+impl Encodable for SchnorrPreSignature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for SchnorrPreSignature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        SchnorrPreSignature::parse(&read_array::<R, 65>(reader)?).map_err(WireError::Adaptor)
+    }
+}
+
+// R || R^ || s' || the proof's c and response, 162 bytes
+impl Encodable for EcdsaPreSignature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for EcdsaPreSignature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        EcdsaPreSignature::parse(&read_array::<R, 162>(reader)?).map_err(WireError::Adaptor)
+    }
+}
+
+// R1 || R2 as compressed SEC, 66 bytes
+impl Encodable for PubNonce {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for PubNonce {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        PubNonce::parse(&read_array::<R, 66>(reader)?).ok_or(WireError::InvalidNonce)
+    }
+}
+
+// the same, with 33 zero bytes for a sum at infinity
+impl Encodable for AggNonce {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for AggNonce {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        AggNonce::parse(&read_array::<R, 66>(reader)?).ok_or(WireError::InvalidNonce)
+    }
+}
+
+// version || CompactSize(script) || script, the preimage of the leaf hash
+impl Encodable for TapLeaf {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        Ok(self.get_version().consensus_encode(writer)? + self.get_script().consensus_encode(writer)?)
+    }
+}
+
+impl Decodable for TapLeaf {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let version = u8::consensus_decode(reader)?;
+        if version & 1 != 0 {
+            return Err(WireError::InvalidLeafVersion(version));
+        }
+        Ok(TapLeaf::new(Vec::<u8>::consensus_decode(reader)?, version))
+    }
+}
+
+// length prefixed, as the last witness element of a script path spend
+impl Encodable for ControlBlock {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for ControlBlock {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        ControlBlock::parse(&Vec::<u8>::consensus_decode(reader)?).map_err(WireError::Taproot)
+    }
+}
+
+// c || s
+impl Encodable for DlogProof {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_bytes().consensus_encode(writer)
+    }
+}
+
+impl Decodable for DlogProof {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        DlogProof::from_bytes(&read_array::<R, 64>(reader)?).map_err(WireError::Sigma)
+    }
+}
+
+impl Encodable for DleqProof {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_bytes().consensus_encode(writer)
+    }
+}
+
+impl Decodable for DleqProof {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        DleqProof::from_bytes(&read_array::<R, 64>(reader)?).map_err(WireError::Sigma)
+    }
+}
+
+// one (c, s) pair per key, so length prefixed
+impl Encodable for OrProof {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_bytes().consensus_encode(writer)
+    }
+}
+
+impl Decodable for OrProof {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        OrProof::from_bytes(&Vec::<u8>::consensus_decode(reader)?).map_err(WireError::Sigma)
+    }
+}
+
+// length prefixed, as the size grows with the number of values and bits
+impl Encodable for RangeProof {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for RangeProof {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        RangeProof::parse(&Vec::<u8>::consensus_decode(reader)?).map_err(WireError::Bulletproof)
+    }
+}
+
+// gamma || c || s, 81 bytes
+impl Encodable for ecvrf::Proof {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.to_bytes().consensus_encode(writer)
+    }
+}
+
+impl Decodable for ecvrf::Proof {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        ecvrf::Proof::from_bytes(&read_array::<R, PROOF_LEN>(reader)?).map_err(WireError::Vrf)
+    }
+}
+
+// e0 then the responses
+impl Encodable for RingSignature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        Ok(write_scalar(writer, &self.get_e0())? + write_scalars(writer, &self.get_s())?)
+    }
+}
+
+impl Decodable for RingSignature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let e0 = read_scalar(reader)?;
+        Ok(RingSignature::new(e0, read_scalars(reader)?))
+    }
+}
+
+// the key image first, then as RingSignature
+impl Encodable for LinkableRingSignature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        let len = self.get_key_image().consensus_encode(writer)?;
+        Ok(len + write_scalar(writer, &self.get_e0())? + write_scalars(writer, &self.get_s())?)
+    }
+}
+
+impl Decodable for LinkableRingSignature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let key_image = S256Point::consensus_decode(reader)?;
+        let e0 = read_scalar(reader)?;
+        Ok(LinkableRingSignature::new(key_image, e0, read_scalars(reader)?))
+    }
+}
+
+// e0, the number of rings, then each ring's responses
+impl Encodable for BorromeanSignature {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        let s = self.get_s();
+        let mut len = write_scalar(writer, &self.get_e0())? + write_compact_size(writer, s.len() as u64)?;
+        for ring in &s {
+            len += write_scalars(writer, ring)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for BorromeanSignature {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let e0 = read_scalar(reader)?;
+        let count = read_length(reader)?;
+        let mut s = vec![];
+        for _ in 0..count {
+            s.push(read_scalars(reader)?);
+        }
+        Ok(BorromeanSignature::new(e0, s))
+    }
+}
+
+impl Encodable for Commitment {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.get_point().consensus_encode(writer)
+    }
+}
+
+impl Decodable for Commitment {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        Ok(Commitment::from_point(S256Point::consensus_decode(reader)?))
+    }
+}
+
+// D || E
+impl Encodable for SigningCommitments {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        Ok(self.get_hiding().consensus_encode(writer)? + self.get_binding().consensus_encode(writer)?)
+    }
+}
+
+impl Decodable for SigningCommitments {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let hiding = S256Point::consensus_decode(reader)?;
+        Ok(SigningCommitments::new(hiding, S256Point::consensus_decode(reader)?))
+    }
+}
+
+// the commitment to the polynomial, then the proof's R and mu
+impl Encodable for Round1Package {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        let len = self.get_commitment().consensus_encode(writer)? + self.get_proof_r().consensus_encode(writer)?;
+        Ok(len + write_scalar(writer, &self.get_proof_mu())?)
+    }
+}
+
+impl Decodable for Round1Package {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        let commitment = Vec::<S256Point>::consensus_decode(reader)?;
+        let proof_r = S256Point::consensus_decode(reader)?;
+        Ok(Round1Package::new(commitment, proof_r, read_scalar(reader)?))
+    }
+}
+
+// the 78 bytes that base58check wraps
+impl Encodable for ExtendedPubKey {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for ExtendedPubKey {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        ExtendedPubKey::from_bytes(&read_array::<R, SERIALIZED_LEN>(reader)?).map_err(WireError::Bip32)
+    }
+}
+
+impl Encodable for ExtendedPrivKey {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.serialize().consensus_encode(writer)
+    }
+}
+
+impl Decodable for ExtendedPrivKey {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        ExtendedPrivKey::from_bytes(&read_array::<R, SERIALIZED_LEN>(reader)?).map_err(WireError::Bip32)
+    }
+}
+
+// a count then each child number as a little endian u32
+impl Encodable for DerivationPath {
+    fn consensus_encode<W: Write>(&self, writer: &mut W) -> Result<usize, WireError> {
+        self.get_children().consensus_encode(writer)
+    }
+}
+
+impl Decodable for DerivationPath {
+    fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, WireError> {
+        Ok(DerivationPath::new(Vec::<u32>::consensus_decode(reader)?))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::adaptor::ecdsa;
+    use crate::adaptor::schnorr::pre_sign;
+    use crate::finite_field::private_key::PrivateKey;
+    use crate::hashes::sha256::sha256;
+    use crate::musig::nonce::{nonce_agg, nonce_gen};
+    use crate::zkp::{bulletproofs, pedersen};
+    use crate::taproot::tree::TAPSCRIPT_LEAF_VERSION;
+    use crate::wallet::network::Network;
+    use num_bigint::BigUint;
+
+    // deterministic stand-ins for random bytes
+    fn random(seed: u64) -> [u8; 32] {
+        sha256(&seed.to_le_bytes())
+    }
+
+    fn round_trip<T: Encodable + Decodable + PartialEq + fmt::Debug>(value: &T) {
+        let bytes = value.to_wire();
+        assert_eq!(value.consensus_encode(&mut vec![]), Ok(bytes.len()));
+        assert_eq!(T::from_wire(&bytes).as_ref(), Ok(value));
+        // every strict prefix is cut short
+        if !bytes.is_empty() {
+            assert!(T::from_wire(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn compact_size_works() {
+        let cases = [
+            (0, "00"),
+            (0xfc, "fc"),
+            (0xfd, "fdfd00"),
+            (0xffff, "fdffff"),
+            (0x10000, "fe00000100"),
+            (0xffffffff, "feffffffff"),
+            (0x100000000, "ff0000000001000000"),
+            (u64::MAX, "ffffffffffffffffff"),
+        ];
+        for (n, expected) in cases {
+            let mut bytes = vec![];
+            assert_eq!(write_compact_size(&mut bytes, n), Ok(expected.len() / 2));
+            assert_eq!(hex::encode(&bytes), expected);
+            assert_eq!(read_compact_size(&mut &bytes[..]), Ok(n));
+        }
+
+        // each marker with a value a shorter form could hold
+        for (bytes, n) in [("fdfc00", 0xfc), ("feffff0000", 0xffff), ("ffffffffff00000000", 0xffffffff)] {
+            let bytes = hex::decode(bytes).unwrap();
+            assert_eq!(read_compact_size(&mut &bytes[..]), Err(WireError::NonCanonicalCompactSize(n)));
+        }
+
+        assert_eq!(read_compact_size(&mut &[0xfe, 0x00][..]), Err(WireError::Io(io::ErrorKind::UnexpectedEof)));
+        assert_eq!(Vec::<u8>::from_wire(&hex::decode("fe00000004").unwrap()), Err(WireError::OversizedLength(0x04000000)));
+    }
+
+    #[test]
+    fn integers_are_little_endian() {
+        assert_eq!(hex::encode(0x12345678u32.to_wire()), "78563412");
+        assert_eq!(hex::encode((-2i32).to_wire()), "feffffff");
+        assert_eq!(hex::encode(0x0102u16.to_wire()), "0201");
+        assert_eq!(hex::encode(1i64.to_wire()), "0100000000000000");
+        assert_eq!(u32::from_wire(&[1, 0, 0, 0, 0]), Err(WireError::TrailingBytes(1)));
+        assert_eq!(u64::from_wire(&[1, 0, 0]), Err(WireError::Io(io::ErrorKind::UnexpectedEof)));
+        assert_eq!(hex::encode(vec![vec![0xabu8], vec![]].to_wire()), "0201ab00");
+    }
+
+    #[test]
+    fn round_trips_hold() {
+        for seed in 0..64u64 {
+            let bytes = random(seed);
+            let n = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+            // shifted so every CompactSize width comes up
+            let shifted = n >> (bytes[8] % 64);
+            let mut encoded = vec![];
+            write_compact_size(&mut encoded, shifted).unwrap();
+            assert_eq!(read_compact_size(&mut &encoded[..]), Ok(shifted));
+
+            round_trip(&n);
+            round_trip(&(n as u32));
+            round_trip(&(n as i64));
+            round_trip(&bytes);
+            round_trip(&bytes[..bytes[9] as usize % 33].to_vec());
+            round_trip(&vec![n as u16; bytes[10] as usize % 5]);
+            round_trip(&DerivationPath::new(vec![n as u32, (n >> 32) as u32]));
+            round_trip(&SchnorrSignature::from_bytes(&[bytes, random(seed + 1000)].concat()).unwrap());
+            round_trip(&TapLeaf::new(bytes[..bytes[11] as usize % 33].to_vec(), bytes[12] & 0xfe));
+
+            // DER leaves out leading zeros, so r and s of every length come up
+            let order = S256Point::order();
+            let r = (BigUint::from_bytes_be(&bytes) % &order) >> (bytes[13] % 255) as usize;
+            let s = (BigUint::from_bytes_be(&random(seed + 2000)) % &order) >> (bytes[14] % 255) as usize;
+            if r > BigUint::from(0u32) && s > BigUint::from(0u32) {
+                round_trip(&Signature::new(r, s));
+            }
+        }
+
+        // points are slow to make, so fewer of them
+        for seed in 0..4u64 {
+            let point = PrivateKey::new(BigUint::from_bytes_be(&random(seed))).get_point();
+            round_trip(&point);
+            let uncompressed = point.sec(false);
+            assert_eq!(S256Point::from_wire(&uncompressed), Ok(point.clone()));
+            round_trip(&XOnlyPublicKey::from_point(&point));
+
+            let mut control = vec![TAPSCRIPT_LEAF_VERSION | (seed as u8 & 1)];
+            control.extend(XOnlyPublicKey::from_point(&point).to_bytes());
+            for i in 0..seed {
+                control.extend(random(seed * 100 + i));
+            }
+            round_trip(&ControlBlock::parse(&control).unwrap());
+
+            let master = ExtendedPrivKey::new_master(&random(seed), Network::Mainnet).unwrap();
+            let child = master.derive_child(seed as u32).unwrap();
+            round_trip(&child);
+            round_trip(&child.to_public());
+        }
+    }
+
+    // the signatures and proofs from elsewhere in the crate, each in the
+    // byte format its own module defines
+    #[test]
+    fn proof_round_trips_hold() {
+        let order = S256Point::order();
+        let random_scalar = |seed: u64| BigUint::from_bytes_be(&random(seed)) % &order;
+
+        for seed in 0..8u64 {
+            let sig = Signature::new(random_scalar(seed), random_scalar(seed + 100));
+            let recoverable = RecoverableSignature::new(sig, seed as u8 % 4);
+            round_trip(&(recoverable, seed % 2 == 0));
+
+            let pair = |c: u64, s: u64| [scalar::to_bytes(&random_scalar(c)), scalar::to_bytes(&random_scalar(s))].concat();
+            round_trip(&DlogProof::from_bytes(&pair(seed + 200, seed + 300)).unwrap());
+            round_trip(&DleqProof::from_bytes(&pair(seed + 200, seed + 300)).unwrap());
+            let pairs: Vec<u8> = (0..=seed).flat_map(|i| pair(seed * 10 + i, seed * 10 + i + 400)).collect();
+            round_trip(&OrProof::from_bytes(&pairs).unwrap());
+
+            let s: Vec<BigUint> = (0..=seed).map(|i| random_scalar(seed * 10 + i + 1000)).collect();
+            round_trip(&RingSignature::new(random_scalar(seed + 1100), s.clone()));
+            // rings of every size up to the seed, including none at all
+            let rings: Vec<Vec<BigUint>> = (0..seed as usize).map(|i| s[..=i].to_vec()).collect();
+            round_trip(&BorromeanSignature::new(random_scalar(seed + 1200), rings));
+        }
+
+        for seed in 0..2u64 {
+            let sk = PrivateKey::new(random_scalar(seed));
+            let adaptor = PrivateKey::new(random_scalar(seed + 500)).get_point();
+            round_trip(&pre_sign(&sk, b"adaptor", &adaptor, &random(seed + 600)).unwrap());
+            let z = random_scalar(seed + 650);
+            round_trip(&ecdsa::pre_sign(&sk, &z, &adaptor, &random(seed + 660)).unwrap());
+
+            let s: Vec<BigUint> = (0..=seed).map(|i| random_scalar(seed * 10 + i + 1300)).collect();
+            round_trip(&LinkableRingSignature::new(adaptor.clone(), random_scalar(seed + 1400), s));
+            round_trip(&pedersen::commit(&random_scalar(seed + 1500), &random_scalar(seed + 1600)));
+            round_trip(&SigningCommitments::new(sk.get_point(), adaptor.clone()));
+            let commitment = vec![sk.get_point(); seed as usize + 1];
+            round_trip(&Round1Package::new(commitment, adaptor.clone(), random_scalar(seed + 1700)));
+
+            let (_, pubnonce) = nonce_gen(&random(seed + 700), Some(&sk), &sk.get_point(), None, None, None);
+            round_trip(&pubnonce);
+            round_trip(&nonce_agg(std::slice::from_ref(&pubnonce)));
+
+            round_trip(&ecvrf::prove(&sk, &random(seed + 800)));
+        }
+
+        // both halves of the aggregate nonce at infinity
+        round_trip(&AggNonce::parse(&[0u8; 66]).unwrap());
+
+        let (proof, _) = bulletproofs::prove(&[200], &[BigUint::from(3u8)], 8, &random(900)).unwrap();
+        round_trip(&proof);
+    }
+
+    #[test]
+    fn decode_rejects_invalid() {
+        // a point off the curve and a DER signature with padding
+        let mut sec = vec![0x02];
+        sec.extend([0xff; 32]);
+        assert_eq!(S256Point::from_wire(&sec), Err(WireError::Sec(SecError::NotOnCurve)));
+        assert_eq!(S256Point::from_wire(&[0x05; 33]), Err(WireError::Sec(SecError::InvalidPrefix(0x05))));
+        let padded = hex::decode("09300702020001020101").unwrap();
+        assert_eq!(Signature::from_wire(&padded), Err(WireError::Der(DerError::ExcessPadding)));
+        assert_eq!(TapLeaf::from_wire(&[0xc1, 0x00]), Err(WireError::InvalidLeafVersion(0xc1)));
+        assert_eq!(
+            ControlBlock::from_wire(&[0x01, 0xc0]),
+            Err(WireError::Taproot(TaprootError::InvalidControlBlockLength(1)))
+        );
+        assert!(matches!(ExtendedPubKey::from_wire(&[0; 78]), Err(WireError::Bip32(_))));
+
+        assert_eq!(S256Point::infinity_point().consensus_encode(&mut vec![]), Err(WireError::PointAtInfinity));
+        assert_eq!(PubNonce::from_wire(&[0u8; 66]), Err(WireError::InvalidNonce));
+        let mut compact = [0u8; 65];
+        compact[0] = 26;
+        assert_eq!(
+            <(RecoverableSignature, bool)>::from_wire(&compact),
+            Err(WireError::Recovery(RecoveryError::InvalidHeader(26)))
+        );
+        assert_eq!(
+            SchnorrPreSignature::from_wire(&[0x04; 65]),
+            Err(WireError::Adaptor(AdaptorError::InvalidPreSignature))
+        );
+        assert_eq!(DlogProof::from_wire(&[0xff; 64]), Err(WireError::Sigma(SigmaError::InvalidScalar)));
+        assert_eq!(OrProof::from_wire(&[0x00]), Err(WireError::Sigma(SigmaError::InvalidLength(0))));
+        assert_eq!(RangeProof::from_wire(&[0x01, 0x00]), Err(WireError::Bulletproof(BulletproofError::InvalidProof)));
+        assert_eq!(ecvrf::Proof::from_wire(&[0x04; PROOF_LEN]), Err(WireError::Vrf(VrfError::InvalidPoint)));
+        assert_eq!(
+            EcdsaPreSignature::from_wire(&[0x04; 162]),
+            Err(WireError::Adaptor(AdaptorError::InvalidPreSignature))
+        );
+
+        // scalars not below n, in the first place and inside a vector
+        let n = scalar::to_bytes(&S256Point::order());
+        assert_eq!(RingSignature::from_wire(&[&n[..], &[0x00]].concat()), Err(WireError::InvalidScalar));
+        let one = scalar::to_bytes(&BigUint::from(1u8));
+        let ring = [&one[..], &[0x01, 0x02], &one, &n].concat();
+        assert_eq!(BorromeanSignature::from_wire(&ring), Err(WireError::InvalidScalar));
+        let g = S256Point::generator();
+        let package = Round1Package::new(vec![g.clone()], g.clone(), BigUint::from(1u8)).to_wire();
+        let overflow = [&package[..package.len() - 32], &n].concat();
+        assert_eq!(Round1Package::from_wire(&overflow), Err(WireError::InvalidScalar));
+        let big = RingSignature::new(S256Point::order(), vec![]);
+        assert_eq!(big.consensus_encode(&mut vec![]), Err(WireError::InvalidScalar));
+
+        // and points at infinity, which only come up when encoding
+        let infinite = SigningCommitments::new(g.clone(), S256Point::infinity_point());
+        assert_eq!(infinite.consensus_encode(&mut vec![]), Err(WireError::PointAtInfinity));
+        let zero = pedersen::commit(&BigUint::from(0u8), &BigUint::from(0u8));
+        assert_eq!(zero.consensus_encode(&mut vec![]), Err(WireError::PointAtInfinity));
+    }
+}
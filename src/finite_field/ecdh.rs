@@ -0,0 +1,112 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::BigUint;
+
+use super::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::sha256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EcdhError {
+    // the other party's key is the point at infinity
+    PointAtInfinity,
+    // the other party's key doesn't satisfy y^2 = x^3 + 7
+    NotOnCurve,
+}
+
+impl fmt::Display for EcdhError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EcdhError::PointAtInfinity => write!(f, "public key is the point at infinity"),
+            EcdhError::NotOnCurve => write!(f, "public key is not on the curve"),
+        }
+    }
+}
+
+impl std::error::Error for EcdhError {}
+
+impl PrivateKey {
+    // The shared secret with the owner of pubkey, hashed the way libsecp256k1's
+    // default ECDH does: sha256 of the compressed shared point. Both sides get
+    // the same result as a * (bG) = b * (aG).
+    pub fn ecdh(&self, pubkey: &S256Point) -> Result<[u8; 32], EcdhError> {
+        let shared = self.shared_point(pubkey)?;
+        Ok(sha256(&shared.sec(true)))
+    }
+
+    // The x coordinate of the shared point without hashing, for protocols that
+    // run their own key derivation. It is not uniformly random, so it should
+    // never be used as a key directly.
+    pub fn ecdh_raw(&self, pubkey: &S256Point) -> Result<[u8; 32], EcdhError> {
+        let shared = self.shared_point(pubkey)?;
+        Ok(shared.get_x().unwrap().to_bytes())
+    }
+
+    // an invalid point could leak bits of the secret (an invalid curve
+    // attack), so the key is checked before multiplying
+    fn shared_point(&self, pubkey: &S256Point) -> Result<S256Point, EcdhError> {
+        if *pubkey == S256Point::infinity_point() {
+            return Err(EcdhError::PointAtInfinity);
+        }
+        if !pubkey.is_on_curve() {
+            return Err(EcdhError::NotOnCurve);
+        }
+        // the secret is below n and the group has prime order, so this
+        // can't be infinity
+        Ok(pubkey.clone() * self.get_secret())
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::finite_field::secp_ec::tests::off_curve_point;
+
+    use super::*;
+
+    // Wycheproof ecdh_secp256k1 tcId 1, which libsecp256k1's ECDH tests run
+    // with a hash function that just copies x. The hashed secret is sha256 of
+    // the compressed shared point, 02 || x.
+    #[test]
+    fn ecdh_works() {
+        let public = S256Point::parse_sec(&hex::decode(
+            "04d8096af8a11e0b80037e1ee68246b5dcbb0aeb1cf1244fd767db80f3fa27da2b396812ea1686e7472e9692eaf3e958e50e9500d3b4c77243db1f2acd67ba9cc4",
+        ).unwrap()).unwrap();
+        let secret = PrivateKey::new(BigUint::from_bytes_be(
+            &hex::decode("00f4b7ff7cccc98813a69fae3df222bfe3f4e28f764bf91b4a10d8096ce446b254").unwrap(),
+        ));
+
+        assert_eq!(
+            hex::encode(secret.ecdh_raw(&public).unwrap()),
+            "544dfae22af6af939042b1d85b71a1e49e9a5614123c4d6ad0c8af65baf87d65"
+        );
+        assert_eq!(
+            hex::encode(secret.ecdh(&public).unwrap()),
+            "81165066322732362ca5d3f0991d7f1f7d0aad7ea533276496785d369e35159a"
+        );
+    }
+
+    #[test]
+    fn ecdh_is_symmetric() {
+        let alice = PrivateKey::new(BigUint::from(0xa11ceu64));
+        let bob = PrivateKey::new(BigUint::from(0xb0bu64));
+
+        assert_eq!(alice.ecdh(&bob.get_point()), bob.ecdh(&alice.get_point()));
+        assert_eq!(alice.ecdh_raw(&bob.get_point()), bob.ecdh_raw(&alice.get_point()));
+        assert_ne!(alice.ecdh(&bob.get_point()), alice.ecdh(&PrivateKey::new(BigUint::from(0xb0cu64)).get_point()));
+    }
+
+    #[test]
+    fn ecdh_rejects_infinity() {
+        let key = PrivateKey::new(BigUint::from(5u8));
+        assert_eq!(key.ecdh(&S256Point::infinity_point()), Err(EcdhError::PointAtInfinity));
+        assert_eq!(key.ecdh_raw(&S256Point::infinity_point()), Err(EcdhError::PointAtInfinity));
+    }
+
+    #[test]
+    fn ecdh_rejects_off_curve_key() {
+        let key = PrivateKey::new(BigUint::from(5u8));
+        assert_eq!(key.ecdh(&off_curve_point()), Err(EcdhError::NotOnCurve));
+        assert_eq!(key.ecdh_raw(&off_curve_point()), Err(EcdhError::NotOnCurve));
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(unused)]
+#![allow(clippy::partialeq_to_none, clippy::erasing_op)]
 use super::field_element::FieldElement;
 use std::{fmt, ops::{Add, Mul}};
 
@@ -28,10 +29,6 @@ impl PartialEq for Point {
     fn eq(&self, other: &Self) -> bool {
         self.x == other.x && self.y == other.y && self.a == other.a && self.b == other.b
     }
-
-    fn ne(&self, other: &Point) -> bool {
-        self.x != other.x || self.y != other.y || self.a != other.a || self.b != other.b
-    }
 }
 impl Eq for Point {}
 
@@ -129,8 +126,8 @@ impl Mul<usize> for Point {
 
     fn mul(self, coefficient: usize) -> Self {
         let mut product = Point {x: None, y: None, a: self.a, b: self.b};
-        let mut coef = coefficient.clone();
-        let mut current = self.clone();
+        let mut coef = coefficient;
+        let mut current = self;
         
         while coef > 0 {
             if coef & 1 == 1 {
@@ -150,8 +147,8 @@ impl Mul<Point> for usize {
 
     fn mul(self, other: Point) -> Self::Output {
         let mut product = Point {x: None, y: None, a: other.a, b: other.b};
-        let mut coef = self.clone();
-        let mut current = other.clone();
+        let mut coef = self;
+        let mut current = other;
         
         while coef > 0 {
             if coef & 1 == 1 {
@@ -32,7 +32,7 @@ impl FieldElement {
 
 impl PartialEq for FieldElement {
     fn eq(&self, other: &Self) -> bool {
-        return self.num == other.num && self.prime == other.prime;
+        self.num == other.num && self.prime == other.prime
     }
 }
 impl Eq for FieldElement {}
@@ -67,13 +67,12 @@ impl Sub for FieldElement {
             panic!("Can't subtract numbers in different fields");
         }
 
-        let num;
-        if self.num < other.num {
+        let num = if self.num < other.num {
             let temp_num = (other.num - self.num) % self.prime;
-            num = self.prime - temp_num;
+            self.prime - temp_num
         } else {
-            num = (self.num - other.num) % self.prime;
-        }
+            (self.num - other.num) % self.prime
+        };
 
         Self {
             num,
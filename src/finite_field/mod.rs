@@ -2,3 +2,7 @@ pub mod field_element;
 pub mod elliptic_curve;
 pub mod secp_field;
 pub mod secp_ec;
+pub mod signature;
+pub mod scalar;
+pub mod private_key;
+pub mod ecdh;
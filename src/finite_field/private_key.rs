@@ -0,0 +1,36 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use super::secp_ec::S256Point;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivateKey {
+    secret: BigUint,
+    point: S256Point,
+}
+
+impl PrivateKey {
+    pub fn new(secret: BigUint) -> Self {
+        if secret.is_zero() || secret >= S256Point::order() {
+            panic!("Secret not in range 1 to n - 1");
+        }
+        let point = S256Point::generator() * secret.clone();
+        Self { secret, point }
+    }
+
+    pub fn get_secret(&self) -> BigUint {
+        self.secret.clone()
+    }
+
+    pub fn get_point(&self) -> S256Point {
+        self.point.clone()
+    }
+}
+
+impl fmt::Display for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PrivateKey({:064x})", self.secret)
+    }
+}
@@ -0,0 +1,62 @@
+#![allow(unused)]
+
+// Helpers for scalars, the integers mod n that points are multiplied by
+
+use num::{BigUint, One, Zero};
+
+use super::secp_ec::S256Point;
+
+// 32 byte big endian
+pub fn to_bytes(num: &BigUint) -> [u8; 32] {
+    let bin = num.to_bytes_be();
+    let mut result = [0u8; 32];
+    result[32 - bin.len()..].copy_from_slice(&bin);
+    result
+}
+
+// interprets bytes as a big endian number and reduces it mod n
+pub fn from_bytes_mod_order(bytes: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(bytes) % S256Point::order()
+}
+
+// n is prime, so num^(n-2) = num^-1 (mod n)
+pub fn inverse(num: &BigUint) -> BigUint {
+    let n = S256Point::order();
+    if (num % &n).is_zero() {
+        panic!("Zero has no inverse mod n");
+    }
+    num.modpow(&(&n - BigUint::from(2u64)), &n)
+}
+
+// n - num, or zero for zero
+pub fn negate(num: &BigUint) -> BigUint {
+    let n = S256Point::order();
+    (&n - num % &n) % n
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_works() {
+        let num = BigUint::from(0xdeadbeefu64);
+        assert_eq!(num.clone() * inverse(&num) % S256Point::order(), BigUint::one());
+    }
+
+    #[test]
+    fn negate_works() {
+        let num = BigUint::from(42u64);
+        assert_eq!((negate(&num) + &num) % S256Point::order(), BigUint::zero());
+        assert_eq!(negate(&BigUint::zero()), BigUint::zero());
+    }
+
+    #[test]
+    fn to_bytes_works() {
+        let bytes = to_bytes(&BigUint::from(0x0102u64));
+        assert_eq!(bytes[..30], [0u8; 30]);
+        assert_eq!(bytes[30..], [1, 2]);
+        assert_eq!(from_bytes_mod_order(&(S256Point::order() + 1u8).to_bytes_be()), BigUint::one());
+    }
+}
@@ -1,8 +1,33 @@
 #![allow(unused)]
+#![allow(clippy::partialeq_to_none)]
 use num::{pow, BigInt, BigUint, FromPrimitive, Num, One, ToPrimitive, Zero};
 
 use super::secp_field::S256Field;
-use std::ops::{Add, Mul, BitAnd};
+use super::signature::{RecoveryError, Signature};
+use std::{fmt, ops::{Add, BitAnd, Mul, Neg, Sub}};
+
+// Reasons SEC bytes can't be parsed into a point
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecError {
+    // compressed keys are 33 bytes and uncompressed ones 65
+    InvalidLength(usize),
+    // the first byte must be 0x02 or 0x03 (compressed) or 0x04 (uncompressed)
+    InvalidPrefix(u8),
+    // the coordinates are not below p or don't satisfy y^2 = x^3 + 7
+    NotOnCurve,
+}
+
+impl fmt::Display for SecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecError::InvalidLength(len) => write!(f, "invalid SEC length {}", len),
+            SecError::InvalidPrefix(prefix) => write!(f, "invalid SEC prefix {:#04x}", prefix),
+            SecError::NotOnCurve => write!(f, "point is not on the curve"),
+        }
+    }
+}
+
+impl std::error::Error for SecError {}
 
 #[derive(Debug, Clone)]
 pub struct S256Point {
@@ -46,16 +71,293 @@ impl S256Point {
         let y = S256Field::new(gy);
         S256Point::new(Some(x), Some(y))
     }
+
+    // n, the order of the group generated by G
+    pub fn order() -> BigUint {
+        BigUint::from_str_radix(
+            "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            16,
+        ).unwrap()
+    }
+
+    // SEC format: 0x04 || x || y uncompressed, or 0x02/0x03 (y even/odd) || x
+    pub fn sec(&self, compressed: bool) -> Vec<u8> {
+        let (x, y) = match (&self.x, &self.y) {
+            (Some(x), Some(y)) => (x, y),
+            _ => panic!("The point at infinity has no SEC encoding"),
+        };
+        if compressed {
+            let mut result = vec![if self.has_even_y() { 0x02 } else { 0x03 }];
+            result.extend_from_slice(&x.to_bytes());
+            result
+        } else {
+            let mut result = vec![0x04];
+            result.extend_from_slice(&x.to_bytes());
+            result.extend_from_slice(&y.to_bytes());
+            result
+        }
+    }
+
+    pub fn parse_sec(sec_bin: &[u8]) -> Result<S256Point, SecError> {
+        if sec_bin.is_empty() {
+            return Err(SecError::InvalidLength(0));
+        }
+        match (sec_bin[0], sec_bin.len()) {
+            (0x04, 65) => {
+                let x = BigUint::from_bytes_be(&sec_bin[1..33]);
+                let y = BigUint::from_bytes_be(&sec_bin[33..]);
+                let p = S256Field::prime();
+                if x >= p || y >= p {
+                    return Err(SecError::NotOnCurve);
+                }
+                let (x, y) = (S256Field::new(x), S256Field::new(y));
+                let b = S256Field::new(BigUint::from(7u64));
+                if y.pow(BigUint::from(2u64)) != x.pow(BigUint::from(3u64)) + b {
+                    return Err(SecError::NotOnCurve);
+                }
+                Ok(S256Point::new(Some(x), Some(y)))
+            }
+            (0x02 | 0x03, 33) => {
+                let point = Self::lift_x(&BigUint::from_bytes_be(&sec_bin[1..])).ok_or(SecError::NotOnCurve)?;
+                Ok(if sec_bin[0] == 0x02 { point } else { -point })
+            }
+            (0x02..=0x04, len) => Err(SecError::InvalidLength(len)),
+            (prefix, _) => Err(SecError::InvalidPrefix(prefix)),
+        }
+    }
+
+    pub fn get_x(&self) -> Option<S256Field> {
+        self.x.clone()
+    }
+
+    pub fn get_y(&self) -> Option<S256Field> {
+        self.y.clone()
+    }
+
+    // false for the point at infinity, which has no y
+    pub fn has_even_y(&self) -> bool {
+        match &self.y {
+            Some(y) => !y.get_num().bit(0),
+            None => false,
+        }
+    }
+
+    // y^2 = x^3 + 7 holds. new already checks this, so it only fails for
+    // points that didn't come through it. The point at infinity counts.
+    pub fn is_on_curve(&self) -> bool {
+        match (&self.x, &self.y) {
+            (Some(x), Some(y)) => {
+                y.clone().pow(BigUint::from(2u64)) == x.clone().pow(BigUint::from(3u64)) + self.b.clone()
+            }
+            _ => true,
+        }
+    }
+
+    // The point with the given x coordinate and an even y, if there is one.
+    // y^2 = x^3 + 7 has a solution only for about half of all x below p.
+    pub fn lift_x(x: &BigUint) -> Option<S256Point> {
+        if *x >= S256Field::prime() {
+            return None;
+        }
+        let x = S256Field::new(x.clone());
+        let alpha = x.pow(BigUint::from(3u64)) + S256Field::new(BigUint::from(7u64));
+        let beta = alpha.sqrt();
+        if beta.pow(BigUint::from(2u64)) != alpha {
+            return None;
+        }
+        let y = if beta.get_num().bit(0) {
+            S256Field::new(S256Field::prime() - beta.get_num())
+        } else {
+            beta
+        };
+        Some(S256Point::new(Some(x), Some(y)))
+    }
+
+    // Sum of k_i * P_i using the bucket method (Pippenger). The scalars are cut
+    // into windows of c bits. For every window each point is added once into the
+    // bucket for its c bit digit, then a running sum over the buckets weights
+    // each one by its digit. That's about 256/c * (terms + 2^c) additions rather
+    // than roughly 384 per term with double-and-add.
+    pub fn multi_mul(terms: &[(BigUint, S256Point)]) -> S256Point {
+        let n = Self::order();
+        let scalars: Vec<BigUint> = terms.iter().map(|(k, _)| k % &n).collect();
+        let c = match terms.len() {
+            0..=1 => 1,
+            2..=7 => 2,
+            8..=31 => 4,
+            32..=127 => 5,
+            _ => 6,
+        };
+
+        // the sums are kept in Jacobian coordinates, which put off the field
+        // inversion every affine addition needs until the very end
+        let points: Vec<Jacobian> = terms.iter().map(|(_, point)| Jacobian::from_affine(point)).collect();
+        let mut result = Jacobian::infinity();
+        for window in (0..256usize.div_ceil(c)).rev() {
+            for _ in 0..c {
+                result = result.double();
+            }
+
+            let mut buckets = vec![Jacobian::infinity(); (1 << c) - 1];
+            for (k, point) in scalars.iter().zip(&points) {
+                let digit = (0..c).fold(0usize, |digit, bit| {
+                    digit | ((k.bit((window * c + bit) as u64) as usize) << bit)
+                });
+                if digit != 0 {
+                    buckets[digit - 1] = buckets[digit - 1].add(point);
+                }
+            }
+
+            let mut running = Jacobian::infinity();
+            let mut window_sum = Jacobian::infinity();
+            for bucket in buckets.iter().rev() {
+                running = running.add(bucket);
+                window_sum = window_sum.add(&running);
+            }
+            result = result.add(&window_sum);
+        }
+        result.to_affine()
+    }
+
+    // u = z/s, v = r/s and the signature is valid if (uG + vP).x = r
+    pub fn verify(&self, z: BigUint, sig: &Signature) -> bool {
+        let n = Self::order();
+        let (r, s) = (sig.get_r(), sig.get_s());
+        if r.is_zero() || r >= n || s.is_zero() || s >= n {
+            return false;
+        }
+
+        let s_inv = s.modpow(&(&n - BigUint::from(2u64)), &n);
+        let u = z * &s_inv % &n;
+        let v = r.clone() * s_inv % &n;
+
+        match (Self::generator() * u + self.clone() * v).x {
+            Some(x) => x.get_num() % n == r,
+            None => false,
+        }
+    }
+
+    // Rebuilds the public key that produced sig over z. The recovery id tells
+    // which of the (up to four) candidate points R was the signing nonce: bit 0
+    // is the parity of R.y and bit 1 is set in the rare case that R.x = r + n.
+    // Then P = r^-1 (sR - zG).
+    pub fn recover(z: BigUint, sig: &Signature, recid: u8) -> Result<S256Point, RecoveryError> {
+        if recid > 3 {
+            return Err(RecoveryError::InvalidRecoveryId(recid));
+        }
+        let n = Self::order();
+        let (r, s) = (sig.get_r(), sig.get_s());
+        if r.is_zero() || r >= n || s.is_zero() || s >= n {
+            return Err(RecoveryError::InvalidSignature);
+        }
+
+        let mut x = r.clone();
+        if recid & 2 == 2 {
+            x += &n;
+        }
+        let point_r = match Self::lift_x(&x) {
+            Some(point) if recid & 1 == 1 => -point,
+            Some(point) => point,
+            None => return Err(RecoveryError::InvalidR),
+        };
+
+        let r_inv = r.modpow(&(&n - BigUint::from(2u64)), &n);
+        let u = (&n - z % &n) * &r_inv % &n;
+        let v = s * r_inv % &n;
+
+        let point = Self::generator() * u + point_r * v;
+        if point == Self::infinity_point() {
+            return Err(RecoveryError::PointAtInfinity);
+        }
+        Ok(point)
+    }
+}
+
+// (X, Y, Z) stands for the affine point (X/Z^2, Y/Z^3), and Z = 0 for the
+// point at infinity. Only used inside multi_mul.
+#[derive(Debug, Clone)]
+struct Jacobian {
+    x: BigUint,
+    y: BigUint,
+    z: BigUint,
+}
+
+impl Jacobian {
+    fn infinity() -> Self {
+        Jacobian { x: BigUint::one(), y: BigUint::one(), z: BigUint::zero() }
+    }
+
+    fn from_affine(point: &S256Point) -> Self {
+        match (&point.x, &point.y) {
+            (Some(x), Some(y)) => Jacobian { x: x.get_num(), y: y.get_num(), z: BigUint::one() },
+            _ => Self::infinity(),
+        }
+    }
+
+    fn to_affine(&self) -> S256Point {
+        if self.z.is_zero() {
+            return S256Point::infinity_point();
+        }
+        let p = S256Field::prime();
+        let z_inv = self.z.modpow(&(&p - 2u8), &p);
+        let z_inv_sq = &z_inv * &z_inv % &p;
+        let x = &self.x * &z_inv_sq % &p;
+        let y = &self.y * z_inv_sq % &p * z_inv % &p;
+        S256Point::new(Some(S256Field::new(x)), Some(S256Field::new(y)))
+    }
+
+    // with a = 0: S = 4XY^2, M = 3X^2, X' = M^2 - 2S, Y' = M(S - X') - 8Y^4, Z' = 2YZ
+    fn double(&self) -> Self {
+        if self.z.is_zero() || self.y.is_zero() {
+            return Self::infinity();
+        }
+        let p = S256Field::prime();
+        let y_sq = &self.y * &self.y % &p;
+        let s = 4u8 * &self.x * &y_sq % &p;
+        let m = 3u8 * &self.x * &self.x % &p;
+        let x = (&m * &m + 2u8 * (&p - &s)) % &p;
+        let y = (&m * ((&s + &p - &x) % &p) + 8u8 * (&p - &y_sq * &y_sq % &p)) % &p;
+        let z = 2u8 * &self.y * &self.z % &p;
+        Jacobian { x, y, z }
+    }
+
+    // U1 = X1 Z2^2, U2 = X2 Z1^2, S1 = Y1 Z2^3, S2 = Y2 Z1^3, H = U2 - U1,
+    // R = S2 - S1, X' = R^2 - H^3 - 2 U1 H^2, Y' = R(U1 H^2 - X') - S1 H^3,
+    // Z' = H Z1 Z2
+    fn add(&self, other: &Jacobian) -> Self {
+        if self.z.is_zero() {
+            return other.clone();
+        }
+        if other.z.is_zero() {
+            return self.clone();
+        }
+        let p = S256Field::prime();
+        let z1_sq = &self.z * &self.z % &p;
+        let z2_sq = &other.z * &other.z % &p;
+        let u1 = &self.x * &z2_sq % &p;
+        let u2 = &other.x * &z1_sq % &p;
+        let s1 = &self.y * &z2_sq % &p * &other.z % &p;
+        let s2 = &other.y * &z1_sq % &p * &self.z % &p;
+        if u1 == u2 {
+            return if s1 == s2 { self.double() } else { Self::infinity() };
+        }
+
+        let h = (&u2 + &p - &u1) % &p;
+        let r = (&s2 + &p - &s1) % &p;
+        let h_sq = &h * &h % &p;
+        let h_cu = &h_sq * &h % &p;
+        let u1_h_sq = &u1 * &h_sq % &p;
+        let x = (&r * &r + 2u8 * &p - &h_cu - 2u8 * &u1_h_sq % &p) % &p;
+        let y = (&r * ((&u1_h_sq + &p - &x) % &p) + &p - &s1 * &h_cu % &p) % &p;
+        let z = h * &self.z % &p * &other.z % &p;
+        Jacobian { x, y, z }
+    }
 }
 
 impl PartialEq for S256Point {
     fn eq(&self, other: &Self) -> bool {
         self.x == other.x && self.y == other.y && self.a == other.a && self.b == other.b
     }
-
-    fn ne(&self, other: &S256Point) -> bool {
-        self.x != other.x || self.y != other.y || self.a != other.a || self.b != other.b
-    }
 }
 impl Eq for S256Point {}
 
@@ -83,7 +385,7 @@ impl Add for S256Point {
                 a: self.a,
                 b: self.b
             }
-        } else if self == other && (self.y.clone().unwrap() == 0 * self.x.clone().unwrap() ){
+        } else if self == other && self.y.clone().unwrap() == S256Field::new(S256Field::zero()) {
             // if the line is a tangent to the curve and y = 0
             // return the identity (i.e)
             S256Point{
@@ -147,13 +449,38 @@ impl Add for S256Point {
     }
 }
 
+// -P is P reflected over the x axis
+impl Neg for S256Point {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self.y {
+            Some(y) => S256Point {
+                x: self.x,
+                y: Some(S256Field::new(S256Field::zero()) - y),
+                a: self.a,
+                b: self.b
+            },
+            None => self,
+        }
+    }
+}
+
+impl Sub for S256Point {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + (-other)
+    }
+}
+
 // S256Point * usize
 impl Mul<usize> for S256Point {
     type Output = Self;
 
     fn mul(self, coefficient: usize) -> Self {
         let mut product = S256Point {x: None, y: None, a: self.a.clone(), b: self.b.clone()};
-        let mut coef = coefficient.clone();
+        let mut coef = coefficient;
         let mut current = self.clone();
         
         while coef > 0 {
@@ -174,7 +501,7 @@ impl Mul<S256Point> for usize {
 
     fn mul(self, other: S256Point) -> Self::Output {
         let mut product = S256Point {x: None, y: None, a: other.a.clone(), b: other.b.clone()};
-        let mut coef = self.clone();
+        let mut coef = self;
         let mut current = other.clone();
         
         while coef > 0 {
@@ -280,4 +607,130 @@ pub mod tests {
         assert_eq!((S256Point::generator()*u + v * point).x.unwrap().get_num(), r);
         // (u*G + v*point).x.num 
     }
-}
\ No newline at end of file
+
+    fn book_example() -> (BigUint, Signature, S256Point) {
+        let z =  BigUint::from_str_radix(
+            "bc62d4b80d9e36da29c16c5d4d9f11731f36052c72401a76c23c0fb5a9b74423",
+            16,
+        ).unwrap();
+        let r =  BigUint::from_str_radix(
+            "37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6",
+            16,
+        ).unwrap();
+        let s =  BigUint::from_str_radix(
+            "8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec",
+            16,
+        ).unwrap();
+        let px =  BigUint::from_str_radix(
+            "04519fac3d910ca7e7138f7013706f619fa8f033e6ec6e09370ea38cee6a7574",
+            16,
+        ).unwrap();
+        let py =  BigUint::from_str_radix(
+            "82b51eab8c27c66e26c858a079bcdf4f1ada34cec420cafc7eac1a42216fb6c4",
+            16,
+        ).unwrap();
+
+        let point = S256Point::new(Some(S256Field::new(px)), Some(S256Field::new(py)));
+        (z, Signature::new(r, s), point)
+    }
+
+    #[test]
+    fn multi_mul_works() {
+        let g = S256Point::generator();
+        let points: Vec<S256Point> = (1..=9u64).map(|k| g.clone() * BigUint::from(k * 1000 + 7)).collect();
+        let scalars: Vec<BigUint> = (1..=9u64)
+            .map(|k| S256Point::order() - BigUint::from(k * 0x1234567))
+            .collect();
+
+        for len in [0, 1, 3, 9] {
+            let terms: Vec<_> = scalars.iter().cloned().zip(points.iter().cloned()).take(len).collect();
+            let expected = terms.iter().fold(S256Point::infinity_point(), |sum, (k, p)| sum + p.clone() * k.clone());
+            assert_eq!(S256Point::multi_mul(&terms), expected);
+        }
+
+        // P - P
+        let terms = vec![(BigUint::one(), g.clone()), (S256Point::order() - 1u8, g)];
+        assert_eq!(S256Point::multi_mul(&terms), S256Point::infinity_point());
+    }
+
+    // G with y bumped by one, which bypasses the check in new
+    pub fn off_curve_point() -> S256Point {
+        let g = S256Point::generator();
+        S256Point { y: Some(g.get_y().unwrap() + S256Field::new(BigUint::one())), ..g }
+    }
+
+    #[test]
+    fn is_on_curve_works() {
+        assert!(S256Point::generator().is_on_curve());
+        assert!(S256Point::infinity_point().is_on_curve());
+        assert!(!off_curve_point().is_on_curve());
+    }
+
+    #[test]
+    fn sec_works() {
+        let point = S256Point::generator() * BigUint::from(5000u64);
+        let uncompressed = hex::decode(
+            "04ffe558e388852f0120e46af2d1b370f85854a8eb0841811ece0e3e03d282d57c\
+             315dc72890a4f10a1481c031b03b351b0dc79901ca18a00cf009dbdb157a1d10",
+        ).unwrap();
+        let compressed = hex::decode("02ffe558e388852f0120e46af2d1b370f85854a8eb0841811ece0e3e03d282d57c").unwrap();
+
+        assert_eq!(point.sec(false), uncompressed);
+        assert_eq!(point.sec(true), compressed);
+        assert_eq!(S256Point::parse_sec(&uncompressed), Ok(point.clone()));
+        assert_eq!(S256Point::parse_sec(&compressed), Ok(point.clone()));
+        assert_eq!(S256Point::parse_sec(&(-point.clone()).sec(true)), Ok(-point));
+
+        assert_eq!(S256Point::parse_sec(&compressed[..32]), Err(SecError::InvalidLength(32)));
+        assert_eq!(S256Point::parse_sec(&[0x05; 33]), Err(SecError::InvalidPrefix(0x05)));
+        let mut off_curve = uncompressed;
+        off_curve[64] ^= 1;
+        assert_eq!(S256Point::parse_sec(&off_curve), Err(SecError::NotOnCurve));
+    }
+
+    #[test]
+    fn verify_works() {
+        let (z, sig, point) = book_example();
+        assert!(point.verify(z.clone(), &sig));
+        assert!(!point.verify(z + 1u8, &sig));
+        assert!(!point.verify(BigUint::one(), &Signature::new(BigUint::zero(), sig.get_s())));
+    }
+
+    #[test]
+    fn recover_works() {
+        let (z, sig, point) = book_example();
+
+        let recovered: Vec<_> = (0..4)
+            .map(|recid| S256Point::recover(z.clone(), &sig, recid))
+            .collect();
+        // only one of the two candidate R points gives back the signer
+        assert_eq!(recovered.iter().filter(|p| **p == Ok(point.clone())).count(), 1);
+        // r + n is bigger than p here so recid 2 and 3 can't be used
+        assert_eq!(recovered[2], Err(RecoveryError::InvalidR));
+        assert_eq!(recovered[3], Err(RecoveryError::InvalidR));
+
+        assert_eq!(S256Point::recover(z, &sig, 4), Err(RecoveryError::InvalidRecoveryId(4)));
+    }
+
+    #[test]
+    fn recover_with_overflowing_r_works() {
+        // a nonce point with n <= R.x < p, so r = R.x - n
+        let n = S256Point::order();
+        let mut x = &n + 1u8;
+        let point_r = loop {
+            let alpha = S256Field::new(x.clone()).pow(BigUint::from(3u64)) + S256Field::new(BigUint::from(7u64));
+            let beta = alpha.sqrt();
+            if beta.pow(BigUint::from(2u64)) == alpha {
+                break S256Point::new(Some(S256Field::new(x.clone())), Some(beta));
+            }
+            x += 1u8;
+        };
+        let parity = point_r.get_y().unwrap().get_num().bit(0) as u8;
+
+        let z = BigUint::from(0xdeadbeefu64);
+        let sig = Signature::new(x - &n, BigUint::from(0xc0ffeeu64));
+        let point = S256Point::recover(z.clone(), &sig, 2 | parity).unwrap();
+        assert!(point.verify(z.clone(), &sig));
+        assert_ne!(S256Point::recover(z, &sig, parity), Ok(point));
+    }
+}
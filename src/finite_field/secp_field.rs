@@ -1,6 +1,6 @@
 #![allow(unused)]
 
-use std::{fmt, ops::{Add, Deref, Div, Mul, Sub}};
+use std::{fmt, ops::{Add, Deref, Div, Mul, Sub}, sync::OnceLock};
 use num::{BigInt, BigUint, FromPrimitive, One, Zero, ToPrimitive, pow};
 
 #[derive(Debug, Clone)]
@@ -11,21 +11,36 @@ pub struct S256Field {
 
 impl S256Field {
     pub fn new(num: BigUint) -> Self {
-        let secp256k1_prime =
-            BigUint::from(2u64).pow(256) - BigUint::from(2u64).pow(32) - BigUint::from(977u64);
+        let secp256k1_prime = Self::prime();
         if num >= secp256k1_prime {
             panic!("Num {} not in field range", num);
         }
         Self {num, prime: secp256k1_prime}
     }
 
+    // p = 2^256 - 2^32 - 977
+    pub fn prime() -> BigUint {
+        static PRIME: OnceLock<BigUint> = OnceLock::new();
+        PRIME.get_or_init(|| {
+            BigUint::from(2u64).pow(256) - BigUint::from(2u64).pow(32) - BigUint::from(977u64)
+        }).clone()
+    }
+
     pub fn get_num(&self) -> BigUint {
         self.num.clone()
     }
 
+    // 32 byte big endian
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let bin = self.num.to_bytes_be();
+        let mut result = [0u8; 32];
+        result[32 - bin.len()..].copy_from_slice(&bin);
+        result
+    }
+
     pub fn pow(&self, power: BigUint) -> Self {
         let exp = power % (&self.prime - BigUint::from_u64(1u64).unwrap());
-        let num = Self::mod_pow(self.num.clone(), exp.into(), &self.prime);
+        let num = Self::mod_pow(self.num.clone(), exp, &self.prime);
         // let mut exp = power;
         // while exp < BigUint::from(0u8) {
         //     exp += (self.prime - BigUint::from(1u8));
@@ -40,17 +55,23 @@ impl S256Field {
             return BigUint::zero();
         }
         let mut result = BigUint::one();
-        base = base % modulus;
+        base %= modulus;
         while exp > BigUint::zero() {
             if &exp % BigUint::from_u64(2u64).unwrap() == BigUint::one() {
                 result = result * &base % modulus;
             }
-            exp = exp >> 1;
+            exp >>= 1;
             base = base.clone() * base % modulus
         }
         result
     }
 
+    // p % 4 == 3, so w^((p + 1) / 4) is a square root of w whenever w has one.
+    // The caller has to square the result to find out if it does.
+    pub fn sqrt(&self) -> Self {
+        self.pow((&self.prime + BigUint::one()) / BigUint::from(4u64))
+    }
+
     // return biguint zero
     pub fn zero() -> BigUint {
         BigUint::from(0u64)
@@ -65,7 +86,7 @@ impl S256Field {
 
 impl PartialEq for S256Field {
     fn eq(&self, other: &Self) -> bool {
-        return self.num == other.num && self.prime == other.prime;
+        self.num == other.num && self.prime == other.prime
     }
 }
 impl Eq for S256Field {}
@@ -97,13 +118,12 @@ impl Sub for S256Field {
             panic!("Can't subtract numbers in different fields");
         }
 
-        let num;
-        if self.num < other.num {
+        let num = if self.num < other.num {
             let temp_num = (other.num - self.num) % self.prime.clone();
-            num = self.prime - temp_num;
+            self.prime - temp_num
         } else {
-            num = (self.num - other.num) % self.prime;
-        }
+            (self.num - other.num) % self.prime
+        };
 
         Self::new(num)
     }
@@ -164,7 +184,8 @@ impl Div for S256Field {
             panic!("Can't divide numbers in different fields");
         }
 
-        let exp = S256Field::mod_pow(other.num,self.prime.clone() - BigUint::from_u64(2u64).unwrap(), &self.prime);
+        // num-bigint's modpow is much faster than mod_pow for big exponents
+        let exp = other.num.modpow(&(self.prime.clone() - BigUint::from_u64(2u64).unwrap()), &self.prime);
         let num = (self.num * exp) % self.prime;
         
         Self::new(num)
@@ -204,4 +225,15 @@ pub mod tests {
 
         assert_eq!(G*n, S256Point::infinity_point());
     }
+
+    #[test]
+    fn sqrt_works() {
+        let y = S256Point::generator().get_y().unwrap();
+        let root = y.pow(BigUint::from(2u64)).sqrt();
+        assert!(root == y || root == S256Field::new(S256Field::prime() - y.get_num()));
+
+        // 3 is not a square mod p, so its "root" doesn't square back to it
+        let three = S256Field::new(BigUint::from(3u64));
+        assert_ne!(three.sqrt().pow(BigUint::from(2u64)), three);
+    }
 }
\ No newline at end of file
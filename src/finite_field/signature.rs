@@ -0,0 +1,514 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use super::secp_ec::S256Point;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    r: BigUint,
+    s: BigUint,
+}
+
+// Reasons a DER encoded signature can be rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerError {
+    // the whole encoding is shorter than 8 or longer than 72 bytes
+    InvalidLength(usize),
+    // a long form length uses this many significant bytes, 4 or more
+    LengthTooLong(usize),
+    // the first byte is not the 0x30 compound marker
+    InvalidSequenceTag(u8),
+    // an integer is not introduced by the 0x02 marker
+    InvalidIntegerTag(u8),
+    // a declared length does not match the bytes that follow it
+    LengthMismatch { declared: usize, actual: usize },
+    // an integer is encoded with no bytes at all
+    ZeroLengthInteger,
+    // the high bit of an integer is set, making it negative
+    NegativeInteger,
+    // an integer starts with a 0x00 byte it does not need
+    ExcessPadding,
+    // r or s is not below the group order n
+    Overflow,
+    // the input ended before a complete signature was read
+    UnexpectedEnd,
+}
+
+impl fmt::Display for DerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerError::InvalidLength(len) => write!(f, "invalid DER signature length {}", len),
+            DerError::LengthTooLong(bytes) => write!(f, "length encoded in {} bytes", bytes),
+            DerError::InvalidSequenceTag(tag) => write!(f, "expected sequence tag 0x30, found {:#04x}", tag),
+            DerError::InvalidIntegerTag(tag) => write!(f, "expected integer tag 0x02, found {:#04x}", tag),
+            DerError::LengthMismatch { declared, actual } => {
+                write!(f, "declared length {} does not match actual length {}", declared, actual)
+            }
+            DerError::ZeroLengthInteger => write!(f, "zero length integer"),
+            DerError::NegativeInteger => write!(f, "negative integer"),
+            DerError::ExcessPadding => write!(f, "integer has excess zero padding"),
+            DerError::Overflow => write!(f, "integer is not below the curve order"),
+            DerError::UnexpectedEnd => write!(f, "unexpected end of input"),
+        }
+    }
+}
+
+impl std::error::Error for DerError {}
+
+impl Signature {
+    pub fn new(r: BigUint, s: BigUint) -> Self {
+        Self { r, s }
+    }
+
+    pub fn get_r(&self) -> BigUint {
+        self.r.clone()
+    }
+
+    pub fn get_s(&self) -> BigUint {
+        self.s.clone()
+    }
+
+    // 0x30 [total-length] 0x02 [r-length] [r] 0x02 [s-length] [s]
+    pub fn to_der(&self) -> Vec<u8> {
+        let r = Self::encode_integer(&self.r);
+        let s = Self::encode_integer(&self.s);
+
+        let mut result = vec![0x30, (r.len() + s.len()) as u8];
+        result.extend(r);
+        result.extend(s);
+        result
+    }
+
+    // big endian bytes with leading zeros stripped, prefixed with 0x00 when
+    // the high bit is set so the integer is not read back as negative
+    fn encode_integer(num: &BigUint) -> Vec<u8> {
+        let mut bin = num.to_bytes_be();
+        if bin[0] & 0x80 != 0 {
+            bin.insert(0, 0x00);
+        }
+
+        let mut result = vec![0x02, bin.len() as u8];
+        result.extend(bin);
+        result
+    }
+
+    // Strict parsing following the BIP66 rules (without the trailing sighash
+    // byte) so only the canonical encoding of a signature is accepted
+    pub fn parse_der(der: &[u8]) -> Result<Self, DerError> {
+        if der.len() < 8 || der.len() > 72 {
+            return Err(DerError::InvalidLength(der.len()));
+        }
+        if der[0] != 0x30 {
+            return Err(DerError::InvalidSequenceTag(der[0]));
+        }
+        if der[1] as usize != der.len() - 2 {
+            return Err(DerError::LengthMismatch { declared: der[1] as usize, actual: der.len() - 2 });
+        }
+
+        if der[2] != 0x02 {
+            return Err(DerError::InvalidIntegerTag(der[2]));
+        }
+        let len_r = der[3] as usize;
+        if len_r == 0 {
+            return Err(DerError::ZeroLengthInteger);
+        }
+        // r has to leave room for the tag and length of s
+        if 5 + len_r >= der.len() {
+            return Err(DerError::LengthMismatch { declared: len_r, actual: der.len() - 6 });
+        }
+
+        if der[4 + len_r] != 0x02 {
+            return Err(DerError::InvalidIntegerTag(der[4 + len_r]));
+        }
+        let len_s = der[5 + len_r] as usize;
+        if len_s == 0 {
+            return Err(DerError::ZeroLengthInteger);
+        }
+        if len_r + len_s + 6 != der.len() {
+            return Err(DerError::LengthMismatch { declared: len_s, actual: der.len() - len_r - 6 });
+        }
+
+        let r = Self::parse_strict_integer(&der[4..4 + len_r])?;
+        let s = Self::parse_strict_integer(&der[6 + len_r..])?;
+        Ok(Self { r, s })
+    }
+
+    fn parse_strict_integer(bytes: &[u8]) -> Result<BigUint, DerError> {
+        if bytes[0] & 0x80 != 0 {
+            return Err(DerError::NegativeInteger);
+        }
+        // a leading zero is only allowed when the next byte would be negative
+        if bytes.len() > 1 && bytes[0] == 0x00 && bytes[1] & 0x80 == 0 {
+            return Err(DerError::ExcessPadding);
+        }
+
+        let num = BigUint::from_bytes_be(bytes);
+        if num >= S256Point::order() {
+            return Err(DerError::Overflow);
+        }
+        Ok(num)
+    }
+
+    // Mirrors Bitcoin Core's ecdsa_signature_parse_der_lax, which accepts the
+    // malformed encodings found in transactions from before BIP66. Lengths may
+    // use the long form, integers may be padded or negative and trailing bytes
+    // are ignored. A value that overflows n yields the (invalid) zero signature
+    // rather than an error, just like Core.
+    pub fn parse_der_lax(der: &[u8]) -> Result<Self, DerError> {
+        let mut pos = 0;
+
+        // sequence tag and length, the length itself is not checked
+        if pos == der.len() {
+            return Err(DerError::UnexpectedEnd);
+        }
+        if der[pos] != 0x30 {
+            return Err(DerError::InvalidSequenceTag(der[pos]));
+        }
+        pos += 1;
+        if pos == der.len() {
+            return Err(DerError::UnexpectedEnd);
+        }
+        let len_byte = der[pos] as usize;
+        pos += 1;
+        if len_byte & 0x80 != 0 {
+            let len_bytes = len_byte - 0x80;
+            if len_bytes > der.len() - pos {
+                return Err(DerError::UnexpectedEnd);
+            }
+            pos += len_bytes;
+        }
+
+        let (r_pos, r_len) = Self::parse_lax_integer(der, &mut pos)?;
+        let (s_pos, s_len) = Self::parse_lax_integer(der, &mut pos)?;
+
+        let r = Self::strip_leading_zeros(&der[r_pos..r_pos + r_len]);
+        let s = Self::strip_leading_zeros(&der[s_pos..s_pos + s_len]);
+
+        let n = S256Point::order();
+        let overflow = r.len() > 32 || s.len() > 32 || {
+            BigUint::from_bytes_be(r) >= n || BigUint::from_bytes_be(s) >= n
+        };
+        if overflow {
+            return Ok(Self { r: BigUint::zero(), s: BigUint::zero() });
+        }
+
+        Ok(Self { r: BigUint::from_bytes_be(r), s: BigUint::from_bytes_be(s) })
+    }
+
+    // reads an integer tag and length at pos and returns where its value
+    // starts and how long it is, leaving pos just past the value
+    fn parse_lax_integer(der: &[u8], pos: &mut usize) -> Result<(usize, usize), DerError> {
+        if *pos == der.len() {
+            return Err(DerError::UnexpectedEnd);
+        }
+        if der[*pos] != 0x02 {
+            return Err(DerError::InvalidIntegerTag(der[*pos]));
+        }
+        *pos += 1;
+
+        if *pos == der.len() {
+            return Err(DerError::UnexpectedEnd);
+        }
+        let mut len_byte = der[*pos] as usize;
+        *pos += 1;
+
+        let len = if len_byte & 0x80 != 0 {
+            len_byte -= 0x80;
+            if len_byte > der.len() - *pos {
+                return Err(DerError::UnexpectedEnd);
+            }
+            while len_byte > 0 && der[*pos] == 0 {
+                *pos += 1;
+                len_byte -= 1;
+            }
+            // Core rejects 4 or more significant length bytes whatever the
+            // width of size_t, it only static_asserts that size_t holds 4
+            // bytes, so this limit is the same on every platform
+            if len_byte >= 4 {
+                return Err(DerError::LengthTooLong(len_byte));
+            }
+            let mut len = 0;
+            while len_byte > 0 {
+                len = (len << 8) + der[*pos] as usize;
+                *pos += 1;
+                len_byte -= 1;
+            }
+            len
+        } else {
+            len_byte
+        };
+
+        if len > der.len() - *pos {
+            return Err(DerError::LengthMismatch { declared: len, actual: der.len() - *pos });
+        }
+        let start = *pos;
+        *pos += len;
+        Ok((start, len))
+    }
+
+    fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+        let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+        &bytes[zeros..]
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Signature({:x},{:x})", self.r, self.s)
+    }
+}
+
+// Reasons a public key can't be recovered from a signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoveryError {
+    // recovery ids only go from 0 to 3
+    InvalidRecoveryId(u8),
+    // r or s is zero or not below n
+    InvalidSignature,
+    // no curve point has the x coordinate selected by r and the recovery id
+    InvalidR,
+    // the recovered key would be the point at infinity
+    PointAtInfinity,
+    // a compact signature has to be exactly 65 bytes
+    InvalidLength(usize),
+    // the header byte of a compact signature must be in 27..=34
+    InvalidHeader(u8),
+}
+
+impl fmt::Display for RecoveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoveryError::InvalidRecoveryId(recid) => write!(f, "invalid recovery id {}", recid),
+            RecoveryError::InvalidSignature => write!(f, "r and s must be in the range 1..n"),
+            RecoveryError::InvalidR => write!(f, "r does not belong to a point on the curve"),
+            RecoveryError::PointAtInfinity => write!(f, "recovered the point at infinity"),
+            RecoveryError::InvalidLength(len) => write!(f, "compact signature must be 65 bytes, got {}", len),
+            RecoveryError::InvalidHeader(header) => write!(f, "invalid compact signature header {}", header),
+        }
+    }
+}
+
+impl std::error::Error for RecoveryError {}
+
+// A signature along with the recovery id needed to rebuild the public key
+// that made it, see S256Point::recover
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoverableSignature {
+    sig: Signature,
+    recid: u8,
+}
+
+impl RecoverableSignature {
+    pub fn new(sig: Signature, recid: u8) -> Self {
+        if recid > 3 {
+            panic!("Recovery id {} not in range 0 to 3", recid);
+        }
+        // r and s have to fit the 32 bytes each of the compact format
+        let n = S256Point::order();
+        if sig.r.is_zero() || sig.r >= n || sig.s.is_zero() || sig.s >= n {
+            panic!("r and s not in range 1 to n - 1");
+        }
+        Self { sig, recid }
+    }
+
+    pub fn get_signature(&self) -> Signature {
+        self.sig.clone()
+    }
+
+    pub fn get_recid(&self) -> u8 {
+        self.recid
+    }
+
+    pub fn recover(&self, z: BigUint) -> Result<S256Point, RecoveryError> {
+        S256Point::recover(z, &self.sig, self.recid)
+    }
+
+    // The 65 byte format used by signed messages: a header byte of
+    // 27 + recid (+ 4 when the key is compressed) followed by r and s as
+    // 32 byte big endian numbers
+    pub fn to_compact(&self, compressed: bool) -> [u8; 65] {
+        let mut result = [0u8; 65];
+        result[0] = 27 + self.recid + if compressed { 4 } else { 0 };
+
+        let r = self.sig.r.to_bytes_be();
+        let s = self.sig.s.to_bytes_be();
+        result[33 - r.len()..33].copy_from_slice(&r);
+        result[65 - s.len()..].copy_from_slice(&s);
+        result
+    }
+
+    // returns the signature and whether the key it recovers to is compressed
+    pub fn from_compact(bytes: &[u8]) -> Result<(Self, bool), RecoveryError> {
+        if bytes.len() != 65 {
+            return Err(RecoveryError::InvalidLength(bytes.len()));
+        }
+        let header = bytes[0];
+        if !(27..=34).contains(&header) {
+            return Err(RecoveryError::InvalidHeader(header));
+        }
+
+        let recid = (header - 27) & 3;
+        let compressed = header >= 31;
+        let r = BigUint::from_bytes_be(&bytes[1..33]);
+        let s = BigUint::from_bytes_be(&bytes[33..]);
+        let n = S256Point::order();
+        if r.is_zero() || r >= n || s.is_zero() || s >= n {
+            return Err(RecoveryError::InvalidSignature);
+        }
+        Ok((Self { sig: Signature::new(r, s), recid }, compressed))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use num::Num;
+
+    use super::*;
+
+    fn book_signature() -> Signature {
+        let r = BigUint::from_str_radix(
+            "37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6",
+            16,
+        ).unwrap();
+        let s = BigUint::from_str_radix(
+            "8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec",
+            16,
+        ).unwrap();
+        Signature::new(r, s)
+    }
+
+    #[test]
+    fn to_der_works() {
+        let der = hex::decode(
+            "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6\
+             0221008ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec",
+        ).unwrap();
+
+        assert_eq!(book_signature().to_der(), der);
+        assert_eq!(Signature::parse_der(&der).unwrap(), book_signature());
+
+        let small = Signature::new(BigUint::from(1u8), BigUint::from(0x80u8));
+        assert_eq!(small.to_der(), hex::decode("300702010102020080").unwrap());
+        assert_eq!(Signature::parse_der(&small.to_der()).unwrap(), small);
+    }
+
+    #[test]
+    fn parse_der_rejects_non_canonical() {
+        let der = book_signature().to_der();
+
+        let mut wrong_sequence = der.clone();
+        wrong_sequence[0] = 0x31;
+        assert_eq!(Signature::parse_der(&wrong_sequence), Err(DerError::InvalidSequenceTag(0x31)));
+
+        let mut trailing = der.clone();
+        trailing.push(0x01);
+        assert_eq!(
+            Signature::parse_der(&trailing),
+            Err(DerError::LengthMismatch { declared: 0x45, actual: 0x46 })
+        );
+
+        let mut wrong_integer = der.clone();
+        wrong_integer[2] = 0x03;
+        assert_eq!(Signature::parse_der(&wrong_integer), Err(DerError::InvalidIntegerTag(0x03)));
+
+        // r = 0x0001 has a zero byte it does not need
+        let padded = hex::decode("300702020001020101").unwrap();
+        assert_eq!(Signature::parse_der(&padded), Err(DerError::ExcessPadding));
+
+        // s = 0x80 without the zero byte reads as a negative number
+        let negative = hex::decode("3006020101020180").unwrap();
+        assert_eq!(Signature::parse_der(&negative), Err(DerError::NegativeInteger));
+
+        let empty_r = hex::decode("3006020002020101").unwrap();
+        assert_eq!(Signature::parse_der(&empty_r), Err(DerError::ZeroLengthInteger));
+
+        assert_eq!(Signature::parse_der(&der[..7]), Err(DerError::InvalidLength(7)));
+
+        // r = n
+        let mut overflow = hex::decode("3026022100").unwrap();
+        overflow.extend(S256Point::order().to_bytes_be());
+        overflow.extend([0x02, 0x01, 0x01]);
+        assert_eq!(Signature::parse_der(&overflow), Err(DerError::Overflow));
+    }
+
+    #[test]
+    fn compact_works() {
+        let sig = RecoverableSignature::new(book_signature(), 1);
+
+        let compact = sig.to_compact(true);
+        assert_eq!(compact[0], 32);
+        assert_eq!(&compact[1..33], &book_signature().get_r().to_bytes_be()[..]);
+        assert_eq!(RecoverableSignature::from_compact(&compact).unwrap(), (sig.clone(), true));
+        assert_eq!(RecoverableSignature::from_compact(&sig.to_compact(false)).unwrap(), (sig, false));
+
+        assert_eq!(RecoverableSignature::from_compact(&compact[1..]), Err(RecoveryError::InvalidLength(64)));
+        let mut bad_header = compact;
+        bad_header[0] = 35;
+        assert_eq!(RecoverableSignature::from_compact(&bad_header), Err(RecoveryError::InvalidHeader(35)));
+        let mut overflow = compact;
+        overflow[33..].copy_from_slice(&S256Point::order().to_bytes_be());
+        assert_eq!(RecoverableSignature::from_compact(&overflow), Err(RecoveryError::InvalidSignature));
+    }
+
+    #[test]
+    #[should_panic]
+    fn recoverable_signature_rejects_overflow() {
+        let s = BigUint::from(1u8) << 256;
+        RecoverableSignature::new(Signature::new(book_signature().get_r(), s), 0);
+    }
+
+    #[test]
+    fn parse_der_lax_works() {
+        let der = book_signature().to_der();
+        assert_eq!(Signature::parse_der_lax(&der).unwrap(), book_signature());
+
+        // excess padding, a long form sequence length and trailing garbage
+        let padded = hex::decode("30810802030000010202008001ff").unwrap();
+        assert!(Signature::parse_der(&padded).is_err());
+        assert_eq!(
+            Signature::parse_der_lax(&padded).unwrap(),
+            Signature::new(BigUint::from(1u8), BigUint::from(0x80u8))
+        );
+
+        // long form integer lengths
+        let long_lengths = hex::decode("3008028101010281020080").unwrap();
+        assert_eq!(
+            Signature::parse_der_lax(&long_lengths).unwrap(),
+            Signature::new(BigUint::from(1u8), BigUint::from(0x80u8))
+        );
+
+        // r = n parses, but as the invalid zero signature
+        let mut overflow = hex::decode("3026022100").unwrap();
+        overflow.extend(S256Point::order().to_bytes_be());
+        overflow.extend([0x02, 0x01, 0x01]);
+        assert_eq!(
+            Signature::parse_der_lax(&overflow).unwrap(),
+            Signature::new(BigUint::zero(), BigUint::zero())
+        );
+
+        // zero length bytes are skipped before counting, 3 significant ones
+        // are read and 4 are too many
+        let zero_length_bytes = hex::decode("300d028500000000010102810180").unwrap();
+        assert_eq!(
+            Signature::parse_der_lax(&zero_length_bytes).unwrap(),
+            Signature::new(BigUint::from(1u8), BigUint::from(0x80u8))
+        );
+        let three_length_bytes = hex::decode("3007028301000001").unwrap();
+        assert_eq!(
+            Signature::parse_der_lax(&three_length_bytes),
+            Err(DerError::LengthMismatch { declared: 0x010000, actual: 1 })
+        );
+        let four_length_bytes = hex::decode("300802840100000001").unwrap();
+        assert_eq!(Signature::parse_der_lax(&four_length_bytes), Err(DerError::LengthTooLong(4)));
+
+        assert_eq!(Signature::parse_der_lax(&[0x30]), Err(DerError::UnexpectedEnd));
+        assert_eq!(Signature::parse_der_lax(&hex::decode("3006030101").unwrap()), Err(DerError::InvalidIntegerTag(0x03)));
+        assert_eq!(
+            Signature::parse_der_lax(&hex::decode("300602050102").unwrap()),
+            Err(DerError::LengthMismatch { declared: 5, actual: 2 })
+        );
+    }
+}
@@ -0,0 +1,314 @@
+#![allow(unused)]
+
+use std::collections::BTreeMap;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+use super::keys::{
+    check_threshold, derive_coefficients, evaluate_commitment, evaluate_polynomial, verify_share, FrostError,
+    KeyPackage, PublicKeyPackage,
+};
+
+// Pedersen's distributed key generation as used by FROST: every participant
+// deals a secret of their own to everyone else, and the group secret is the
+// sum of all of them, which nobody ever learns.
+
+// Broadcast to everyone in the first round: the commitment to the polynomial
+// and a Schnorr proof of knowing its constant term, which stops anyone from
+// picking their commitment after seeing the others' (a rogue key attack)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round1Package {
+    commitment: Vec<S256Point>,
+    proof_r: S256Point,
+    proof_mu: BigUint,
+}
+
+impl Round1Package {
+    pub fn new(commitment: Vec<S256Point>, proof_r: S256Point, proof_mu: BigUint) -> Self {
+        Self { commitment, proof_r, proof_mu }
+    }
+
+    pub fn get_commitment(&self) -> Vec<S256Point> {
+        self.commitment.clone()
+    }
+
+    pub fn get_proof_r(&self) -> S256Point {
+        self.proof_r.clone()
+    }
+
+    pub fn get_proof_mu(&self) -> BigUint {
+        self.proof_mu.clone()
+    }
+}
+
+// Kept by the participant between rounds one and two. Not Clone, it holds
+// the polynomial and is consumed by part2.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Round1Secret {
+    identifier: u32,
+    coefficients: Vec<BigUint>,
+    commitment: Vec<S256Point>,
+    max_signers: usize,
+}
+
+// Kept by the participant between rounds two and three: their own share of
+// their own polynomial
+#[derive(Debug, PartialEq, Eq)]
+pub struct Round2Secret {
+    identifier: u32,
+    own_share: BigUint,
+    commitment: Vec<S256Point>,
+    threshold: usize,
+    max_signers: usize,
+}
+
+// c = H(id || C_0 || R) for the proof of knowledge
+fn proof_challenge(identifier: u32, constant: &S256Point, r: &S256Point) -> BigUint {
+    let mut preimage = identifier.to_be_bytes().to_vec();
+    preimage.extend(constant.sec(true));
+    preimage.extend(r.sec(true));
+    scalar::from_bytes_mod_order(&tagged_hash("FROST/dkg", &preimage))
+}
+
+// Picks a random polynomial of degree threshold - 1 and commits to it. rand
+// must be fresh randomness; the package goes to every other participant.
+pub fn part1(
+    identifier: u32,
+    threshold: usize,
+    max_signers: usize,
+    rand: &[u8; 32],
+) -> Result<(Round1Secret, Round1Package), FrostError> {
+    check_threshold(threshold, max_signers)?;
+    if identifier == 0 {
+        return Err(FrostError::InvalidIdentifier(identifier));
+    }
+
+    // one more than needed, the last is the nonce for the proof
+    let mut coefficients = derive_coefficients("FROST/dkg coefficient", rand, &BigUint::from(identifier), threshold + 1);
+    let k = coefficients.pop().unwrap();
+    let g = S256Point::generator();
+    let commitment: Vec<S256Point> = coefficients.iter().map(|a| g.clone() * a.clone()).collect();
+
+    let proof_r = g * k.clone();
+    let c = proof_challenge(identifier, &commitment[0], &proof_r);
+    let proof_mu = (k + &coefficients[0] * c) % S256Point::order();
+
+    let secret = Round1Secret { identifier, coefficients, commitment: commitment.clone(), max_signers };
+    Ok((secret, Round1Package { commitment, proof_r, proof_mu }))
+}
+
+// Checks everyone else's round one package and computes the share f_i(j) for
+// each other participant j. The shares must go to their owners over a private
+// and authenticated channel.
+pub fn part2(
+    secret: Round1Secret,
+    round1_packages: &BTreeMap<u32, Round1Package>,
+) -> Result<(Round2Secret, BTreeMap<u32, BigUint>), FrostError> {
+    let threshold = secret.coefficients.len();
+    check_packages(secret.identifier, secret.max_signers, round1_packages)?;
+
+    let g = S256Point::generator();
+    let mut shares = BTreeMap::new();
+    for (id, package) in round1_packages {
+        check_commitment(*id, package, threshold)?;
+        let c = proof_challenge(*id, &package.commitment[0], &package.proof_r);
+        let expected = package.proof_r.clone() + package.commitment[0].clone() * c;
+        if package.proof_mu >= S256Point::order() || g.clone() * package.proof_mu.clone() != expected {
+            return Err(FrostError::InvalidProofOfKnowledge(*id));
+        }
+        shares.insert(*id, evaluate_polynomial(&secret.coefficients, *id));
+    }
+
+    let own_share = evaluate_polynomial(&secret.coefficients, secret.identifier);
+    let round2 = Round2Secret {
+        identifier: secret.identifier,
+        own_share,
+        commitment: secret.commitment,
+        threshold,
+        max_signers: secret.max_signers,
+    };
+    Ok((round2, shares))
+}
+
+// Checks the shares received from everyone else against their commitments
+// and adds them up to the final secret share. Every participant ends up with
+// the same group key and verifying shares.
+pub fn part3(
+    secret: &Round2Secret,
+    round1_packages: &BTreeMap<u32, Round1Package>,
+    round2_shares: &BTreeMap<u32, BigUint>,
+) -> Result<(KeyPackage, PublicKeyPackage), FrostError> {
+    check_packages(secret.identifier, secret.max_signers, round1_packages)?;
+
+    let n = S256Point::order();
+    let mut secret_share = secret.own_share.clone();
+    for (id, package) in round1_packages {
+        check_commitment(*id, package, secret.threshold)?;
+        let share = round2_shares.get(id).ok_or(FrostError::InvalidSecretShare(*id))?;
+        if *share >= n || !verify_share(secret.identifier, share, &package.commitment) {
+            return Err(FrostError::InvalidSecretShare(*id));
+        }
+        secret_share = (secret_share + share) % &n;
+    }
+
+    let mut commitments: BTreeMap<u32, &[S256Point]> =
+        round1_packages.iter().map(|(id, package)| (*id, &package.commitment[..])).collect();
+    commitments.insert(secret.identifier, &secret.commitment);
+
+    let group_key = commitments
+        .values()
+        .fold(S256Point::infinity_point(), |acc, commitment| acc + commitment[0].clone());
+    let verifying_shares: BTreeMap<u32, S256Point> = commitments
+        .keys()
+        .map(|j| {
+            let share = commitments
+                .values()
+                .fold(S256Point::infinity_point(), |acc, commitment| acc + evaluate_commitment(commitment, *j));
+            (*j, share)
+        })
+        .collect();
+
+    let key_package = KeyPackage::new(secret.identifier, secret_share, group_key.clone(), secret.threshold);
+    Ok((key_package, PublicKeyPackage::new(verifying_shares, group_key)))
+}
+
+// there must be a package from each of the other max_signers - 1 participants
+fn check_packages(
+    identifier: u32,
+    max_signers: usize,
+    round1_packages: &BTreeMap<u32, Round1Package>,
+) -> Result<(), FrostError> {
+    if round1_packages.contains_key(&identifier) {
+        return Err(FrostError::DuplicateIdentifier(identifier));
+    }
+    if round1_packages.contains_key(&0) {
+        return Err(FrostError::InvalidIdentifier(0));
+    }
+    if round1_packages.len() + 1 != max_signers {
+        return Err(FrostError::NotEnoughParticipants { threshold: max_signers, participants: round1_packages.len() + 1 });
+    }
+    Ok(())
+}
+
+// one point per coefficient, and a constant term that can be hashed and
+// added into the group key
+fn check_commitment(identifier: u32, package: &Round1Package, threshold: usize) -> Result<(), FrostError> {
+    if package.commitment.len() != threshold || package.commitment[0] == S256Point::infinity_point() {
+        return Err(FrostError::InvalidCommitment(identifier));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::frost::keys::lagrange_coefficient;
+
+    use super::*;
+
+    // runs the whole DKG between participants 1..=max_signers
+    pub fn run_dkg(threshold: usize, max_signers: usize) -> (Vec<KeyPackage>, PublicKeyPackage) {
+        let ids: Vec<u32> = (1..=max_signers as u32).collect();
+        let mut round1_secrets = BTreeMap::new();
+        let mut round1_packages = BTreeMap::new();
+        for id in &ids {
+            let (secret, package) = part1(*id, threshold, max_signers, &[*id as u8; 32]).unwrap();
+            round1_secrets.insert(*id, secret);
+            round1_packages.insert(*id, package);
+        }
+        let others = |id: u32| -> BTreeMap<u32, Round1Package> {
+            round1_packages.iter().filter(|(other, _)| **other != id).map(|(k, v)| (*k, v.clone())).collect()
+        };
+
+        let mut round2_secrets = BTreeMap::new();
+        // (from, to) -> share
+        let mut sent = BTreeMap::new();
+        for (id, secret) in round1_secrets {
+            let (round2, shares) = part2(secret, &others(id)).unwrap();
+            round2_secrets.insert(id, round2);
+            for (to, share) in shares {
+                sent.insert((id, to), share);
+            }
+        }
+
+        let mut key_packages = Vec::new();
+        let mut public = None;
+        for id in &ids {
+            let received: BTreeMap<u32, BigUint> =
+                sent.iter().filter(|((_, to), _)| to == id).map(|((from, _), share)| (*from, share.clone())).collect();
+            let (key_package, public_package) = part3(&round2_secrets[id], &others(*id), &received).unwrap();
+            if let Some(previous) = &public {
+                assert_eq!(previous, &public_package);
+            }
+            public = Some(public_package);
+            key_packages.push(key_package);
+        }
+        (key_packages, public.unwrap())
+    }
+
+    #[test]
+    fn dkg_works() {
+        let (key_packages, public) = run_dkg(2, 3);
+        for key_package in &key_packages {
+            assert_eq!(key_package.get_group_key(), public.get_group_key());
+            assert_eq!(public.get_verifying_share(key_package.get_identifier()), Some(key_package.get_verifying_share()));
+        }
+
+        // the shares of any two interpolate to the (never computed) group secret
+        let ids = [2u32, 3];
+        let secret = ids.iter().fold(BigUint::zero(), |acc, id| {
+            acc + lagrange_coefficient(*id, &ids).unwrap() * key_packages[*id as usize - 1].get_secret_share()
+        }) % S256Point::order();
+        assert_eq!(S256Point::generator() * secret, public.get_group_key());
+    }
+
+    #[test]
+    fn dkg_rejects_cheaters() {
+        let (secret1, package1) = part1(1, 2, 3, &[1; 32]).unwrap();
+        let (_, package2) = part1(2, 2, 3, &[2; 32]).unwrap();
+        let (_, package3) = part1(3, 2, 3, &[3; 32]).unwrap();
+
+        // participant 3 swaps in someone else's commitment without knowing its secret
+        let mut forged = package3.clone();
+        forged.commitment = package2.commitment.clone();
+        let packages = BTreeMap::from([(2, package2.clone()), (3, forged)]);
+        assert_eq!(part2(secret1, &packages).unwrap_err(), FrostError::InvalidProofOfKnowledge(3));
+
+        // participant 2 sends participant 1 a share off their polynomial
+        let (secret1, _) = part1(1, 2, 3, &[1; 32]).unwrap();
+        let (secret2, _) = part1(2, 2, 3, &[2; 32]).unwrap();
+        let (secret3, _) = part1(3, 2, 3, &[3; 32]).unwrap();
+        let packages = BTreeMap::from([(2, package2.clone()), (3, package3.clone())]);
+        let (round2, _) = part2(secret1, &packages).unwrap();
+        let (_, shares2) = part2(secret2, &BTreeMap::from([(1, package1.clone()), (3, package3.clone())])).unwrap();
+        let (_, shares3) = part2(secret3, &BTreeMap::from([(1, package1), (2, package2)])).unwrap();
+        let received = BTreeMap::from([(2, shares2[&1].clone() + 1u8), (3, shares3[&1].clone())]);
+        assert_eq!(part3(&round2, &packages, &received).unwrap_err(), FrostError::InvalidSecretShare(2));
+    }
+
+    #[test]
+    fn dkg_rejects_bad_commitments() {
+        let (secret1, package1) = part1(1, 2, 3, &[1; 32]).unwrap();
+        let (secret2, package2) = part1(2, 2, 3, &[2; 32]).unwrap();
+        let (_, package3) = part1(3, 2, 3, &[3; 32]).unwrap();
+        let packages = BTreeMap::from([(2, package2.clone()), (3, package3.clone())]);
+        let (round2, _) = part2(secret1, &packages).unwrap();
+        let (_, shares2) = part2(secret2, &BTreeMap::from([(1, package1), (3, package3.clone())])).unwrap();
+        let received = BTreeMap::from([(2, shares2[&1].clone()), (3, BigUint::from(1u8))]);
+
+        // part3 doesn't trust that the packages are the ones part2 checked
+        let mut short = package3.clone();
+        short.commitment.clear();
+        let with_short = BTreeMap::from([(2, package2.clone()), (3, short)]);
+        assert_eq!(part3(&round2, &with_short, &received).unwrap_err(), FrostError::InvalidCommitment(3));
+
+        let mut infinite = package3;
+        infinite.commitment[0] = S256Point::infinity_point();
+        let with_infinite = BTreeMap::from([(2, package2), (3, infinite)]);
+        assert_eq!(part3(&round2, &with_infinite, &received).unwrap_err(), FrostError::InvalidCommitment(3));
+
+        let (secret1, _) = part1(1, 2, 3, &[1; 32]).unwrap();
+        assert_eq!(part2(secret1, &with_infinite).unwrap_err(), FrostError::InvalidCommitment(3));
+    }
+}
@@ -0,0 +1,283 @@
+#![allow(unused)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use num::{BigUint, One, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+use crate::schnorr::bip340::XOnlyPublicKey;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrostError {
+    // the threshold must be at least 1 and at most the number of signers
+    InvalidThreshold { threshold: usize, max_signers: usize },
+    // identifiers are the x coordinates of the shares, so 0 is not allowed
+    InvalidIdentifier(u32),
+    // the same identifier shows up twice
+    DuplicateIdentifier(u32),
+    // fewer participants than the threshold
+    NotEnoughParticipants { threshold: usize, participants: usize },
+    // the participant's commitment is malformed or missing
+    InvalidCommitment(u32),
+    // the participant's proof of knowledge of their secret doesn't verify
+    InvalidProofOfKnowledge(u32),
+    // the share sent by the participant doesn't match their commitment
+    InvalidSecretShare(u32),
+    // the participant's signature share doesn't verify
+    InvalidSignatureShare(u32),
+    // the participant is not part of the signing package
+    UnknownParticipant(u32),
+    // every share checks out but the aggregate doesn't verify under the
+    // group key, so the verifying shares don't match it
+    InvalidSignature,
+}
+
+impl fmt::Display for FrostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrostError::InvalidThreshold { threshold, max_signers } => {
+                write!(f, "invalid threshold {} of {} signers", threshold, max_signers)
+            }
+            FrostError::InvalidIdentifier(id) => write!(f, "invalid identifier {}", id),
+            FrostError::DuplicateIdentifier(id) => write!(f, "duplicate identifier {}", id),
+            FrostError::NotEnoughParticipants { threshold, participants } => {
+                write!(f, "{} participants, need at least {}", participants, threshold)
+            }
+            FrostError::InvalidCommitment(id) => write!(f, "invalid commitment from participant {}", id),
+            FrostError::InvalidProofOfKnowledge(id) => write!(f, "invalid proof of knowledge from participant {}", id),
+            FrostError::InvalidSecretShare(id) => write!(f, "invalid secret share from participant {}", id),
+            FrostError::InvalidSignatureShare(id) => write!(f, "invalid signature share from participant {}", id),
+            FrostError::UnknownParticipant(id) => write!(f, "unknown participant {}", id),
+            FrostError::InvalidSignature => write!(f, "signature doesn't verify under the group key"),
+        }
+    }
+}
+
+impl std::error::Error for FrostError {}
+
+// What a single participant holds after key generation: their share s_i =
+// f(i) of the group secret f(0), the matching s_i * G and the group key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPackage {
+    identifier: u32,
+    secret_share: BigUint,
+    verifying_share: S256Point,
+    group_key: S256Point,
+    threshold: usize,
+}
+
+impl KeyPackage {
+    pub fn new(identifier: u32, secret_share: BigUint, group_key: S256Point, threshold: usize) -> Self {
+        if identifier == 0 {
+            panic!("Identifier must not be zero");
+        }
+        if secret_share.is_zero() || secret_share >= S256Point::order() {
+            panic!("Secret share not in range 1 to n - 1");
+        }
+        let verifying_share = S256Point::generator() * secret_share.clone();
+        Self { identifier, secret_share, verifying_share, group_key, threshold }
+    }
+
+    pub fn get_identifier(&self) -> u32 {
+        self.identifier
+    }
+
+    pub fn get_secret_share(&self) -> BigUint {
+        self.secret_share.clone()
+    }
+
+    pub fn get_verifying_share(&self) -> S256Point {
+        self.verifying_share.clone()
+    }
+
+    pub fn get_group_key(&self) -> S256Point {
+        self.group_key.clone()
+    }
+
+    pub fn get_threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+// The public side of key generation, which the coordinator needs to check
+// signature shares: everyone's s_i * G and the group key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyPackage {
+    verifying_shares: BTreeMap<u32, S256Point>,
+    group_key: S256Point,
+}
+
+impl PublicKeyPackage {
+    pub fn new(verifying_shares: BTreeMap<u32, S256Point>, group_key: S256Point) -> Self {
+        Self { verifying_shares, group_key }
+    }
+
+    pub fn get_verifying_share(&self, identifier: u32) -> Option<S256Point> {
+        self.verifying_shares.get(&identifier).cloned()
+    }
+
+    pub fn get_group_key(&self) -> S256Point {
+        self.group_key.clone()
+    }
+
+    // the key BIP340 signatures from the group verify under
+    pub fn get_xonly_group_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.group_key)
+    }
+}
+
+// coefficients[0] + coefficients[1] x + ... evaluated with Horner's rule
+pub fn evaluate_polynomial(coefficients: &[BigUint], x: u32) -> BigUint {
+    let n = S256Point::order();
+    let x = BigUint::from(x);
+    coefficients
+        .iter()
+        .rev()
+        .fold(BigUint::zero(), |acc, coefficient| (acc * &x + coefficient) % &n)
+}
+
+// the same evaluation "in the exponent": sum of x^k C_k, which is f(x) * G
+// when C_k = a_k * G
+pub fn evaluate_commitment(commitment: &[S256Point], x: u32) -> S256Point {
+    let n = S256Point::order();
+    let mut power = BigUint::one();
+    let mut terms = Vec::with_capacity(commitment.len());
+    for point in commitment {
+        terms.push((power.clone(), point.clone()));
+        power = power * x % &n;
+    }
+    S256Point::multi_mul(&terms)
+}
+
+// Feldman's check that the share s_i = f(i) really lies on the polynomial
+// committed to, without learning anything else about it
+pub fn verify_share(identifier: u32, share: &BigUint, commitment: &[S256Point]) -> bool {
+    S256Point::generator() * share.clone() == evaluate_commitment(commitment, identifier)
+}
+
+// lambda_i = product over j != i of j / (j - i), which weighs f(i) so that the
+// sum over any threshold sized set of shares gives back f(0)
+pub fn lagrange_coefficient(identifier: u32, identifiers: &[u32]) -> Result<BigUint, FrostError> {
+    let n = S256Point::order();
+    if identifier == 0 {
+        return Err(FrostError::InvalidIdentifier(identifier));
+    }
+    if !identifiers.contains(&identifier) {
+        return Err(FrostError::UnknownParticipant(identifier));
+    }
+
+    let mut numerator = BigUint::one();
+    let mut denominator = BigUint::one();
+    for (index, other) in identifiers.iter().enumerate() {
+        if *other == 0 {
+            return Err(FrostError::InvalidIdentifier(*other));
+        }
+        if identifiers[..index].contains(other) {
+            return Err(FrostError::DuplicateIdentifier(*other));
+        }
+        if *other == identifier {
+            continue;
+        }
+        numerator = numerator * other % &n;
+        let difference = (BigUint::from(*other) + &n - identifier) % &n;
+        denominator = denominator * difference % &n;
+    }
+    Ok(numerator * scalar::inverse(&denominator) % n)
+}
+
+pub(super) fn check_threshold(threshold: usize, max_signers: usize) -> Result<(), FrostError> {
+    if threshold == 0 || threshold > max_signers || max_signers > u32::MAX as usize {
+        return Err(FrostError::InvalidThreshold { threshold, max_signers });
+    }
+    Ok(())
+}
+
+// the polynomial's coefficients after the constant term, derived from rand
+// and the secret so that a weak rand alone doesn't give them away
+pub(super) fn derive_coefficients(tag: &str, rand: &[u8; 32], secret: &BigUint, count: usize) -> Vec<BigUint> {
+    (0..count as u32)
+        .map(|i| {
+            let mut preimage = rand.to_vec();
+            preimage.extend_from_slice(&scalar::to_bytes(secret));
+            preimage.extend_from_slice(&i.to_be_bytes());
+            scalar::from_bytes_mod_order(&tagged_hash(tag, &preimage))
+        })
+        .collect()
+}
+
+// Splits secret into max_signers shares, any threshold of which can sign.
+// The dealer sees the whole secret, so this only fits when one party is
+// trusted anyway; otherwise use the DKG. rand must be fresh randomness. The
+// commitment to the polynomial is returned so participants can check their
+// shares with verify_share.
+pub fn trusted_dealer_keygen(
+    secret: &PrivateKey,
+    threshold: usize,
+    max_signers: usize,
+    rand: &[u8; 32],
+) -> Result<(Vec<KeyPackage>, PublicKeyPackage, Vec<S256Point>), FrostError> {
+    check_threshold(threshold, max_signers)?;
+
+    let mut coefficients = vec![secret.get_secret()];
+    coefficients.extend(derive_coefficients("FROST/dealer", rand, &secret.get_secret(), threshold - 1));
+    let commitment: Vec<S256Point> = coefficients.iter().map(|a| S256Point::generator() * a.clone()).collect();
+    let group_key = secret.get_point();
+
+    let mut key_packages = Vec::with_capacity(max_signers);
+    let mut verifying_shares = BTreeMap::new();
+    for identifier in 1..=max_signers as u32 {
+        let share = evaluate_polynomial(&coefficients, identifier);
+        let key_package = KeyPackage::new(identifier, share, group_key.clone(), threshold);
+        verifying_shares.insert(identifier, key_package.get_verifying_share());
+        key_packages.push(key_package);
+    }
+    Ok((key_packages, PublicKeyPackage::new(verifying_shares, group_key), commitment))
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn lagrange_interpolation_works() {
+        // f(x) = 5 + 3x + 2x^2
+        let coefficients: Vec<BigUint> = [5u8, 3, 2].iter().map(|c| BigUint::from(*c)).collect();
+        let ids = [2u32, 4, 7];
+        let secret = ids.iter().fold(BigUint::zero(), |acc, id| {
+            acc + lagrange_coefficient(*id, &ids).unwrap() * evaluate_polynomial(&coefficients, *id)
+        }) % S256Point::order();
+        assert_eq!(secret, BigUint::from(5u8));
+
+        assert_eq!(lagrange_coefficient(3, &ids), Err(FrostError::UnknownParticipant(3)));
+        assert_eq!(lagrange_coefficient(2, &[2, 4, 4]), Err(FrostError::DuplicateIdentifier(4)));
+    }
+
+    #[test]
+    fn trusted_dealer_keygen_works() {
+        let secret = PrivateKey::new(BigUint::from(0x5ec2e7u64));
+        let (key_packages, public, commitment) = trusted_dealer_keygen(&secret, 2, 3, &[7; 32]).unwrap();
+        assert_eq!(key_packages.len(), 3);
+        assert_eq!(public.get_group_key(), secret.get_point());
+
+        for key_package in &key_packages {
+            let id = key_package.get_identifier();
+            assert!(verify_share(id, &key_package.get_secret_share(), &commitment));
+            assert_eq!(public.get_verifying_share(id), Some(key_package.get_verifying_share()));
+        }
+        assert!(!verify_share(1, &key_packages[1].get_secret_share(), &commitment));
+
+        // any two shares give back the secret
+        let ids = [1u32, 3];
+        let recovered = ids.iter().fold(BigUint::zero(), |acc, id| {
+            acc + lagrange_coefficient(*id, &ids).unwrap() * key_packages[*id as usize - 1].get_secret_share()
+        }) % S256Point::order();
+        assert_eq!(recovered, secret.get_secret());
+
+        assert_eq!(
+            trusted_dealer_keygen(&secret, 4, 3, &[7; 32]),
+            Err(FrostError::InvalidThreshold { threshold: 4, max_signers: 3 })
+        );
+    }
+}
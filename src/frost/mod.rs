@@ -0,0 +1,3 @@
+pub mod keys;
+pub mod dkg;
+pub mod sign;
@@ -0,0 +1,354 @@
+#![allow(unused)]
+
+use std::collections::BTreeMap;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+use crate::hashes::sha256::{sha256, tagged_hash};
+use crate::schnorr::bip340::{challenge, SchnorrSignature, XOnlyPublicKey};
+
+use super::keys::{lagrange_coefficient, FrostError, KeyPackage, PublicKeyPackage};
+
+// A participant's hiding and binding nonces d and e. Not Clone: signing
+// consumes them, as signing twice with the same nonces leaks the share.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SigningNonces {
+    hiding: BigUint,
+    binding: BigUint,
+}
+
+// D = dG and E = eG, sent to the coordinator in the first round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningCommitments {
+    hiding: S256Point,
+    binding: S256Point,
+}
+
+impl SigningCommitments {
+    pub fn new(hiding: S256Point, binding: S256Point) -> Self {
+        Self { hiding, binding }
+    }
+
+    pub fn get_hiding(&self) -> S256Point {
+        self.hiding.clone()
+    }
+
+    pub fn get_binding(&self) -> S256Point {
+        self.binding.clone()
+    }
+}
+
+// Round one. rand must be fresh randomness; it is hashed together with the
+// secret share so a bad rand alone doesn't expose the nonces.
+pub fn commit(key_package: &KeyPackage, rand: &[u8; 32]) -> (SigningNonces, SigningCommitments) {
+    let nonce = |i: u8| {
+        let mut preimage = rand.to_vec();
+        preimage.extend_from_slice(&scalar::to_bytes(&key_package.get_secret_share()));
+        preimage.push(i);
+        scalar::from_bytes_mod_order(&tagged_hash("FROST/nonce", &preimage))
+    };
+    let (hiding, binding) = (nonce(0), nonce(1));
+    if hiding.is_zero() || binding.is_zero() {
+        panic!("Nonce is zero");
+    }
+
+    let g = S256Point::generator();
+    let commitments = SigningCommitments { hiding: g.clone() * hiding.clone(), binding: g * binding.clone() };
+    (SigningNonces { hiding, binding }, commitments)
+}
+
+// What the coordinator sends out for round two: who signs and the message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningPackage {
+    commitments: BTreeMap<u32, SigningCommitments>,
+    msg: Vec<u8>,
+}
+
+impl SigningPackage {
+    pub fn new(commitments: BTreeMap<u32, SigningCommitments>, msg: &[u8]) -> Self {
+        Self { commitments, msg: msg.to_vec() }
+    }
+
+    pub fn get_msg(&self) -> Vec<u8> {
+        self.msg.clone()
+    }
+
+    fn identifiers(&self) -> Vec<u32> {
+        self.commitments.keys().copied().collect()
+    }
+
+    // rho_i = H(Q || H(msg) || H(commitments) || i). Binding each nonce to the
+    // full signing set and message is what makes concurrent sessions safe.
+    fn binding_factors(&self, group_key: &XOnlyPublicKey) -> BTreeMap<u32, BigUint> {
+        let encoded: Vec<u8> = self
+            .commitments
+            .iter()
+            .flat_map(|(id, c)| [id.to_be_bytes().to_vec(), c.hiding.sec(true), c.binding.sec(true)].concat())
+            .collect();
+        let mut prefix = group_key.to_bytes().to_vec();
+        prefix.extend_from_slice(&sha256(&self.msg));
+        prefix.extend_from_slice(&sha256(&encoded));
+
+        self.commitments
+            .keys()
+            .map(|id| {
+                let mut preimage = prefix.clone();
+                preimage.extend_from_slice(&id.to_be_bytes());
+                (*id, scalar::from_bytes_mod_order(&tagged_hash("FROST/rho", &preimage)))
+            })
+            .collect()
+    }
+
+    // R = sum of D_i + rho_i E_i, along with the binding factors. The
+    // commitments come from the other signers, and one at infinity has no
+    // encoding to hash.
+    fn group_commitment(&self, group_key: &XOnlyPublicKey) -> Result<(S256Point, BTreeMap<u32, BigUint>), FrostError> {
+        let infinity = S256Point::infinity_point();
+        if let Some((id, _)) = self.commitments.iter().find(|(_, c)| c.hiding == infinity || c.binding == infinity) {
+            return Err(FrostError::InvalidCommitment(*id));
+        }
+        let rhos = self.binding_factors(group_key);
+        let mut terms = Vec::with_capacity(2 * self.commitments.len());
+        for (id, c) in &self.commitments {
+            terms.push((BigUint::from(1u8), c.hiding.clone()));
+            terms.push((rhos[id].clone(), c.binding.clone()));
+        }
+        Ok((S256Point::multi_mul(&terms), rhos))
+    }
+}
+
+// Round two: z_i = d_i + rho_i e_i + lambda_i s_i c. As in BIP340 the nonces
+// are negated when R has an odd y and the share when the group key does.
+pub fn sign(
+    signing_package: &SigningPackage,
+    nonces: SigningNonces,
+    key_package: &KeyPackage,
+) -> Result<BigUint, FrostError> {
+    let n = S256Point::order();
+    let id = key_package.get_identifier();
+    let ids = signing_package.identifiers();
+    if ids.len() < key_package.get_threshold() {
+        return Err(FrostError::NotEnoughParticipants { threshold: key_package.get_threshold(), participants: ids.len() });
+    }
+    let own = signing_package.commitments.get(&id).ok_or(FrostError::UnknownParticipant(id))?;
+    let g = S256Point::generator();
+    if own.hiding != g.clone() * nonces.hiding.clone() || own.binding != g * nonces.binding.clone() {
+        return Err(FrostError::InvalidCommitment(id));
+    }
+
+    let group_key = key_package.get_group_key();
+    let xonly = XOnlyPublicKey::from_point(&group_key);
+    let (r, rhos) = signing_package.group_commitment(&xonly)?;
+    if r == S256Point::infinity_point() {
+        return Err(FrostError::InvalidCommitment(id));
+    }
+    let c = challenge(&r.get_x().unwrap().to_bytes(), &xonly, &signing_package.msg);
+    let lambda = lagrange_coefficient(id, &ids)?;
+
+    let mut k = (nonces.hiding + &rhos[&id] * nonces.binding) % &n;
+    if !r.has_even_y() {
+        k = scalar::negate(&k);
+    }
+    let mut share = key_package.get_secret_share();
+    if !group_key.has_even_y() {
+        share = scalar::negate(&share);
+    }
+    Ok((k + lambda * share % &n * c) % n)
+}
+
+// z_i G = R_i + lambda_i c Y_i with R_i = D_i + rho_i E_i and the same
+// negations as sign, so a bad share can be pinned on its signer
+pub fn verify_signature_share(
+    identifier: u32,
+    signature_share: &BigUint,
+    signing_package: &SigningPackage,
+    public_key_package: &PublicKeyPackage,
+) -> Result<(), FrostError> {
+    let n = S256Point::order();
+    let invalid = FrostError::InvalidSignatureShare(identifier);
+    let commitments = signing_package.commitments.get(&identifier).ok_or(FrostError::UnknownParticipant(identifier))?;
+    let verifying_share = public_key_package
+        .get_verifying_share(identifier)
+        .ok_or(FrostError::UnknownParticipant(identifier))?;
+    if *signature_share >= n {
+        return Err(invalid);
+    }
+
+    let group_key = public_key_package.get_group_key();
+    let xonly = XOnlyPublicKey::from_point(&group_key);
+    let (r, rhos) = signing_package.group_commitment(&xonly)?;
+    if r == S256Point::infinity_point() {
+        return Err(invalid);
+    }
+    let c = challenge(&r.get_x().unwrap().to_bytes(), &xonly, &signing_package.msg);
+    let lambda = lagrange_coefficient(identifier, &signing_package.identifiers())?;
+
+    let mut r_i = commitments.hiding.clone() + commitments.binding.clone() * rhos[&identifier].clone();
+    if !r.has_even_y() {
+        r_i = -r_i;
+    }
+    let mut y_i = verifying_share;
+    if !group_key.has_even_y() {
+        y_i = -y_i;
+    }
+    if S256Point::generator() * signature_share.clone() != r_i + y_i * (lambda * c % n) {
+        return Err(invalid);
+    }
+    Ok(())
+}
+
+// Sums the shares into (R.x, z), a plain BIP340 signature under the group
+// key. When the result doesn't verify every share is checked to find out
+// who misbehaved, and if they all pass the public key package itself is bad.
+pub fn aggregate(
+    signing_package: &SigningPackage,
+    signature_shares: &BTreeMap<u32, BigUint>,
+    public_key_package: &PublicKeyPackage,
+) -> Result<SchnorrSignature, FrostError> {
+    let n = S256Point::order();
+    for id in signing_package.commitments.keys() {
+        if !signature_shares.contains_key(id) {
+            return Err(FrostError::InvalidSignatureShare(*id));
+        }
+    }
+    if let Some(id) = signature_shares.keys().find(|id| !signing_package.commitments.contains_key(id)) {
+        return Err(FrostError::UnknownParticipant(*id));
+    }
+
+    let xonly = public_key_package.get_xonly_group_key();
+    let (r, _) = signing_package.group_commitment(&xonly)?;
+    if r == S256Point::infinity_point() {
+        return Err(FrostError::InvalidCommitment(*signing_package.commitments.keys().next().unwrap_or(&0)));
+    }
+    let z = signature_shares.values().fold(BigUint::zero(), |acc, share| (acc + share) % &n);
+    let sig = SchnorrSignature::new(r.get_x().unwrap().get_num(), z);
+
+    if !xonly.verify(&signing_package.msg, &sig) {
+        for (id, share) in signature_shares {
+            verify_signature_share(*id, share, signing_package, public_key_package)?;
+        }
+        return Err(FrostError::InvalidSignature);
+    }
+    Ok(sig)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::finite_field::private_key::PrivateKey;
+    use crate::frost::dkg::tests::run_dkg;
+    use crate::frost::keys::trusted_dealer_keygen;
+
+    use super::*;
+
+    // runs both signing rounds with the given signers
+    fn sign_with(key_packages: &[KeyPackage], signers: &[u32], msg: &[u8]) -> (SigningPackage, BTreeMap<u32, BigUint>) {
+        let mut nonces = BTreeMap::new();
+        let mut commitments = BTreeMap::new();
+        for id in signers {
+            let (nonce, commitment) = commit(&key_packages[*id as usize - 1], &[*id as u8 + 100; 32]);
+            nonces.insert(*id, nonce);
+            commitments.insert(*id, commitment);
+        }
+        let signing_package = SigningPackage::new(commitments, msg);
+        let shares = nonces
+            .into_iter()
+            .map(|(id, nonce)| (id, sign(&signing_package, nonce, &key_packages[id as usize - 1]).unwrap()))
+            .collect();
+        (signing_package, shares)
+    }
+
+    #[test]
+    fn trusted_dealer_signing_works() {
+        let msg = b"FROST with a dealer";
+        // group keys with an odd and an even y
+        for (secret, signers) in [(0xf205u64, [1u32, 2, 3]), (3, [2, 4, 5])] {
+            let secret = PrivateKey::new(BigUint::from(secret));
+            let (key_packages, public, _) = trusted_dealer_keygen(&secret, 3, 5, &[9; 32]).unwrap();
+
+            let (signing_package, shares) = sign_with(&key_packages, &signers, msg);
+            for (id, share) in &shares {
+                assert_eq!(verify_signature_share(*id, share, &signing_package, &public), Ok(()));
+            }
+            let sig = aggregate(&signing_package, &shares, &public).unwrap();
+            assert!(secret.x_only_public_key().verify(msg, &sig));
+        }
+    }
+
+    #[test]
+    fn dkg_signing_works() {
+        let (key_packages, public) = run_dkg(2, 3);
+        let msg = b"FROST with a DKG";
+        let (signing_package, shares) = sign_with(&key_packages, &[1, 3], msg);
+        let sig = aggregate(&signing_package, &shares, &public).unwrap();
+        assert!(public.get_xonly_group_key().verify(msg, &sig));
+
+        // too few signers
+        let (nonce, commitment) = commit(&key_packages[0], &[1; 32]);
+        let lonely = SigningPackage::new(BTreeMap::from([(1, commitment)]), msg);
+        assert_eq!(
+            sign(&lonely, nonce, &key_packages[0]),
+            Err(FrostError::NotEnoughParticipants { threshold: 2, participants: 1 })
+        );
+    }
+
+    #[test]
+    fn aggregate_finds_bad_share() {
+        let secret = PrivateKey::new(BigUint::from(0xbad5u64));
+        let (key_packages, public, _) = trusted_dealer_keygen(&secret, 2, 3, &[1; 32]).unwrap();
+        let (signing_package, mut shares) = sign_with(&key_packages, &[1, 2], b"msg");
+
+        *shares.get_mut(&2).unwrap() += 1u8;
+        assert_eq!(aggregate(&signing_package, &shares, &public), Err(FrostError::InvalidSignatureShare(2)));
+    }
+
+    #[test]
+    fn infinite_commitment_is_rejected() {
+        let secret = PrivateKey::new(BigUint::from(0xbad5u64));
+        let (key_packages, public, _) = trusted_dealer_keygen(&secret, 2, 3, &[1; 32]).unwrap();
+        let (signing_package, shares) = sign_with(&key_packages, &[1, 2], b"msg");
+
+        // participant 2 sends the point at infinity as their hiding commitment
+        let (nonce, own) = commit(&key_packages[0], &[101; 32]);
+        let binding = signing_package.commitments[&2].get_binding();
+        let infinite = SigningCommitments::new(S256Point::infinity_point(), binding);
+        let bad_package = SigningPackage::new(BTreeMap::from([(1, own), (2, infinite)]), b"msg");
+        assert_eq!(sign(&bad_package, nonce, &key_packages[0]), Err(FrostError::InvalidCommitment(2)));
+        assert_eq!(aggregate(&bad_package, &shares, &public), Err(FrostError::InvalidCommitment(2)));
+        assert_eq!(
+            verify_signature_share(1, &shares[&1], &bad_package, &public),
+            Err(FrostError::InvalidCommitment(2))
+        );
+    }
+
+    #[test]
+    fn aggregate_rejects_tampered_verifying_share() {
+        let n = S256Point::order();
+        let secret = PrivateKey::new(BigUint::from(0xbad5u64));
+        let (key_packages, public, _) = trusted_dealer_keygen(&secret, 2, 3, &[1; 32]).unwrap();
+        let msg = b"msg";
+        let (signing_package, mut shares) = sign_with(&key_packages, &[1, 2], msg);
+
+        // bump z_2 by one and move Y_2 along with it, so that z_2 still
+        // checks out against Y_2 while the sum no longer verifies
+        let xonly = public.get_xonly_group_key();
+        let (r, _) = signing_package.group_commitment(&xonly).unwrap();
+        let c = challenge(&r.get_x().unwrap().to_bytes(), &xonly, msg);
+        let lambda = lagrange_coefficient(2, &[1, 2]).unwrap();
+        let step = S256Point::generator() * scalar::inverse(&(lambda * c % &n));
+        let share = public.get_verifying_share(2).unwrap();
+        let tampered_share = if public.get_group_key().has_even_y() { share + step } else { share - step };
+        let tampered = PublicKeyPackage::new(
+            BTreeMap::from([
+                (1, public.get_verifying_share(1).unwrap()),
+                (2, tampered_share),
+                (3, public.get_verifying_share(3).unwrap()),
+            ]),
+            public.get_group_key(),
+        );
+        *shares.get_mut(&2).unwrap() += 1u8;
+
+        assert_eq!(verify_signature_share(2, &shares[&2], &signing_package, &tampered), Ok(()));
+        assert_eq!(aggregate(&signing_package, &shares, &tampered), Err(FrostError::InvalidSignature));
+    }
+}
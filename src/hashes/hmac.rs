@@ -0,0 +1,122 @@
+#![allow(unused)]
+
+use super::sha256::{sha256, Sha256};
+use super::sha512::{sha512, Sha512};
+
+// RFC 2104 HMAC: H((key ^ opad) || H((key ^ ipad) || msg)), with keys longer
+// than a block hashed first and shorter ones padded with zeros
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+// the key padded to a block and xored with pad
+fn padded_key<const N: usize>(key: &[u8], hashed: &[u8], pad: u8) -> [u8; N] {
+    let key = if key.len() > N { hashed } else { key };
+    let mut result = [pad; N];
+    for (byte, k) in result.iter_mut().zip(key) {
+        *byte ^= k;
+    }
+    result
+}
+
+#[derive(Debug, Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    pub fn new(key: &[u8]) -> Self {
+        let hashed = if key.len() > 64 { sha256(key).to_vec() } else { vec![] };
+        let mut inner = Sha256::new();
+        inner.update(&padded_key::<64>(key, &hashed, IPAD));
+        let mut outer = Sha256::new();
+        outer.update(&padded_key::<64>(key, &hashed, OPAD));
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HmacSha512 {
+    inner: Sha512,
+    outer: Sha512,
+}
+
+impl HmacSha512 {
+    pub fn new(key: &[u8]) -> Self {
+        let hashed = if key.len() > 128 { sha512(key).to_vec() } else { vec![] };
+        let mut inner = Sha512::new();
+        inner.update(&padded_key::<128>(key, &hashed, IPAD));
+        let mut outer = Sha512::new();
+        outer.update(&padded_key::<128>(key, &hashed, OPAD));
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; 64] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    let mut engine = HmacSha256::new(key);
+    engine.update(msg);
+    engine.finalize()
+}
+
+pub fn hmac_sha512(key: &[u8], msg: &[u8]) -> [u8; 64] {
+    let mut engine = HmacSha512::new(key);
+    engine.update(msg);
+    engine.finalize()
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // RFC 4231 test cases 1, 2 and 6
+    #[test]
+    fn hmac_works() {
+        let vectors: [(Vec<u8>, &[u8], &str, &str); 3] = [
+            (
+                vec![0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+            // a key longer than both block sizes
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+                "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            ),
+        ];
+        for (key, msg, expected_256, expected_512) in vectors {
+            assert_eq!(hex::encode(hmac_sha256(&key, msg)), expected_256);
+            assert_eq!(hex::encode(hmac_sha512(&key, msg)), expected_512);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod hmac;
+pub mod pbkdf2;
+pub mod ripemd160;
+pub mod sha256;
+pub mod sha512;
@@ -0,0 +1,43 @@
+#![allow(unused)]
+
+use super::hmac::HmacSha512;
+
+// RFC 8018 PBKDF2 with HMAC-SHA512, for a single 64 byte block, which is all
+// BIP39 needs: U1 = HMAC(password, salt || 1), U_i = HMAC(password, U_i-1)
+// and the result is U1 ^ U2 ^ ... ^ U_iterations
+pub fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 64] {
+    // the keyed engine is cloned every round rather than rehashing the key
+    let keyed = HmacSha512::new(password);
+    let mut engine = keyed.clone();
+    engine.update(salt);
+    engine.update(&1u32.to_be_bytes());
+    let mut u = engine.finalize();
+    let mut result = u;
+    for _ in 1..iterations {
+        let mut engine = keyed.clone();
+        engine.update(&u);
+        u = engine.finalize();
+        for (byte, x) in result.iter_mut().zip(u.iter()) {
+            *byte ^= x;
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn pbkdf2_works() {
+        let vectors = [
+            (1, "867f70cf1ade02cff3752599a3a53dc4af34c7a669815ae5d513554e1c8cf252c02d470a285a0501bad999bfe943c08f050235d7d68b1da55e63f73b60a57fce"),
+            (2, "e1d9c16aa681708a45f5c7c4e215ceb66e011a2e9f0040713f18aefdb866d53cf76cab2868a39b9f7840edce4fef5a82be67335c77a6068e04112754f27ccf4e"),
+            (4096, "d197b1b33db0143e018b12f3d1d1479e6cdebdcc97c5c0f87f6902e072f457b5143f30602641b3d55cd335988cb36b84376060ecd532e039b742a239434af2d5"),
+        ];
+        for (iterations, expected) in vectors {
+            assert_eq!(hex::encode(pbkdf2_hmac_sha512(b"password", b"salt", iterations)), expected);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+#![allow(unused)]
+
+use super::sha256::sha256;
+
+// RIPEMD-160 (Dobbertin, Bosselaers and Preneel): two parallel lines of 80
+// steps over little endian words, mixed together at the end of each block
+
+// the message word each step reads, left and right line
+const R_LEFT: [usize; 80] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5, 2, 14, 11, 8,
+    3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12,
+    1, 9, 11, 10, 0, 8, 12, 4, 13, 3, 7, 15, 14, 5, 6, 2,
+    4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13,
+];
+
+const R_RIGHT: [usize; 80] = [
+    5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12,
+    6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12, 4, 9, 1, 2,
+    15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13,
+    8, 6, 4, 1, 3, 11, 15, 0, 5, 12, 2, 13, 9, 7, 10, 14,
+    12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11,
+];
+
+// the left rotation of each step
+const S_LEFT: [u32; 80] = [
+    11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8,
+    7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15, 9, 11, 7, 13, 12,
+    11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5,
+    11, 12, 14, 15, 14, 15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12,
+    9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6,
+];
+
+const S_RIGHT: [u32; 80] = [
+    8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6,
+    9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12, 7, 6, 15, 13, 11,
+    9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5,
+    15, 5, 8, 11, 14, 14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8,
+    8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11,
+];
+
+// the constant of each round of 16 steps
+const K_LEFT: [u32; 5] = [0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e];
+const K_RIGHT: [u32; 5] = [0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9, 0x00000000];
+
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+// the boolean function of round j, the right line going through them backwards
+fn f(round: usize, x: u32, y: u32, z: u32) -> u32 {
+    match round {
+        0 => x ^ y ^ z,
+        1 => (x & y) | (!x & z),
+        2 => (x | !y) ^ z,
+        3 => (x & z) | (y & !z),
+        _ => x ^ (y | !z),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ripemd160 {
+    state: [u32; 5],
+    // bytes not yet making up a full 64 byte block
+    buffer: Vec<u8>,
+    // total number of bytes fed in so far
+    length: u64,
+}
+
+impl Ripemd160 {
+    pub fn new() -> Self {
+        Self { state: INITIAL_STATE, buffer: Vec::with_capacity(64), length: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        self.buffer.extend_from_slice(data);
+
+        let blocks = self.buffer.len() / 64;
+        for i in 0..blocks {
+            let mut block = [0u8; 64];
+            block.copy_from_slice(&self.buffer[i * 64..(i + 1) * 64]);
+            self.compress(&block);
+        }
+        self.buffer.drain(..blocks * 64);
+    }
+
+    // the same padding as SHA-256, but with a little endian length
+    pub fn finalize(mut self) -> [u8; 20] {
+        let bit_length = self.length * 8;
+        let mut padding = vec![0x80];
+        padding.resize(1 + (119 - self.buffer.len()) % 64, 0);
+        padding.extend_from_slice(&bit_length.to_le_bytes());
+        self.update(&padding);
+
+        let mut result = [0u8; 20];
+        for (chunk, word) in result.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        result
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut x = [0u32; 16];
+        for (i, chunk) in block.chunks(4).enumerate() {
+            x[i] = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        let [mut al, mut bl, mut cl, mut dl, mut el] = self.state;
+        let [mut ar, mut br, mut cr, mut dr, mut er] = self.state;
+        for j in 0..80 {
+            let round = j / 16;
+            let t = al
+                .wrapping_add(f(round, bl, cl, dl))
+                .wrapping_add(x[R_LEFT[j]])
+                .wrapping_add(K_LEFT[round])
+                .rotate_left(S_LEFT[j])
+                .wrapping_add(el);
+            al = el;
+            el = dl;
+            dl = cl.rotate_left(10);
+            cl = bl;
+            bl = t;
+
+            let t = ar
+                .wrapping_add(f(4 - round, br, cr, dr))
+                .wrapping_add(x[R_RIGHT[j]])
+                .wrapping_add(K_RIGHT[round])
+                .rotate_left(S_RIGHT[j])
+                .wrapping_add(er);
+            ar = er;
+            er = dr;
+            dr = cr.rotate_left(10);
+            cr = br;
+            br = t;
+        }
+
+        let [h0, h1, h2, h3, h4] = self.state;
+        self.state = [
+            h1.wrapping_add(cl).wrapping_add(dr),
+            h2.wrapping_add(dl).wrapping_add(er),
+            h3.wrapping_add(el).wrapping_add(ar),
+            h4.wrapping_add(al).wrapping_add(br),
+            h0.wrapping_add(bl).wrapping_add(cr),
+        ];
+    }
+}
+
+impl Default for Ripemd160 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn ripemd160(data: &[u8]) -> [u8; 20] {
+    let mut engine = Ripemd160::new();
+    engine.update(data);
+    engine.finalize()
+}
+
+// ripemd160(sha256(data)), what P2PKH and P2WPKH commit to
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    ripemd160(&sha256(data))
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn ripemd160_works() {
+        let vectors: [(&[u8], &str); 4] = [
+            (b"", "9c1185a5c5e9fc54612808977ee8f548b2258d31"),
+            (b"a", "0bdc9d2d256b3ee9daae347be6f4dc835a467ffe"),
+            (b"abc", "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc"),
+            (b"message digest", "5d0689ef49d2fae572b881b123a85ffa21595f36"),
+        ];
+        for (msg, expected) in vectors {
+            assert_eq!(hex::encode(ripemd160(msg)), expected);
+        }
+        assert_eq!(
+            hex::encode(ripemd160(&vec![0x61u8; 1_000_000])),
+            "52783243c1697bdbe16d37f97f68f08325dc1528"
+        );
+
+        // updates that straddle block boundaries
+        let data: Vec<u8> = b"1234567890".repeat(8);
+        let mut engine = Ripemd160::new();
+        for chunk in data.chunks(7) {
+            engine.update(chunk);
+        }
+        assert_eq!(hex::encode(engine.finalize()), "9b752e45573d4b39f4dbd3323cab82bf63326bfb");
+    }
+
+    // the key hash of the compressed public key for secret 1
+    #[test]
+    fn hash160_works() {
+        let pubkey = hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        assert_eq!(hex::encode(hash160(&pubkey)), "751e76e8199196d454941c45d1b3a323f1433bd6");
+    }
+}
@@ -0,0 +1,253 @@
+#![allow(unused)]
+
+// FIPS 180-4 SHA-256
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    // bytes not yet making up a full 64 byte block
+    buffer: Vec<u8>,
+    // total number of bytes fed in so far
+    length: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self { state: INITIAL_STATE, buffer: Vec::with_capacity(64), length: 0 }
+    }
+
+    // An engine that already has sha256(tag) || sha256(tag) in it, for BIP340
+    // tagged hashes. That prefix is exactly one block, so it could as well
+    // start from a precomputed midstate.
+    pub fn tagged(tag: &str) -> Self {
+        let tag_hash = sha256(tag.as_bytes());
+        let mut engine = Self::new();
+        engine.update(&tag_hash);
+        engine.update(&tag_hash);
+        engine
+    }
+
+    // Resumes hashing from a midstate after length bytes. length has to be a
+    // whole number of 64 byte blocks.
+    pub fn from_midstate(midstate: &[u8; 32], length: u64) -> Self {
+        if !length.is_multiple_of(64) {
+            panic!("Midstate length {} is not a multiple of 64", length);
+        }
+        let mut state = [0u32; 8];
+        for (word, chunk) in state.iter_mut().zip(midstate.chunks(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Self { state, buffer: Vec::with_capacity(64), length }
+    }
+
+    // The internal state after the blocks hashed so far, or None when the
+    // bytes fed in don't end on a block boundary. Together with get_length
+    // it is all from_midstate needs.
+    pub fn midstate(&self) -> Option<[u8; 32]> {
+        if !self.buffer.is_empty() {
+            return None;
+        }
+        let mut result = [0u8; 32];
+        for (chunk, word) in result.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        Some(result)
+    }
+
+    pub fn get_length(&self) -> u64 {
+        self.length
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        self.buffer.extend_from_slice(data);
+
+        let blocks = self.buffer.len() / 64;
+        for i in 0..blocks {
+            let mut block = [0u8; 64];
+            block.copy_from_slice(&self.buffer[i * 64..(i + 1) * 64]);
+            self.compress(&block);
+        }
+        self.buffer.drain(..blocks * 64);
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        // a single 1 bit, zeros up to 56 mod 64 bytes, then the bit length
+        let bit_length = self.length * 8;
+        let mut padding = vec![0x80];
+        padding.resize(1 + (119 - self.buffer.len()) % 64, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+
+        let mut result = [0u8; 32];
+        for (chunk, word) in result.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        result
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (word, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut engine = Sha256::new();
+    engine.update(data);
+    engine.finalize()
+}
+
+// sha256(sha256(data)), Bitcoin's hash for block and transaction ids
+pub fn hash256(data: &[u8]) -> [u8; 32] {
+    sha256(&sha256(data))
+}
+
+// BIP340 tagged hash: sha256(sha256(tag) || sha256(tag) || msg), which keeps
+// hashes used for different purposes from ever colliding
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let mut engine = Sha256::tagged(tag);
+    engine.update(msg);
+    engine.finalize()
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_works() {
+        assert_eq!(
+            hex::encode(sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex::encode(sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex::encode(sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex::encode(sha256(
+                b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu"
+            )),
+            "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1"
+        );
+        assert_eq!(
+            hex::encode(sha256(&vec![0x61u8; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn hash256_works() {
+        assert_eq!(
+            hex::encode(hash256(b"")),
+            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"
+        );
+        assert_eq!(
+            hex::encode(hash256(b"hello")),
+            "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50"
+        );
+    }
+
+    #[test]
+    fn midstate_works() {
+        // the midstate libsecp256k1 hardcodes for BIP340 challenges
+        let engine = Sha256::tagged("BIP0340/challenge");
+        let midstate = engine.midstate().unwrap();
+        assert_eq!(
+            hex::encode(midstate),
+            "9cecba112392538111679112d1627e0f97c87550003cc76590f6116433e9b66a"
+        );
+        assert_eq!(engine.get_length(), 64);
+
+        let mut resumed = Sha256::from_midstate(&midstate, 64);
+        resumed.update(b"msg");
+        assert_eq!(resumed.finalize(), tagged_hash("BIP0340/challenge", b"msg"));
+
+        // not on a block boundary
+        let mut engine = Sha256::new();
+        engine.update(b"abc");
+        assert_eq!(engine.midstate(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_midstate_rejects_partial_block() {
+        Sha256::from_midstate(&[0; 32], 63);
+    }
+
+    #[test]
+    fn update_in_pieces_works() {
+        let data = vec![0x61u8; 1000];
+        let mut engine = Sha256::new();
+        for chunk in data.chunks(7) {
+            engine.update(chunk);
+        }
+        assert_eq!(engine.finalize(), sha256(&data));
+    }
+
+    #[test]
+    fn tagged_hash_works() {
+        let tag_hash = sha256(b"BIP0340/challenge");
+        let mut preimage = tag_hash.to_vec();
+        preimage.extend_from_slice(&tag_hash);
+        preimage.extend_from_slice(b"msg");
+        assert_eq!(tagged_hash("BIP0340/challenge", b"msg"), sha256(&preimage));
+    }
+}
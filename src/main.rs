@@ -1,4 +1,16 @@
+mod adaptor;
+mod encoding;
 mod finite_field;
+mod frost;
+mod hashes;
+mod miniscript;
+mod musig;
+mod ring;
+mod schnorr;
+mod taproot;
+mod vrf;
+mod wallet;
+mod zkp;
 fn main() {
     // insert functions
 }
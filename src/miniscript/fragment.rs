@@ -0,0 +1,1010 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use crate::encoding::hex::{from_hex, to_hex};
+use crate::finite_field::secp_ec::S256Point;
+use crate::hashes::ripemd160::hash160;
+
+use super::types::{Base, Type};
+
+// timelocks are script numbers of at most 4 bytes, so below 2^31
+const MAX_TIMELOCK: usize = 0x7fffffff;
+
+// OP_CHECKMULTISIG takes at most 20 keys
+const MAX_MULTISIG_KEYS: usize = 20;
+
+// the largest witness script that is standard to spend
+const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+
+// the most non-push opcodes a script may run, a consensus limit
+const MAX_OPS_PER_SCRIPT: usize = 201;
+
+// witness element sizes with their length byte: a DER signature with its
+// sighash byte, a compressed key, a 32 byte preimage and the number 1
+pub(super) const SIGNATURE_SIZE: usize = 73;
+pub(super) const KEY_SIZE: usize = 34;
+pub(super) const PREIMAGE_SIZE: usize = 33;
+pub(super) const ONE_SIZE: usize = 2;
+pub(super) const EMPTY_SIZE: usize = 1;
+
+// Reasons an expression isn't valid miniscript or policy, or can't be
+// satisfied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiniscriptError {
+    // unbalanced brackets or an expression that isn't name(arguments)
+    InvalidSyntax(String),
+    UnknownFragment(String),
+    // the fragment takes a different number of arguments
+    InvalidArgumentCount { fragment: String, count: usize },
+    // keys are hex compressed public keys
+    InvalidKey(String),
+    // hashes are 32 or 20 bytes of hex depending on the fragment
+    InvalidHash(String),
+    // timelocks are 1 to 2^31 - 1, weights and thresholds positive numbers
+    InvalidNumber(String),
+    // thresh() and multi() need 1 <= k <= n, and multi() n <= 20
+    InvalidThreshold { k: usize, n: usize },
+    // a child doesn't have the type the fragment needs, see types.rs
+    TypeError(String),
+    // some satisfaction needs both a height and a time lock of the same
+    // kind, which no transaction can have
+    TimelockMix,
+    // the script is over MAX_STANDARD_P2WSH_SCRIPT_SIZE
+    ScriptTooLarge(usize),
+    // the script has over MAX_OPS_PER_SCRIPT opcodes
+    TooManyOps(usize),
+    // the signatures, preimages and timelocks available aren't enough
+    Unsatisfiable,
+    // every satisfaction available could be changed by a third party
+    Malleable,
+}
+
+impl fmt::Display for MiniscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiniscriptError::InvalidSyntax(s) => write!(f, "invalid expression {:?}", s),
+            MiniscriptError::UnknownFragment(name) => write!(f, "unknown fragment {:?}", name),
+            MiniscriptError::InvalidArgumentCount { fragment, count } => {
+                write!(f, "{}() can't take {} arguments", fragment, count)
+            }
+            MiniscriptError::InvalidKey(s) => write!(f, "invalid key {:?}", s),
+            MiniscriptError::InvalidHash(s) => write!(f, "invalid hash {:?}", s),
+            MiniscriptError::InvalidNumber(s) => write!(f, "invalid number {:?}", s),
+            MiniscriptError::InvalidThreshold { k, n } => write!(f, "invalid threshold {} of {}", k, n),
+            MiniscriptError::TypeError(reason) => write!(f, "type error: {}", reason),
+            MiniscriptError::TimelockMix => write!(f, "mixes height and time locks"),
+            MiniscriptError::ScriptTooLarge(size) => {
+                write!(f, "script is {} bytes, over {}", size, MAX_STANDARD_P2WSH_SCRIPT_SIZE)
+            }
+            MiniscriptError::TooManyOps(count) => {
+                write!(f, "script has {} opcodes, over {}", count, MAX_OPS_PER_SCRIPT)
+            }
+            MiniscriptError::Unsatisfiable => write!(f, "not enough signatures, preimages or time to satisfy"),
+            MiniscriptError::Malleable => write!(f, "no satisfaction that can't be malleated"),
+        }
+    }
+}
+
+impl std::error::Error for MiniscriptError {}
+
+// splits at the commas that aren't inside brackets
+fn split_args(s: &str) -> Result<Vec<&str>, MiniscriptError> {
+    let mut args = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1).ok_or_else(|| MiniscriptError::InvalidSyntax(s.to_string()))?,
+            ',' if depth == 0 => {
+                args.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(MiniscriptError::InvalidSyntax(s.to_string()));
+    }
+    args.push(&s[start..]);
+    Ok(args)
+}
+
+// name(arg,...) into the name and its arguments
+pub(super) fn split_call(s: &str) -> Result<(&str, Vec<&str>), MiniscriptError> {
+    let invalid = || MiniscriptError::InvalidSyntax(s.to_string());
+    let (name, rest) = s.split_once('(').ok_or_else(invalid)?;
+    let inner = rest.strip_suffix(')').ok_or_else(invalid)?;
+    Ok((name, split_args(inner).map_err(|_| invalid())?))
+}
+
+pub(super) fn check_arg_count(name: &str, args: &[&str], count: usize) -> Result<(), MiniscriptError> {
+    if args.len() != count {
+        return Err(MiniscriptError::InvalidArgumentCount { fragment: name.to_string(), count: args.len() });
+    }
+    Ok(())
+}
+
+// P2WSH only allows compressed keys
+pub(super) fn parse_key(s: &str) -> Result<S256Point, MiniscriptError> {
+    let invalid = || MiniscriptError::InvalidKey(s.to_string());
+    let sec = from_hex(s).ok_or_else(invalid)?;
+    if sec.len() != 33 {
+        return Err(invalid());
+    }
+    S256Point::parse_sec(&sec).map_err(|_| invalid())
+}
+
+pub(super) fn parse_hash<const N: usize>(s: &str) -> Result<[u8; N], MiniscriptError> {
+    let bytes = from_hex(s).ok_or_else(|| MiniscriptError::InvalidHash(s.to_string()))?;
+    bytes.try_into().map_err(|_| MiniscriptError::InvalidHash(s.to_string()))
+}
+
+// decimal with no sign or leading zeros
+pub(super) fn parse_number(s: &str) -> Result<usize, MiniscriptError> {
+    let invalid = || MiniscriptError::InvalidNumber(s.to_string());
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) || (s.len() > 1 && s.starts_with('0')) {
+        return Err(invalid());
+    }
+    s.parse().map_err(|_| invalid())
+}
+
+pub(super) fn parse_timelock(s: &str) -> Result<u32, MiniscriptError> {
+    match parse_number(s)? {
+        n @ 1..=MAX_TIMELOCK => Ok(n as u32),
+        _ => Err(MiniscriptError::InvalidNumber(s.to_string())),
+    }
+}
+
+// The fragments of miniscript, each a piece of script with a type. The
+// single letter wrappers are Alt (a:), Swap (s:), Check (c:), DupIf (d:),
+// Verify (v:), NonZero (j:) and ZeroNotEqual (n:); t:, l: and u: are
+// shorthand for and_v(X,1), or_i(0,X) and or_i(X,0).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminal {
+    False,
+    True,
+    PkK(S256Point),
+    PkH(S256Point),
+    Older(u32),
+    After(u32),
+    Sha256([u8; 32]),
+    Hash256([u8; 32]),
+    Ripemd160([u8; 20]),
+    Hash160([u8; 20]),
+    AndOr(Box<Miniscript>, Box<Miniscript>, Box<Miniscript>),
+    AndV(Box<Miniscript>, Box<Miniscript>),
+    AndB(Box<Miniscript>, Box<Miniscript>),
+    OrB(Box<Miniscript>, Box<Miniscript>),
+    OrC(Box<Miniscript>, Box<Miniscript>),
+    OrD(Box<Miniscript>, Box<Miniscript>),
+    OrI(Box<Miniscript>, Box<Miniscript>),
+    Thresh(usize, Vec<Miniscript>),
+    Multi(usize, Vec<S256Point>),
+    Alt(Box<Miniscript>),
+    Swap(Box<Miniscript>),
+    Check(Box<Miniscript>),
+    DupIf(Box<Miniscript>),
+    Verify(Box<Miniscript>),
+    NonZero(Box<Miniscript>),
+    ZeroNotEqual(Box<Miniscript>),
+}
+
+// A miniscript expression for P2WSH, type checked when it is built so that
+// every one in existence has a valid type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Miniscript {
+    node: Terminal,
+    ty: Type,
+}
+
+// pushes n the way script numbers are: OP_0, OP_1 to OP_16, or minimal
+// little endian bytes with room for the sign bit
+fn push_number(script: &mut Vec<u8>, n: u32) {
+    match n {
+        0 => script.push(0x00),
+        1..=16 => script.push(0x50 + n as u8),
+        _ => {
+            let mut bytes: Vec<u8> = n.to_le_bytes().into_iter().collect();
+            while bytes.last() == Some(&0) {
+                bytes.pop();
+            }
+            if bytes.last().unwrap() & 0x80 != 0 {
+                bytes.push(0x00);
+            }
+            script.push(bytes.len() as u8);
+            script.extend(bytes);
+        }
+    }
+}
+
+// the larger of two sizes where either may be missing
+fn max_size(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+impl Miniscript {
+    pub fn new(node: Terminal) -> Result<Self, MiniscriptError> {
+        if let Terminal::Multi(k, keys) = &node {
+            if *k == 0 || *k > keys.len() || keys.len() > MAX_MULTISIG_KEYS {
+                return Err(MiniscriptError::InvalidThreshold { k: *k, n: keys.len() });
+            }
+        }
+        let ty = Type::of(&node)?;
+        Ok(Self { node, ty })
+    }
+
+    pub fn get_node(&self) -> &Terminal {
+        &self.node
+    }
+
+    pub fn get_type(&self) -> Type {
+        self.ty
+    }
+
+    // A top level expression, see check_top_level
+    pub fn parse(s: &str) -> Result<Self, MiniscriptError> {
+        let ms = Self::parse_expr(s)?;
+        ms.check_top_level()?;
+        Ok(ms)
+    }
+
+    // A whole script has to be B to leave a result for the script to end
+    // with, must not mix timelocks, and has to be within the size and
+    // opcode limits to be spendable
+    pub(super) fn check_top_level(&self) -> Result<(), MiniscriptError> {
+        if self.ty.get_base() != Base::B {
+            return Err(MiniscriptError::TypeError(format!("top level is {}, not B", self.ty)));
+        }
+        if !self.ty.has('k') {
+            return Err(MiniscriptError::TimelockMix);
+        }
+        let size = self.script_size();
+        if size > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+            return Err(MiniscriptError::ScriptTooLarge(size));
+        }
+        let ops = self.op_count();
+        if ops > MAX_OPS_PER_SCRIPT {
+            return Err(MiniscriptError::TooManyOps(ops));
+        }
+        Ok(())
+    }
+
+    fn parse_expr(s: &str) -> Result<Self, MiniscriptError> {
+        // wrappers come before a colon, the leftmost one outermost
+        let name_end = s.find('(').unwrap_or(s.len());
+        if let Some(colon) = s[..name_end].find(':') {
+            let (wrappers, inner) = (&s[..colon], &s[colon + 1..]);
+            let mut ms = Self::parse_expr(inner)?;
+            for wrapper in wrappers.chars().rev() {
+                ms = Self::wrap(wrapper, ms)?;
+            }
+            return Ok(ms);
+        }
+
+        let node = match s {
+            "0" => Terminal::False,
+            "1" => Terminal::True,
+            _ => {
+                let (name, args) = split_call(s)?;
+                let sub = |i: usize| Self::parse_expr(args[i]).map(Box::new);
+                match name {
+                    "pk_k" | "pk_h" | "pk" | "pkh" => {
+                        check_arg_count(name, &args, 1)?;
+                        let key = parse_key(args[0])?;
+                        match name {
+                            "pk_k" => Terminal::PkK(key),
+                            "pk_h" => Terminal::PkH(key),
+                            "pk" => Terminal::Check(Box::new(Self::new(Terminal::PkK(key))?)),
+                            _ => Terminal::Check(Box::new(Self::new(Terminal::PkH(key))?)),
+                        }
+                    }
+                    "older" | "after" => {
+                        check_arg_count(name, &args, 1)?;
+                        let n = parse_timelock(args[0])?;
+                        if name == "older" {
+                            Terminal::Older(n)
+                        } else {
+                            Terminal::After(n)
+                        }
+                    }
+                    "sha256" | "hash256" | "ripemd160" | "hash160" => {
+                        check_arg_count(name, &args, 1)?;
+                        match name {
+                            "sha256" => Terminal::Sha256(parse_hash(args[0])?),
+                            "hash256" => Terminal::Hash256(parse_hash(args[0])?),
+                            "ripemd160" => Terminal::Ripemd160(parse_hash(args[0])?),
+                            _ => Terminal::Hash160(parse_hash(args[0])?),
+                        }
+                    }
+                    "andor" => {
+                        check_arg_count(name, &args, 3)?;
+                        Terminal::AndOr(sub(0)?, sub(1)?, sub(2)?)
+                    }
+                    "and_v" | "and_b" | "or_b" | "or_c" | "or_d" | "or_i" => {
+                        check_arg_count(name, &args, 2)?;
+                        let (x, y) = (sub(0)?, sub(1)?);
+                        match name {
+                            "and_v" => Terminal::AndV(x, y),
+                            "and_b" => Terminal::AndB(x, y),
+                            "or_b" => Terminal::OrB(x, y),
+                            "or_c" => Terminal::OrC(x, y),
+                            "or_d" => Terminal::OrD(x, y),
+                            _ => Terminal::OrI(x, y),
+                        }
+                    }
+                    "thresh" | "multi" => {
+                        let (k, rest) = args.split_first().unwrap();
+                        let k = parse_number(k)?;
+                        if name == "thresh" {
+                            let subs = rest.iter().map(|arg| Self::parse_expr(arg)).collect::<Result<_, _>>()?;
+                            Terminal::Thresh(k, subs)
+                        } else {
+                            let keys = rest.iter().map(|arg| parse_key(arg)).collect::<Result<_, _>>()?;
+                            Terminal::Multi(k, keys)
+                        }
+                    }
+                    _ => return Err(MiniscriptError::UnknownFragment(name.to_string())),
+                }
+            }
+        };
+        Self::new(node)
+    }
+
+    fn wrap(wrapper: char, ms: Self) -> Result<Self, MiniscriptError> {
+        let false_ = || Self::new(Terminal::False).map(Box::new);
+        let node = match wrapper {
+            'a' => Terminal::Alt(Box::new(ms)),
+            's' => Terminal::Swap(Box::new(ms)),
+            'c' => Terminal::Check(Box::new(ms)),
+            'd' => Terminal::DupIf(Box::new(ms)),
+            'v' => Terminal::Verify(Box::new(ms)),
+            'j' => Terminal::NonZero(Box::new(ms)),
+            'n' => Terminal::ZeroNotEqual(Box::new(ms)),
+            't' => Terminal::AndV(Box::new(ms), Box::new(Self::new(Terminal::True)?)),
+            'l' => Terminal::OrI(false_()?, Box::new(ms)),
+            'u' => Terminal::OrI(Box::new(ms), false_()?),
+            _ => return Err(MiniscriptError::UnknownFragment(format!("{}:", wrapper))),
+        };
+        Self::new(node)
+    }
+
+    // whether the script ends in OP_EQUAL, OP_CHECKSIG, OP_CHECKMULTISIG or
+    // OP_NUMEQUAL, so v: can use their VERIFY form instead of OP_VERIFY
+    fn has_verify_form(&self) -> bool {
+        match &self.node {
+            Terminal::Check(_) | Terminal::Multi(_, _) | Terminal::Thresh(_, _) => true,
+            Terminal::Sha256(_) | Terminal::Hash256(_) | Terminal::Ripemd160(_) | Terminal::Hash160(_) => true,
+            Terminal::AndV(_, y) => y.has_verify_form(),
+            Terminal::Swap(x) => x.has_verify_form(),
+            _ => false,
+        }
+    }
+
+    // the witness script
+    pub fn encode(&self) -> Vec<u8> {
+        let mut script = vec![];
+        match &self.node {
+            Terminal::False => script.push(0x00),
+            Terminal::True => script.push(0x51),
+            Terminal::PkK(key) => {
+                script.push(0x21);
+                script.extend(key.sec(true));
+            }
+            // OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY
+            Terminal::PkH(key) => {
+                script.extend([0x76, 0xa9, 0x14]);
+                script.extend(hash160(&key.sec(true)));
+                script.push(0x88);
+            }
+            Terminal::Older(n) => {
+                push_number(&mut script, *n);
+                script.push(0xb2);
+            }
+            Terminal::After(n) => {
+                push_number(&mut script, *n);
+                script.push(0xb1);
+            }
+            // OP_SIZE <32> OP_EQUALVERIFY OP_<hash> <h> OP_EQUAL
+            Terminal::Sha256(h) | Terminal::Hash256(h) => {
+                let opcode = if matches!(self.node, Terminal::Sha256(_)) { 0xa8 } else { 0xaa };
+                script.extend([0x82, 0x01, 0x20, 0x88, opcode, 0x20]);
+                script.extend(h);
+                script.push(0x87);
+            }
+            Terminal::Ripemd160(h) | Terminal::Hash160(h) => {
+                let opcode = if matches!(self.node, Terminal::Ripemd160(_)) { 0xa6 } else { 0xa9 };
+                script.extend([0x82, 0x01, 0x20, 0x88, opcode, 0x14]);
+                script.extend(h);
+                script.push(0x87);
+            }
+            // [X] OP_NOTIF [Z] OP_ELSE [Y] OP_ENDIF
+            Terminal::AndOr(x, y, z) => {
+                script.extend(x.encode());
+                script.push(0x64);
+                script.extend(z.encode());
+                script.push(0x67);
+                script.extend(y.encode());
+                script.push(0x68);
+            }
+            Terminal::AndV(x, y) => {
+                script.extend(x.encode());
+                script.extend(y.encode());
+            }
+            // [X] [Y] OP_BOOLAND and [X] [Z] OP_BOOLOR
+            Terminal::AndB(x, y) | Terminal::OrB(x, y) => {
+                script.extend(x.encode());
+                script.extend(y.encode());
+                script.push(if matches!(self.node, Terminal::AndB(_, _)) { 0x9a } else { 0x9b });
+            }
+            // [X] OP_NOTIF [Z] OP_ENDIF
+            Terminal::OrC(x, z) => {
+                script.extend(x.encode());
+                script.push(0x64);
+                script.extend(z.encode());
+                script.push(0x68);
+            }
+            // [X] OP_IFDUP OP_NOTIF [Z] OP_ENDIF
+            Terminal::OrD(x, z) => {
+                script.extend(x.encode());
+                script.extend([0x73, 0x64]);
+                script.extend(z.encode());
+                script.push(0x68);
+            }
+            // OP_IF [X] OP_ELSE [Z] OP_ENDIF
+            Terminal::OrI(x, z) => {
+                script.push(0x63);
+                script.extend(x.encode());
+                script.push(0x67);
+                script.extend(z.encode());
+                script.push(0x68);
+            }
+            // [X1] [X2] OP_ADD ... [Xn] OP_ADD <k> OP_EQUAL
+            Terminal::Thresh(k, subs) => {
+                for (i, sub) in subs.iter().enumerate() {
+                    script.extend(sub.encode());
+                    if i > 0 {
+                        script.push(0x93);
+                    }
+                }
+                push_number(&mut script, *k as u32);
+                script.push(0x87);
+            }
+            // <k> <key1> ... <keyn> <n> OP_CHECKMULTISIG
+            Terminal::Multi(k, keys) => {
+                push_number(&mut script, *k as u32);
+                for key in keys {
+                    script.push(0x21);
+                    script.extend(key.sec(true));
+                }
+                push_number(&mut script, keys.len() as u32);
+                script.push(0xae);
+            }
+            // OP_TOALTSTACK [X] OP_FROMALTSTACK
+            Terminal::Alt(x) => {
+                script.push(0x6b);
+                script.extend(x.encode());
+                script.push(0x6c);
+            }
+            Terminal::Swap(x) => {
+                script.push(0x7c);
+                script.extend(x.encode());
+            }
+            Terminal::Check(x) => {
+                script.extend(x.encode());
+                script.push(0xac);
+            }
+            // OP_DUP OP_IF [X] OP_ENDIF
+            Terminal::DupIf(x) => {
+                script.extend([0x76, 0x63]);
+                script.extend(x.encode());
+                script.push(0x68);
+            }
+            // each VERIFY opcode comes right after the one it verifies
+            Terminal::Verify(x) => {
+                script.extend(x.encode());
+                if x.has_verify_form() {
+                    *script.last_mut().unwrap() += 1;
+                } else {
+                    script.push(0x69);
+                }
+            }
+            // OP_SIZE OP_0NOTEQUAL OP_IF [X] OP_ENDIF
+            Terminal::NonZero(x) => {
+                script.extend([0x82, 0x92, 0x63]);
+                script.extend(x.encode());
+                script.push(0x68);
+            }
+            Terminal::ZeroNotEqual(x) => {
+                script.extend(x.encode());
+                script.push(0x92);
+            }
+        }
+        script
+    }
+
+    pub fn script_size(&self) -> usize {
+        self.encode().len()
+    }
+
+    // The opcodes counting towards MAX_OPS_PER_SCRIPT: every one above
+    // OP_16, whether its branch runs or not, plus the keys of a
+    // CHECKMULTISIG, here counted as if every one runs
+    pub fn op_count(&self) -> usize {
+        let script = self.encode();
+        let mut count = 0;
+        let mut i = 0;
+        while i < script.len() {
+            match script[i] {
+                // the encoder only makes direct pushes
+                len @ 0x01..=0x4b => i += len as usize,
+                0x61.. => count += 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        count + self.multisig_keys()
+    }
+
+    fn multisig_keys(&self) -> usize {
+        match &self.node {
+            Terminal::Multi(_, keys) => keys.len(),
+            Terminal::AndOr(x, y, z) => x.multisig_keys() + y.multisig_keys() + z.multisig_keys(),
+            Terminal::AndV(x, y)
+            | Terminal::AndB(x, y)
+            | Terminal::OrB(x, y)
+            | Terminal::OrC(x, y)
+            | Terminal::OrD(x, y)
+            | Terminal::OrI(x, y) => x.multisig_keys() + y.multisig_keys(),
+            Terminal::Thresh(_, subs) => subs.iter().map(|sub| sub.multisig_keys()).sum(),
+            Terminal::Alt(x)
+            | Terminal::Swap(x)
+            | Terminal::Check(x)
+            | Terminal::DupIf(x)
+            | Terminal::Verify(x)
+            | Terminal::NonZero(x)
+            | Terminal::ZeroNotEqual(x) => x.multisig_keys(),
+            _ => 0,
+        }
+    }
+
+    // The most bytes a satisfaction puts in the witness, counting each
+    // element's length byte but not the witness script itself
+    pub fn max_satisfaction_size(&self) -> Option<usize> {
+        let size = match &self.node {
+            Terminal::False => return None,
+            Terminal::True | Terminal::Older(_) | Terminal::After(_) => 0,
+            Terminal::PkK(_) => SIGNATURE_SIZE,
+            Terminal::PkH(_) => SIGNATURE_SIZE + KEY_SIZE,
+            Terminal::Sha256(_) | Terminal::Hash256(_) | Terminal::Ripemd160(_) | Terminal::Hash160(_) => {
+                PREIMAGE_SIZE
+            }
+            Terminal::AndOr(x, y, z) => {
+                let through_y = x.max_satisfaction_size().zip(y.max_satisfaction_size()).map(|(x, y)| x + y);
+                let through_z = x.max_dissatisfaction_size().zip(z.max_satisfaction_size()).map(|(x, z)| x + z);
+                return max_size(through_y, through_z);
+            }
+            Terminal::AndV(x, y) | Terminal::AndB(x, y) => x.max_satisfaction_size()? + y.max_satisfaction_size()?,
+            Terminal::OrB(x, z) => {
+                let through_x = x.max_satisfaction_size().zip(z.max_dissatisfaction_size()).map(|(x, z)| x + z);
+                let through_z = x.max_dissatisfaction_size().zip(z.max_satisfaction_size()).map(|(x, z)| x + z);
+                return max_size(through_x, through_z);
+            }
+            Terminal::OrC(x, z) | Terminal::OrD(x, z) => {
+                let through_z = x.max_dissatisfaction_size().zip(z.max_satisfaction_size()).map(|(x, z)| x + z);
+                return max_size(x.max_satisfaction_size(), through_z);
+            }
+            Terminal::OrI(x, z) => {
+                let through_x = x.max_satisfaction_size().map(|x| x + ONE_SIZE);
+                let through_z = z.max_satisfaction_size().map(|z| z + EMPTY_SIZE);
+                return max_size(through_x, through_z);
+            }
+            // every sub is dissatisfied except the k whose satisfaction
+            // costs the most more
+            Terminal::Thresh(k, subs) => {
+                let dissatisfied: usize = subs.iter().map(|sub| sub.max_dissatisfaction_size().unwrap()).sum();
+                let mut extra: Vec<isize> = subs
+                    .iter()
+                    .filter_map(|sub| {
+                        let sat = sub.max_satisfaction_size()? as isize;
+                        Some(sat - sub.max_dissatisfaction_size().unwrap() as isize)
+                    })
+                    .collect();
+                if extra.len() < *k {
+                    return None;
+                }
+                extra.sort_by(|a, b| b.cmp(a));
+                (dissatisfied as isize + extra[..*k].iter().sum::<isize>()) as usize
+            }
+            // the dummy element and k signatures
+            Terminal::Multi(k, _) => EMPTY_SIZE + k * SIGNATURE_SIZE,
+            Terminal::Alt(x) | Terminal::Swap(x) | Terminal::Check(x) | Terminal::Verify(x) => {
+                return x.max_satisfaction_size()
+            }
+            Terminal::NonZero(x) | Terminal::ZeroNotEqual(x) => return x.max_satisfaction_size(),
+            Terminal::DupIf(x) => x.max_satisfaction_size()? + ONE_SIZE,
+        };
+        Some(size)
+    }
+
+    // the same for a dissatisfaction, which only d fragments have
+    pub fn max_dissatisfaction_size(&self) -> Option<usize> {
+        let size = match &self.node {
+            Terminal::False => 0,
+            Terminal::True | Terminal::Older(_) | Terminal::After(_) => return None,
+            Terminal::PkK(_) => EMPTY_SIZE,
+            Terminal::PkH(_) => EMPTY_SIZE + KEY_SIZE,
+            Terminal::Sha256(_) | Terminal::Hash256(_) | Terminal::Ripemd160(_) | Terminal::Hash160(_) => {
+                PREIMAGE_SIZE
+            }
+            Terminal::AndOr(x, _, z) => x.max_dissatisfaction_size()? + z.max_dissatisfaction_size()?,
+            Terminal::AndV(_, _) | Terminal::OrC(_, _) | Terminal::Verify(_) => return None,
+            Terminal::AndB(x, y) | Terminal::OrB(x, y) | Terminal::OrD(x, y) => {
+                x.max_dissatisfaction_size()? + y.max_dissatisfaction_size()?
+            }
+            Terminal::OrI(x, z) => {
+                let through_x = x.max_dissatisfaction_size().map(|x| x + ONE_SIZE);
+                let through_z = z.max_dissatisfaction_size().map(|z| z + EMPTY_SIZE);
+                return max_size(through_x, through_z);
+            }
+            Terminal::Thresh(_, subs) => subs.iter().map(|sub| sub.max_dissatisfaction_size()).sum::<Option<usize>>()?,
+            Terminal::Multi(k, _) => EMPTY_SIZE + k * EMPTY_SIZE,
+            Terminal::Alt(x) | Terminal::Swap(x) | Terminal::Check(x) | Terminal::ZeroNotEqual(x) => {
+                return x.max_dissatisfaction_size()
+            }
+            Terminal::DupIf(_) | Terminal::NonZero(_) => EMPTY_SIZE,
+        };
+        Some(size)
+    }
+
+    // the wrappers this node starts with and what they wrap, so that
+    // c:pk_k(K) prints as pk(K) and v:c:pk_k(K) as v:pk(K)
+    fn display_parts(&self) -> (String, String) {
+        let wrapped = |wrapper: char, x: &Miniscript| {
+            let (wrappers, body) = x.display_parts();
+            (format!("{}{}", wrapper, wrappers), body)
+        };
+        match &self.node {
+            Terminal::Check(x) => match &x.node {
+                Terminal::PkK(key) => (String::new(), format!("pk({})", to_hex(&key.sec(true)))),
+                Terminal::PkH(key) => (String::new(), format!("pkh({})", to_hex(&key.sec(true)))),
+                _ => wrapped('c', x),
+            },
+            Terminal::Alt(x) => wrapped('a', x),
+            Terminal::Swap(x) => wrapped('s', x),
+            Terminal::DupIf(x) => wrapped('d', x),
+            Terminal::Verify(x) => wrapped('v', x),
+            Terminal::NonZero(x) => wrapped('j', x),
+            Terminal::ZeroNotEqual(x) => wrapped('n', x),
+            Terminal::AndV(x, y) if y.node == Terminal::True => wrapped('t', x),
+            Terminal::OrI(x, z) if x.node == Terminal::False => wrapped('l', z),
+            Terminal::OrI(x, z) if z.node == Terminal::False => wrapped('u', x),
+            _ => (String::new(), self.body()),
+        }
+    }
+
+    fn body(&self) -> String {
+        let list = |items: Vec<String>| items.join(",");
+        match &self.node {
+            Terminal::False => "0".to_string(),
+            Terminal::True => "1".to_string(),
+            Terminal::PkK(key) => format!("pk_k({})", to_hex(&key.sec(true))),
+            Terminal::PkH(key) => format!("pk_h({})", to_hex(&key.sec(true))),
+            Terminal::Older(n) => format!("older({})", n),
+            Terminal::After(n) => format!("after({})", n),
+            Terminal::Sha256(h) => format!("sha256({})", to_hex(h)),
+            Terminal::Hash256(h) => format!("hash256({})", to_hex(h)),
+            Terminal::Ripemd160(h) => format!("ripemd160({})", to_hex(h)),
+            Terminal::Hash160(h) => format!("hash160({})", to_hex(h)),
+            Terminal::AndOr(x, y, z) => format!("andor({},{},{})", x, y, z),
+            Terminal::AndV(x, y) => format!("and_v({},{})", x, y),
+            Terminal::AndB(x, y) => format!("and_b({},{})", x, y),
+            Terminal::OrB(x, z) => format!("or_b({},{})", x, z),
+            Terminal::OrC(x, z) => format!("or_c({},{})", x, z),
+            Terminal::OrD(x, z) => format!("or_d({},{})", x, z),
+            Terminal::OrI(x, z) => format!("or_i({},{})", x, z),
+            Terminal::Thresh(k, subs) => {
+                format!("thresh({},{})", k, list(subs.iter().map(|sub| sub.to_string()).collect()))
+            }
+            Terminal::Multi(k, keys) => {
+                format!("multi({},{})", k, list(keys.iter().map(|key| to_hex(&key.sec(true))).collect()))
+            }
+            // only reached through display_parts
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Miniscript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (wrappers, body) = self.display_parts();
+        if wrappers.is_empty() {
+            write!(f, "{}", body)
+        } else {
+            write!(f, "{}:{}", wrappers, body)
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::finite_field::private_key::PrivateKey;
+    use num_bigint::BigUint;
+
+    fn sec_hex(secret: u32) -> String {
+        hex::encode(PrivateKey::new(BigUint::from(secret)).get_point().sec(true))
+    }
+
+    #[test]
+    fn encode_works() {
+        let (a, b) = (sec_hex(1), sec_hex(2));
+        let cases = [
+            // <A> OP_CHECKSIG
+            (format!("pk({})", a), format!("21{}ac", a)),
+            // v: turns OP_CHECKSIG into OP_CHECKSIGVERIFY, 144 needs a sign byte
+            (
+                format!("or_d(pk({}),and_v(v:pk({}),older(144)))", a, b),
+                format!("21{}ac736421{}ad029000b268", a, b),
+            ),
+            (
+                format!("pkh({})", a),
+                format!("76a914{}88ac", hex::encode(hash160(&hex::decode(&a).unwrap()))),
+            ),
+            (format!("multi(1,{},{})", a, b), format!("5121{}21{}52ae", a, b)),
+            // the sub results are summed with OP_ADD and compared with k
+            (
+                format!("thresh(2,pk({}),s:pk({}),sln:older(144))", a, b),
+                format!("21{}ac7c21{}ac937c630067029000b29268935287", a, b),
+            ),
+            ("and_b(1,a:after(500000))".to_string(), "516b0320a107b16c9a".to_string()),
+            // OP_CHECKLOCKTIMEVERIFY has no VERIFY form, so OP_VERIFY follows
+            ("tv:after(1)".to_string(), "51b16951".to_string()),
+            ("dv:older(16)".to_string(), "766360b26968".to_string()),
+            (format!("j:pk({})", a), format!("82926321{}ac68", a)),
+        ];
+        for (ms, script) in cases {
+            let parsed = Miniscript::parse(&ms).unwrap();
+            assert_eq!(hex::encode(parsed.encode()), script);
+            assert_eq!(parsed.to_string(), ms);
+            assert_eq!(parsed.script_size(), script.len() / 2);
+        }
+    }
+
+    #[test]
+    fn types_work() {
+        let a = sec_hex(1);
+        let cases = [
+            ("0".to_string(), "Bzdusemk"),
+            ("1".to_string(), "Bzufmk"),
+            ("older(1)".to_string(), "Bzfmk"),
+            (format!("pk_k({})", a), "Kondusemk"),
+            (format!("pk({})", a), "Bondusemk"),
+            (format!("v:pk({})", a), "Vonsfmk"),
+            (format!("s:pk({})", a), "Wdusemk"),
+            (format!("a:pk({})", a), "Wdusemk"),
+            ("n:older(1)".to_string(), "Bzufmk"),
+            ("l:n:older(1)".to_string(), "Boduemk"),
+            ("d:v:older(1)".to_string(), "Bondemk"),
+            // a dissatisfaction of pk with a nonzero top would also work
+            (format!("j:pk({})", a), "Bondusmk"),
+            // without a signature the preimage can be swapped for any other
+            // 32 bytes when dissatisfying
+            ("sha256(0000000000000000000000000000000000000000000000000000000000000000)".to_string(), "Bondumk"),
+        ];
+        for (ms, ty) in cases {
+            assert_eq!(Miniscript::parse_expr(&ms).unwrap().get_type().to_string(), ty, "{}", ms);
+        }
+
+        // the top level has to be B
+        assert!(matches!(Miniscript::parse(&format!("v:pk({})", a)), Err(MiniscriptError::TypeError(_))));
+        // and_v needs its first argument V
+        assert!(matches!(
+            Miniscript::parse(&format!("and_v(pk({}),older(1))", a)),
+            Err(MiniscriptError::TypeError(_))
+        ));
+        // or_d needs its first argument d and u
+        assert!(matches!(Miniscript::parse("or_d(older(1),1)"), Err(MiniscriptError::TypeError(_))));
+        // s: needs one input
+        assert!(matches!(Miniscript::parse("and_b(1,s:older(1))"), Err(MiniscriptError::TypeError(_))));
+        // c: needs K
+        assert!(matches!(Miniscript::parse("c:d:v:older(16)"), Err(MiniscriptError::TypeError(_))));
+    }
+
+    // Vectors from Bitcoin Core's miniscript tests: the script, the opcode
+    // count, the largest satisfaction and whether it is non-malleable and
+    // needs a signature
+    #[test]
+    fn reference_vectors_pass() {
+        let cases = [
+            (
+                "lltvln:after(1231488000)",
+                "6300676300676300670400046749b1926869516868",
+                12,
+                3,
+                true,
+                false,
+            ),
+            (
+                "uuj:and_v(v:multi(2,03d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a,\
+                 025601570cb47f238d2b0286db4a990fa0f3ba28d1a319f5e7cf55c2a2444da7cc),after(1231488000))",
+                "6363829263522103d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a21025601570c\
+                 b47f238d2b0286db4a990fa0f3ba28d1a319f5e7cf55c2a2444da7cc52af0400046749b168670068670068",
+                14,
+                2 + 2 + 1 + 2 * 73,
+                true,
+                true,
+            ),
+            (
+                "or_b(un:multi(2,03daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee8729,\
+                 024ce119c96e2fa357200b559b2f7dd5a5f02d5290aff74b03f3e471b273211c97),al:older(16))",
+                "63522103daed4f2be3a8bf278e70132fb0beb7522f570e144bf615c07e996d443dee872921024ce119c96e2fa357\
+                 200b559b2f7dd5a5f02d5290aff74b03f3e471b273211c9752ae926700686b63006760b2686c9b",
+                14,
+                2 + 1 + 2 * 73 + 2,
+                false,
+                false,
+            ),
+            (
+                "j:and_v(vdv:after(1567547623),older(2016))",
+                "829263766304e7e06e5db169686902e007b268",
+                11,
+                2,
+                true,
+                false,
+            ),
+            (
+                "t:and_v(vu:hash256(131772552c01444cd81360818376a040b7c3b2b7b0a53550ee3edde216cec61b),\
+                 v:sha256(ec4916dd28fc4c10d78e287ca5d9cc51ee1ae73cbfde08c6b37324cbfaac8bc5))",
+                "6382012088aa20131772552c01444cd81360818376a040b7c3b2b7b0a53550ee3edde216cec61b876700686982012088\
+                 a820ec4916dd28fc4c10d78e287ca5d9cc51ee1ae73cbfde08c6b37324cbfaac8bc58851",
+                12,
+                2 + 33 + 33,
+                true,
+                false,
+            ),
+            (
+                "t:andor(multi(3,02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e,\
+                 03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556,\
+                 02e493dbf1c10d80f3581e4904930b1404cc6c13900ee0758474fa94abe8c4cd13),\
+                 v:older(4194305),v:sha256(9267d3dbed802941483f1afa2a6bc68de5f653128aca9bf1461c5d0a3ad36ed2))",
+                "532102d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e2103fff97bd5755eeea420453a\
+                 14355235d382f6472f8568a18b2f057a14602975562102e493dbf1c10d80f3581e4904930b1404cc6c13900ee0758474\
+                 fa94abe8c4cd1353ae6482012088a8209267d3dbed802941483f1afa2a6bc68de5f653128aca9bf1461c5d0a3ad36ed2\
+                 886703010040b2696851",
+                13,
+                1 + 3 * 73,
+                true,
+                false,
+            ),
+            (
+                "or_d(multi(1,02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9),\
+                 or_b(multi(3,022f01e5e15cca351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a01,\
+                 032fa2104d6b38d11b0230010559879124e42ab8dfeff5ff29dc9cdadd4ecacc3f,\
+                 03d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a),su:after(500000)))",
+                "512102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f951ae73645321022f01e5e15cca\
+                 351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a0121032fa2104d6b38d11b0230010559879124e42ab8df\
+                 eff5ff29dc9cdadd4ecacc3f2103d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a53ae\
+                 7c630320a107b16700689b68",
+                15,
+                2 + 1 + 3 * 73 + 1,
+                true,
+                false,
+            ),
+        ];
+        for (ms, script, ops, sat, non_malleable, needs_sig) in cases {
+            let parsed = Miniscript::parse(ms).unwrap();
+            assert_eq!(hex::encode(parsed.encode()), script, "{}", ms);
+            assert_eq!(parsed.to_string(), ms);
+            assert_eq!(parsed.op_count(), ops, "{}", ms);
+            assert_eq!(parsed.max_satisfaction_size(), Some(sat), "{}", ms);
+            assert_eq!(parsed.get_type().has('m'), non_malleable, "{}", ms);
+            assert_eq!(parsed.get_type().has('s'), needs_sig, "{}", ms);
+        }
+    }
+
+    // Bitcoin Core's timelock vectors: heights and times of the same kind
+    // may be alternatives but can't both be needed
+    #[test]
+    fn timelock_mixes_are_rejected() {
+        let key = "03d30199d74fb5a22d47b6e054e2f378cedacffcb89904a61d75d0dbd407143e65";
+        let valid = [
+            "after(100)".to_string(),
+            "after(1000000000)".to_string(),
+            "or_b(l:after(100),al:after(1000000000))".to_string(),
+            format!("thresh(1,c:pk_k({}),altv:after(1000000000),altv:after(100))", key),
+            // a relative height and an absolute time are different kinds
+            "and_v(v:older(144),after(1000000000))".to_string(),
+        ];
+        for ms in valid {
+            assert!(Miniscript::parse(&ms).is_ok(), "{}", ms);
+        }
+        let mixed = [
+            "and_b(after(100),a:after(1000000000))".to_string(),
+            format!("thresh(2,ltv:after(1000000000),altv:after(100),a:pk({}))", key),
+            "and_v(v:after(100),after(500000001))".to_string(),
+            // bit 22 makes a relative lock a time
+            "and_v(v:older(144),older(4194305))".to_string(),
+            format!("andor(pk({}),and_v(v:older(4194305),older(1)),after(1))", key),
+        ];
+        for ms in mixed {
+            assert_eq!(Miniscript::parse(&ms), Err(MiniscriptError::TimelockMix), "{}", ms);
+        }
+    }
+
+    // the standard P2WSH script size and the consensus opcode limit
+    #[test]
+    fn limits_are_enforced() {
+        let a = sec_hex(1);
+        let thresh = |n: usize| {
+            let subs: Vec<String> = (0..n).map(|_| format!("s:pk({})", a)).collect();
+            format!("thresh(1,pk({}),{})", a, subs.join(","))
+        };
+        // 37 bytes and 3 opcodes for each s:pk and its OP_ADD
+        assert_eq!(Miniscript::parse(&thresh(66)).unwrap().op_count(), 1 + 66 * 3 + 1);
+        assert_eq!(Miniscript::parse(&thresh(67)), Err(MiniscriptError::TooManyOps(1 + 67 * 3 + 1)));
+        assert_eq!(Miniscript::parse(&thresh(100)), Err(MiniscriptError::ScriptTooLarge(35 + 100 * 37 + 2)));
+
+        // the keys of a CHECKMULTISIG count too
+        let keys = vec![a.clone(); 20].join(",");
+        let ms = Miniscript::parse(&format!("and_v(v:multi(1,{}),multi(1,{}))", keys, keys)).unwrap();
+        assert_eq!(ms.op_count(), 2 + 2 * 20);
+    }
+
+    #[test]
+    fn sizes_work() {
+        let (a, b, c) = (sec_hex(1), sec_hex(2), sec_hex(3));
+        let cases = [
+            (format!("pk({})", a), Some(73), Some(1)),
+            (format!("pkh({})", a), Some(107), Some(35)),
+            (format!("multi(2,{},{},{})", a, b, c), Some(147), Some(3)),
+            (format!("or_d(pk({}),and_v(v:pk({}),older(144)))", a, b), Some(74), None),
+            (format!("or_i(pk({}),pk({}))", a, b), Some(75), Some(3)),
+            (format!("thresh(2,pk({}),s:pk({}),sln:older(144))", a, b), Some(148), Some(4)),
+            ("and_v(v:older(1),0)".to_string(), None, None),
+        ];
+        for (ms, sat, dissat) in cases {
+            let parsed = Miniscript::parse(&ms).unwrap();
+            assert_eq!(parsed.max_satisfaction_size(), sat, "{}", ms);
+            assert_eq!(parsed.max_dissatisfaction_size(), dissat, "{}", ms);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid() {
+        let a = sec_hex(1);
+        assert_eq!(
+            Miniscript::parse("and_v(v:older(1)"),
+            Err(MiniscriptError::InvalidSyntax("and_v(v:older(1)".to_string()))
+        );
+        assert_eq!(Miniscript::parse("foo(1)"), Err(MiniscriptError::UnknownFragment("foo".to_string())));
+        assert_eq!(Miniscript::parse("x:older(1)"), Err(MiniscriptError::UnknownFragment("x:".to_string())));
+        assert_eq!(
+            Miniscript::parse("or_i(1)"),
+            Err(MiniscriptError::InvalidArgumentCount { fragment: "or_i".to_string(), count: 1 })
+        );
+        assert_eq!(Miniscript::parse("older(0)"), Err(MiniscriptError::InvalidNumber("0".to_string())));
+        assert_eq!(Miniscript::parse("after(2147483648)"), Err(MiniscriptError::InvalidNumber("2147483648".to_string())));
+        assert_eq!(Miniscript::parse("sha256(00)"), Err(MiniscriptError::InvalidHash("00".to_string())));
+        // uncompressed keys aren't allowed in P2WSH
+        let uncompressed = hex::encode(PrivateKey::new(BigUint::from(1u32)).get_point().sec(false));
+        assert_eq!(
+            Miniscript::parse(&format!("pk({})", uncompressed)),
+            Err(MiniscriptError::InvalidKey(uncompressed))
+        );
+        assert_eq!(
+            Miniscript::parse(&format!("multi(3,{},{})", a, a)),
+            Err(MiniscriptError::InvalidThreshold { k: 3, n: 2 })
+        );
+        assert_eq!(
+            Miniscript::parse(&format!("thresh(0,pk({}))", a)),
+            Err(MiniscriptError::InvalidThreshold { k: 0, n: 1 })
+        );
+    }
+}
@@ -0,0 +1,420 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use crate::encoding::hex::to_hex;
+use crate::finite_field::secp_ec::S256Point;
+
+use super::fragment::{
+    check_arg_count, parse_hash, parse_key, parse_number, parse_timelock, split_call, Miniscript, MiniscriptError,
+    Terminal,
+};
+
+// CHECKMULTISIG takes at most 20 keys, past that thresh() is used
+const MAX_MULTISIG_KEYS: usize = 20;
+
+// A spending policy, what has to happen for coins to move without saying
+// how the script does it. Or carries the odds of each side, written N@X,
+// which the compiler uses to make the likelier side cheaper.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Policy {
+    Key(S256Point),
+    After(u32),
+    Older(u32),
+    Sha256([u8; 32]),
+    Hash256([u8; 32]),
+    Ripemd160([u8; 20]),
+    Hash160([u8; 20]),
+    And(Box<Policy>, Box<Policy>),
+    Or(usize, Box<Policy>, usize, Box<Policy>),
+    Thresh(usize, Vec<Policy>),
+}
+
+// a compiled miniscript with the witness size it expects to need, averaged
+// over the odds of each or() branch
+#[derive(Debug, Clone)]
+struct Candidate {
+    ms: Miniscript,
+    sat: f64,
+}
+
+impl Candidate {
+    // Only non-malleable miniscripts are kept, as no parent of a malleable
+    // one is non-malleable
+    fn new(node: Terminal, sat: f64) -> Option<Self> {
+        let ms = Miniscript::new(node).ok().filter(|ms| ms.get_type().has('m'))?;
+        Some(Self { ms, sat })
+    }
+
+    fn has(&self, property: char) -> bool {
+        self.ms.get_type().has(property)
+    }
+
+    fn dissat(&self) -> Option<f64> {
+        self.ms.max_dissatisfaction_size().map(|size| size as f64)
+    }
+
+    // bytes of script plus expected bytes of witness
+    fn cost(&self) -> f64 {
+        self.ms.script_size() as f64 + self.sat
+    }
+
+    fn wrap(&self, wrapper: fn(Box<Miniscript>) -> Terminal) -> Option<Self> {
+        Self::new(wrapper(Box::new(self.ms.clone())), self.sat)
+    }
+
+    fn verify(&self) -> Option<Self> {
+        self.wrap(Terminal::Verify)
+    }
+
+    // s: is a byte shorter than a: but needs a single input
+    fn to_w(&self) -> Option<Self> {
+        if self.has('o') {
+            self.wrap(Terminal::Swap)
+        } else {
+            self.wrap(Terminal::Alt)
+        }
+    }
+
+    // n: makes it u, and l: makes it d at the cost of an extra element to
+    // pick the branch
+    fn to_du(&self) -> Option<Self> {
+        let mut candidate = self.clone();
+        if !candidate.has('u') {
+            candidate = candidate.wrap(Terminal::ZeroNotEqual)?;
+        }
+        if !candidate.has('d') {
+            let false_ = Box::new(Miniscript::new(Terminal::False).ok()?);
+            candidate = Self::new(Terminal::OrI(false_, Box::new(candidate.ms)), candidate.sat + 1.0)?;
+        }
+        Some(candidate)
+    }
+}
+
+// The cheapest B candidate of each type, so parents needing some of the
+// properties can pick from them
+fn best_per_class(candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let mut all = candidates.clone();
+    all.extend(candidates.iter().filter_map(Candidate::to_du));
+    let mut best: Vec<Candidate> = vec![];
+    for candidate in all {
+        match best.iter_mut().find(|c| c.ms.get_type() == candidate.ms.get_type()) {
+            Some(c) if candidate.cost() < c.cost() => *c = candidate,
+            Some(_) => {}
+            None => best.push(candidate),
+        }
+    }
+    best
+}
+
+impl Policy {
+    pub fn parse(s: &str) -> Result<Self, MiniscriptError> {
+        let (name, args) = split_call(s)?;
+        let policy = match name {
+            "pk" => {
+                check_arg_count(name, &args, 1)?;
+                Policy::Key(parse_key(args[0])?)
+            }
+            "after" | "older" => {
+                check_arg_count(name, &args, 1)?;
+                let n = parse_timelock(args[0])?;
+                if name == "after" {
+                    Policy::After(n)
+                } else {
+                    Policy::Older(n)
+                }
+            }
+            "sha256" | "hash256" | "ripemd160" | "hash160" => {
+                check_arg_count(name, &args, 1)?;
+                match name {
+                    "sha256" => Policy::Sha256(parse_hash(args[0])?),
+                    "hash256" => Policy::Hash256(parse_hash(args[0])?),
+                    "ripemd160" => Policy::Ripemd160(parse_hash(args[0])?),
+                    _ => Policy::Hash160(parse_hash(args[0])?),
+                }
+            }
+            "and" => {
+                check_arg_count(name, &args, 2)?;
+                Policy::And(Box::new(Self::parse(args[0])?), Box::new(Self::parse(args[1])?))
+            }
+            "or" => {
+                check_arg_count(name, &args, 2)?;
+                let (a, left) = Self::parse_weighted(args[0])?;
+                let (b, right) = Self::parse_weighted(args[1])?;
+                Policy::Or(a, Box::new(left), b, Box::new(right))
+            }
+            "thresh" => {
+                let (k, rest) = args.split_first().unwrap();
+                let k = parse_number(k)?;
+                if k == 0 || k > rest.len() {
+                    return Err(MiniscriptError::InvalidThreshold { k, n: rest.len() });
+                }
+                Policy::Thresh(k, rest.iter().map(|arg| Self::parse(arg)).collect::<Result<_, _>>()?)
+            }
+            _ => return Err(MiniscriptError::UnknownFragment(name.to_string())),
+        };
+        Ok(policy)
+    }
+
+    // an or() argument with an optional N@ weight, 1 if there's none
+    fn parse_weighted(s: &str) -> Result<(usize, Self), MiniscriptError> {
+        match s.split_once('@') {
+            Some((weight, policy)) if !weight.contains('(') => match parse_number(weight)? {
+                0 => Err(MiniscriptError::InvalidNumber(weight.to_string())),
+                weight => Ok((weight, Self::parse(policy)?)),
+            },
+            _ => Ok((1, Self::parse(s)?)),
+        }
+    }
+
+    // The non-malleable miniscript with the smallest script plus expected
+    // witness that has the same spending conditions. Like a parsed one it
+    // can't mix timelocks or be over the script limits.
+    pub fn compile(&self) -> Result<Miniscript, MiniscriptError> {
+        let ms = self
+            .candidates()
+            .into_iter()
+            .min_by(|a, b| a.cost().total_cmp(&b.cost()))
+            .map(|candidate| candidate.ms)
+            .ok_or_else(|| MiniscriptError::TypeError(format!("no non-malleable miniscript for {}", self)))?;
+        ms.check_top_level()?;
+        Ok(ms)
+    }
+
+    fn candidates(&self) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        match self {
+            Policy::Key(key) => {
+                let pk_k = Miniscript::new(Terminal::PkK(key.clone())).unwrap();
+                let pk_h = Miniscript::new(Terminal::PkH(key.clone())).unwrap();
+                candidates.extend(Candidate::new(Terminal::Check(Box::new(pk_k)), 73.0));
+                candidates.extend(Candidate::new(Terminal::Check(Box::new(pk_h)), 107.0));
+            }
+            Policy::After(n) => candidates.extend(Candidate::new(Terminal::After(*n), 0.0)),
+            Policy::Older(n) => candidates.extend(Candidate::new(Terminal::Older(*n), 0.0)),
+            Policy::Sha256(h) => candidates.extend(Candidate::new(Terminal::Sha256(*h), 33.0)),
+            Policy::Hash256(h) => candidates.extend(Candidate::new(Terminal::Hash256(*h), 33.0)),
+            Policy::Ripemd160(h) => candidates.extend(Candidate::new(Terminal::Ripemd160(*h), 33.0)),
+            Policy::Hash160(h) => candidates.extend(Candidate::new(Terminal::Hash160(*h), 33.0)),
+            Policy::And(left, right) => {
+                let (left, right) = (left.candidates(), right.candidates());
+                for (ps, qs) in [(&left, &right), (&right, &left)] {
+                    for p in ps {
+                        for q in qs {
+                            candidates.extend(Self::and(p, q));
+                        }
+                    }
+                }
+            }
+            Policy::Or(a, left, b, right) => {
+                let (left, right) = (left.candidates(), right.candidates());
+                let (a, b) = (*a as f64 / (a + b) as f64, *b as f64 / (a + b) as f64);
+                for (ps, qs, pa, pb) in [(&left, &right, a, b), (&right, &left, b, a)] {
+                    for p in ps {
+                        for q in qs {
+                            candidates.extend(Self::or(p, q, pa, pb));
+                        }
+                    }
+                }
+            }
+            Policy::Thresh(k, subs) => candidates.extend(Self::thresh(*k, subs)),
+        }
+        best_per_class(candidates)
+    }
+
+    // and_v(v:P,Q) and and_b(P,W(Q))
+    fn and(p: &Candidate, q: &Candidate) -> Vec<Candidate> {
+        let sat = p.sat + q.sat;
+        let mut candidates = vec![];
+        if let Some(v) = p.verify() {
+            candidates.extend(Candidate::new(Terminal::AndV(Box::new(v.ms), Box::new(q.ms.clone())), sat));
+        }
+        if let Some(w) = q.to_w() {
+            candidates.extend(Candidate::new(Terminal::AndB(Box::new(p.ms.clone()), Box::new(w.ms)), sat));
+        }
+        candidates
+    }
+
+    // or_b(P,W(Q)), or_d(P,Q) and or_i(P,Q), with P satisfied pa of the time
+    fn or(p: &Candidate, q: &Candidate, pa: f64, pb: f64) -> Vec<Candidate> {
+        let (x, z) = (Box::new(p.ms.clone()), Box::new(q.ms.clone()));
+        let mut candidates = vec![];
+        if let (Some(p_dissat), Some(q_dissat)) = (p.dissat(), q.dissat()) {
+            if let Some(w) = q.to_w() {
+                let sat = pa * (p.sat + q_dissat) + pb * (q.sat + p_dissat);
+                candidates.extend(Candidate::new(Terminal::OrB(x.clone(), Box::new(w.ms)), sat));
+            }
+        }
+        if let (Some(p_dissat), true) = (p.dissat(), p.has('u')) {
+            let sat = pa * p.sat + pb * (q.sat + p_dissat);
+            candidates.extend(Candidate::new(Terminal::OrD(x.clone(), z.clone()), sat));
+        }
+        let sat = pa * (p.sat + 2.0) + pb * (q.sat + 1.0);
+        candidates.extend(Candidate::new(Terminal::OrI(x, z), sat));
+        candidates
+    }
+
+    // multi() for keys alone, otherwise thresh() with every sub made du and
+    // all but the first W. Subs need e for the thresh() to be non-malleable.
+    fn thresh(k: usize, subs: &[Policy]) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        let keys: Vec<S256Point> = subs
+            .iter()
+            .filter_map(|sub| match sub {
+                Policy::Key(key) => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+        if keys.len() == subs.len() && keys.len() <= MAX_MULTISIG_KEYS {
+            candidates.extend(Candidate::new(Terminal::Multi(k, keys), 1.0 + 73.0 * k as f64));
+        }
+
+        let mut parts = vec![];
+        for (i, sub) in subs.iter().enumerate() {
+            let du = sub
+                .candidates()
+                .into_iter()
+                .filter(|c| c.has('d') && c.has('u') && c.has('e'))
+                .min_by(|a, b| a.cost().total_cmp(&b.cost()));
+            let part = match (i, du) {
+                (0, Some(du)) => du,
+                (_, Some(du)) => match du.to_w() {
+                    Some(w) => w,
+                    None => return candidates,
+                },
+                (_, None) => return candidates,
+            };
+            parts.push(part);
+        }
+        // the k subs that add the least to the witness when satisfied
+        let dissat: f64 = parts.iter().map(|part| part.dissat().unwrap()).sum();
+        let mut extra: Vec<f64> = parts.iter().map(|part| part.sat - part.dissat().unwrap()).collect();
+        extra.sort_by(|a, b| a.total_cmp(b));
+        let sat = dissat + extra[..k].iter().sum::<f64>();
+        let subs = parts.into_iter().map(|part| part.ms).collect();
+        candidates.extend(Candidate::new(Terminal::Thresh(k, subs), sat));
+        candidates
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weighted = |weight: usize, policy: &Policy| match weight {
+            1 => policy.to_string(),
+            _ => format!("{}@{}", weight, policy),
+        };
+        match self {
+            Policy::Key(key) => write!(f, "pk({})", to_hex(&key.sec(true))),
+            Policy::After(n) => write!(f, "after({})", n),
+            Policy::Older(n) => write!(f, "older({})", n),
+            Policy::Sha256(h) => write!(f, "sha256({})", to_hex(h)),
+            Policy::Hash256(h) => write!(f, "hash256({})", to_hex(h)),
+            Policy::Ripemd160(h) => write!(f, "ripemd160({})", to_hex(h)),
+            Policy::Hash160(h) => write!(f, "hash160({})", to_hex(h)),
+            Policy::And(left, right) => write!(f, "and({},{})", left, right),
+            Policy::Or(a, left, b, right) => write!(f, "or({},{})", weighted(*a, left), weighted(*b, right)),
+            Policy::Thresh(k, subs) => {
+                let subs: Vec<String> = subs.iter().map(|sub| sub.to_string()).collect();
+                write!(f, "thresh({},{})", k, subs.join(","))
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::finite_field::private_key::PrivateKey;
+    use num_bigint::BigUint;
+
+    fn sec_hex(secret: u32) -> String {
+        hex::encode(PrivateKey::new(BigUint::from(secret)).get_point().sec(true))
+    }
+
+    // policy and miniscript with A, B and C replaced by the keys of 1, 2 and 3
+    fn with_keys(s: &str) -> String {
+        s.replace('A', &sec_hex(1)).replace('B', &sec_hex(2)).replace('C', &sec_hex(3))
+    }
+
+    #[test]
+    fn compile_works() {
+        let cases = [
+            ("pk(A)", "pk(A)"),
+            ("and(pk(A),pk(B))", "and_v(v:pk(A),pk(B))"),
+            ("or(pk(A),and(pk(B),older(144)))", "or_d(pk(A),and_v(v:pk(B),older(144)))"),
+            ("thresh(2,pk(A),pk(B),older(144))", "thresh(2,pk(A),s:pk(B),sln:older(144))"),
+            ("thresh(2,pk(A),pk(B),pk(C))", "multi(2,A,B,C)"),
+        ];
+        for (policy, expected) in cases {
+            let policy = Policy::parse(&with_keys(policy)).unwrap();
+            let ms = policy.compile().unwrap();
+            assert_eq!(ms.to_string(), with_keys(expected));
+            assert_eq!(Miniscript::parse(&ms.to_string()), Ok(ms));
+        }
+    }
+
+    #[test]
+    fn weights_favor_likely_branch() {
+        // the likelier branch goes under OP_ELSE, picked by the smaller
+        // empty element, and the unlikely one is cheaper with the shorter
+        // script of pkh() despite its bigger witness
+        let cases = [
+            (
+                "or(9@and(pk(A),older(5)),and(pk(B),after(7)))",
+                "or_i(and_v(v:pkh(B),after(7)),and_v(v:pk(A),older(5)))",
+            ),
+            (
+                "or(and(pk(A),older(5)),9@and(pk(B),after(7)))",
+                "or_i(and_v(v:pkh(A),older(5)),and_v(v:pk(B),after(7)))",
+            ),
+        ];
+        for (policy, expected) in cases {
+            let parsed = Policy::parse(&with_keys(policy)).unwrap();
+            assert_eq!(parsed.to_string(), with_keys(policy));
+            assert_eq!(parsed.compile().unwrap().to_string(), with_keys(expected));
+        }
+    }
+
+    #[test]
+    fn compile_rejects_insane_policies() {
+        // a height and a time can't both be met
+        let mixed = Policy::parse("and(after(100),after(500000001))").unwrap();
+        assert_eq!(mixed.compile(), Err(MiniscriptError::TimelockMix));
+        let mixed = Policy::parse(&with_keys("or(pk(A),and(older(144),older(4194305)))")).unwrap();
+        assert_eq!(mixed.compile(), Err(MiniscriptError::TimelockMix));
+        // but can be alternatives
+        assert!(Policy::parse(&with_keys("or(after(100),and(pk(A),after(500000001)))")).unwrap().compile().is_ok());
+
+        // 37 bytes for each key after the first is over 3600 bytes
+        let keys: Vec<String> = (1..=200).map(|i| format!("pk({})", sec_hex(i))).collect();
+        let large = Policy::parse(&format!("thresh(1,{})", keys.join(","))).unwrap();
+        assert!(matches!(large.compile(), Err(MiniscriptError::ScriptTooLarge(_))));
+
+        // without a signature anyone could take the other branch
+        let malleable = Policy::parse(&format!("or(sha256({}),after(100))", hex::encode([0u8; 32]))).unwrap();
+        assert!(matches!(malleable.compile(), Err(MiniscriptError::TypeError(_))));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_policies() {
+        assert_eq!(
+            Policy::parse(&with_keys("thresh(3,pk(A),pk(B))")),
+            Err(MiniscriptError::InvalidThreshold { k: 3, n: 2 })
+        );
+        assert_eq!(
+            Policy::parse("older(0)"),
+            Err(MiniscriptError::InvalidNumber("0".to_string()))
+        );
+        assert_eq!(
+            Policy::parse(&with_keys("and(pk(A))")),
+            Err(MiniscriptError::InvalidArgumentCount { fragment: "and".to_string(), count: 1 })
+        );
+        assert_eq!(
+            Policy::parse(&with_keys("xor(pk(A),pk(B))")),
+            Err(MiniscriptError::UnknownFragment("xor".to_string()))
+        );
+        assert!(matches!(Policy::parse("pk(02ab)"), Err(MiniscriptError::InvalidKey(_))));
+        assert!(matches!(Policy::parse(&with_keys("or(0@pk(A),pk(B))")), Err(MiniscriptError::InvalidNumber(_))));
+    }
+}
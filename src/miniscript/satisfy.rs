@@ -0,0 +1,453 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+
+use crate::finite_field::secp_ec::S256Point;
+use crate::hashes::ripemd160::{hash160, ripemd160};
+use crate::hashes::sha256::{hash256, sha256};
+
+use super::fragment::{Miniscript, MiniscriptError, Terminal};
+
+// sequence numbers with this bit set have no relative timelock
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+
+// set for a relative timelock in units of 512 seconds rather than blocks
+pub(super) const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+
+const SEQUENCE_LOCKTIME_MASK: u32 = 0xffff;
+
+// lock times below this are block heights, from it up they're unix times
+pub(super) const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+// a final input doesn't enforce the transaction's lock time
+const SEQUENCE_FINAL: u32 = 0xffffffff;
+
+// witness elements, bottom of the stack first
+type Witness = Vec<Vec<u8>>;
+
+// What a spender has to satisfy a miniscript with: signatures by key,
+// preimages, and the lock time and sequence of the spending input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Satisfier {
+    signatures: HashMap<Vec<u8>, Vec<u8>>,
+    preimages: HashMap<Vec<u8>, [u8; 32]>,
+    lock_time: u32,
+    sequence: u32,
+}
+
+impl Satisfier {
+    pub fn new(lock_time: u32, sequence: u32) -> Self {
+        Self { signatures: HashMap::new(), preimages: HashMap::new(), lock_time, sequence }
+    }
+
+    // a DER signature with its sighash byte
+    pub fn add_signature(&mut self, key: &S256Point, signature: Vec<u8>) {
+        self.signatures.insert(key.sec(true), signature);
+    }
+
+    // kept under each of its hashes, so it satisfies any hash fragment of it
+    pub fn add_preimage(&mut self, preimage: [u8; 32]) {
+        self.preimages.insert(sha256(&preimage).to_vec(), preimage);
+        self.preimages.insert(hash256(&preimage).to_vec(), preimage);
+        self.preimages.insert(ripemd160(&preimage).to_vec(), preimage);
+        self.preimages.insert(hash160(&preimage).to_vec(), preimage);
+    }
+
+    fn signature(&self, key: &S256Point) -> Option<Vec<u8>> {
+        self.signatures.get(&key.sec(true)).cloned()
+    }
+
+    // the preimage only if it hashes to h with this hash function, not just
+    // with one of the others
+    fn preimage(&self, h: &[u8], hash: impl Fn(&[u8]) -> Vec<u8>) -> Option<Vec<u8>> {
+        let preimage = self.preimages.get(h)?;
+        (hash(preimage) == h).then(|| preimage.to_vec())
+    }
+
+    // BIP68: the input's relative lock must be enabled, of the same kind,
+    // and at least n
+    fn check_older(&self, n: u32) -> bool {
+        if self.sequence & SEQUENCE_DISABLE_FLAG != 0 {
+            return false;
+        }
+        if self.sequence & SEQUENCE_TYPE_FLAG != n & SEQUENCE_TYPE_FLAG {
+            return false;
+        }
+        self.sequence & SEQUENCE_LOCKTIME_MASK >= n & SEQUENCE_LOCKTIME_MASK
+    }
+
+    // both heights or both times, and the input not final
+    fn check_after(&self, n: u32) -> bool {
+        if self.sequence == SEQUENCE_FINAL {
+            return false;
+        }
+        if (self.lock_time < LOCKTIME_THRESHOLD) != (n < LOCKTIME_THRESHOLD) {
+            return false;
+        }
+        self.lock_time >= n
+    }
+}
+
+// bytes in the witness, a length byte for each element
+fn witness_size(witness: &Witness) -> usize {
+    witness.iter().map(|element| 1 + element.len()).sum()
+}
+
+// A witness along with what a third party could do with it. Without a
+// signature they can swap it for any other that also needs none; a
+// malleable one they can change as it is; and a non-canonical one is a form
+// honest signers don't make, so it's only used when nothing else works.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Solution {
+    witness: Witness,
+    has_sig: bool,
+    malleable: bool,
+    non_canonical: bool,
+}
+
+impl Solution {
+    fn new(witness: Witness) -> Option<Self> {
+        Some(Self { witness, has_sig: false, malleable: false, non_canonical: false })
+    }
+
+    fn with_sig(mut self) -> Self {
+        self.has_sig = true;
+        self
+    }
+
+    fn malleable(mut self) -> Self {
+        self.malleable = true;
+        self
+    }
+
+    fn non_canonical(mut self) -> Self {
+        self.non_canonical = true;
+        self
+    }
+}
+
+fn empty() -> Option<Solution> {
+    Solution::new(vec![])
+}
+
+fn element(bytes: Vec<u8>) -> Option<Solution> {
+    Solution::new(vec![bytes])
+}
+
+// a then b on top of it, if both exist
+fn concat(a: Option<Solution>, b: Option<Solution>) -> Option<Solution> {
+    let (mut a, b) = (a?, b?);
+    a.witness.extend(b.witness);
+    a.has_sig |= b.has_sig;
+    a.malleable |= b.malleable;
+    a.non_canonical |= b.non_canonical;
+    Some(a)
+}
+
+// The one of two solutions a spender should use, as in Bitcoin Core. One
+// without a signature has to be picked over one with, since anyone could
+// switch to it, and if neither has a signature anyone can switch between
+// them, so both are malleable. Otherwise the smaller wins, a on a tie.
+fn choose(a: Option<Solution>, b: Option<Solution>) -> Option<Solution> {
+    let (mut a, mut b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        (a, None) => return a,
+        (None, b) => return b,
+    };
+    if a.non_canonical != b.non_canonical {
+        return Some(if a.non_canonical { b } else { a });
+    }
+    if a.has_sig != b.has_sig {
+        return Some(if a.has_sig { b } else { a });
+    }
+    if !a.has_sig {
+        a.malleable = true;
+        b.malleable = true;
+    } else if a.malleable != b.malleable {
+        return Some(if a.malleable { b } else { a });
+    }
+    Some(if witness_size(&b.witness) < witness_size(&a.witness) { b } else { a })
+}
+
+impl Miniscript {
+    // The smallest witness stack that satisfies the script and that a third
+    // party can't change into another that also does, bottom first, leaving
+    // out the witness script itself. A satisfaction they could change is an
+    // error: the transaction would still be valid but its witness, and so
+    // its size and fee rate, wouldn't be ours to decide.
+    pub fn satisfy(&self, satisfier: &Satisfier) -> Result<Witness, MiniscriptError> {
+        match self.satisfactions(satisfier).0 {
+            Some(sat) if sat.malleable => Err(MiniscriptError::Malleable),
+            Some(sat) => Ok(sat.witness),
+            None => Err(MiniscriptError::Unsatisfiable),
+        }
+    }
+
+    // the best satisfaction and dissatisfaction, if they exist. Children
+    // that run first consume the top of the stack, so their witness goes
+    // after the ones that run later.
+    fn satisfactions(&self, satisfier: &Satisfier) -> (Option<Solution>, Option<Solution>) {
+        let zero = || element(vec![]);
+        let one = || element(vec![1]);
+        match self.get_node() {
+            Terminal::False => (None, empty()),
+            Terminal::True => (empty(), None),
+            Terminal::PkK(key) => {
+                let sat = satisfier.signature(key).and_then(element).map(Solution::with_sig);
+                (sat, zero())
+            }
+            Terminal::PkH(key) => {
+                let sec = key.sec(true);
+                let sat = satisfier.signature(key).and_then(element).map(Solution::with_sig);
+                (concat(sat, element(sec.clone())), concat(zero(), element(sec)))
+            }
+            Terminal::Older(n) => (satisfier.check_older(*n).then(empty).flatten(), None),
+            Terminal::After(n) => (satisfier.check_after(*n).then(empty).flatten(), None),
+            // any 32 bytes other than the preimage dissatisfy, so a third
+            // party can pick others
+            Terminal::Sha256(h) => {
+                let sat = satisfier.preimage(h, |p| sha256(p).to_vec());
+                (sat.and_then(element), element(vec![0; 32]).map(Solution::malleable))
+            }
+            Terminal::Hash256(h) => {
+                let sat = satisfier.preimage(h, |p| hash256(p).to_vec());
+                (sat.and_then(element), element(vec![0; 32]).map(Solution::malleable))
+            }
+            Terminal::Ripemd160(h) => {
+                let sat = satisfier.preimage(h, |p| ripemd160(p).to_vec());
+                (sat.and_then(element), element(vec![0; 32]).map(Solution::malleable))
+            }
+            Terminal::Hash160(h) => {
+                let sat = satisfier.preimage(h, |p| hash160(p).to_vec());
+                (sat.and_then(element), element(vec![0; 32]).map(Solution::malleable))
+            }
+            Terminal::AndOr(x, y, z) => {
+                let (x_sat, x_dissat) = x.satisfactions(satisfier);
+                let (y_sat, y_dissat) = y.satisfactions(satisfier);
+                let (z_sat, z_dissat) = z.satisfactions(satisfier);
+                let sat = choose(concat(y_sat, x_sat.clone()), concat(z_sat, x_dissat.clone()));
+                let dissat =
+                    choose(concat(y_dissat, x_sat).map(Solution::non_canonical), concat(z_dissat, x_dissat));
+                (sat, dissat)
+            }
+            Terminal::AndV(x, y) => {
+                let (x_sat, _) = x.satisfactions(satisfier);
+                let (y_sat, y_dissat) = y.satisfactions(satisfier);
+                (concat(y_sat, x_sat.clone()), concat(y_dissat, x_sat).map(Solution::non_canonical))
+            }
+            Terminal::AndB(x, y) => {
+                let (x_sat, x_dissat) = x.satisfactions(satisfier);
+                let (y_sat, y_dissat) = y.satisfactions(satisfier);
+                let odd = |a, b| concat(a, b).map(|s: Solution| s.malleable().non_canonical());
+                let dissat = choose(
+                    choose(concat(y_dissat.clone(), x_dissat.clone()), odd(y_sat.clone(), x_dissat)),
+                    odd(y_dissat, x_sat.clone()),
+                );
+                (concat(y_sat, x_sat), dissat)
+            }
+            Terminal::OrB(x, z) => {
+                let (x_sat, x_dissat) = x.satisfactions(satisfier);
+                let (z_sat, z_dissat) = z.satisfactions(satisfier);
+                let both = concat(z_sat.clone(), x_sat.clone()).map(|s| s.malleable().non_canonical());
+                let sat = choose(
+                    choose(concat(z_dissat.clone(), x_sat), concat(z_sat, x_dissat.clone())),
+                    both,
+                );
+                (sat, concat(z_dissat, x_dissat))
+            }
+            Terminal::OrC(x, z) | Terminal::OrD(x, z) => {
+                let (x_sat, x_dissat) = x.satisfactions(satisfier);
+                let (z_sat, z_dissat) = z.satisfactions(satisfier);
+                let sat = choose(x_sat, concat(z_sat, x_dissat.clone()));
+                match self.get_node() {
+                    Terminal::OrD(_, _) => (sat, concat(z_dissat, x_dissat)),
+                    _ => (sat, None),
+                }
+            }
+            // 1 takes the OP_IF branch, empty the OP_ELSE one
+            Terminal::OrI(x, z) => {
+                let (x_sat, x_dissat) = x.satisfactions(satisfier);
+                let (z_sat, z_dissat) = z.satisfactions(satisfier);
+                let sat = choose(concat(x_sat, one()), concat(z_sat, zero()));
+                let dissat = choose(concat(x_dissat, one()), concat(z_dissat, zero()));
+                (sat, dissat)
+            }
+            // sats[j] is the best way to satisfy j of the subs seen so far
+            // and dissatisfy the rest, going from the last sub to the first.
+            // Satisfying some but not k is a dissatisfaction too, though not
+            // a canonical one.
+            Terminal::Thresh(k, subs) => {
+                let mut sats = vec![empty()];
+                for sub in subs.iter().rev() {
+                    let (sat, dissat) = sub.satisfactions(satisfier);
+                    let mut next = vec![concat(sats[0].clone(), dissat.clone())];
+                    for j in 1..sats.len() {
+                        let without = concat(sats[j].clone(), dissat.clone());
+                        next.push(choose(without, concat(sats[j - 1].clone(), sat.clone())));
+                    }
+                    next.push(concat(sats[sats.len() - 1].clone(), sat));
+                    sats = next;
+                }
+                let mut dissat = None;
+                for (i, solution) in sats.iter().enumerate() {
+                    if i == 0 {
+                        dissat = choose(dissat, solution.clone());
+                    } else if i != *k {
+                        dissat = choose(dissat, solution.clone().map(|s| s.malleable().non_canonical()));
+                    }
+                }
+                (sats[*k].clone(), dissat)
+            }
+            // the extra element CHECKMULTISIG pops, then signatures in the
+            // same order as their keys
+            Terminal::Multi(k, keys) => {
+                let sigs: Vec<Vec<u8>> = keys.iter().filter_map(|key| satisfier.signature(key)).take(*k).collect();
+                let sat = (sigs.len() == *k).then(|| [vec![vec![]], sigs].concat());
+                (sat.and_then(Solution::new).map(Solution::with_sig), Solution::new(vec![vec![]; k + 1]))
+            }
+            Terminal::Alt(x) | Terminal::Swap(x) | Terminal::Check(x) | Terminal::ZeroNotEqual(x) => {
+                x.satisfactions(satisfier)
+            }
+            Terminal::DupIf(x) => (concat(x.satisfactions(satisfier).0, one()), zero()),
+            Terminal::Verify(x) => (x.satisfactions(satisfier).0, None),
+            // A dissatisfaction of X with a nonzero top element would also
+            // work, so if X has one without a signature, assume that's the
+            // kind and the empty element can be swapped for it
+            Terminal::NonZero(x) => {
+                let (sat, dissat) = x.satisfactions(satisfier);
+                let swappable = dissat.is_some_and(|dissat| !dissat.has_sig);
+                (sat, zero().map(|s| if swappable { s.malleable() } else { s }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::finite_field::private_key::PrivateKey;
+    use num_bigint::BigUint;
+
+    fn key(secret: u32) -> S256Point {
+        PrivateKey::new(BigUint::from(secret)).get_point()
+    }
+
+    fn sec_hex(secret: u32) -> String {
+        hex::encode(key(secret).sec(true))
+    }
+
+    // a stand-in for a DER signature, which the satisfier doesn't check
+    fn signature(tag: u8) -> Vec<u8> {
+        vec![tag; 72]
+    }
+
+    #[test]
+    fn satisfy_picks_smallest_branch() {
+        let ms = Miniscript::parse(&format!("or_d(pk({}),and_v(v:pk({}),older(144)))", sec_hex(1), sec_hex(2))).unwrap();
+
+        // nothing to sign with
+        let mut satisfier = Satisfier::new(0, 144);
+        assert_eq!(ms.satisfy(&satisfier), Err(MiniscriptError::Unsatisfiable));
+
+        // B alone needs the timelock too
+        satisfier.add_signature(&key(2), signature(2));
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![signature(2), vec![]]));
+        assert_eq!(ms.satisfy(&Satisfier::new(0, 143)), Err(MiniscriptError::Unsatisfiable));
+        let mut early = Satisfier::new(0, 143);
+        early.add_signature(&key(2), signature(2));
+        assert_eq!(ms.satisfy(&early), Err(MiniscriptError::Unsatisfiable));
+
+        // A's signature alone is smaller
+        satisfier.add_signature(&key(1), signature(1));
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![signature(1)]));
+    }
+
+    #[test]
+    fn satisfy_thresh_and_multi() {
+        let thresh = format!("thresh(2,pk({}),s:pk({}),sln:older(144))", sec_hex(1), sec_hex(2));
+        let ms = Miniscript::parse(&thresh).unwrap();
+        let mut satisfier = Satisfier::new(0, 144);
+        satisfier.add_signature(&key(2), signature(2));
+        // the first sub's witness is on top
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![vec![], signature(2), vec![]]));
+        assert!(ms.satisfy(&Satisfier::new(0, 0)).is_err());
+
+        let multi = format!("multi(2,{},{},{})", sec_hex(1), sec_hex(2), sec_hex(3));
+        let ms = Miniscript::parse(&multi).unwrap();
+        let mut satisfier = Satisfier::new(0, 0);
+        satisfier.add_signature(&key(3), signature(3));
+        satisfier.add_signature(&key(1), signature(1));
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![vec![], signature(1), signature(3)]));
+        assert_eq!(witness_size(&ms.satisfy(&satisfier).unwrap()), ms.max_satisfaction_size().unwrap());
+    }
+
+    #[test]
+    fn satisfy_with_preimages_and_locktime() {
+        let preimage = [7u8; 32];
+        let ms = Miniscript::parse(&format!(
+            "andor(pk({}),sha256({}),and_v(v:hash160({}),after(500000)))",
+            sec_hex(1),
+            hex::encode(sha256(&preimage)),
+            hex::encode(hash160(&preimage)),
+        ))
+        .unwrap();
+
+        let mut satisfier = Satisfier::new(500000, 0xfffffffe);
+        satisfier.add_preimage(preimage);
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![preimage.to_vec(), vec![]]));
+
+        // a time lock can't satisfy a height, nor can a final input
+        let mut by_time = Satisfier::new(LOCKTIME_THRESHOLD, 0xfffffffe);
+        by_time.add_preimage(preimage);
+        assert!(ms.satisfy(&by_time).is_err());
+        let mut is_final = Satisfier::new(500000, SEQUENCE_FINAL);
+        is_final.add_preimage(preimage);
+        assert!(ms.satisfy(&is_final).is_err());
+
+        // before the lock time only the signature and sha256 branch works
+        let mut early = Satisfier::new(499999, 0xfffffffe);
+        early.add_preimage(preimage);
+        assert!(ms.satisfy(&early).is_err());
+        early.add_signature(&key(1), signature(1));
+        assert_eq!(ms.satisfy(&early), Ok(vec![preimage.to_vec(), signature(1)]));
+    }
+
+    // Without the preimage the 32 zero bytes dissatisfying sha256 could be
+    // any others, and with it the signature isn't needed
+    #[test]
+    fn satisfy_rejects_malleable() {
+        let preimage = [7u8; 32];
+        let ms = Miniscript::parse(&format!("or_d(sha256({}),pk({}))", hex::encode(sha256(&preimage)), sec_hex(1)))
+            .unwrap();
+        assert!(!ms.get_type().has('m'));
+
+        let mut satisfier = Satisfier::new(0, 0);
+        satisfier.add_signature(&key(1), signature(1));
+        assert_eq!(ms.satisfy(&satisfier), Err(MiniscriptError::Malleable));
+        satisfier.add_preimage(preimage);
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![preimage.to_vec()]));
+    }
+
+    // Like Bitcoin Core's malleable or_b(un:multi(..),al:older(16)) vector.
+    // The older(16) branch needs no signature, but the multi() beside it can
+    // be dissatisfied in two ways, and a signature doesn't help as anyone
+    // could switch to that branch. Before the timelock it's safe.
+    #[test]
+    fn satisfy_prefers_unsigned_alternative() {
+        let ms = Miniscript::parse(&format!("or_b(un:multi(1,{},{}),al:older(16))", sec_hex(1), sec_hex(2))).unwrap();
+        let mut satisfier = Satisfier::new(0, 16);
+        assert_eq!(ms.satisfy(&satisfier), Err(MiniscriptError::Malleable));
+        satisfier.add_signature(&key(2), signature(2));
+        assert_eq!(ms.satisfy(&satisfier), Err(MiniscriptError::Malleable));
+        let mut early = Satisfier::new(0, 15);
+        early.add_signature(&key(2), signature(2));
+        assert_eq!(ms.satisfy(&early), Ok(vec![vec![1], vec![], signature(2), vec![1]]));
+
+        // where both branches need a signature neither can be swapped
+        let ms = Miniscript::parse(&format!("or_b(pk({}),s:pk({}))", sec_hex(1), sec_hex(2))).unwrap();
+        let mut satisfier = Satisfier::new(0, 0);
+        satisfier.add_signature(&key(1), signature(1));
+        satisfier.add_signature(&key(2), signature(2));
+        assert_eq!(ms.satisfy(&satisfier), Ok(vec![vec![], signature(1)]));
+    }
+}
@@ -0,0 +1,440 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use super::fragment::{MiniscriptError, Terminal};
+use super::satisfy::{LOCKTIME_THRESHOLD, SEQUENCE_TYPE_FLAG};
+
+// What running a fragment leaves on the stack:
+// B pushes nonzero when satisfied and zero when dissatisfied,
+// V pushes nothing and aborts unless satisfied,
+// K pushes a key for a CHECKSIG to consume,
+// W is a B that works on the element under the top, for combining with others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    B,
+    V,
+    K,
+    W,
+}
+
+// A base type and the properties of P2WSH miniscript. For correctness:
+// z consumes no stack elements, o consumes exactly one, n never needs an
+// empty top element, d has a dissatisfaction and u leaves exactly 1 on the
+// stack when satisfied. For malleability: s every satisfaction needs a
+// signature, f every dissatisfaction needs one, e exactly one
+// dissatisfaction needs no signature, and m a non-malleable satisfaction always
+// exists. k means no satisfaction needs both a height and a time lock of
+// the same kind, which no transaction could meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type {
+    base: Base,
+    z: bool,
+    o: bool,
+    n: bool,
+    d: bool,
+    u: bool,
+    s: bool,
+    f: bool,
+    e: bool,
+    m: bool,
+    k: bool,
+    timelocks: Timelocks,
+}
+
+// The kinds of timelock some satisfaction of a fragment may need
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Timelocks {
+    relative_time: bool,
+    relative_height: bool,
+    absolute_time: bool,
+    absolute_height: bool,
+}
+
+impl Timelocks {
+    fn older(n: u32) -> Self {
+        let time = n & SEQUENCE_TYPE_FLAG != 0;
+        Self { relative_time: time, relative_height: !time, ..Self::default() }
+    }
+
+    fn after(n: u32) -> Self {
+        let time = n >= LOCKTIME_THRESHOLD;
+        Self { absolute_time: time, absolute_height: !time, ..Self::default() }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            relative_time: self.relative_time || other.relative_time,
+            relative_height: self.relative_height || other.relative_height,
+            absolute_time: self.absolute_time || other.absolute_time,
+            absolute_height: self.absolute_height || other.absolute_height,
+        }
+    }
+
+    // whether satisfying both at once could need a height and a time of the
+    // same kind
+    fn conflicts(self, other: Self) -> bool {
+        (self.relative_time && other.relative_height)
+            || (self.relative_height && other.relative_time)
+            || (self.absolute_time && other.absolute_height)
+            || (self.absolute_height && other.absolute_time)
+    }
+}
+
+impl Type {
+    // B with no properties, for the ones a rule leaves unset
+    const EMPTY: Type = Type {
+        base: Base::B,
+        z: false,
+        o: false,
+        n: false,
+        d: false,
+        u: false,
+        s: false,
+        f: false,
+        e: false,
+        m: false,
+        k: false,
+        timelocks: Timelocks {
+            relative_time: false,
+            relative_height: false,
+            absolute_time: false,
+            absolute_height: false,
+        },
+    };
+
+    fn new(base: Base, properties: &str) -> Self {
+        let has = |c| properties.contains(c);
+        Self { base, z: has('z'), o: has('o'), n: has('n'), d: has('d'), u: has('u'), ..Self::EMPTY }
+    }
+
+    pub fn get_base(&self) -> Base {
+        self.base
+    }
+
+    // one of z, o, n, d, u, s, f, e, m and k
+    pub fn has(&self, property: char) -> bool {
+        match property {
+            'z' => self.z,
+            'o' => self.o,
+            'n' => self.n,
+            'd' => self.d,
+            'u' => self.u,
+            's' => self.s,
+            'f' => self.f,
+            'e' => self.e,
+            'm' => self.m,
+            'k' => self.k,
+            _ => false,
+        }
+    }
+
+    // whether this is base with at least the given properties, like "Bdu"
+    fn is(&self, spec: &str) -> bool {
+        let base = match &spec[..1] {
+            "B" => Base::B,
+            "V" => Base::V,
+            "K" => Base::K,
+            _ => Base::W,
+        };
+        self.base == base && spec[1..].chars().all(|c| self.has(c))
+    }
+
+    // The type of a fragment from the types of its children, or why it has
+    // none
+    pub fn of(node: &Terminal) -> Result<Self, MiniscriptError> {
+        let mut ty = Self::correctness(node)?;
+        (ty.s, ty.f, ty.e, ty.m) = Self::malleability(node);
+        (ty.timelocks, ty.k) = Self::timelocks(node);
+        Ok(ty)
+    }
+
+    // the base type and z, o, n, d and u, which are all a fragment needs to
+    // be valid
+    fn correctness(node: &Terminal) -> Result<Self, MiniscriptError> {
+        use Base::*;
+        let require = |ok: bool, reason: &str| {
+            if ok {
+                Ok(())
+            } else {
+                Err(MiniscriptError::TypeError(reason.to_string()))
+            }
+        };
+        let ty = match node {
+            Terminal::False => Type::new(B, "zud"),
+            Terminal::True => Type::new(B, "zu"),
+            Terminal::PkK(_) => Type::new(K, "ondu"),
+            Terminal::PkH(_) => Type::new(K, "ndu"),
+            Terminal::Older(_) | Terminal::After(_) => Type::new(B, "z"),
+            Terminal::Sha256(_) | Terminal::Hash256(_) | Terminal::Ripemd160(_) | Terminal::Hash160(_) => {
+                Type::new(B, "ondu")
+            }
+            Terminal::Multi(_, _) => Type::new(B, "ndu"),
+            Terminal::AndOr(x, y, z) => {
+                let (x, y, z) = (x.get_type(), y.get_type(), z.get_type());
+                require(x.is("Bdu"), "andor(X,Y,Z) needs X to be Bdu")?;
+                require(y.base == z.base && y.base != W, "andor(X,Y,Z) needs Y and Z both B, K or V")?;
+                Type {
+                    base: y.base,
+                    z: x.z && y.z && z.z,
+                    o: (x.z && y.o && z.o) || (x.o && y.z && z.z),
+                    n: false,
+                    d: z.d,
+                    u: y.u && z.u,
+                    ..Type::EMPTY
+                }
+            }
+            Terminal::AndV(x, y) => {
+                let (x, y) = (x.get_type(), y.get_type());
+                require(x.base == V, "and_v(X,Y) needs X to be V")?;
+                require(y.base != W, "and_v(X,Y) needs Y to be B, K or V")?;
+                Type {
+                    base: y.base,
+                    z: x.z && y.z,
+                    o: (x.z && y.o) || (x.o && y.z),
+                    n: x.n || (x.z && y.n),
+                    d: false,
+                    u: y.u,
+                    ..Type::EMPTY
+                }
+            }
+            Terminal::AndB(x, y) => {
+                let (x, y) = (x.get_type(), y.get_type());
+                require(x.base == B && y.base == W, "and_b(X,Y) needs X to be B and Y to be W")?;
+                Type {
+                    base: B,
+                    z: x.z && y.z,
+                    o: (x.z && y.o) || (x.o && y.z),
+                    n: x.n || (x.z && y.n),
+                    d: x.d && y.d,
+                    u: true,
+                    ..Type::EMPTY
+                }
+            }
+            Terminal::OrB(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                require(x.is("Bd") && z.is("Wd"), "or_b(X,Z) needs X to be Bd and Z to be Wd")?;
+                Type {
+                    base: B,
+                    z: x.z && z.z,
+                    o: (x.z && z.o) || (x.o && z.z),
+                    n: false,
+                    d: true,
+                    u: true,
+                    ..Type::EMPTY
+                }
+            }
+            Terminal::OrC(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                require(x.is("Bdu") && z.base == V, "or_c(X,Z) needs X to be Bdu and Z to be V")?;
+                Type { base: V, z: x.z && z.z, o: x.o && z.z, n: false, d: false, u: false, ..Type::EMPTY }
+            }
+            Terminal::OrD(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                require(x.is("Bdu") && z.base == B, "or_d(X,Z) needs X to be Bdu and Z to be B")?;
+                Type { base: B, z: x.z && z.z, o: x.o && z.z, n: false, d: z.d, u: z.u, ..Type::EMPTY }
+            }
+            Terminal::OrI(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                require(x.base == z.base && x.base != W, "or_i(X,Z) needs X and Z both B, K or V")?;
+                Type { base: x.base, z: false, o: x.z && z.z, n: false, d: x.d || z.d, u: x.u && z.u, ..Type::EMPTY }
+            }
+            Terminal::Thresh(k, subs) => {
+                if *k == 0 || *k > subs.len() {
+                    return Err(MiniscriptError::InvalidThreshold { k: *k, n: subs.len() });
+                }
+                let types: Vec<Type> = subs.iter().map(|sub| sub.get_type()).collect();
+                require(types[0].is("Bdu"), "thresh() needs its first argument to be Bdu")?;
+                require(types[1..].iter().all(|ty| ty.is("Wdu")), "thresh() needs the rest to be Wdu")?;
+                let zero_count = types.iter().filter(|ty| ty.z).count();
+                let one_count = types.iter().filter(|ty| ty.o).count();
+                Type {
+                    base: B,
+                    z: zero_count == types.len(),
+                    o: zero_count == types.len() - 1 && one_count == 1,
+                    n: false,
+                    d: true,
+                    u: true,
+                    ..Type::EMPTY
+                }
+            }
+            Terminal::Alt(x) => {
+                let x = x.get_type();
+                require(x.base == B, "a:X needs X to be B")?;
+                Type { base: W, z: false, o: false, n: false, d: x.d, u: x.u, ..Type::EMPTY }
+            }
+            Terminal::Swap(x) => {
+                let x = x.get_type();
+                require(x.is("Bo"), "s:X needs X to be Bo")?;
+                Type { base: W, z: false, o: false, n: false, d: x.d, u: x.u, ..Type::EMPTY }
+            }
+            Terminal::Check(x) => {
+                let x = x.get_type();
+                require(x.base == K, "c:X needs X to be K")?;
+                Type { base: B, z: false, o: x.o, n: x.n, d: x.d, u: true, ..Type::EMPTY }
+            }
+            // u would need MINIMALIF, which is only policy outside tapscript
+            Terminal::DupIf(x) => {
+                let x = x.get_type();
+                require(x.is("Vz"), "d:X needs X to be Vz")?;
+                Type::new(B, "ond")
+            }
+            Terminal::Verify(x) => {
+                let x = x.get_type();
+                require(x.base == B, "v:X needs X to be B")?;
+                Type { base: V, z: x.z, o: x.o, n: x.n, d: false, u: false, ..Type::EMPTY }
+            }
+            Terminal::NonZero(x) => {
+                let x = x.get_type();
+                require(x.is("Bn"), "j:X needs X to be Bn")?;
+                Type { base: B, z: false, o: x.o, n: true, d: true, u: x.u, ..Type::EMPTY }
+            }
+            Terminal::ZeroNotEqual(x) => {
+                let x = x.get_type();
+                require(x.base == B, "n:X needs X to be B")?;
+                Type { base: B, z: x.z, o: x.o, n: x.n, d: x.d, u: true, ..Type::EMPTY }
+            }
+        };
+        Ok(ty)
+    }
+
+    // s, f, e and m. A third party can't make signatures, so a choice
+    // between satisfactions is only safe when the ones they could switch to
+    // need a signature.
+    fn malleability(node: &Terminal) -> (bool, bool, bool, bool) {
+        match node {
+            Terminal::False => (true, false, true, true),
+            Terminal::True => (false, true, false, true),
+            Terminal::PkK(_) | Terminal::PkH(_) | Terminal::Multi(_, _) => (true, false, true, true),
+            Terminal::Older(_) | Terminal::After(_) => (false, true, false, true),
+            // any 32 bytes but the preimage dissatisfy
+            Terminal::Sha256(_) | Terminal::Hash256(_) | Terminal::Ripemd160(_) | Terminal::Hash160(_) => {
+                (false, false, false, true)
+            }
+            Terminal::AndOr(x, y, z) => {
+                let (x, y, z) = (x.get_type(), y.get_type(), z.get_type());
+                (
+                    z.s && (x.s || y.s),
+                    z.f && (x.s || y.f),
+                    z.e && (x.s || y.f),
+                    x.m && y.m && z.m && x.e && (x.s || y.s || z.s),
+                )
+            }
+            Terminal::AndV(x, y) => {
+                let (x, y) = (x.get_type(), y.get_type());
+                (x.s || y.s, x.s || y.f, false, x.m && y.m)
+            }
+            Terminal::AndB(x, y) => {
+                let (x, y) = (x.get_type(), y.get_type());
+                (
+                    x.s || y.s,
+                    (x.f && (x.s || y.f)) || (y.f && y.s),
+                    x.e && y.e && x.s && y.s,
+                    x.m && y.m,
+                )
+            }
+            Terminal::OrB(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                (x.s && z.s, false, x.e && z.e, x.m && z.m && x.e && z.e && (x.s || z.s))
+            }
+            Terminal::OrC(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                (x.s && z.s, true, false, x.m && z.m && x.e && (x.s || z.s))
+            }
+            Terminal::OrD(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                (x.s && z.s, z.f, z.e, x.m && z.m && x.e && (x.s || z.s))
+            }
+            Terminal::OrI(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                (x.s && z.s, x.f && z.f, (x.e && z.f) || (z.e && x.f), x.m && z.m && (x.s || z.s))
+            }
+            // more than n - k signed subs means any k include one
+            Terminal::Thresh(k, subs) => {
+                let types: Vec<Type> = subs.iter().map(|sub| sub.get_type()).collect();
+                let signed = types.iter().filter(|ty| ty.s).count();
+                let all_e = types.iter().all(|ty| ty.e);
+                let all_m = types.iter().all(|ty| ty.m);
+                let n = subs.len();
+                (signed > n - k, false, all_e && signed == n, all_e && all_m && signed >= n - k)
+            }
+            Terminal::Alt(x) | Terminal::Swap(x) | Terminal::ZeroNotEqual(x) => {
+                let x = x.get_type();
+                (x.s, x.f, x.e, x.m)
+            }
+            Terminal::Check(x) => {
+                let x = x.get_type();
+                (true, x.f, x.e, x.m)
+            }
+            Terminal::DupIf(x) => {
+                let x = x.get_type();
+                (x.s, false, true, x.m)
+            }
+            Terminal::Verify(x) => {
+                let x = x.get_type();
+                (x.s, true, false, x.m)
+            }
+            Terminal::NonZero(x) => {
+                let x = x.get_type();
+                (x.s, false, x.f, x.m)
+            }
+        }
+    }
+
+    // the timelocks a fragment may need and k. Satisfying a fragment means
+    // satisfying both sides of an and, X and Y of andor and k subs of thresh,
+    // so those are where heights and times can end up mixed.
+    fn timelocks(node: &Terminal) -> (Timelocks, bool) {
+        match node {
+            Terminal::Older(n) => (Timelocks::older(*n), true),
+            Terminal::After(n) => (Timelocks::after(*n), true),
+            Terminal::AndOr(x, y, z) => {
+                let (x, y, z) = (x.get_type(), y.get_type(), z.get_type());
+                let k = x.k && y.k && z.k && !x.timelocks.conflicts(y.timelocks);
+                (x.timelocks.union(y.timelocks).union(z.timelocks), k)
+            }
+            Terminal::AndV(x, y) | Terminal::AndB(x, y) => {
+                let (x, y) = (x.get_type(), y.get_type());
+                (x.timelocks.union(y.timelocks), x.k && y.k && !x.timelocks.conflicts(y.timelocks))
+            }
+            Terminal::OrB(x, z) | Terminal::OrC(x, z) | Terminal::OrD(x, z) | Terminal::OrI(x, z) => {
+                let (x, z) = (x.get_type(), z.get_type());
+                (x.timelocks.union(z.timelocks), x.k && z.k)
+            }
+            Terminal::Thresh(k, subs) => {
+                let mut timelocks = Timelocks::default();
+                let mut no_mix = true;
+                for sub in subs {
+                    let ty = sub.get_type();
+                    no_mix = no_mix && ty.k && (*k == 1 || !timelocks.conflicts(ty.timelocks));
+                    timelocks = timelocks.union(ty.timelocks);
+                }
+                (timelocks, no_mix)
+            }
+            Terminal::Alt(x)
+            | Terminal::Swap(x)
+            | Terminal::Check(x)
+            | Terminal::DupIf(x)
+            | Terminal::Verify(x)
+            | Terminal::NonZero(x)
+            | Terminal::ZeroNotEqual(x) => {
+                let x = x.get_type();
+                (x.timelocks, x.k)
+            }
+            _ => (Timelocks::default(), true),
+        }
+    }
+}
+
+// the base followed by its properties, like Bondusemk
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.base)?;
+        for property in ['z', 'o', 'n', 'd', 'u', 's', 'f', 'e', 'm', 'k'] {
+            if self.has(property) {
+                write!(f, "{}", property)?;
+            }
+        }
+        Ok(())
+    }
+}
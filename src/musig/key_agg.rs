@@ -0,0 +1,245 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, One, Zero};
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+use crate::schnorr::bip340::XOnlyPublicKey;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MusigError {
+    // no public keys were given to aggregate
+    NoKeys,
+    // a tweak is not below n
+    InvalidTweak,
+    // aggregating or tweaking gave the point at infinity
+    InfinityResult,
+    // a secret key or nonce is zero or not below n
+    InvalidSecret,
+    // the public key in the secret nonce doesn't belong to the secret key
+    PublicKeyMismatch,
+    // the signer's public key is not one of the aggregated keys
+    SignerNotFound,
+    // the contribution of the signer at this index is invalid
+    InvalidContribution(usize),
+}
+
+impl fmt::Display for MusigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MusigError::NoKeys => write!(f, "no public keys to aggregate"),
+            MusigError::InvalidTweak => write!(f, "tweak is not below the curve order"),
+            MusigError::InfinityResult => write!(f, "result is the point at infinity"),
+            MusigError::InvalidSecret => write!(f, "secret is not in the range 1..n"),
+            MusigError::PublicKeyMismatch => write!(f, "secret nonce was made for a different key"),
+            MusigError::SignerNotFound => write!(f, "signer's key is not in the list of keys"),
+            MusigError::InvalidContribution(index) => write!(f, "invalid contribution from signer {}", index),
+        }
+    }
+}
+
+impl std::error::Error for MusigError {}
+
+// The aggregate key Q along with what tweaking did to it: Q = gacc * (sum of
+// a_i P_i) + tacc * G, where gacc is 1 or -1 (n - 1) and tacc the tweak sum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    q: S256Point,
+    gacc: BigUint,
+    tacc: BigUint,
+}
+
+impl KeyAggContext {
+    pub fn get_plain_pk(&self) -> S256Point {
+        self.q.clone()
+    }
+
+    pub fn get_xonly_pk(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.q)
+    }
+
+    pub fn get_gacc(&self) -> BigUint {
+        self.gacc.clone()
+    }
+
+    pub fn get_tacc(&self) -> BigUint {
+        self.tacc.clone()
+    }
+
+    // Adds tG to the aggregate key. A plain tweak (as in BIP32) is added to Q
+    // itself, an x-only tweak (as in taproot) to the even y version of Q.
+    pub fn apply_tweak(&self, tweak: &[u8; 32], is_xonly: bool) -> Result<Self, MusigError> {
+        let n = S256Point::order();
+        let g = if is_xonly && !self.q.has_even_y() { &n - 1u8 } else { BigUint::one() };
+        let t = BigUint::from_bytes_be(tweak);
+        if t >= n {
+            return Err(MusigError::InvalidTweak);
+        }
+
+        let q = self.q.clone() * g.clone() + S256Point::generator() * t.clone();
+        if q == S256Point::infinity_point() {
+            return Err(MusigError::InfinityResult);
+        }
+        Ok(Self {
+            q,
+            gacc: &g * &self.gacc % &n,
+            tacc: (t + g * &self.tacc) % &n,
+        })
+    }
+}
+
+// sorts keys by their compressed encoding, so everyone gets the same
+// aggregate key no matter which order they learned the keys in
+pub fn key_sort(pubkeys: &[S256Point]) -> Vec<S256Point> {
+    let mut sorted = pubkeys.to_vec();
+    sorted.sort_by_key(|pk| pk.sec(true));
+    sorted
+}
+
+// Q = sum of a_i P_i. The coefficients stop anyone from picking their key
+// as a function of the others' to cancel them out (a rogue key attack).
+pub fn key_agg(pubkeys: &[S256Point]) -> Result<KeyAggContext, MusigError> {
+    if pubkeys.is_empty() {
+        return Err(MusigError::NoKeys);
+    }
+    let terms: Vec<(BigUint, S256Point)> = pubkeys
+        .iter()
+        .map(|pk| (key_agg_coeff(pubkeys, pk), pk.clone()))
+        .collect();
+    let q = S256Point::multi_mul(&terms);
+    if q == S256Point::infinity_point() {
+        return Err(MusigError::InfinityResult);
+    }
+    Ok(KeyAggContext { q, gacc: BigUint::one(), tacc: BigUint::zero() })
+}
+
+// a_i = hash_KeyAgg coefficient(L || P_i) with L the hash of all keys, except
+// for the second distinct key, which gets 1 to save a multiplication
+pub fn key_agg_coeff(pubkeys: &[S256Point], pk: &S256Point) -> BigUint {
+    let second = pubkeys.iter().find(|key| *key != &pubkeys[0]);
+    if second == Some(pk) {
+        return BigUint::one();
+    }
+
+    let mut preimage = hash_keys(pubkeys).to_vec();
+    preimage.extend(pk.sec(true));
+    scalar::from_bytes_mod_order(&tagged_hash("KeyAgg coefficient", &preimage))
+}
+
+fn hash_keys(pubkeys: &[S256Point]) -> [u8; 32] {
+    let preimage: Vec<u8> = pubkeys.iter().flat_map(|pk| pk.sec(true)).collect();
+    tagged_hash("KeyAgg list", &preimage)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn pubkeys() -> Vec<S256Point> {
+        [
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66",
+        ]
+        .iter()
+        .map(|sec| S256Point::parse_sec(&hex::decode(sec).unwrap()).unwrap())
+        .collect()
+    }
+
+    // from the BIP327 key aggregation vectors
+    #[test]
+    fn key_agg_vectors_pass() {
+        let pks = pubkeys();
+        let cases: [(&[usize], &str); 4] = [
+            (&[0, 1, 2], "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C"),
+            (&[2, 1, 0], "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B"),
+            (&[0, 0, 0], "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935"),
+            (&[0, 0, 1, 1], "69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E"),
+        ];
+        for (indices, expected) in cases {
+            let keys: Vec<S256Point> = indices.iter().map(|i| pks[*i].clone()).collect();
+            let ctx = key_agg(&keys).unwrap();
+            assert_eq!(hex::encode_upper(ctx.get_xonly_pk().to_bytes()), expected);
+        }
+    }
+
+    // keys, tweaks with whether they are x-only, and the error
+    type ErrorCase = (&'static [usize], &'static [(usize, bool)], MusigError);
+
+    // the BIP327 key aggregation error vectors. Keys that don't parse are
+    // reported as the contribution of the signer at their index.
+    #[test]
+    fn key_agg_error_vectors_pass() {
+        let pubkeys = [
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66",
+            // not on the curve
+            "020000000000000000000000000000000000000000000000000000000000000005",
+            // x exceeds the field size
+            "02FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC30",
+            // first byte is not 2 or 3
+            "04F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+        ];
+        let tweaks = [
+            // n itself
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+            "252E4BD67410A76CDF933D30EAA1608214037F1B105A013ECCD3C5C184A6110B",
+        ];
+        let cases: [ErrorCase; 5] = [
+            (&[0, 3], &[], MusigError::InvalidContribution(1)),
+            (&[0, 4], &[], MusigError::InvalidContribution(1)),
+            (&[5, 0], &[], MusigError::InvalidContribution(0)),
+            (&[0, 1], &[(0, true)], MusigError::InvalidTweak),
+            // tweaking gives the point at infinity
+            (&[6], &[(1, false)], MusigError::InfinityResult),
+        ];
+        for (key_indices, tweak_indices, expected) in cases {
+            let result = key_indices
+                .iter()
+                .enumerate()
+                .map(|(i, index)| {
+                    S256Point::parse_sec(&hex::decode(pubkeys[*index]).unwrap())
+                        .map_err(|_| MusigError::InvalidContribution(i))
+                })
+                .collect::<Result<Vec<S256Point>, MusigError>>()
+                .and_then(|keys| key_agg(&keys))
+                .and_then(|ctx| {
+                    tweak_indices.iter().try_fold(ctx, |ctx, (index, is_xonly)| {
+                        let tweak: [u8; 32] = hex::decode(tweaks[*index]).unwrap().try_into().unwrap();
+                        ctx.apply_tweak(&tweak, *is_xonly)
+                    })
+                });
+            assert_eq!(result, Err(expected));
+        }
+    }
+
+    #[test]
+    fn key_sort_works() {
+        let pks = pubkeys();
+        let sorted = key_sort(&pks);
+        assert_eq!(sorted, vec![pks[2].clone(), pks[0].clone(), pks[1].clone()]);
+        assert_eq!(key_sort(&[pks[1].clone(), pks[2].clone(), pks[0].clone()]), sorted);
+    }
+
+    #[test]
+    fn apply_tweak_works() {
+        let ctx = key_agg(&pubkeys()).unwrap();
+        let n = S256Point::order();
+
+        let mut too_big = [0u8; 32];
+        too_big.copy_from_slice(&n.to_bytes_be());
+        assert_eq!(ctx.apply_tweak(&too_big, true), Err(MusigError::InvalidTweak));
+
+        let tweak = [1u8; 32];
+        let t = BigUint::from_bytes_be(&tweak);
+        let plain = ctx.apply_tweak(&tweak, false).unwrap();
+        assert_eq!(plain.get_plain_pk(), ctx.get_plain_pk() + S256Point::generator() * t.clone());
+
+        let xonly = ctx.apply_tweak(&tweak, true).unwrap();
+        assert_eq!(xonly.get_plain_pk(), ctx.get_xonly_pk().get_point() + S256Point::generator() * t);
+    }
+}
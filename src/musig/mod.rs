@@ -0,0 +1,3 @@
+pub mod key_agg;
+pub mod nonce;
+pub mod session;
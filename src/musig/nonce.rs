@@ -0,0 +1,274 @@
+#![allow(unused)]
+
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+use super::key_agg::MusigError;
+
+// The two secret nonces of a signer along with the key they are meant for.
+// It isn't Clone: signing consumes it, so the same nonce can't sign twice.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SecNonce {
+    k1: BigUint,
+    k2: BigUint,
+    pk: S256Point,
+}
+
+impl SecNonce {
+    // k1 || k2 || compressed pk, 97 bytes
+    pub fn parse(bytes: &[u8]) -> Result<Self, MusigError> {
+        if bytes.len() != 97 {
+            return Err(MusigError::InvalidSecret);
+        }
+        let pk = S256Point::parse_sec(&bytes[64..]).map_err(|_| MusigError::PublicKeyMismatch)?;
+        Ok(Self {
+            k1: BigUint::from_bytes_be(&bytes[..32]),
+            k2: BigUint::from_bytes_be(&bytes[32..64]),
+            pk,
+        })
+    }
+
+    pub fn get_pk(&self) -> S256Point {
+        self.pk.clone()
+    }
+
+    pub(super) fn into_parts(self) -> (BigUint, BigUint, S256Point) {
+        (self.k1, self.k2, self.pk)
+    }
+}
+
+// k1 * G and k2 * G, sent to the other signers in the first round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PubNonce {
+    r1: S256Point,
+    r2: S256Point,
+}
+
+impl PubNonce {
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut result = [0u8; 66];
+        result[..33].copy_from_slice(&self.r1.sec(true));
+        result[33..].copy_from_slice(&self.r2.sec(true));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 66 {
+            return None;
+        }
+        Some(Self {
+            r1: S256Point::parse_sec(&bytes[..33]).ok()?,
+            r2: S256Point::parse_sec(&bytes[33..]).ok()?,
+        })
+    }
+
+    pub fn get_points(&self) -> (S256Point, S256Point) {
+        (self.r1.clone(), self.r2.clone())
+    }
+}
+
+// The sums of everyone's R1 and R2. Unlike a single nonce either may be the
+// point at infinity, which is serialized as 33 zero bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggNonce {
+    r1: S256Point,
+    r2: S256Point,
+}
+
+impl AggNonce {
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut result = [0u8; 66];
+        result[..33].copy_from_slice(&sec_ext(&self.r1));
+        result[33..].copy_from_slice(&sec_ext(&self.r2));
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 66 {
+            return None;
+        }
+        Some(Self { r1: parse_sec_ext(&bytes[..33])?, r2: parse_sec_ext(&bytes[33..])? })
+    }
+
+    pub fn get_points(&self) -> (S256Point, S256Point) {
+        (self.r1.clone(), self.r2.clone())
+    }
+}
+
+fn sec_ext(point: &S256Point) -> Vec<u8> {
+    if *point == S256Point::infinity_point() {
+        vec![0u8; 33]
+    } else {
+        point.sec(true)
+    }
+}
+
+fn parse_sec_ext(bytes: &[u8]) -> Option<S256Point> {
+    if bytes == [0u8; 33] {
+        return Some(S256Point::infinity_point());
+    }
+    S256Point::parse_sec(bytes).ok()
+}
+
+// NonceGen from BIP327. rand must be fresh randomness for every call: reusing
+// a nonce for two different messages gives away the secret key. Everything
+// else is optional and only hardens the nonce against a broken rand.
+pub fn nonce_gen(
+    rand: &[u8; 32],
+    sk: Option<&PrivateKey>,
+    pk: &S256Point,
+    aggpk: Option<&[u8; 32]>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> (SecNonce, PubNonce) {
+    let mut rand = *rand;
+    if let Some(sk) = sk {
+        let mask = tagged_hash("MuSig/aux", &rand);
+        for ((byte, secret), mask) in rand.iter_mut().zip(scalar::to_bytes(&sk.get_secret())).zip(mask) {
+            *byte = secret ^ mask;
+        }
+    }
+
+    let pk_bytes = pk.sec(true);
+    let aggpk: &[u8] = match aggpk {
+        Some(aggpk) => aggpk,
+        None => &[],
+    };
+    let msg_prefixed = match msg {
+        Some(msg) => {
+            let mut result = vec![1u8];
+            result.extend_from_slice(&(msg.len() as u64).to_be_bytes());
+            result.extend_from_slice(msg);
+            result
+        }
+        None => vec![0u8],
+    };
+    let extra_in = extra_in.unwrap_or(&[]);
+
+    let k: Vec<BigUint> = (0..2u8)
+        .map(|i| {
+            let mut preimage = rand.to_vec();
+            preimage.push(pk_bytes.len() as u8);
+            preimage.extend_from_slice(&pk_bytes);
+            preimage.push(aggpk.len() as u8);
+            preimage.extend_from_slice(aggpk);
+            preimage.extend_from_slice(&msg_prefixed);
+            preimage.extend_from_slice(&(extra_in.len() as u32).to_be_bytes());
+            preimage.extend_from_slice(extra_in);
+            preimage.push(i);
+            scalar::from_bytes_mod_order(&tagged_hash("MuSig/nonce", &preimage))
+        })
+        .collect();
+    if k[0].is_zero() || k[1].is_zero() {
+        panic!("Nonce is zero");
+    }
+
+    let g = S256Point::generator();
+    let pubnonce = PubNonce { r1: g.clone() * k[0].clone(), r2: g * k[1].clone() };
+    let secnonce = SecNonce { k1: k[0].clone(), k2: k[1].clone(), pk: pk.clone() };
+    (secnonce, pubnonce)
+}
+
+pub fn nonce_agg(pubnonces: &[PubNonce]) -> AggNonce {
+    let mut r1 = S256Point::infinity_point();
+    let mut r2 = S256Point::infinity_point();
+    for nonce in pubnonces {
+        r1 = r1 + nonce.r1.clone();
+        r2 = r2 + nonce.r2.clone();
+    }
+    AggNonce { r1, r2 }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // the BIP327 nonce generation vectors: sk, aggpk, msg and extra_in, with
+    // None for the ones left out, then the expected secnonce and pubnonce.
+    // An empty message is not the same as no message.
+    #[test]
+    fn nonce_gen_vectors_pass() {
+        let rand = [0x0f; 32];
+        let pk = "024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766";
+        let long_msg = "2626262626262626262626262626262626262626262626262626262626262626262626262626";
+        let cases = [
+            (
+                Some("0202020202020202020202020202020202020202020202020202020202020202"),
+                pk,
+                Some("0707070707070707070707070707070707070707070707070707070707070707"),
+                Some("0101010101010101010101010101010101010101010101010101010101010101"),
+                Some("0808080808080808080808080808080808080808080808080808080808080808"),
+                "B114E502BEAA4E301DD08A50264172C84E41650E6CB726B410C0694D59EFFB64\
+                 95B5CAF28D045B973D63E3C99A44B807BDE375FD6CB39E46DC4A511708D0E9D2\
+                 024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766",
+                "02F7BE7089E8376EB355272368766B17E88E7DB72047D05E56AA881EA52B3B35DF\
+                 02C29C8046FDD0DED4C7E55869137200FBDBFE2EB654267B6D7013602CAED3115A",
+            ),
+            (
+                Some("0202020202020202020202020202020202020202020202020202020202020202"),
+                pk,
+                Some("0707070707070707070707070707070707070707070707070707070707070707"),
+                Some(""),
+                Some("0808080808080808080808080808080808080808080808080808080808080808"),
+                "E862B068500320088138468D47E0E6F147E01B6024244AE45EAC40ACE5929B9F\
+                 0789E051170B9E705D0B9EB49049A323BBBBB206D8E05C19F46C6228742AA7A9\
+                 024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766",
+                "023034FA5E2679F01EE66E12225882A7A48CC66719B1B9D3B6C4DBD743EFEDA2C5\
+                 03F3FD6F01EB3A8E9CB315D73F1F3D287CAFBB44AB321153C6287F407600205109",
+            ),
+            (
+                Some("0202020202020202020202020202020202020202020202020202020202020202"),
+                pk,
+                Some("0707070707070707070707070707070707070707070707070707070707070707"),
+                Some(long_msg),
+                Some("0808080808080808080808080808080808080808080808080808080808080808"),
+                "3221975ACBDEA6820EABF02A02B7F27D3A8EF68EE42787B88CBEFD9AA06AF363\
+                 2EE85B1A61D8EF31126D4663A00DD96E9D1D4959E72D70FE5EBB6E7696EBA66F\
+                 024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766",
+                "02E5BBC21C69270F59BD634FCBFA281BE9D76601295345112C58954625BF23793A\
+                 021307511C79F95D38ACACFF1B4DA98228B77E65AA216AD075E9673286EFB4EAF3",
+            ),
+            (
+                None,
+                "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+                None,
+                None,
+                None,
+                "89BDD787D0284E5E4D5FC572E49E316BAB7E21E3B1830DE37DFE80156FA41A6D\
+                 0B17AE8D024C53679699A6FD7944D9C4A366B514BAF43088E0708B1023DD2897\
+                 02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+                "02C96E7CB1E8AA5DAC64D872947914198F607D90ECDE5200DE52978AD5DED63C00\
+                 0299EC5117C2D29EDEE8A2092587C3909BE694D5CFF0667D6C02EA4059F7CD9786",
+            ),
+        ];
+        for (sk, pk, aggpk, msg, extra_in, expected_secnonce, expected_pubnonce) in cases {
+            let sk = sk.map(|sk| PrivateKey::new(BigUint::from_bytes_be(&hex::decode(sk).unwrap())));
+            let pk = S256Point::parse_sec(&hex::decode(pk).unwrap()).unwrap();
+            let aggpk: Option<[u8; 32]> = aggpk.map(|aggpk| hex::decode(aggpk).unwrap().try_into().unwrap());
+            let msg = msg.map(|msg| hex::decode(msg).unwrap());
+            let extra_in = extra_in.map(|extra_in| hex::decode(extra_in).unwrap());
+
+            let (secnonce, pubnonce) =
+                nonce_gen(&rand, sk.as_ref(), &pk, aggpk.as_ref(), msg.as_deref(), extra_in.as_deref());
+            assert_eq!(secnonce, SecNonce::parse(&hex::decode(expected_secnonce).unwrap()).unwrap());
+            assert_eq!(hex::encode_upper(pubnonce.serialize()), expected_pubnonce);
+        }
+    }
+
+    #[test]
+    fn nonce_agg_works() {
+        let g = S256Point::generator();
+        let a = PubNonce { r1: g.clone(), r2: g.clone() * BigUint::from(2u8) };
+        let b = PubNonce { r1: -g.clone(), r2: g.clone() };
+
+        let agg = nonce_agg(&[a.clone(), b]);
+        assert_eq!(agg.get_points(), (S256Point::infinity_point(), g * BigUint::from(3u8)));
+        // the infinite R1 is written as zeros
+        assert_eq!(agg.serialize()[..33], [0u8; 33]);
+        assert_eq!(AggNonce::parse(&agg.serialize()), Some(agg));
+        assert_eq!(PubNonce::parse(&a.serialize()), Some(a));
+    }
+}
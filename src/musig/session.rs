@@ -0,0 +1,470 @@
+#![allow(unused)]
+
+use num::{BigUint, One, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+use crate::schnorr::bip340::{challenge, SchnorrSignature, XOnlyPublicKey};
+
+use super::key_agg::{key_agg, key_agg_coeff, KeyAggContext, MusigError};
+use super::nonce::{AggNonce, PubNonce, SecNonce};
+
+// Everything the signers agree on for the second round: the keys (with any
+// tweaks), the aggregate nonce and the message. From these each signer
+// derives the same final nonce R = R1 + bR2 and challenge e.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionContext {
+    pubkeys: Vec<S256Point>,
+    key_agg_ctx: KeyAggContext,
+    msg: Vec<u8>,
+    b: BigUint,
+    r: S256Point,
+    e: BigUint,
+}
+
+impl SessionContext {
+    // tweaks are (tweak, is_xonly) pairs applied in order
+    pub fn new(
+        aggnonce: &AggNonce,
+        pubkeys: &[S256Point],
+        tweaks: &[([u8; 32], bool)],
+        msg: &[u8],
+    ) -> Result<Self, MusigError> {
+        let mut key_agg_ctx = key_agg(pubkeys)?;
+        for (tweak, is_xonly) in tweaks {
+            key_agg_ctx = key_agg_ctx.apply_tweak(tweak, *is_xonly)?;
+        }
+        let q = key_agg_ctx.get_xonly_pk();
+
+        let mut preimage = aggnonce.serialize().to_vec();
+        preimage.extend_from_slice(&q.to_bytes());
+        preimage.extend_from_slice(msg);
+        let b = scalar::from_bytes_mod_order(&tagged_hash("MuSig/noncecoef", &preimage));
+
+        // R can only be infinite if someone cheated, in which case signing
+        // goes ahead with G so the cheater can be caught by their partial sig
+        let (r1, r2) = aggnonce.get_points();
+        let mut r = r1 + r2 * b.clone();
+        if r == S256Point::infinity_point() {
+            r = S256Point::generator();
+        }
+        let e = challenge(&r.get_x().unwrap().to_bytes(), &q, msg);
+
+        Ok(Self { pubkeys: pubkeys.to_vec(), key_agg_ctx, msg: msg.to_vec(), b, r, e })
+    }
+
+    pub fn get_key_agg_ctx(&self) -> KeyAggContext {
+        self.key_agg_ctx.clone()
+    }
+
+    // g is -1 when the aggregate key has an odd y, as BIP340 will negate it
+    fn g(&self) -> BigUint {
+        if self.key_agg_ctx.get_plain_pk().has_even_y() {
+            BigUint::one()
+        } else {
+            S256Point::order() - 1u8
+        }
+    }
+
+    // s_i = k1 + b k2 + e a_i d_i, with the nonces negated when R has an
+    // odd y and the key negated to match the x-only aggregate key. Taking the
+    // secret nonce by value makes reusing it a compile error.
+    pub fn sign(&self, secnonce: SecNonce, sk: &PrivateKey) -> Result<BigUint, MusigError> {
+        let n = S256Point::order();
+        let (k1, k2, pk) = secnonce.into_parts();
+        if k1.is_zero() || k1 >= n || k2.is_zero() || k2 >= n {
+            return Err(MusigError::InvalidSecret);
+        }
+        if sk.get_point() != pk {
+            return Err(MusigError::PublicKeyMismatch);
+        }
+        if !self.pubkeys.contains(&pk) {
+            return Err(MusigError::SignerNotFound);
+        }
+
+        let (k1, k2) = if self.r.has_even_y() {
+            (k1, k2)
+        } else {
+            (scalar::negate(&k1), scalar::negate(&k2))
+        };
+        let a = key_agg_coeff(&self.pubkeys, &pk);
+        let d = self.g() * self.key_agg_ctx.get_gacc() % &n * sk.get_secret() % &n;
+
+        Ok((k1 + &self.b * k2 + &self.e * a * d) % n)
+    }
+
+    // s_i G = R1_i + b R2_i + e a_i g P_i, with the same negations as sign
+    pub fn partial_sig_verify(&self, psig: &BigUint, pubnonce: &PubNonce, pk: &S256Point) -> bool {
+        let n = S256Point::order();
+        if *psig >= n || !self.pubkeys.contains(pk) {
+            return false;
+        }
+
+        let (r1, r2) = pubnonce.get_points();
+        let mut r_i = r1 + r2 * self.b.clone();
+        if !self.r.has_even_y() {
+            r_i = -r_i;
+        }
+        let a = key_agg_coeff(&self.pubkeys, pk);
+        let g = self.g() * self.key_agg_ctx.get_gacc() % &n;
+
+        S256Point::generator() * psig.clone() == r_i + pk.clone() * (&self.e * a % &n * g % &n)
+    }
+
+    // s = sum of s_i + e g tacc, which turns the partial signatures into a
+    // BIP340 signature for the (tweaked) aggregate key
+    pub fn partial_sig_agg(&self, psigs: &[BigUint]) -> Result<SchnorrSignature, MusigError> {
+        let n = S256Point::order();
+        let mut s = BigUint::zero();
+        for (i, psig) in psigs.iter().enumerate() {
+            if *psig >= n {
+                return Err(MusigError::InvalidContribution(i));
+            }
+            s += psig;
+        }
+        let s = (s + &self.e * self.g() * self.key_agg_ctx.get_tacc()) % n;
+        Ok(SchnorrSignature::new(self.r.get_x().unwrap().get_num(), s))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::musig::nonce::{nonce_agg, nonce_gen};
+
+    use super::*;
+
+    // keys that don't parse are reported as the contribution of the signer
+    // at their index
+    fn parse_keys(pubkeys: &[&str], indices: &[usize]) -> Result<Vec<S256Point>, MusigError> {
+        indices
+            .iter()
+            .enumerate()
+            .map(|(i, index)| {
+                S256Point::parse_sec(&hex::decode(pubkeys[*index]).unwrap()).map_err(|_| MusigError::InvalidContribution(i))
+            })
+            .collect()
+    }
+
+    fn parse_nonces(pubnonces: &[&str], indices: &[usize]) -> Vec<PubNonce> {
+        indices.iter().map(|i| PubNonce::parse(&hex::decode(pubnonces[*i]).unwrap()).unwrap()).collect()
+    }
+
+    fn parse_tweaks(tweaks: &[&str], indices: &[(usize, bool)]) -> Vec<([u8; 32], bool)> {
+        indices
+            .iter()
+            .map(|(i, is_xonly)| (hex::decode(tweaks[*i]).unwrap().try_into().unwrap(), *is_xonly))
+            .collect()
+    }
+
+    // keys, nonces, aggregate nonce, message, signer and partial signature
+    type SignCase = (&'static [usize], &'static [usize], usize, usize, usize, &'static str);
+
+    // aggregate nonce, nonces, keys, tweaks, partial signatures and the
+    // final signature
+    type SigAggCase = (&'static str, &'static [usize], &'static [usize], &'static [(usize, bool)], &'static [usize], &'static str);
+
+    const SK: &str = "7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671";
+
+    const SECNONCE: &str = "508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61\
+                            FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7\
+                            03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9";
+
+    const PUBNONCES: [&str; 5] = [
+        "0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+        "032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046",
+        // the negation of the first, so the two add up to infinity
+        "0237C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0387BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        // R1 is not on the curve
+        "0200000000000000000000000000000000000000000000000000000000000000090287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+    ];
+
+    const AGGNONCE: &str = "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9";
+
+    const MSG: &str = "F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF";
+
+    // the BIP327 sign/verify vectors: the same signer at a different
+    // position each time, an aggregate nonce at infinity, an empty message
+    // and one longer than 32 bytes
+    #[test]
+    fn sign_verify_vectors_pass() {
+        let sk = PrivateKey::new(BigUint::from_bytes_be(&hex::decode(SK).unwrap()));
+        let pubkeys = [
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661",
+        ];
+        let aggnonces = [AGGNONCE, &"00".repeat(66)];
+        let msgs = [MSG, "", "2626262626262626262626262626262626262626262626262626262626262626262626262626"];
+
+        let cases: [SignCase; 6] = [
+            (&[0, 1, 2], &[0, 1, 2], 0, 0, 0, "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB"),
+            (&[1, 0, 2], &[1, 0, 2], 0, 0, 1, "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52"),
+            (&[1, 2, 0], &[1, 2, 0], 0, 0, 2, "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900"),
+            (&[0, 1], &[0, 3], 1, 0, 0, "AE386064B26105404798F75DE2EB9AF5EDA5387B064B83D049CB7C5E08879531"),
+            (&[0, 1, 2], &[0, 1, 2], 0, 1, 0, "D7D63FFD644CCDA4E62BC2BC0B1D02DD32A1DC3030E155195810231D1037D82D"),
+            (&[0, 1, 2], &[0, 1, 2], 0, 2, 0, "E184351828DA5094A97C79CABDAAA0BFB87608C32E8829A4DF5340A6F243B78C"),
+        ];
+        for (key_indices, nonce_indices, aggnonce_index, msg_index, signer, expected) in cases {
+            let keys = parse_keys(&pubkeys, key_indices).unwrap();
+            let pubnonces = parse_nonces(&PUBNONCES, nonce_indices);
+            let aggnonce = AggNonce::parse(&hex::decode(aggnonces[aggnonce_index]).unwrap()).unwrap();
+            assert_eq!(nonce_agg(&pubnonces), aggnonce);
+            let msg = hex::decode(msgs[msg_index]).unwrap();
+            let ctx = SessionContext::new(&aggnonce, &keys, &[], &msg).unwrap();
+
+            let psig = ctx.sign(SecNonce::parse(&hex::decode(SECNONCE).unwrap()).unwrap(), &sk).unwrap();
+            assert_eq!(hex::encode_upper(scalar::to_bytes(&psig)), expected);
+            assert!(ctx.partial_sig_verify(&psig, &pubnonces[signer], &keys[signer]));
+        }
+    }
+
+    #[test]
+    fn sign_verify_error_vectors_pass() {
+        let sk = PrivateKey::new(BigUint::from_bytes_be(&hex::decode(SK).unwrap()));
+        let pubkeys = [
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661",
+            // not on the curve
+            "020000000000000000000000000000000000000000000000000000000000000007",
+        ];
+        let aggnonce = AggNonce::parse(&hex::decode(AGGNONCE).unwrap()).unwrap();
+        let msg = hex::decode(MSG).unwrap();
+        let secnonce = || SecNonce::parse(&hex::decode(SECNONCE).unwrap()).unwrap();
+
+        // the signer's key is missing from the list, or one of the keys is
+        // invalid
+        let ctx = SessionContext::new(&aggnonce, &parse_keys(&pubkeys, &[1, 2]).unwrap(), &[], &msg).unwrap();
+        assert_eq!(ctx.sign(secnonce(), &sk), Err(MusigError::SignerNotFound));
+        assert_eq!(parse_keys(&pubkeys, &[1, 0, 3]), Err(MusigError::InvalidContribution(2)));
+
+        // aggregate nonces with a bad first byte, an x not on the curve and
+        // an x that exceeds the field size
+        for bad in [
+            "048465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9",
+            "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61020000000000000000000000000000000000000000000000000000000000000009",
+            "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD6102FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC30",
+        ] {
+            assert_eq!(AggNonce::parse(&hex::decode(bad).unwrap()), None);
+        }
+
+        // a secnonce with k1 = 0
+        let keys = parse_keys(&pubkeys, &[0, 1, 2]).unwrap();
+        let ctx = SessionContext::new(&aggnonce, &keys, &[], &msg).unwrap();
+        let zero_nonce = SecNonce::parse(&[&[0u8; 64][..], &hex::decode(pubkeys[0]).unwrap()].concat()).unwrap();
+        assert_eq!(ctx.sign(zero_nonce, &sk), Err(MusigError::InvalidSecret));
+
+        // partial signatures that don't verify: the negation of a valid one,
+        // one checked against the wrong signer and one that exceeds n
+        let pubnonces = parse_nonces(&PUBNONCES, &[0, 1, 2]);
+        let cases = [
+            ("97AC833ADCB1AFA42EBF9E0725616F3C9A0D5B614F6FE283CEAAA37A8FFAF406", 0),
+            ("68537CC5234E505BD14061F8DA9E90C220A181855FD8BDB7F127BB12403B4D3B", 1),
+            ("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141", 0),
+        ];
+        for (psig, signer) in cases {
+            let psig = BigUint::from_bytes_be(&hex::decode(psig).unwrap());
+            assert!(!ctx.partial_sig_verify(&psig, &pubnonces[signer], &keys[signer]));
+        }
+
+        // and verifying against an invalid pubnonce or key
+        assert_eq!(PubNonce::parse(&hex::decode(PUBNONCES[4]).unwrap()), None);
+        assert_eq!(parse_keys(&pubkeys, &[3, 1, 2]), Err(MusigError::InvalidContribution(0)));
+    }
+
+    // the BIP327 tweak vectors, with plain and x-only tweaks mixed in every
+    // order. The signer is the last of the three keys.
+    #[test]
+    fn tweak_vectors_pass() {
+        let sk = PrivateKey::new(BigUint::from_bytes_be(&hex::decode(SK).unwrap()));
+        let pubkeys = [
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+        ];
+        let tweaks = [
+            "E8F791FF9225A2AF0102AFFF4A9A723D9612A682A25EBE79802B263CDFCD83BB",
+            "AE2EA797CC0FE72AC5B97B97F3C6957D7E4199A167A58EB08BCAFFDA70AC0455",
+            "F52ECBC565B3D8BEA2DFD5B75A4F457E54369809322E4120831626F290FA87E0",
+            "1969AD73CC177FA0B4FCED6DF1F7BF9907E665FDE9BA196A74FED0A3CF5AEF9D",
+            // n itself
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+        ];
+        let keys = parse_keys(&pubkeys, &[1, 2, 0]).unwrap();
+        let pubnonces = parse_nonces(&PUBNONCES, &[1, 2, 0]);
+        let aggnonce = AggNonce::parse(&hex::decode(AGGNONCE).unwrap()).unwrap();
+        assert_eq!(nonce_agg(&pubnonces), aggnonce);
+        let msg = hex::decode(MSG).unwrap();
+
+        let cases: [(&[(usize, bool)], &str); 5] = [
+            (&[(0, true)], "E28A5C66E61E178C2BA19DB77B6CF9F7E2F0F56C17918CD13135E60CC848FE91"),
+            (&[(0, false)], "38B0767798252F21BF5702C48028B095428320F73A4B14DB1E25DE58543D2D2D"),
+            (&[(0, false), (1, true)], "408A0A21C4A0F5DACAF9646AD6EB6FECD7F7A11F03ED1F48DFFF2185BC2C2408"),
+            (
+                &[(0, false), (1, false), (2, true), (3, true)],
+                "45ABD206E61E3DF2EC9E264A6FEC8292141A633C28586388235541F9ADE75435",
+            ),
+            (
+                &[(0, true), (1, false), (2, true), (3, false)],
+                "B255FDCAC27B40C7CE7848E2D3B7BF5EA0ED756DA81565AC804CCCA3E1D5D239",
+            ),
+        ];
+        for (tweak_indices, expected) in cases {
+            let ctx = SessionContext::new(&aggnonce, &keys, &parse_tweaks(&tweaks, tweak_indices), &msg).unwrap();
+            let psig = ctx.sign(SecNonce::parse(&hex::decode(SECNONCE).unwrap()).unwrap(), &sk).unwrap();
+            assert_eq!(hex::encode_upper(scalar::to_bytes(&psig)), expected);
+            assert!(ctx.partial_sig_verify(&psig, &pubnonces[2], &keys[2]));
+        }
+
+        assert_eq!(
+            SessionContext::new(&aggnonce, &keys, &parse_tweaks(&tweaks, &[(4, false)]), &msg),
+            Err(MusigError::InvalidTweak)
+        );
+    }
+
+    // the BIP327 signature aggregation vectors. The result has to be a valid
+    // BIP340 signature for the tweaked aggregate key.
+    #[test]
+    fn sig_agg_vectors_pass() {
+        let pubkeys = [
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            "02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05",
+            "03C7FB101D97FF930ACD0C6760852EF64E69083DE0B06AC6335724754BB4B0522C",
+            "02352433B21E7E05D3B452B81CAE566E06D2E003ECE16D1074AABA4289E0E3D581",
+        ];
+        let pubnonces = [
+            "036E5EE6E28824029FEA3E8A9DDD2C8483F5AF98F7177C3AF3CB6F47CAF8D94AE902DBA67E4A1F3680826172DA15AFB1A8CA85C7C5CC88900905C8DC8C328511B53E",
+            "03E4F798DA48A76EEC1C9CC5AB7A880FFBA201A5F064E627EC9CB0031D1D58FC5103E06180315C5A522B7EC7C08B69DCD721C313C940819296D0A7AB8E8795AC1F00",
+            "02C0068FD25523A31578B8077F24F78F5BD5F2422AFF47C1FADA0F36B3CEB6C7D202098A55D1736AA5FCC21CF0729CCE852575C06C081125144763C2C4C4A05C09B6",
+            "031F5C87DCFBFCF330DEE4311D85E8F1DEA01D87A6F1C14CDFC7E4F1D8C441CFA40277BF176E9F747C34F81B0D9F072B1B404A86F402C2D86CF9EA9E9C69876EA3B9",
+            "023F7042046E0397822C4144A17F8B63D78748696A46C3B9F0A901D296EC3406C302022B0B464292CF9751D699F10980AC764E6F671EFCA15069BBE62B0D1C62522A",
+            "02D97DDA5988461DF58C5897444F116A7C74E5711BF77A9446E27806563F3B6C47020CBAD9C363A7737F99FA06B6BE093CEAFF5397316C5AC46915C43767AE867C00",
+        ];
+        let tweaks = [
+            "B511DA492182A91B0FFB9A98020D55F260AE86D7ECBD0399C7383D59A5F2AF7C",
+            "A815FE049EE3C5AAB66310477FBC8BCCCAC2F3395F59F921C364ACD78A2F48DC",
+            "75448A87274B056468B977BE06EB1E9F657577B7320B0A3376EA51FD420D18A8",
+        ];
+        let psigs = [
+            "B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB",
+            "6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64",
+            "9A87D3B79EC67228CB97878B76049B15DBD05B8158D17B5B9114D3C226887505",
+            "66F82EA90923689B855D36C6B7E032FB9970301481B99E01CDB4D6AC7C347A15",
+            "4F5AEE41510848A6447DCD1BBC78457EF69024944C87F40250D3EF2C25D33EFE",
+            "DDEF427BBB847CC027BEFF4EDB01038148917832253EBC355FC33F4A8E2FCCE4",
+            "97B890A26C981DA8102D3BC294159D171D72810FDF7C6A691DEF02F0F7AF3FDC",
+            "53FA9E08BA5243CBCB0D797C5EE83BC6728E539EB76C2D0BF0F971EE4E909971",
+            // n itself
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+        ];
+        let msg = hex::decode("599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869").unwrap();
+        let parse_psigs =
+            |indices: &[usize]| -> Vec<BigUint> { indices.iter().map(|i| BigUint::from_bytes_be(&hex::decode(psigs[*i]).unwrap())).collect() };
+
+        let cases: [SigAggCase; 4] = [
+            (
+                "0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B",
+                &[0, 1],
+                &[0, 1],
+                &[],
+                &[0, 1],
+                "041DA22223CE65C92C9A0D6C2CAC828AAF1EEE56304FEC371DDF91EBB2B9EF0912F1038025857FEDEB3FF696F8B99FA4BB2C5812F6095A2E0004EC99CE18DE1E",
+            ),
+            (
+                "0224AFD36C902084058B51B5D36676BBA4DC97C775873768E58822F87FE437D792028CB15929099EEE2F5DAE404CD39357591BA32E9AF4E162B8D3E7CB5EFE31CB20",
+                &[0, 2],
+                &[0, 2],
+                &[],
+                &[2, 3],
+                "1069B67EC3D2F3C7C08291ACCB17A9C9B8F2819A52EB5DF8726E17E7D6B52E9F01800260A7E9DAC450F4BE522DE4CE12BA91AEAF2B4279219EF74BE1D286ADD9",
+            ),
+            (
+                "0208C5C438C710F4F96A61E9FF3C37758814B8C3AE12BFEA0ED2C87FF6954FF186020B1816EA104B4FCA2D304D733E0E19CEAD51303FF6420BFD222335CAA402916D",
+                &[0, 3],
+                &[0, 2],
+                &[(0, false)],
+                &[4, 5],
+                "5C558E1DCADE86DA0B2F02626A512E30A22CF5255CAEA7EE32C38E9A71A0E9148BA6C0E6EC7683B64220F0298696F1B878CD47B107B81F7188812D593971E0CC",
+            ),
+            (
+                "02B5AD07AFCD99B6D92CB433FBD2A28FDEB98EAE2EB09B6014EF0F8197CD58403302E8616910F9293CF692C49F351DB86B25E352901F0E237BAFDA11F1C1CEF29FFD",
+                &[0, 4],
+                &[0, 3],
+                &[(0, true), (1, false), (2, true)],
+                &[6, 7],
+                "839B08820B681DBA8DAF4CC7B104E8F2638F9388F8D7A555DC17B6E6971D7426CE07BF6AB01F1DB50E4E33719295F4094572B79868E440FB3DEFD3FAC1DB589E",
+            ),
+        ];
+        for (aggnonce, nonce_indices, key_indices, tweak_indices, psig_indices, expected) in cases {
+            let aggnonce = AggNonce::parse(&hex::decode(aggnonce).unwrap()).unwrap();
+            assert_eq!(nonce_agg(&parse_nonces(&pubnonces, nonce_indices)), aggnonce);
+            let keys = parse_keys(&pubkeys, key_indices).unwrap();
+            let ctx = SessionContext::new(&aggnonce, &keys, &parse_tweaks(&tweaks, tweak_indices), &msg).unwrap();
+
+            let sig = ctx.partial_sig_agg(&parse_psigs(psig_indices)).unwrap();
+            assert_eq!(hex::encode_upper(sig.to_bytes()), expected);
+            assert!(ctx.get_key_agg_ctx().get_xonly_pk().verify(&msg, &sig));
+        }
+
+        // the second partial signature exceeds n
+        let aggnonce = nonce_agg(&parse_nonces(&pubnonces, &[0, 4]));
+        let keys = parse_keys(&pubkeys, &[0, 3]).unwrap();
+        let tweaks = parse_tweaks(&tweaks, &[(0, true), (1, false), (2, true)]);
+        let ctx = SessionContext::new(&aggnonce, &keys, &tweaks, &msg).unwrap();
+        assert_eq!(ctx.partial_sig_agg(&parse_psigs(&[7, 8])), Err(MusigError::InvalidContribution(1)));
+    }
+
+    // three signers with a taproot style x-only tweak end up with a signature
+    // that plain BIP340 accepts for the tweaked aggregate key
+    #[test]
+    fn signing_round_trip_works() {
+        let secrets: Vec<PrivateKey> = (1..=3u64).map(|i| PrivateKey::new(BigUint::from(i * 0x1f2e3d4c5b))).collect();
+        let pubkeys: Vec<S256Point> = secrets.iter().map(|sk| sk.get_point()).collect();
+        let msg = b"MuSig2 round trip";
+        let tweaks = [([0x2a; 32], true)];
+
+        let mut nonces: Vec<(SecNonce, PubNonce)> = secrets
+            .iter()
+            .enumerate()
+            .map(|(i, sk)| nonce_gen(&[i as u8; 32], Some(sk), &sk.get_point(), None, Some(msg), None))
+            .collect();
+        let pubnonces: Vec<PubNonce> = nonces.iter().map(|(_, pubnonce)| pubnonce.clone()).collect();
+        let ctx = SessionContext::new(&nonce_agg(&pubnonces), &pubkeys, &tweaks, msg).unwrap();
+
+        let psigs: Vec<BigUint> = nonces
+            .drain(..)
+            .zip(&secrets)
+            .map(|((secnonce, _), sk)| ctx.sign(secnonce, sk).unwrap())
+            .collect();
+        for ((psig, pubnonce), pk) in psigs.iter().zip(&pubnonces).zip(&pubkeys) {
+            assert!(ctx.partial_sig_verify(psig, pubnonce, pk));
+        }
+
+        let sig = ctx.partial_sig_agg(&psigs).unwrap();
+        assert!(ctx.get_key_agg_ctx().get_xonly_pk().verify(msg, &sig));
+        assert!(!key_agg(&pubkeys).unwrap().get_xonly_pk().verify(msg, &sig));
+
+        let mut bad = psigs.clone();
+        bad[1] = S256Point::order();
+        assert_eq!(ctx.partial_sig_agg(&bad), Err(MusigError::InvalidContribution(1)));
+    }
+
+    #[test]
+    fn sign_rejects_wrong_key() {
+        let sk = PrivateKey::new(BigUint::from(7u64));
+        let other = PrivateKey::new(BigUint::from(8u64));
+        let pubkeys = vec![sk.get_point(), other.get_point()];
+
+        let (_, pubnonce) = nonce_gen(&[1; 32], None, &sk.get_point(), None, None, None);
+        let ctx = SessionContext::new(&nonce_agg(&[pubnonce]), &pubkeys, &[], b"msg").unwrap();
+
+        let (secnonce, _) = nonce_gen(&[1; 32], None, &sk.get_point(), None, None, None);
+        assert_eq!(ctx.sign(secnonce, &other), Err(MusigError::PublicKeyMismatch));
+
+        let stranger = PrivateKey::new(BigUint::from(9u64));
+        let (secnonce, _) = nonce_gen(&[1; 32], None, &stranger.get_point(), None, None, None);
+        assert_eq!(ctx.sign(secnonce, &stranger), Err(MusigError::SignerNotFound));
+    }
+}
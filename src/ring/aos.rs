@@ -0,0 +1,356 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RingError {
+    // a ring needs at least one key
+    EmptyRing,
+    // the signer's index is outside the ring
+    InvalidIndex(usize),
+    // the key at the signer's index isn't the signer's
+    WrongSecret,
+    // a Borromean signature needs exactly one signer per ring
+    SignerCountMismatch { rings: usize, signers: usize },
+    // the key at this position is the point at infinity
+    InfinityKey(usize),
+}
+
+impl fmt::Display for RingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RingError::EmptyRing => write!(f, "ring is empty"),
+            RingError::InvalidIndex(index) => write!(f, "index {} is outside the ring", index),
+            RingError::WrongSecret => write!(f, "secret key is not the one in the ring"),
+            RingError::SignerCountMismatch { rings, signers } => write!(f, "{} signers for {} rings", signers, rings),
+            RingError::InfinityKey(index) => write!(f, "key {} is the point at infinity", index),
+        }
+    }
+}
+
+impl std::error::Error for RingError {}
+
+pub(super) fn check_signer(ring: &[S256Point], index: usize, sk: &PrivateKey) -> Result<(), RingError> {
+    if ring.is_empty() {
+        return Err(RingError::EmptyRing);
+    }
+    if index >= ring.len() {
+        return Err(RingError::InvalidIndex(index));
+    }
+    if let Some(position) = ring.iter().position(|pk| *pk == S256Point::infinity_point()) {
+        return Err(RingError::InfinityKey(position));
+    }
+    if ring[index] != sk.get_point() {
+        return Err(RingError::WrongSecret);
+    }
+    Ok(())
+}
+
+// the hash of all keys in order, so a signature is bound to its ring
+pub(super) fn hash_ring(ring: &[S256Point]) -> [u8; 32] {
+    let preimage: Vec<u8> = ring.iter().flat_map(|pk| pk.sec(true)).collect();
+    tagged_hash("Ring/keys", &preimage)
+}
+
+// the scalars the signer makes up for everyone else's position, derived from
+// fresh randomness together with the secret and what is being signed
+pub(super) fn derive_scalar(rand: &[u8; 32], sk: &PrivateKey, context: &[u8], index: usize) -> BigUint {
+    let mut preimage = rand.to_vec();
+    preimage.extend_from_slice(&scalar::to_bytes(&sk.get_secret()));
+    preimage.extend_from_slice(context);
+    preimage.extend_from_slice(&(index as u64).to_be_bytes());
+    scalar::from_bytes_mod_order(&tagged_hash("Ring/nonce", &preimage))
+}
+
+// sG - eP. It is the point at infinity only when s = ex for P = xG, which a
+// signer never hits by chance but a forger can pick, so verifiers check for it
+// before it goes into a challenge.
+pub(super) fn ring_point(s: &BigUint, e: &BigUint, base: &S256Point, point: &S256Point) -> S256Point {
+    S256Point::multi_mul(&[(s.clone(), base.clone()), (scalar::negate(e), point.clone())])
+}
+
+// e = H(ring || msg || points), with the points being R (or R and the key
+// image term for linkable signatures)
+fn challenge(ring_hash: &[u8; 32], msg: &[u8], points: &[&S256Point]) -> BigUint {
+    let mut preimage = ring_hash.to_vec();
+    preimage.extend_from_slice(&(msg.len() as u64).to_be_bytes());
+    preimage.extend_from_slice(msg);
+    for point in points {
+        preimage.extend(point.sec(true));
+    }
+    scalar::from_bytes_mod_order(&tagged_hash("Ring/challenge", &preimage))
+}
+
+// An Abe-Ohkubo-Suzuki ring signature: proof that one of the keys in the
+// ring signed, without saying which. Each challenge is the hash of the
+// previous position's commitment sG - eP, and the chain closes into a loop
+// only if one position was answered with a secret key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingSignature {
+    e0: BigUint,
+    s: Vec<BigUint>,
+}
+
+impl RingSignature {
+    pub fn new(e0: BigUint, s: Vec<BigUint>) -> Self {
+        Self { e0, s }
+    }
+
+    pub fn get_e0(&self) -> BigUint {
+        self.e0.clone()
+    }
+
+    pub fn get_s(&self) -> Vec<BigUint> {
+        self.s.clone()
+    }
+
+    // signs as the key at ring[index]. rand should be fresh randomness.
+    pub fn sign(
+        sk: &PrivateKey,
+        ring: &[S256Point],
+        index: usize,
+        msg: &[u8],
+        rand: &[u8; 32],
+    ) -> Result<Self, RingError> {
+        check_signer(ring, index, sk)?;
+        let g = S256Point::generator();
+        let ring_hash = hash_ring(ring);
+        let context = [&ring_hash[..], msg].concat();
+        let size = ring.len();
+
+        // start right after the signer with a fresh commitment and go around
+        let k = derive_scalar(rand, sk, &context, size);
+        let mut s = vec![BigUint::zero(); size];
+        let mut e = vec![BigUint::zero(); size];
+        e[(index + 1) % size] = challenge(&ring_hash, msg, &[&(g.clone() * k.clone())]);
+        for step in 1..size {
+            let i = (index + step) % size;
+            s[i] = derive_scalar(rand, sk, &context, i);
+            let r = ring_point(&s[i], &e[i], &g, &ring[i]);
+            e[(i + 1) % size] = challenge(&ring_hash, msg, &[&r]);
+        }
+        s[index] = (k + &e[index] * sk.get_secret()) % S256Point::order();
+        Ok(Self { e0: e[0].clone(), s })
+    }
+
+    pub fn verify(&self, ring: &[S256Point], msg: &[u8]) -> bool {
+        let n = S256Point::order();
+        if ring.is_empty() || self.s.len() != ring.len() || self.s.iter().any(|s| *s >= n) {
+            return false;
+        }
+        if ring.contains(&S256Point::infinity_point()) {
+            return false;
+        }
+        let g = S256Point::generator();
+        let ring_hash = hash_ring(ring);
+        let mut e = self.e0.clone();
+        for (s, pk) in self.s.iter().zip(ring) {
+            let r = ring_point(s, &e, &g, pk);
+            if r == S256Point::infinity_point() {
+                return false;
+            }
+            e = challenge(&ring_hash, msg, &[&r]);
+        }
+        e == self.e0
+    }
+}
+
+// Hp(P), a point nobody knows the discrete log of, found by hashing P to an
+// x coordinate until one is on the curve
+pub fn hash_to_point(pk: &S256Point) -> S256Point {
+    let mut counter = 0u32;
+    loop {
+        let mut preimage = pk.sec(true);
+        preimage.extend_from_slice(&counter.to_be_bytes());
+        if let Some(point) = S256Point::lift_x(&BigUint::from_bytes_be(&tagged_hash("Ring/keyimage", &preimage))) {
+            return point;
+        }
+        counter += 1;
+    }
+}
+
+// I = x Hp(P). It depends only on the key, so two signatures by the same key
+// have the same image whatever the rings, while it says nothing about which
+// key that is.
+pub fn key_image(sk: &PrivateKey) -> S256Point {
+    hash_to_point(&sk.get_point()) * sk.get_secret()
+}
+
+// A linkable ring signature (LSAG): an AOS ring that also proves, with the
+// same responses, that the key image was made with the signer's key. Used
+// to spot a key signing twice, as in double spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkableRingSignature {
+    key_image: S256Point,
+    e0: BigUint,
+    s: Vec<BigUint>,
+}
+
+impl LinkableRingSignature {
+    pub fn new(key_image: S256Point, e0: BigUint, s: Vec<BigUint>) -> Self {
+        Self { key_image, e0, s }
+    }
+
+    pub fn get_key_image(&self) -> S256Point {
+        self.key_image.clone()
+    }
+
+    pub fn get_e0(&self) -> BigUint {
+        self.e0.clone()
+    }
+
+    pub fn get_s(&self) -> Vec<BigUint> {
+        self.s.clone()
+    }
+
+    pub fn sign(
+        sk: &PrivateKey,
+        ring: &[S256Point],
+        index: usize,
+        msg: &[u8],
+        rand: &[u8; 32],
+    ) -> Result<Self, RingError> {
+        check_signer(ring, index, sk)?;
+        let g = S256Point::generator();
+        let image = key_image(sk);
+        let ring_hash = hash_ring(ring);
+        let context = [&ring_hash[..], msg, &image.sec(true)].concat();
+        let size = ring.len();
+
+        let k = derive_scalar(rand, sk, &context, size);
+        let mut s = vec![BigUint::zero(); size];
+        let mut e = vec![BigUint::zero(); size];
+        let start = [g.clone() * k.clone(), hash_to_point(&ring[index]) * k.clone()];
+        e[(index + 1) % size] = challenge(&ring_hash, msg, &[&image, &start[0], &start[1]]);
+        for step in 1..size {
+            let i = (index + step) % size;
+            s[i] = derive_scalar(rand, sk, &context, i);
+            let l = ring_point(&s[i], &e[i], &g, &ring[i]);
+            let r = ring_point(&s[i], &e[i], &hash_to_point(&ring[i]), &image);
+            e[(i + 1) % size] = challenge(&ring_hash, msg, &[&image, &l, &r]);
+        }
+        s[index] = (k + &e[index] * sk.get_secret()) % S256Point::order();
+        Ok(Self { key_image: image, e0: e[0].clone(), s })
+    }
+
+    // L_i = s_i G - e_i P_i and R_i = s_i Hp(P_i) - e_i I both feed the next
+    // challenge, so the loop closes only if I = x Hp(P) for the x that
+    // answered
+    pub fn verify(&self, ring: &[S256Point], msg: &[u8]) -> bool {
+        let n = S256Point::order();
+        if ring.is_empty() || self.s.len() != ring.len() || self.s.iter().any(|s| *s >= n) {
+            return false;
+        }
+        if self.key_image == S256Point::infinity_point() || ring.contains(&S256Point::infinity_point()) {
+            return false;
+        }
+        let g = S256Point::generator();
+        let ring_hash = hash_ring(ring);
+        let mut e = self.e0.clone();
+        for (s, pk) in self.s.iter().zip(ring) {
+            let l = ring_point(s, &e, &g, pk);
+            let r = ring_point(s, &e, &hash_to_point(pk), &self.key_image);
+            if l == S256Point::infinity_point() || r == S256Point::infinity_point() {
+                return false;
+            }
+            e = challenge(&ring_hash, msg, &[&self.key_image, &l, &r]);
+        }
+        e == self.e0
+    }
+
+    // whether both signatures were made with the same key
+    pub fn is_linked(&self, other: &LinkableRingSignature) -> bool {
+        self.key_image == other.key_image
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn keys(count: u64) -> Vec<PrivateKey> {
+        (1..=count).map(|i| PrivateKey::new(BigUint::from(i * 0x9e3779b9))).collect()
+    }
+
+    #[test]
+    fn ring_signature_works() {
+        let keys = keys(4);
+        let ring: Vec<S256Point> = keys.iter().map(|sk| sk.get_point()).collect();
+        let msg = b"one of us";
+
+        for (index, sk) in keys.iter().enumerate() {
+            let sig = RingSignature::sign(sk, &ring, index, msg, &[index as u8; 32]).unwrap();
+            assert!(sig.verify(&ring, msg));
+            assert!(!sig.verify(&ring, b"one of them"));
+            assert!(!sig.verify(&ring[1..], msg));
+
+            let mut reordered = ring.clone();
+            reordered.swap(0, 1);
+            assert!(!sig.verify(&reordered, msg));
+
+            let mut tampered = sig.clone();
+            tampered.s[(index + 1) % 4] += 1u8;
+            assert!(!tampered.verify(&ring, msg));
+        }
+
+        let single = RingSignature::sign(&keys[0], &ring[..1], 0, msg, &[0; 32]).unwrap();
+        assert!(single.verify(&ring[..1], msg));
+        assert_eq!(RingSignature::sign(&keys[0], &ring, 1, msg, &[0; 32]), Err(RingError::WrongSecret));
+        assert_eq!(RingSignature::sign(&keys[0], &ring, 4, msg, &[0; 32]), Err(RingError::InvalidIndex(4)));
+        assert_eq!(RingSignature::sign(&keys[0], &[], 0, msg, &[0; 32]), Err(RingError::EmptyRing));
+    }
+
+    #[test]
+    fn linkable_ring_signature_works() {
+        let keys = keys(3);
+        let ring: Vec<S256Point> = keys.iter().map(|sk| sk.get_point()).collect();
+
+        for (index, sk) in keys.iter().enumerate() {
+            let sig = LinkableRingSignature::sign(sk, &ring, index, b"spend", &[1; 32]).unwrap();
+            assert!(sig.verify(&ring, b"spend"));
+            assert!(!sig.verify(&ring, b"spend again"));
+        }
+
+        // the same key in a different ring and message is still linked
+        let first = LinkableRingSignature::sign(&keys[1], &ring, 1, b"spend", &[1; 32]).unwrap();
+        let other_ring = vec![ring[2].clone(), ring[1].clone()];
+        let second = LinkableRingSignature::sign(&keys[1], &other_ring, 1, b"spend again", &[2; 32]).unwrap();
+        let third = LinkableRingSignature::sign(&keys[2], &ring, 2, b"spend", &[1; 32]).unwrap();
+        assert!(first.is_linked(&second));
+        assert!(!first.is_linked(&third));
+
+        // a key image for someone else's key doesn't verify
+        let mut forged = first.clone();
+        forged.key_image = key_image(&keys[0]);
+        assert!(!forged.verify(&ring, b"spend"));
+    }
+    #[test]
+    fn verify_rejects_infinity() {
+        let keys = keys(2);
+        let ring: Vec<S256Point> = keys.iter().map(|sk| sk.get_point()).collect();
+        let msg = b"nobody";
+
+        let sig = RingSignature::sign(&keys[0], &ring, 0, msg, &[0; 32]).unwrap();
+        let with_infinity = vec![ring[0].clone(), S256Point::infinity_point()];
+        assert!(!sig.verify(&with_infinity, msg));
+        assert_eq!(RingSignature::sign(&keys[0], &with_infinity, 0, msg, &[0; 32]), Err(RingError::InfinityKey(1)));
+        let single = RingSignature { e0: BigUint::from(1u8), s: vec![BigUint::from(1u8)] };
+        assert!(!single.verify(&[S256Point::infinity_point()], msg));
+
+        // s = e x makes sG - eP the point at infinity
+        let e0 = BigUint::from(5u8);
+        let s = e0.clone() * keys[0].get_secret() % S256Point::order();
+        let forged = RingSignature { e0: e0.clone(), s: vec![s.clone()] };
+        assert!(!forged.verify(&ring[..1], msg));
+
+        let linkable = LinkableRingSignature::sign(&keys[0], &ring, 0, msg, &[0; 32]).unwrap();
+        assert!(!linkable.verify(&with_infinity, msg));
+        let forged = LinkableRingSignature { key_image: key_image(&keys[0]), e0, s: vec![s] };
+        assert!(!forged.verify(&ring[..1], msg));
+    }
+}
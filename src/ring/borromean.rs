@@ -0,0 +1,202 @@
+#![allow(unused)]
+
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+use super::aos::{check_signer, derive_scalar, hash_ring, ring_point, RingError};
+
+// A Borromean ring signature (Maxwell and Poelstra): one signer in each of
+// several rings, all sharing a single starting challenge e0. That's one
+// scalar for the whole set instead of one per ring, which is what made
+// these the range proofs of Confidential Transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorromeanSignature {
+    e0: BigUint,
+    s: Vec<Vec<BigUint>>,
+}
+
+// e_(i,j+1) = H(msg || R_ij || i || j)
+fn challenge(msg_hash: &[u8; 32], r: &S256Point, ring: usize, position: usize) -> BigUint {
+    let mut preimage = msg_hash.to_vec();
+    preimage.extend(r.sec(true));
+    preimage.extend_from_slice(&(ring as u64).to_be_bytes());
+    preimage.extend_from_slice(&(position as u64).to_be_bytes());
+    scalar::from_bytes_mod_order(&tagged_hash("Borromean/challenge", &preimage))
+}
+
+// e0 = H(R_0,last || R_1,last || ... || msg)
+fn shared_challenge(msg_hash: &[u8; 32], last: &[S256Point]) -> BigUint {
+    let mut preimage: Vec<u8> = last.iter().flat_map(|r| r.sec(true)).collect();
+    preimage.extend_from_slice(msg_hash);
+    scalar::from_bytes_mod_order(&tagged_hash("Borromean/e0", &preimage))
+}
+
+// the message along with every ring
+fn hash_message(rings: &[Vec<S256Point>], msg: &[u8]) -> [u8; 32] {
+    let mut preimage: Vec<u8> = rings.iter().flat_map(|ring| hash_ring(ring)).collect();
+    preimage.extend_from_slice(msg);
+    tagged_hash("Borromean/msg", &preimage)
+}
+
+impl BorromeanSignature {
+    pub fn new(e0: BigUint, s: Vec<Vec<BigUint>>) -> Self {
+        Self { e0, s }
+    }
+
+    pub fn get_e0(&self) -> BigUint {
+        self.e0.clone()
+    }
+
+    // one response per key, ring by ring
+    pub fn get_s(&self) -> Vec<Vec<BigUint>> {
+        self.s.clone()
+    }
+
+    // signers[i] is (index, key) of the signer in rings[i]. rand should be
+    // fresh randomness.
+    pub fn sign(
+        rings: &[Vec<S256Point>],
+        signers: &[(usize, PrivateKey)],
+        msg: &[u8],
+        rand: &[u8; 32],
+    ) -> Result<Self, RingError> {
+        if rings.is_empty() {
+            return Err(RingError::EmptyRing);
+        }
+        if rings.len() != signers.len() {
+            return Err(RingError::SignerCountMismatch { rings: rings.len(), signers: signers.len() });
+        }
+        for (ring, (index, sk)) in rings.iter().zip(signers) {
+            check_signer(ring, *index, sk)?;
+        }
+        let n = S256Point::order();
+        let g = S256Point::generator();
+        let msg_hash = hash_message(rings, msg);
+
+        // from each signer to the end of their ring
+        let mut nonces = Vec::with_capacity(rings.len());
+        let mut s: Vec<Vec<BigUint>> = rings.iter().map(|ring| vec![BigUint::zero(); ring.len()]).collect();
+        let mut last = Vec::with_capacity(rings.len());
+        for (i, (ring, (index, sk))) in rings.iter().zip(signers).enumerate() {
+            let context = [&msg_hash[..], &(i as u64).to_be_bytes()].concat();
+            let k = derive_scalar(rand, sk, &context, ring.len());
+            let mut r = g.clone() * k.clone();
+            for j in index + 1..ring.len() {
+                let e = challenge(&msg_hash, &r, i, j - 1);
+                s[i][j] = derive_scalar(rand, sk, &context, j);
+                r = ring_point(&s[i][j], &e, &g, &ring[j]);
+            }
+            nonces.push(k);
+            last.push(r);
+        }
+
+        // then from e0 round to the signer, who closes the ring
+        let e0 = shared_challenge(&msg_hash, &last);
+        for (i, (ring, (index, sk))) in rings.iter().zip(signers).enumerate() {
+            let context = [&msg_hash[..], &(i as u64).to_be_bytes()].concat();
+            let mut e = e0.clone();
+            for j in 0..*index {
+                s[i][j] = derive_scalar(rand, sk, &context, j);
+                e = challenge(&msg_hash, &ring_point(&s[i][j], &e, &g, &ring[j]), i, j);
+            }
+            s[i][*index] = (&nonces[i] + e * sk.get_secret()) % &n;
+        }
+        Ok(Self { e0, s })
+    }
+
+    pub fn verify(&self, rings: &[Vec<S256Point>], msg: &[u8]) -> bool {
+        let n = S256Point::order();
+        if rings.is_empty() || rings.len() != self.s.len() {
+            return false;
+        }
+        if rings.iter().any(|ring| ring.contains(&S256Point::infinity_point())) {
+            return false;
+        }
+        let g = S256Point::generator();
+        let msg_hash = hash_message(rings, msg);
+
+        let mut last = Vec::with_capacity(rings.len());
+        for (i, (ring, s)) in rings.iter().zip(&self.s).enumerate() {
+            if ring.is_empty() || ring.len() != s.len() || s.iter().any(|s| *s >= n) {
+                return false;
+            }
+            let mut e = self.e0.clone();
+            for j in 0..ring.len() {
+                let r = ring_point(&s[j], &e, &g, &ring[j]);
+                if r == S256Point::infinity_point() {
+                    return false;
+                }
+                if j + 1 == ring.len() {
+                    last.push(r);
+                } else {
+                    e = challenge(&msg_hash, &r, i, j);
+                }
+            }
+        }
+        shared_challenge(&msg_hash, &last) == self.e0
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::ring::aos::tests::keys;
+
+    use super::*;
+
+    #[test]
+    fn borromean_signature_works() {
+        let keys = keys(6);
+        let points: Vec<S256Point> = keys.iter().map(|sk| sk.get_point()).collect();
+        let rings = vec![points[..2].to_vec(), points[2..5].to_vec(), points[5..].to_vec()];
+        let msg = b"borromean";
+
+        // every choice of signer in each ring
+        for (a, b) in [(0, 0), (1, 2), (0, 1), (1, 0)] {
+            let signers = vec![(a, keys[a].clone()), (b, keys[2 + b].clone()), (0, keys[5].clone())];
+            let sig = BorromeanSignature::sign(&rings, &signers, msg, &[7; 32]).unwrap();
+            assert!(sig.verify(&rings, msg));
+            assert!(!sig.verify(&rings, b"something else"));
+            assert!(!sig.verify(&rings[..2], msg));
+
+            let mut tampered = sig.clone();
+            tampered.s[1][0] += 1u8;
+            assert!(!tampered.verify(&rings, msg));
+
+            let mut swapped = rings.clone();
+            swapped.swap(0, 2);
+            assert!(!sig.verify(&swapped, msg));
+        }
+
+        let wrong = vec![(0, keys[1].clone()), (0, keys[2].clone()), (0, keys[5].clone())];
+        assert_eq!(BorromeanSignature::sign(&rings, &wrong, msg, &[7; 32]), Err(RingError::WrongSecret));
+        assert_eq!(
+            BorromeanSignature::sign(&rings, &wrong[..2], msg, &[7; 32]),
+            Err(RingError::SignerCountMismatch { rings: 3, signers: 2 })
+        );
+    }
+
+    #[test]
+    fn verify_rejects_infinity() {
+        let keys = keys(2);
+        let rings = vec![vec![keys[0].get_point()], vec![keys[1].get_point()]];
+        let msg = b"borromean";
+        let signers = vec![(0, keys[0].clone()), (0, keys[1].clone())];
+        let sig = BorromeanSignature::sign(&rings, &signers, msg, &[7; 32]).unwrap();
+
+        let with_infinity = vec![rings[0].clone(), vec![keys[1].get_point(), S256Point::infinity_point()]];
+        assert!(!sig.verify(&with_infinity, msg));
+        assert_eq!(
+            BorromeanSignature::sign(&with_infinity, &signers, msg, &[7; 32]),
+            Err(RingError::InfinityKey(1))
+        );
+
+        // s = e0 x makes the first commitment the point at infinity
+        let e0 = BigUint::from(5u8);
+        let s = e0.clone() * keys[0].get_secret() % S256Point::order();
+        let forged = BorromeanSignature { e0, s: vec![vec![s], vec![BigUint::from(1u8)]] };
+        assert!(!forged.verify(&rings, msg));
+    }
+}
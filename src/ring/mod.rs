@@ -0,0 +1,2 @@
+pub mod aos;
+pub mod borromean;
@@ -0,0 +1,152 @@
+#![allow(unused)]
+
+use num::{BigUint, One};
+
+use crate::finite_field::{scalar, secp_ec::S256Point, secp_field::S256Field};
+use crate::hashes::sha256::{sha256, tagged_hash, Sha256};
+
+use super::bip340::{challenge, SchnorrSignature, XOnlyPublicKey};
+
+// Checks all signatures at once: with random a_i (a_1 = 1) the batch is valid
+// if (sum a_i s_i)G = sum a_i R_i + sum a_i e_i P_i. The randomizers keep a
+// forger from making invalid signatures cancel each other out. An empty batch
+// is valid. When this fails, find_invalid tells which signature is to blame.
+pub fn verify_batch(batch: &[(XOnlyPublicKey, &[u8], SchnorrSignature)]) -> bool {
+    let n = S256Point::order();
+    let randomizers = randomizers(batch);
+
+    let mut s_sum = BigUint::from(0u8);
+    let mut terms = Vec::with_capacity(2 * batch.len() + 1);
+    for ((pubkey, msg, sig), a) in batch.iter().zip(randomizers) {
+        let r = sig.get_r();
+        let s = sig.get_s();
+        if s >= n {
+            return false;
+        }
+        let point_r = match S256Point::lift_x(&r) {
+            Some(point) => point,
+            None => return false,
+        };
+        let e = challenge(&scalar::to_bytes(&r), pubkey, msg);
+
+        s_sum = (s_sum + &a * s) % &n;
+        terms.push((scalar::negate(&a), point_r));
+        terms.push((scalar::negate(&(a * e % &n)), pubkey.get_point()));
+    }
+    terms.push((s_sum, S256Point::generator()));
+
+    S256Point::multi_mul(&terms) == S256Point::infinity_point()
+}
+
+// Index of the first invalid signature, or None if the whole batch verifies.
+// Halves of the batch are checked recursively, so a single bad signature is
+// found with about 2 log2(len) batch verifications.
+pub fn find_invalid(batch: &[(XOnlyPublicKey, &[u8], SchnorrSignature)]) -> Option<usize> {
+    if verify_batch(batch) {
+        return None;
+    }
+    if batch.len() == 1 {
+        return Some(0);
+    }
+
+    let (left, right) = batch.split_at(batch.len() / 2);
+    match find_invalid(left) {
+        Some(index) => Some(index),
+        None => find_invalid(right).map(|index| index + left.len()),
+    }
+}
+
+// a_1 = 1 and the rest are derived from a hash of the whole batch, so they
+// can't be known before every key, message and signature is fixed
+fn randomizers(batch: &[(XOnlyPublicKey, &[u8], SchnorrSignature)]) -> Vec<BigUint> {
+    let mut engine = Sha256::new();
+    for (pubkey, msg, sig) in batch {
+        engine.update(&pubkey.to_bytes());
+        engine.update(&sha256(msg));
+        engine.update(&sig.to_bytes());
+    }
+    let seed = engine.finalize();
+
+    (0..batch.len() as u32)
+        .map(|i| {
+            if i == 0 {
+                return BigUint::one();
+            }
+            let mut preimage = seed.to_vec();
+            preimage.extend_from_slice(&i.to_be_bytes());
+            scalar::from_bytes_mod_order(&tagged_hash("BIP0340/batch", &preimage))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::finite_field::private_key::PrivateKey;
+
+    use super::*;
+
+    const TEST_VECTORS: &str = include_str!("bip340_test_vectors.csv");
+
+    // (public key, message, signature, expected result) for every vector
+    // with a public key that parses
+    fn test_vectors() -> Vec<(XOnlyPublicKey, Vec<u8>, SchnorrSignature, bool)> {
+        TEST_VECTORS
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.splitn(8, ',').collect();
+                let pubkey = XOnlyPublicKey::from_bytes(&hex::decode(fields[2]).unwrap()).ok()?;
+                let msg = hex::decode(fields[4]).unwrap();
+                let sig = SchnorrSignature::from_bytes(&hex::decode(fields[5]).unwrap()).unwrap();
+                Some((pubkey, msg, sig, fields[6] == "TRUE"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn verify_batch_works() {
+        let vectors = test_vectors();
+        let valid: Vec<_> = vectors.iter().filter(|v| v.3).collect();
+        let batch: Vec<_> = valid.iter().map(|(pk, msg, sig, _)| (pk.clone(), &msg[..], sig.clone())).collect();
+        assert!(verify_batch(&batch));
+        assert!(verify_batch(&[]));
+
+        // every invalid vector spoils an otherwise valid batch
+        for (pubkey, msg, sig, _) in vectors.iter().filter(|v| !v.3) {
+            let mut spoiled = batch[..2].to_vec();
+            spoiled.push((pubkey.clone(), &msg[..], sig.clone()));
+            assert!(!verify_batch(&spoiled));
+        }
+    }
+
+    #[test]
+    fn verify_batch_catches_swapped_signatures() {
+        let keys: Vec<PrivateKey> = (1..=2u64).map(|k| PrivateKey::new(BigUint::from(k * 0xabcdef))).collect();
+        let msgs = [b"first".to_vec(), b"second".to_vec()];
+        let sigs: Vec<_> = keys.iter().zip(&msgs).map(|(key, msg)| key.sign_schnorr(msg, &[0u8; 32])).collect();
+
+        // two signatures over the wrong messages each
+        let batch = vec![
+            (keys[0].x_only_public_key(), &msgs[0][..], sigs[1].clone()),
+            (keys[1].x_only_public_key(), &msgs[1][..], sigs[0].clone()),
+        ];
+        assert!(!verify_batch(&batch));
+    }
+
+    #[test]
+    fn find_invalid_works() {
+        let vectors = test_vectors();
+        let mut batch: Vec<_> = vectors
+            .iter()
+            .filter(|v| v.3)
+            .map(|(pk, msg, sig, _)| (pk.clone(), &msg[..], sig.clone()))
+            .collect();
+        assert_eq!(find_invalid(&batch), None);
+
+        // s + 1
+        let bad = &mut batch[5].2;
+        *bad = SchnorrSignature::new(bad.get_r(), (bad.get_s() + 1u8) % S256Point::order());
+        assert_eq!(find_invalid(&batch), Some(5));
+    }
+}
@@ -0,0 +1,238 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point, secp_field::S256Field};
+use crate::hashes::sha256::tagged_hash;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchnorrError {
+    // public keys are 32 bytes and signatures 64 bytes
+    InvalidLength(usize),
+    // the x coordinate is not below p or has no point on the curve
+    InvalidPublicKey,
+}
+
+impl fmt::Display for SchnorrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchnorrError::InvalidLength(len) => write!(f, "invalid length {}", len),
+            SchnorrError::InvalidPublicKey => write!(f, "x coordinate is not on the curve"),
+        }
+    }
+}
+
+impl std::error::Error for SchnorrError {}
+
+// A public key identified by its x coordinate alone. Of the two points
+// sharing that x, BIP340 always means the one with an even y.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XOnlyPublicKey {
+    point: S256Point,
+}
+
+impl XOnlyPublicKey {
+    // negates the point when its y is odd
+    pub fn from_point(point: &S256Point) -> Self {
+        if *point == S256Point::infinity_point() {
+            panic!("The point at infinity has no x coordinate");
+        }
+        if point.has_even_y() {
+            Self { point: point.clone() }
+        } else {
+            Self { point: -point.clone() }
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SchnorrError> {
+        if bytes.len() != 32 {
+            return Err(SchnorrError::InvalidLength(bytes.len()));
+        }
+        match S256Point::lift_x(&BigUint::from_bytes_be(bytes)) {
+            Some(point) => Ok(Self { point }),
+            None => Err(SchnorrError::InvalidPublicKey),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.point.get_x().unwrap().to_bytes()
+    }
+
+    pub fn get_point(&self) -> S256Point {
+        self.point.clone()
+    }
+
+    // R = sG - eP must have an even y and the x coordinate r
+    pub fn verify(&self, msg: &[u8], sig: &SchnorrSignature) -> bool {
+        if sig.r >= S256Field::prime() || sig.s >= S256Point::order() {
+            return false;
+        }
+        let e = challenge(&scalar::to_bytes(&sig.r), self, msg);
+
+        let point_r = S256Point::generator() * sig.s.clone() - self.point.clone() * e;
+        match point_r.get_x() {
+            Some(x) => point_r.has_even_y() && x.get_num() == sig.r,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for XOnlyPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.to_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// (R.x, s), serialized as 64 bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchnorrSignature {
+    r: BigUint,
+    s: BigUint,
+}
+
+impl SchnorrSignature {
+    pub fn new(r: BigUint, s: BigUint) -> Self {
+        Self { r, s }
+    }
+
+    // ranges are left to verify, which rejects r >= p and s >= n
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SchnorrError> {
+        if bytes.len() != 64 {
+            return Err(SchnorrError::InvalidLength(bytes.len()));
+        }
+        Ok(Self {
+            r: BigUint::from_bytes_be(&bytes[..32]),
+            s: BigUint::from_bytes_be(&bytes[32..]),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut result = [0u8; 64];
+        result[..32].copy_from_slice(&scalar::to_bytes(&self.r));
+        result[32..].copy_from_slice(&scalar::to_bytes(&self.s));
+        result
+    }
+
+    pub fn get_r(&self) -> BigUint {
+        self.r.clone()
+    }
+
+    pub fn get_s(&self) -> BigUint {
+        self.s.clone()
+    }
+}
+
+// e = hash_BIP0340/challenge(R.x || P.x || m) mod n
+pub fn challenge(r: &[u8; 32], pubkey: &XOnlyPublicKey, msg: &[u8]) -> BigUint {
+    let mut preimage = r.to_vec();
+    preimage.extend_from_slice(&pubkey.to_bytes());
+    preimage.extend_from_slice(msg);
+    scalar::from_bytes_mod_order(&tagged_hash("BIP0340/challenge", &preimage))
+}
+
+impl PrivateKey {
+    pub fn x_only_public_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.get_point())
+    }
+
+    // The secret is negated when P has an odd y so that it matches the x-only
+    // key. aux_rand should be fresh randomness; it is mixed into the nonce to
+    // protect against side channels, but signing stays safe if it isn't.
+    pub fn sign_schnorr(&self, msg: &[u8], aux_rand: &[u8; 32]) -> SchnorrSignature {
+        let point = self.get_point();
+        let d = if point.has_even_y() {
+            self.get_secret()
+        } else {
+            scalar::negate(&self.get_secret())
+        };
+        let pubkey = XOnlyPublicKey::from_point(&point);
+
+        let mut t = scalar::to_bytes(&d);
+        for (byte, mask) in t.iter_mut().zip(tagged_hash("BIP0340/aux", aux_rand)) {
+            *byte ^= mask;
+        }
+        let mut preimage = t.to_vec();
+        preimage.extend_from_slice(&pubkey.to_bytes());
+        preimage.extend_from_slice(msg);
+        let k = scalar::from_bytes_mod_order(&tagged_hash("BIP0340/nonce", &preimage));
+        if k.is_zero() {
+            panic!("Nonce is zero");
+        }
+
+        let point_r = S256Point::generator() * k.clone();
+        let k = if point_r.has_even_y() { k } else { scalar::negate(&k) };
+        let r = point_r.get_x().unwrap().to_bytes();
+
+        let e = challenge(&r, &pubkey, msg);
+        let s = (k + e * d) % S256Point::order();
+        SchnorrSignature { r: BigUint::from_bytes_be(&r), s }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // index, secret key, public key, aux_rand, message, signature, result, comment
+    const TEST_VECTORS: &str = include_str!("bip340_test_vectors.csv");
+
+    #[test]
+    fn test_vectors_pass() {
+        for line in TEST_VECTORS.lines().skip(1) {
+            let fields: Vec<&str> = line.splitn(8, ',').collect();
+            let index = fields[0];
+            let msg = hex::decode(fields[4]).unwrap();
+            let sig_bytes = hex::decode(fields[5]).unwrap();
+            let expected = fields[6] == "TRUE";
+
+            if !fields[1].is_empty() {
+                let secret = PrivateKey::new(BigUint::from_bytes_be(&hex::decode(fields[1]).unwrap()));
+                let aux_rand: [u8; 32] = hex::decode(fields[3]).unwrap().try_into().unwrap();
+                assert_eq!(
+                    hex::encode_upper(secret.x_only_public_key().to_bytes()),
+                    fields[2],
+                    "vector {}",
+                    index
+                );
+                assert_eq!(
+                    secret.sign_schnorr(&msg, &aux_rand).to_bytes().to_vec(),
+                    sig_bytes,
+                    "vector {}",
+                    index
+                );
+            }
+
+            let sig = SchnorrSignature::from_bytes(&sig_bytes).unwrap();
+            let verified = match XOnlyPublicKey::from_bytes(&hex::decode(fields[2]).unwrap()) {
+                Ok(pubkey) => pubkey.verify(&msg, &sig),
+                Err(_) => false,
+            };
+            assert_eq!(verified, expected, "vector {}", index);
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_invalid_keys() {
+        // p itself, and an x with no point on the curve
+        let p = hex::decode("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F").unwrap();
+        assert_eq!(XOnlyPublicKey::from_bytes(&p), Err(SchnorrError::InvalidPublicKey));
+        let off_curve = hex::decode("EEFDEA4CDB677750A420FEE807EACF21EB9898AE79B9768766E4FAA04A2D4A34").unwrap();
+        assert_eq!(XOnlyPublicKey::from_bytes(&off_curve), Err(SchnorrError::InvalidPublicKey));
+        assert_eq!(XOnlyPublicKey::from_bytes(&p[1..]), Err(SchnorrError::InvalidLength(31)));
+        assert_eq!(SchnorrSignature::from_bytes(&p), Err(SchnorrError::InvalidLength(32)));
+    }
+
+    #[test]
+    fn from_point_works() {
+        let secret = PrivateKey::new(BigUint::from(3u64));
+        let pubkey = secret.x_only_public_key();
+        assert!(pubkey.get_point().has_even_y());
+        assert_eq!(XOnlyPublicKey::from_point(&-secret.get_point()), pubkey);
+        assert_eq!(XOnlyPublicKey::from_bytes(&pubkey.to_bytes()).unwrap(), pubkey);
+    }
+}
@@ -0,0 +1,242 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+use super::bip340::{challenge, SchnorrSignature, XOnlyPublicKey};
+
+// Blind Schnorr signatures: the signer signs a message it never sees, and
+// can't recognize the signature afterwards. The result is a plain BIP340
+// signature.
+//
+// WARNING: running several signing sessions at the same time is insecure.
+// With enough open sessions a user can solve the ROS problem (Benhamouda et
+// al. 2021) and walk away with one more valid signature than the signer
+// ever handed out, in well under a second for a few hundred sessions.
+// BlindSigner therefore refuses to open a session while another one is
+// still open. Don't work around that by using several signers with the same
+// key.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlindError {
+    // the signer already has an open session, opening another would allow a
+    // ROS attack
+    SessionInProgress,
+    // the signer has no open session to answer a challenge in
+    NoSession,
+    // the signer's nonce point is the point at infinity
+    InvalidNonce,
+    // the signer's response doesn't verify against its nonce and key
+    InvalidBlindSignature,
+}
+
+impl fmt::Display for BlindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlindError::SessionInProgress => write!(
+                f,
+                "a signing session is already open; concurrent blind signing sessions are vulnerable to ROS attacks"
+            ),
+            BlindError::NoSession => write!(f, "no signing session is open"),
+            BlindError::InvalidNonce => write!(f, "nonce is the point at infinity"),
+            BlindError::InvalidBlindSignature => write!(f, "blind signature doesn't verify"),
+        }
+    }
+}
+
+impl std::error::Error for BlindError {}
+
+// The signer's side, which holds at most one session at a time: commit opens
+// one, sign or abort closes it. The nonce is taken out when signing, so it
+// can't answer two challenges.
+#[derive(Debug)]
+pub struct BlindSigner {
+    key: PrivateKey,
+    nonce: Option<BigUint>,
+}
+
+impl BlindSigner {
+    pub fn new(key: PrivateKey) -> Self {
+        Self { key, nonce: None }
+    }
+
+    pub fn x_only_public_key(&self) -> XOnlyPublicKey {
+        self.key.x_only_public_key()
+    }
+
+    pub fn has_open_session(&self) -> bool {
+        self.nonce.is_some()
+    }
+
+    // Opens a session and returns the nonce point R = kG for the user. rand
+    // must be fresh randomness.
+    pub fn commit(&mut self, rand: &[u8; 32]) -> Result<S256Point, BlindError> {
+        if self.nonce.is_some() {
+            return Err(BlindError::SessionInProgress);
+        }
+        let mut preimage = rand.to_vec();
+        preimage.extend_from_slice(&scalar::to_bytes(&self.key.get_secret()));
+        let k = scalar::from_bytes_mod_order(&tagged_hash("BlindSchnorr/nonce", &preimage));
+        if k.is_zero() {
+            panic!("Nonce is zero");
+        }
+        let r = S256Point::generator() * k.clone();
+        self.nonce = Some(k);
+        Ok(r)
+    }
+
+    // s = k + cd for the user's blinded challenge c, closing the session. The
+    // key is negated as in BIP340 when its y is odd.
+    pub fn sign(&mut self, challenge: &BigUint) -> Result<BigUint, BlindError> {
+        let k = self.nonce.take().ok_or(BlindError::NoSession)?;
+        let point = self.key.get_point();
+        let d = if point.has_even_y() { self.key.get_secret() } else { scalar::negate(&self.key.get_secret()) };
+        Ok((k + challenge % S256Point::order() * d) % S256Point::order())
+    }
+
+    // closes the session without signing, e.g. when the user went away
+    pub fn abort(&mut self) {
+        self.nonce = None;
+    }
+}
+
+// The user's side of one session. The nonce is shifted to R' = R + aG + bP
+// and the challenge to c = c' + b, so nothing the signer sees can be matched
+// to the final signature (R'.x, s + a).
+#[derive(Debug, PartialEq, Eq)]
+pub struct BlindUser {
+    pubkey: XOnlyPublicKey,
+    nonce: S256Point,
+    alpha: BigUint,
+    blinded_nonce: S256Point,
+    challenge: BigUint,
+}
+
+impl BlindUser {
+    // Blinds the signer's nonce and returns the challenge to send it. rand
+    // must be fresh randomness, as the blinding factors are all that keeps
+    // the signer from linking the signature to this session.
+    pub fn new(
+        pubkey: &XOnlyPublicKey,
+        nonce: &S256Point,
+        msg: &[u8],
+        rand: &[u8; 32],
+    ) -> Result<(Self, BigUint), BlindError> {
+        if *nonce == S256Point::infinity_point() {
+            return Err(BlindError::InvalidNonce);
+        }
+        let g = S256Point::generator();
+        let point = pubkey.get_point();
+
+        // R' must have an even y for BIP340; otherwise try other factors
+        let mut counter = 0u32;
+        loop {
+            let factor = |i: u8| {
+                let mut preimage = rand.to_vec();
+                preimage.extend_from_slice(&counter.to_be_bytes());
+                preimage.extend(nonce.sec(true));
+                preimage.extend_from_slice(msg);
+                preimage.push(i);
+                scalar::from_bytes_mod_order(&tagged_hash("BlindSchnorr/blinding", &preimage))
+            };
+            let (alpha, beta) = (factor(0), factor(1));
+            let blinded = S256Point::multi_mul(&[
+                (BigUint::from(1u8), nonce.clone()),
+                (alpha.clone(), g.clone()),
+                (beta.clone(), point.clone()),
+            ]);
+            if blinded.has_even_y() {
+                let e = challenge(&blinded.get_x().unwrap().to_bytes(), pubkey, msg);
+                let c = (e + beta) % S256Point::order();
+                let user = Self {
+                    pubkey: pubkey.clone(),
+                    nonce: nonce.clone(),
+                    alpha,
+                    blinded_nonce: blinded,
+                    challenge: c.clone(),
+                };
+                return Ok((user, c));
+            }
+            counter += 1;
+        }
+    }
+
+    // checks sG = R + cP and unblinds to the BIP340 signature (R'.x, s + a)
+    pub fn unblind(self, s: &BigUint) -> Result<SchnorrSignature, BlindError> {
+        let n = S256Point::order();
+        let expected = self.nonce + self.pubkey.get_point() * self.challenge;
+        if *s >= n || S256Point::generator() * s.clone() != expected {
+            return Err(BlindError::InvalidBlindSignature);
+        }
+        Ok(SchnorrSignature::new(self.blinded_nonce.get_x().unwrap().get_num(), (s + self.alpha) % n))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn blind_signing_works() {
+        let mut signer = BlindSigner::new(PrivateKey::new(BigUint::from(0xb11du64)));
+        let pubkey = signer.x_only_public_key();
+        let msg = b"token 1234";
+
+        let r = signer.commit(&[1; 32]).unwrap();
+        let (user, c) = BlindUser::new(&pubkey, &r, msg, &[2; 32]).unwrap();
+        let s = signer.sign(&c).unwrap();
+        let sig = user.unblind(&s).unwrap();
+        assert!(pubkey.verify(msg, &sig));
+
+        // what the signer saw doesn't show up in the signature
+        assert_ne!(sig.get_r(), r.get_x().unwrap().get_num());
+        assert_ne!(sig.get_s(), s);
+    }
+
+    // with an odd y key, negated as in BIP340
+    #[test]
+    fn blind_signing_works_for_odd_key() {
+        let key = PrivateKey::new(BigUint::from(0xf205u64));
+        assert!(!key.get_point().has_even_y());
+        let mut signer = BlindSigner::new(key);
+        let pubkey = signer.x_only_public_key();
+
+        let r = signer.commit(&[3; 32]).unwrap();
+        let (user, c) = BlindUser::new(&pubkey, &r, b"msg", &[4; 32]).unwrap();
+        let sig = user.unblind(&signer.sign(&c).unwrap()).unwrap();
+        assert!(pubkey.verify(b"msg", &sig));
+    }
+
+    #[test]
+    fn signer_allows_one_session_at_a_time() {
+        let mut signer = BlindSigner::new(PrivateKey::new(BigUint::from(0xb11du64)));
+        assert_eq!(signer.sign(&BigUint::from(1u8)), Err(BlindError::NoSession));
+
+        signer.commit(&[1; 32]).unwrap();
+        assert!(signer.has_open_session());
+        assert_eq!(signer.commit(&[2; 32]), Err(BlindError::SessionInProgress));
+
+        signer.sign(&BigUint::from(1u8)).unwrap();
+        // the nonce is gone, so the same session can't answer twice
+        assert_eq!(signer.sign(&BigUint::from(2u8)), Err(BlindError::NoSession));
+
+        signer.commit(&[2; 32]).unwrap();
+        signer.abort();
+        assert!(!signer.has_open_session());
+        assert!(signer.commit(&[3; 32]).is_ok());
+    }
+
+    #[test]
+    fn unblind_rejects_bad_response() {
+        let mut signer = BlindSigner::new(PrivateKey::new(BigUint::from(0xb11du64)));
+        let pubkey = signer.x_only_public_key();
+        let r = signer.commit(&[1; 32]).unwrap();
+        let (user, c) = BlindUser::new(&pubkey, &r, b"msg", &[2; 32]).unwrap();
+        let s = signer.sign(&c).unwrap();
+        assert_eq!(user.unblind(&(s + 1u8)), Err(BlindError::InvalidBlindSignature));
+    }
+}
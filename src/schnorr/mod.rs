@@ -0,0 +1,6 @@
+pub mod bip340;
+pub mod batch;
+pub mod blind;
+
+#[allow(unused_imports)]
+pub use batch::{find_invalid, verify_batch};
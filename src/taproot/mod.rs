@@ -0,0 +1,2 @@
+pub mod tweak;
+pub mod tree;
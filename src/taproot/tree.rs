@@ -0,0 +1,469 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use crate::encoding::wire::Encodable;
+use crate::hashes::sha256::tagged_hash;
+use crate::schnorr::bip340::XOnlyPublicKey;
+
+use super::tweak::tap_tweak;
+
+// the leaf version of BIP342 tapscript
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+// script trees deeper than this can't be spent, the control block is limited
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaprootError {
+    // leaves were added at depths that don't form a complete binary tree
+    InvalidTreeShape,
+    // a leaf is more than 128 levels below the root
+    TreeTooDeep(usize),
+    // no leaf was added to the builder
+    EmptyTree,
+    // control blocks are 33 + 32m bytes with m at most 128
+    InvalidControlBlockLength(usize),
+    InvalidInternalKey,
+}
+
+impl fmt::Display for TaprootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaprootError::InvalidTreeShape => write!(f, "leaf depths don't form a complete tree"),
+            TaprootError::TreeTooDeep(depth) => write!(f, "leaf depth {} is over 128", depth),
+            TaprootError::EmptyTree => write!(f, "script tree has no leaves"),
+            TaprootError::InvalidControlBlockLength(len) => write!(f, "invalid control block length {}", len),
+            TaprootError::InvalidInternalKey => write!(f, "invalid internal key in control block"),
+        }
+    }
+}
+
+impl std::error::Error for TaprootError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapLeaf {
+    script: Vec<u8>,
+    version: u8,
+}
+
+impl TapLeaf {
+    pub fn new(script: Vec<u8>, version: u8) -> Self {
+        // the low bit of the control block byte holds the output key parity
+        if version & 1 != 0 {
+            panic!("Leaf version {:#04x} is odd", version);
+        }
+        Self { script, version }
+    }
+
+    pub fn get_script(&self) -> Vec<u8> {
+        self.script.clone()
+    }
+
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+
+    // hash_TapLeaf(version || compact_size(script) || script), which is
+    // the leaf's wire encoding
+    pub fn leaf_hash(&self) -> [u8; 32] {
+        tagged_hash("TapLeaf", &self.to_wire())
+    }
+}
+
+// hash_TapBranch of the two child hashes in lexicographic order, so the
+// order of the children doesn't matter to the commitment
+pub fn tap_branch_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    let mut preimage = left.to_vec();
+    preimage.extend_from_slice(right);
+    tagged_hash("TapBranch", &preimage)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TapTree {
+    Leaf(TapLeaf),
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    pub fn leaf(script: Vec<u8>, version: u8) -> Self {
+        TapTree::Leaf(TapLeaf::new(script, version))
+    }
+
+    pub fn branch(left: TapTree, right: TapTree) -> Self {
+        TapTree::Branch(Box::new(left), Box::new(right))
+    }
+
+    // the merkle root when called on the whole tree
+    pub fn node_hash(&self) -> [u8; 32] {
+        match self {
+            TapTree::Leaf(leaf) => leaf.leaf_hash(),
+            TapTree::Branch(left, right) => tap_branch_hash(&left.node_hash(), &right.node_hash()),
+        }
+    }
+
+    // Every leaf, left to right, with its merkle path: the hashes of the
+    // siblings met walking from the leaf up to the root.
+    pub fn leaves(&self) -> Vec<(TapLeaf, Vec<[u8; 32]>)> {
+        match self {
+            TapTree::Leaf(leaf) => vec![(leaf.clone(), vec![])],
+            TapTree::Branch(left, right) => {
+                let mut result = vec![];
+                for (child, sibling) in [(left, right), (right, left)] {
+                    let sibling_hash = sibling.node_hash();
+                    for (leaf, mut path) in child.leaves() {
+                        path.push(sibling_hash);
+                        result.push((leaf, path));
+                    }
+                }
+                result
+            }
+        }
+    }
+
+    // The control block for spending leaf from an output with this tree and
+    // internal_key, or None if the leaf is not in the tree
+    pub fn control_block(&self, internal_key: &XOnlyPublicKey, leaf: &TapLeaf) -> Option<ControlBlock> {
+        let (_, merkle_branch) = self.leaves().into_iter().find(|(candidate, _)| candidate == leaf)?;
+        let (_, odd) = tap_tweak(internal_key, Some(&self.node_hash()));
+        Some(ControlBlock {
+            leaf_version: leaf.version,
+            output_key_parity: odd,
+            internal_key: internal_key.clone(),
+            merkle_branch,
+        })
+    }
+}
+
+// Assembles a TapTree from leaves given in depth first order along with their
+// depth, which is how PSBTs (BIP371) and descriptors list script trees.
+#[derive(Debug, Clone, Default)]
+pub struct TapTreeBuilder {
+    // subtrees still waiting for a sibling, with the depth they sit at
+    branch: Vec<(usize, TapTree)>,
+}
+
+impl TapTreeBuilder {
+    pub fn new() -> Self {
+        Self { branch: vec![] }
+    }
+
+    pub fn add_leaf(mut self, depth: usize, leaf: TapLeaf) -> Result<Self, TaprootError> {
+        if depth > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(TaprootError::TreeTooDeep(depth));
+        }
+        // nothing can be added once the tree is complete
+        if let Some((0, _)) = self.branch.last() {
+            return Err(TaprootError::InvalidTreeShape);
+        }
+
+        // Merge with pending siblings for as long as there are any. The depths
+        // on the stack only ever increase, and a subtree deeper than the new
+        // node could never be completed anymore.
+        let mut node = (depth, TapTree::Leaf(leaf));
+        while let Some((sibling_depth, _)) = self.branch.last() {
+            if *sibling_depth > node.0 {
+                return Err(TaprootError::InvalidTreeShape);
+            }
+            if *sibling_depth < node.0 {
+                break;
+            }
+            let (_, sibling) = self.branch.pop().unwrap();
+            node = (node.0 - 1, TapTree::branch(sibling, node.1));
+        }
+        self.branch.push(node);
+        Ok(self)
+    }
+
+    pub fn finalize(mut self) -> Result<TapTree, TaprootError> {
+        match self.branch.len() {
+            0 => Err(TaprootError::EmptyTree),
+            1 if self.branch[0].0 == 0 => Ok(self.branch.pop().unwrap().1),
+            _ => Err(TaprootError::InvalidTreeShape),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlBlock {
+    leaf_version: u8,
+    // true when the output key has an odd y
+    output_key_parity: bool,
+    internal_key: XOnlyPublicKey,
+    merkle_branch: Vec<[u8; 32]>,
+}
+
+impl ControlBlock {
+    pub fn get_leaf_version(&self) -> u8 {
+        self.leaf_version
+    }
+
+    pub fn get_internal_key(&self) -> XOnlyPublicKey {
+        self.internal_key.clone()
+    }
+
+    pub fn get_merkle_branch(&self) -> Vec<[u8; 32]> {
+        self.merkle_branch.clone()
+    }
+
+    // (leaf version | parity) || internal key || merkle path
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.leaf_version | self.output_key_parity as u8];
+        result.extend_from_slice(&self.internal_key.to_bytes());
+        for hash in &self.merkle_branch {
+            result.extend_from_slice(hash);
+        }
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, TaprootError> {
+        if bytes.len() < 33 || !(bytes.len() - 33).is_multiple_of(32)
+            || (bytes.len() - 33) / 32 > TAPROOT_CONTROL_MAX_NODE_COUNT
+        {
+            return Err(TaprootError::InvalidControlBlockLength(bytes.len()));
+        }
+        let internal_key = XOnlyPublicKey::from_bytes(&bytes[1..33])
+            .map_err(|_| TaprootError::InvalidInternalKey)?;
+        let merkle_branch = bytes[33..]
+            .chunks(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        Ok(Self {
+            leaf_version: bytes[0] & 0xfe,
+            output_key_parity: bytes[0] & 1 == 1,
+            internal_key,
+            merkle_branch,
+        })
+    }
+
+    // The script path check of BIP341: hashing script up the merkle path and
+    // tweaking the internal key with the result must give the output key.
+    pub fn verify_taproot_commitment(&self, output_key: &XOnlyPublicKey, script: &[u8]) -> bool {
+        let leaf = TapLeaf::new(script.to_vec(), self.leaf_version);
+        let root = self
+            .merkle_branch
+            .iter()
+            .fold(leaf.leaf_hash(), |node, sibling| tap_branch_hash(&node, sibling));
+
+        let (point, odd) = tap_tweak(&self.internal_key, Some(&root));
+        odd == self.output_key_parity && XOnlyPublicKey::from_point(&point) == *output_key
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn xonly(hex_str: &str) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_bytes(&hex::decode(hex_str).unwrap()).unwrap()
+    }
+
+    fn leaf(script: &str, version: u8) -> TapTree {
+        TapTree::leaf(hex::decode(script).unwrap(), version)
+    }
+
+    // Checks a scriptPubKey vector from the BIP341 wallet test vectors: leaf
+    // hashes, merkle root, tweak, output key and the control block of every
+    // leaf, in the order the leaves appear in the tree
+    fn check_vector(
+        internal_key: &str,
+        tree: &TapTree,
+        leaf_hashes: &[&str],
+        merkle_root: &str,
+        tweak: &str,
+        tweaked_key: &str,
+        control_blocks: &[&str],
+    ) {
+        let internal_key = xonly(internal_key);
+        let leaves = tree.leaves();
+
+        let hashes: Vec<String> = leaves.iter().map(|(leaf, _)| hex::encode(leaf.leaf_hash())).collect();
+        assert_eq!(hashes, leaf_hashes);
+        let root = tree.node_hash();
+        assert_eq!(hex::encode(root), merkle_root);
+
+        let t = super::super::tweak::tap_tweak_hash(&internal_key, Some(&root));
+        assert_eq!(hex::encode(crate::finite_field::scalar::to_bytes(&t)), tweak);
+        let (point, _) = tap_tweak(&internal_key, Some(&root));
+        let output_key = XOnlyPublicKey::from_point(&point);
+        assert_eq!(output_key.to_string(), tweaked_key);
+
+        for ((leaf, _), expected) in leaves.iter().zip(control_blocks) {
+            let control_block = tree.control_block(&internal_key, leaf).unwrap();
+            assert_eq!(hex::encode(control_block.serialize()), *expected);
+            assert_eq!(ControlBlock::parse(&control_block.serialize()).unwrap(), control_block);
+            assert!(control_block.verify_taproot_commitment(&output_key, &leaf.get_script()));
+        }
+    }
+
+    #[test]
+    fn single_leaf_vectors_pass() {
+        let tree = leaf("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac", 0xc0);
+        check_vector(
+            "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+            &tree,
+            &["5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"],
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21",
+            "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001",
+            "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+            &["c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27"],
+        );
+
+        let tree = leaf("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac", 0xc0);
+        check_vector(
+            "93478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820",
+            &tree,
+            &["c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b"],
+            "c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b",
+            "6af9e28dbf9d6aaf027696e2598a5b3d056f5fd2355a7fd5a37a0e5008132d30",
+            "e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+            &["c093478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820"],
+        );
+    }
+
+    #[test]
+    fn two_leaf_vectors_pass() {
+        // the second leaf uses an unknown leaf version
+        let tree = TapTree::branch(
+            leaf("20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac", 0xc0),
+            leaf("06424950333431", 0xfa),
+        );
+        check_vector(
+            "ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592",
+            &tree,
+            &[
+                "8ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7",
+                "f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a",
+            ],
+            "6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef",
+            "9e0517edc8259bb3359255400b23ca9507f2a91cd1e4250ba068b4eafceba4a9",
+            "712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+            &[
+                "c0ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a",
+                "faee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf37865928ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7",
+            ],
+        );
+
+        let tree = TapTree::branch(
+            leaf("2044b178d64c32c4a05cc4f4d1407268f764c940d20ce97abfd44db5c3592b72fdac", 0xc0),
+            leaf("07546170726f6f74", 0xc0),
+        );
+        check_vector(
+            "f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd8",
+            &tree,
+            &[
+                "64512fecdb5afa04f98839b50e6f0cb7b1e539bf6f205f67934083cdcc3c8d89",
+                "2cb2b90daa543b544161530c925f285b06196940d6085ca9474d41dc3822c5cb",
+            ],
+            "ab179431c28d3b68fb798957faf5497d69c883c6fb1e1cd9f81483d87bac90cc",
+            "639f0281b7ac49e742cd25b7f188657626da1ad169209078e2761cefd91fd65e",
+            "77e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+            &[
+                "c1f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd82cb2b90daa543b544161530c925f285b06196940d6085ca9474d41dc3822c5cb",
+                "c1f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd864512fecdb5afa04f98839b50e6f0cb7b1e539bf6f205f67934083cdcc3c8d89",
+            ],
+        );
+    }
+
+    #[test]
+    fn three_leaf_vectors_pass() {
+        let tree = TapTree::branch(
+            leaf("2072ea6adcf1d371dea8fba1035a09f3d24ed5a059799bae114084130ee5898e69ac", 0xc0),
+            TapTree::branch(
+                leaf("202352d137f2f3ab38d1eaa976758873377fa5ebb817372c71e2c542313d4abda8ac", 0xc0),
+                leaf("207337c0dd4253cb86f2c43a2351aadd82cccb12a172cd120452b9bb8324f2186aac", 0xc0),
+            ),
+        );
+        check_vector(
+            "e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6f",
+            &tree,
+            &[
+                "2645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817",
+                "ba982a91d4fc552163cb1c0da03676102d5b7a014304c01f0c77b2b8e888de1c",
+                "9e31407bffa15fefbf5090b149d53959ecdf3f62b1246780238c24501d5ceaf6",
+            ],
+            "ccbd66c6f7e8fdab47b3a486f59d28262be857f30d4773f2d5ea47f7761ce0e2",
+            "b57bfa183d28eeb6ad688ddaabb265b4a41fbf68e5fed2c72c74de70d5a786f4",
+            "91b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605",
+            &[
+                "c0e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6fffe578e9ea769027e4f5a3de40732f75a88a6353a09d767ddeb66accef85e553",
+                "c0e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6f9e31407bffa15fefbf5090b149d53959ecdf3f62b1246780238c24501d5ceaf62645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817",
+                "c0e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6fba982a91d4fc552163cb1c0da03676102d5b7a014304c01f0c77b2b8e888de1c2645a02e0aac1fe69d69755733a9b7621b694bb5b5cde2bbfc94066ed62b9817",
+            ],
+        );
+
+        // the output key here has an odd y, so the control blocks start with c1
+        let tree = TapTree::branch(
+            leaf("2071981521ad9fc9036687364118fb6ccd2035b96a423c59c5430e98310a11abe2ac", 0xc0),
+            TapTree::branch(
+                leaf("20d5094d2dbe9b76e2c245a2b89b6006888952e2faa6a149ae318d69e520617748ac", 0xc0),
+                leaf("20c440b462ad48c7a77f94cd4532d8f2119dcebbd7c9764557e62726419b08ad4cac", 0xc0),
+            ),
+        );
+        check_vector(
+            "55adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312d",
+            &tree,
+            &[
+                "f154e8e8e17c31d3462d7132589ed29353c6fafdb884c5a6e04ea938834f0d9d",
+                "737ed1fe30bc42b8022d717b44f0d93516617af64a64753b7a06bf16b26cd711",
+                "d7485025fceb78b9ed667db36ed8b8dc7b1f0b307ac167fa516fe4352b9f4ef7",
+            ],
+            "2f6b2c5397b6d68ca18e09a3f05161668ffe93a988582d55c6f07bd5b3329def",
+            "6579138e7976dc13b6a92f7bfd5a2fc7684f5ea42419d43368301470f3b74ed9",
+            "75169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831",
+            &[
+                "c155adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312d3cd369a528b326bc9d2133cbd2ac21451acb31681a410434672c8e34fe757e91",
+                "c155adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312dd7485025fceb78b9ed667db36ed8b8dc7b1f0b307ac167fa516fe4352b9f4ef7f154e8e8e17c31d3462d7132589ed29353c6fafdb884c5a6e04ea938834f0d9d",
+                "c155adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312d737ed1fe30bc42b8022d717b44f0d93516617af64a64753b7a06bf16b26cd711f154e8e8e17c31d3462d7132589ed29353c6fafdb884c5a6e04ea938834f0d9d",
+            ],
+        );
+    }
+
+    #[test]
+    fn builder_works() {
+        let a = TapLeaf::new(vec![0x51], TAPSCRIPT_LEAF_VERSION);
+        let b = TapLeaf::new(vec![0x52], TAPSCRIPT_LEAF_VERSION);
+        let c = TapLeaf::new(vec![0x53], TAPSCRIPT_LEAF_VERSION);
+
+        let tree = TapTreeBuilder::new()
+            .add_leaf(1, a.clone()).unwrap()
+            .add_leaf(2, b.clone()).unwrap()
+            .add_leaf(2, c.clone()).unwrap()
+            .finalize()
+            .unwrap();
+        let expected = TapTree::branch(
+            TapTree::Leaf(a.clone()),
+            TapTree::branch(TapTree::Leaf(b.clone()), TapTree::Leaf(c.clone())),
+        );
+        assert_eq!(tree, expected);
+
+        let single = TapTreeBuilder::new().add_leaf(0, a.clone()).unwrap().finalize().unwrap();
+        assert_eq!(single, TapTree::Leaf(a.clone()));
+
+        // a lone leaf at depth 1 has no sibling
+        let incomplete = TapTreeBuilder::new().add_leaf(1, a.clone()).unwrap().finalize();
+        assert_eq!(incomplete, Err(TaprootError::InvalidTreeShape));
+        // nothing can follow a complete tree
+        let overfull = TapTreeBuilder::new().add_leaf(0, a.clone()).unwrap().add_leaf(0, b.clone());
+        assert_eq!(overfull.unwrap_err(), TaprootError::InvalidTreeShape);
+        assert_eq!(TapTreeBuilder::new().finalize(), Err(TaprootError::EmptyTree));
+        assert_eq!(TapTreeBuilder::new().add_leaf(129, c).unwrap_err(), TaprootError::TreeTooDeep(129));
+    }
+
+    #[test]
+    fn control_block_rejects_wrong_script() {
+        let tree = TapTree::branch(TapTree::leaf(vec![0x51], 0xc0), TapTree::leaf(vec![0x52], 0xc0));
+        let internal_key = xonly("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let (point, _) = tap_tweak(&internal_key, Some(&tree.node_hash()));
+        let output_key = XOnlyPublicKey::from_point(&point);
+
+        let (first, _) = tree.leaves()[0].clone();
+        let control_block = tree.control_block(&internal_key, &first).unwrap();
+        assert!(control_block.verify_taproot_commitment(&output_key, &[0x51]));
+        assert!(!control_block.verify_taproot_commitment(&output_key, &[0x52]));
+        assert!(tree.control_block(&internal_key, &TapLeaf::new(vec![0x53], 0xc0)).is_none());
+        assert_eq!(ControlBlock::parse(&[0xc0; 34]), Err(TaprootError::InvalidControlBlockLength(34)));
+    }
+}
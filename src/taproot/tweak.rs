@@ -0,0 +1,126 @@
+#![allow(unused)]
+
+use num::BigUint;
+
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+use crate::schnorr::bip340::XOnlyPublicKey;
+
+// t = hash_TapTweak(P || merkle_root), where a key with no script tree is
+// tweaked by its own x coordinate only
+pub fn tap_tweak_hash(internal_key: &XOnlyPublicKey, merkle_root: Option<&[u8; 32]>) -> BigUint {
+    let mut preimage = internal_key.to_bytes().to_vec();
+    if let Some(root) = merkle_root {
+        preimage.extend_from_slice(root);
+    }
+    let t = BigUint::from_bytes_be(&tagged_hash("TapTweak", &preimage));
+    if t >= S256Point::order() {
+        panic!("Tweak is not below the curve order");
+    }
+    t
+}
+
+// Q = P + tG, the key committed to in a taproot output, along with whether
+// Q.y is odd. The output itself only carries Q.x, so the parity has to go
+// in the control block of script path spends.
+pub fn tap_tweak(internal_key: &XOnlyPublicKey, merkle_root: Option<&[u8; 32]>) -> (S256Point, bool) {
+    let t = tap_tweak_hash(internal_key, merkle_root);
+    let output_key = internal_key.get_point() + S256Point::generator() * t;
+    let odd = !output_key.has_even_y();
+    (output_key, odd)
+}
+
+impl PrivateKey {
+    // the secret for the output key of tap_tweak, used to spend by key path
+    pub fn tap_tweak(&self, merkle_root: Option<&[u8; 32]>) -> PrivateKey {
+        let internal_key = self.x_only_public_key();
+        let secret = if self.get_point().has_even_y() {
+            self.get_secret()
+        } else {
+            scalar::negate(&self.get_secret())
+        };
+        let t = tap_tweak_hash(&internal_key, merkle_root);
+        PrivateKey::new((secret + t) % S256Point::order())
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn xonly(hex_str: &str) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_bytes(&hex::decode(hex_str).unwrap()).unwrap()
+    }
+
+    // BIP341 wallet test vector for a key with no scripts
+    #[test]
+    fn tap_tweak_without_scripts_works() {
+        let internal_key = xonly("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+
+        let t = tap_tweak_hash(&internal_key, None);
+        assert_eq!(hex::encode(scalar::to_bytes(&t)), "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70");
+
+        let (output_key, _) = tap_tweak(&internal_key, None);
+        assert_eq!(
+            XOnlyPublicKey::from_point(&output_key).to_string(),
+            "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"
+        );
+    }
+
+    #[test]
+    fn private_key_tap_tweak_works() {
+        let secret = PrivateKey::new(BigUint::from(0x1234567890u64));
+        let root = [7u8; 32];
+
+        let (output_key, _) = tap_tweak(&secret.x_only_public_key(), Some(&root));
+        let tweaked = secret.tap_tweak(Some(&root));
+        assert_eq!(tweaked.x_only_public_key(), XOnlyPublicKey::from_point(&output_key));
+
+        let sig = tweaked.sign_schnorr(b"key path", &[0u8; 32]);
+        assert!(XOnlyPublicKey::from_point(&output_key).verify(b"key path", &sig));
+    }
+
+    // BIP341 keyPathSpending wallet vectors: internal secret, merkle root,
+    // internal key, tweak, tweaked secret, sighash and the signature in the
+    // witness. The sighashes are taken as given since there is no
+    // transaction code here. Signatures are produced with zero aux_rand;
+    // the ones left out are only checked to verify.
+    #[test]
+    fn key_path_spending_vectors_pass() {
+        let vectors = [
+            ("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa", "", "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d", "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70", "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9", "7e584883b084ace0469c6962a9a7d2a9060e1f3c218ab40d32c77651482122bc", ""),
+            ("1e4da49f6aaf4e5cd175fe08a32bb5cb4863d963921255f33d3bc31e1343907f", "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21", "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27", "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001", "ea260c3b10e60f6de018455cd0278f2f5b7e454be1999572789e6a9565d26080", "6ffd256e108685b41831385f57eebf2fca041bc6b5e607ea11b3e03d4cf9d9ba", ""),
+            ("d3c7af07da2d54f7a7735d3d0fc4f0a73164db638b2f2f7c43f711f6d4aa7e64", "c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b", "93478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820", "6af9e28dbf9d6aaf027696e2598a5b3d056f5fd2355a7fd5a37a0e5008132d30", "97323385e57015b75b0339a549c56a948eb961555973f0951f555ae6039ef00d", "9f90136737540ccc18707e1fd398ad222a1a7e4dd65cbfd22dbe4660191efa58", ""),
+            ("f36bb07a11e469ce941d16b63b11b9b9120a84d9d87cff2c84a8d4affb438f4e", "ccbd66c6f7e8fdab47b3a486f59d28262be857f30d4773f2d5ea47f7761ce0e2", "e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6f", "b57bfa183d28eeb6ad688ddaabb265b4a41fbf68e5fed2c72c74de70d5a786f4", "a8e7aa924f0d58854185a490e6c41f6efb7b675c0f3331b7f14b549400b4d501", "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef", "b4010dd48a617db09926f729e79c33ae0b4e94b79f04a1ae93ede6315eb3669de185a17d2b0ac9ee09fd4c64b678a0b61a0a86fa888a273c8511be83bfd6810f"),
+            ("415cfe9c15d9cea27d8104d5517c06e9de48e2f986b695e4f5ffebf230e725d8", "2f6b2c5397b6d68ca18e09a3f05161668ffe93a988582d55c6f07bd5b3329def", "55adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312d", "6579138e7976dc13b6a92f7bfd5a2fc7684f5ea42419d43368301470f3b74ed9", "241c14f2639d0d7139282aa6abde28dd8a067baa9d633e4e7230287ec2d02901", "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85", "a3785919a2ce3c4ce26f298c3d51619bc474ae24014bcdd31328cd8cfbab2eff3395fa0a16fe5f486d12f22a9cedded5ae74feb4bbe5351346508c5405bcfee0"),
+            ("c7b0e81f0a9a0b0499e112279d718cca98e79a12e2f137c72ae5b213aad0d103", "6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef", "ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592", "9e0517edc8259bb3359255400b23ca9507f2a91cd1e4250ba068b4eafceba4a9", "65b6000cd2bfa6b7cf736767a8955760e62b6649058cbc970b7c0871d786346b", "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10", "ea0c6ba90763c2d3a296ad82ba45881abb4f426b3f87af162dd24d5109edc1cdd11915095ba47c3a9963dc1e6c432939872bc49212fe34c632cd3ab9fed429c4"),
+            ("77863416be0d0665e517e1c375fd6f75839544eca553675ef7fdf4949518ebaa", "ab179431c28d3b68fb798957faf5497d69c883c6fb1e1cd9f81483d87bac90cc", "f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd8", "639f0281b7ac49e742cd25b7f188657626da1ad169209078e2761cefd91fd65e", "ec18ce6af99f43815db543f47b8af5ff5df3b2cb7315c955aa4a86e8143d2bf5", "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2", "bbc9584a11074e83bc8c6759ec55401f0ae7b03ef290c3139814f545b58a9f8127258000874f44bc46db7646322107d4d86aec8e73b8719a61fff761d75b5dd9"),
+        ];
+
+        for (secret, root, internal_key, tweak, tweaked_secret, sighash, sig) in vectors {
+            let secret = PrivateKey::new(BigUint::from_bytes_be(&hex::decode(secret).unwrap()));
+            let root: Option<[u8; 32]> = if root.is_empty() {
+                None
+            } else {
+                Some(hex::decode(root).unwrap().try_into().unwrap())
+            };
+            let sighash = hex::decode(sighash).unwrap();
+
+            assert_eq!(secret.x_only_public_key().to_string(), internal_key);
+            let t = tap_tweak_hash(&secret.x_only_public_key(), root.as_ref());
+            assert_eq!(hex::encode(scalar::to_bytes(&t)), tweak);
+
+            let tweaked = secret.tap_tweak(root.as_ref());
+            assert_eq!(hex::encode(scalar::to_bytes(&tweaked.get_secret())), tweaked_secret);
+            let (output_key, _) = tap_tweak(&secret.x_only_public_key(), root.as_ref());
+            let output_key = XOnlyPublicKey::from_point(&output_key);
+
+            let signature = tweaked.sign_schnorr(&sighash, &[0u8; 32]);
+            if !sig.is_empty() {
+                assert_eq!(hex::encode(signature.to_bytes()), sig);
+            }
+            assert!(output_key.verify(&sighash, &signature));
+        }
+    }
+}
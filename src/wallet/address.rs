@@ -0,0 +1,385 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use crate::encoding::base58::{self, Base58Error};
+use crate::encoding::bech32::{self, Bech32Error, Variant};
+use crate::finite_field::secp_ec::S256Point;
+use crate::hashes::ripemd160::hash160;
+use crate::schnorr::bip340::XOnlyPublicKey;
+use crate::taproot::tweak::tap_tweak;
+
+use super::network::Network;
+
+// The addresses a single key can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+    // pay to the hash of the compressed key
+    P2pkh,
+    // pay to the hash of the uncompressed key, as early wallets did
+    P2pkhUncompressed,
+    // P2WPKH nested in P2SH, for senders that predate segwit
+    P2shP2wpkh,
+    P2wpkh,
+    // BIP86: the key tweaked with no script tree
+    P2tr,
+}
+
+// What a parsed address pays to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    // a witness version or program length with no meaning yet
+    WitnessUnknown(u8),
+}
+
+// Reasons a string or script isn't a valid address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    Base58(Base58Error),
+    Bech32(Bech32Error),
+    // the Base58 version byte isn't one for P2PKH or P2SH
+    UnknownVersionByte(u8),
+    // Base58 addresses carry a version byte and a 20 byte hash
+    InvalidPayloadLength(usize),
+    // witness versions go from 0 to 16
+    InvalidWitnessVersion(u8),
+    // programs are 2 to 40 bytes, and 20 or 32 for version 0
+    InvalidWitnessProgramLength(usize),
+    // version 0 must use Bech32 and later versions Bech32m
+    WrongChecksumVariant { version: u8 },
+    // the script isn't one of the standard outputs an address stands for
+    UnsupportedScript,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::Base58(err) => write!(f, "base58: {}", err),
+            AddressError::Bech32(err) => write!(f, "bech32: {}", err),
+            AddressError::UnknownVersionByte(byte) => write!(f, "unknown version byte {:#04x}", byte),
+            AddressError::InvalidPayloadLength(len) => write!(f, "invalid payload length {}", len),
+            AddressError::InvalidWitnessVersion(version) => write!(f, "invalid witness version {}", version),
+            AddressError::InvalidWitnessProgramLength(len) => {
+                write!(f, "invalid witness program length {}", len)
+            }
+            AddressError::WrongChecksumVariant { version } => {
+                write!(f, "wrong checksum variant for witness version {}", version)
+            }
+            AddressError::UnsupportedScript => write!(f, "script has no address"),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+// An address, kept as the scriptPubKey it pays to along with its network.
+// Parsing can only tell mainnet from the rest for Base58 addresses, and
+// mainnet, regtest and the rest for Bech32 ones, so the test networks that
+// share an encoding come back as Testnet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    network: Network,
+    address_type: AddressType,
+    script_pubkey: Vec<u8>,
+}
+
+// OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG
+pub(super) fn p2pkh_script(hash: &[u8; 20]) -> Vec<u8> {
+    let mut script = vec![0x76, 0xa9, 0x14];
+    script.extend_from_slice(hash);
+    script.extend_from_slice(&[0x88, 0xac]);
+    script
+}
+
+// OP_HASH160 <hash> OP_EQUAL
+pub(super) fn p2sh_script(hash: &[u8; 20]) -> Vec<u8> {
+    let mut script = vec![0xa9, 0x14];
+    script.extend_from_slice(hash);
+    script.push(0x87);
+    script
+}
+
+// OP_n <program>
+pub(super) fn witness_script(version: u8, program: &[u8]) -> Vec<u8> {
+    let mut script = vec![if version == 0 { 0x00 } else { 0x50 + version }, program.len() as u8];
+    script.extend_from_slice(program);
+    script
+}
+
+impl Address {
+    pub fn get_network(&self) -> Network {
+        self.network
+    }
+
+    pub fn get_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    pub fn get_script_pubkey(&self) -> Vec<u8> {
+        self.script_pubkey.clone()
+    }
+
+    // the address for a P2PKH, P2SH or witness output script
+    pub fn from_script_pubkey(script: &[u8], network: Network) -> Result<Self, AddressError> {
+        let address_type = match script {
+            [0x76, 0xa9, 0x14, .., 0x88, 0xac] if script.len() == 25 => AddressType::P2pkh,
+            [0xa9, 0x14, .., 0x87] if script.len() == 23 => AddressType::P2sh,
+            [version, len, program @ ..]
+                if (*version == 0x00 || (0x51..=0x60).contains(version)) && *len as usize == program.len() =>
+            {
+                let version = if *version == 0 { 0 } else { version - 0x50 };
+                witness_type(version, program.len())?
+            }
+            _ => return Err(AddressError::UnsupportedScript),
+        };
+        Ok(Self { network, address_type, script_pubkey: script.to_vec() })
+    }
+
+    pub fn parse(s: &str) -> Result<Self, AddressError> {
+        let lower = s.to_lowercase();
+        let network = [Network::Mainnet, Network::Regtest, Network::Testnet]
+            .into_iter()
+            .find(|network| lower.starts_with(&format!("{}1", network.hrp())));
+        match network {
+            Some(network) => Self::parse_segwit(s, network),
+            None => Self::parse_base58(s),
+        }
+    }
+
+    fn parse_base58(s: &str) -> Result<Self, AddressError> {
+        let payload = base58::decode_check(s).map_err(AddressError::Base58)?;
+        if payload.len() != 21 {
+            return Err(AddressError::InvalidPayloadLength(payload.len()));
+        }
+        let hash: [u8; 20] = payload[1..].try_into().unwrap();
+        let (network, address_type, script_pubkey) = match payload[0] {
+            0x00 => (Network::Mainnet, AddressType::P2pkh, p2pkh_script(&hash)),
+            0x6f => (Network::Testnet, AddressType::P2pkh, p2pkh_script(&hash)),
+            0x05 => (Network::Mainnet, AddressType::P2sh, p2sh_script(&hash)),
+            0xc4 => (Network::Testnet, AddressType::P2sh, p2sh_script(&hash)),
+            byte => return Err(AddressError::UnknownVersionByte(byte)),
+        };
+        Ok(Self { network, address_type, script_pubkey })
+    }
+
+    fn parse_segwit(s: &str, network: Network) -> Result<Self, AddressError> {
+        let (hrp, data, variant) = bech32::decode(s).map_err(AddressError::Bech32)?;
+        if hrp != network.hrp() {
+            return Err(AddressError::Bech32(Bech32Error::InvalidHrp));
+        }
+        let (&version, values) = data.split_first().ok_or(AddressError::InvalidWitnessProgramLength(0))?;
+        if version > 16 {
+            return Err(AddressError::InvalidWitnessVersion(version));
+        }
+        if (version == 0) != (variant == Variant::Bech32) {
+            return Err(AddressError::WrongChecksumVariant { version });
+        }
+        let program = bech32::convert_bits(values, 5, 8, false).map_err(AddressError::Bech32)?;
+        let address_type = witness_type(version, program.len())?;
+        Ok(Self { network, address_type, script_pubkey: witness_script(version, &program) })
+    }
+
+    // whether the address can be used on network, which it can on any network
+    // that shares its encoding
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        match self.address_type {
+            AddressType::P2pkh | AddressType::P2sh => {
+                (self.network == Network::Mainnet) == (network == Network::Mainnet)
+            }
+            _ => self.network.hrp() == network.hrp(),
+        }
+    }
+}
+
+fn witness_type(version: u8, len: usize) -> Result<AddressType, AddressError> {
+    match (version, len) {
+        (0, 20) => Ok(AddressType::P2wpkh),
+        (0, 32) => Ok(AddressType::P2wsh),
+        (0, len) => Err(AddressError::InvalidWitnessProgramLength(len)),
+        (1, 32) => Ok(AddressType::P2tr),
+        (version, 2..=40) => Ok(AddressType::WitnessUnknown(version)),
+        (_, len) => Err(AddressError::InvalidWitnessProgramLength(len)),
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let script = &self.script_pubkey;
+        let base58 = |prefix: u8, hash: &[u8]| {
+            let mut payload = vec![prefix];
+            payload.extend_from_slice(hash);
+            base58::encode_check(&payload)
+        };
+        let encoded = match self.address_type {
+            AddressType::P2pkh => base58(self.network.p2pkh_prefix(), &script[3..23]),
+            AddressType::P2sh => base58(self.network.p2sh_prefix(), &script[2..22]),
+            _ => {
+                let version = if script[0] == 0 { 0 } else { script[0] - 0x50 };
+                let variant = if version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+                let mut data = vec![version];
+                data.extend(bech32::convert_bits(&script[2..], 8, 5, true).unwrap());
+                bech32::encode(self.network.hrp(), &data, variant).unwrap()
+            }
+        };
+        write!(f, "{}", encoded)
+    }
+}
+
+impl S256Point {
+    pub fn address(&self, kind: AddressKind, network: Network) -> Address {
+        let (address_type, script_pubkey) = match kind {
+            AddressKind::P2pkh => (AddressType::P2pkh, p2pkh_script(&hash160(&self.sec(true)))),
+            AddressKind::P2pkhUncompressed => (AddressType::P2pkh, p2pkh_script(&hash160(&self.sec(false)))),
+            AddressKind::P2shP2wpkh => {
+                let redeem_script = witness_script(0, &hash160(&self.sec(true)));
+                (AddressType::P2sh, p2sh_script(&hash160(&redeem_script)))
+            }
+            AddressKind::P2wpkh => (AddressType::P2wpkh, witness_script(0, &hash160(&self.sec(true)))),
+            AddressKind::P2tr => {
+                let (output_key, _) = tap_tweak(&XOnlyPublicKey::from_point(self), None);
+                let program = output_key.get_x().unwrap().to_bytes();
+                (AddressType::P2tr, witness_script(1, &program))
+            }
+        };
+        Address { network, address_type, script_pubkey }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use num::BigUint;
+
+    use crate::finite_field::private_key::PrivateKey;
+
+    use super::*;
+
+    fn point(sec: &str) -> S256Point {
+        S256Point::parse_sec(&hex::decode(sec).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn address_works() {
+        let g = PrivateKey::new(BigUint::from(1u8)).get_point();
+        let cases = [
+            (AddressKind::P2pkh, Network::Mainnet, "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"),
+            (AddressKind::P2pkhUncompressed, Network::Mainnet, "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm"),
+            (AddressKind::P2pkh, Network::Testnet, "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r"),
+            (AddressKind::P2wpkh, Network::Mainnet, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            (AddressKind::P2wpkh, Network::Testnet, "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            (AddressKind::P2wpkh, Network::Signet, "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            (AddressKind::P2wpkh, Network::Regtest, "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"),
+        ];
+        for (kind, network, expected) in cases {
+            assert_eq!(g.address(kind, network).to_string(), expected);
+        }
+
+        // BIP84, BIP49 and BIP86 first receiving addresses
+        let bip84 = point("0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c");
+        assert_eq!(
+            bip84.address(AddressKind::P2wpkh, Network::Mainnet).to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        let bip49 = point("03a1af804ac108a8a51782198c2d034b28bf90c8803f5a53f76276fa69a4eae77f");
+        assert_eq!(
+            bip49.address(AddressKind::P2shP2wpkh, Network::Testnet).to_string(),
+            "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2"
+        );
+        let bip86 = point("03cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115");
+        let address = bip86.address(AddressKind::P2tr, Network::Mainnet);
+        assert_eq!(address.to_string(), "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr");
+        assert_eq!(
+            hex::encode(address.get_script_pubkey()),
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        );
+    }
+
+    #[test]
+    fn parse_works() {
+        let cases = [
+            ("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH", Network::Mainnet, AddressType::P2pkh),
+            ("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r", Network::Testnet, AddressType::P2pkh),
+            ("2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2", Network::Testnet, AddressType::P2sh),
+            ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", Network::Mainnet, AddressType::P2wpkh),
+            ("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080", Network::Regtest, AddressType::P2wpkh),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                Network::Testnet,
+                AddressType::P2wsh,
+            ),
+            (
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+                Network::Mainnet,
+                AddressType::P2tr,
+            ),
+            // BIP350 valid addresses for future witness versions
+            (
+                "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+                Network::Mainnet,
+                AddressType::WitnessUnknown(1),
+            ),
+            ("BC1SW50QGDZ25J", Network::Mainnet, AddressType::WitnessUnknown(16)),
+        ];
+        for (s, network, address_type) in cases {
+            let address = Address::parse(s).unwrap();
+            assert_eq!(address.get_network(), network);
+            assert_eq!(address.get_type(), address_type);
+            assert!(address.to_string().eq_ignore_ascii_case(s));
+            assert_eq!(Address::from_script_pubkey(&address.get_script_pubkey(), network), Ok(address));
+        }
+
+        let p2pkh = Address::parse("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap();
+        assert_eq!(hex::encode(p2pkh.get_script_pubkey()), "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac");
+        assert!(p2pkh.is_valid_for_network(Network::Mainnet));
+        assert!(!p2pkh.is_valid_for_network(Network::Regtest));
+        let testnet = Address::parse("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r").unwrap();
+        assert!(testnet.is_valid_for_network(Network::Regtest));
+        let signet = Address::parse("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        assert!(signet.is_valid_for_network(Network::Signet));
+        assert!(!signet.is_valid_for_network(Network::Regtest));
+    }
+
+    // BIP350 invalid addresses
+    #[test]
+    fn parse_rejects_invalid_addresses() {
+        // later versions with a Bech32 checksum, version 0 with Bech32m
+        assert_eq!(
+            Address::parse("tb1zqqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0sza5hzy"),
+            Err(AddressError::WrongChecksumVariant { version: 2 })
+        );
+        assert_eq!(
+            Address::parse("BC1SQQQSYQCYQ5RQWZQFPG9SCRGWPUGPZYSNPQ796Q"),
+            Err(AddressError::WrongChecksumVariant { version: 16 })
+        );
+        assert_eq!(
+            Address::parse("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"),
+            Err(AddressError::WrongChecksumVariant { version: 0 })
+        );
+        assert_eq!(Address::parse("bc13qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqq0hg8yd"), Err(AddressError::InvalidWitnessVersion(17)));
+        assert_eq!(Address::parse("bc1pqystr2wj"), Err(AddressError::InvalidWitnessProgramLength(1)));
+        assert_eq!(
+            Address::parse("bc1qqqqqqqqqqqqqqqqqqqqqqqqqqqv8de0q"),
+            Err(AddressError::InvalidWitnessProgramLength(16))
+        );
+        assert!(matches!(
+            Address::parse("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"),
+            Err(AddressError::Bech32(Bech32Error::InvalidChecksum { .. }))
+        ));
+
+        // a typo in a Base58 address and a WIF key instead of an address
+        assert!(matches!(
+            Address::parse("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMJ"),
+            Err(AddressError::Base58(Base58Error::InvalidChecksum { .. }))
+        ));
+        assert_eq!(
+            Address::parse("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn"),
+            Err(AddressError::InvalidPayloadLength(34))
+        );
+        assert_eq!(Address::from_script_pubkey(&[0x6a], Network::Mainnet), Err(AddressError::UnsupportedScript));
+    }
+}
@@ -0,0 +1,698 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::encoding::base58::{self, Base58Error};
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+use crate::hashes::hmac::hmac_sha512;
+use crate::hashes::ripemd160::hash160;
+
+use super::network::Network;
+
+// child numbers from here on are hardened, derived from the private key only
+pub const HARDENED: u32 = 0x80000000;
+
+// version, depth, parent fingerprint, child number, chain code and key
+pub const SERIALIZED_LEN: usize = 78;
+
+// Reasons a key can't be created, derived or parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bip32Error {
+    Base58(Base58Error),
+    // seeds are 16 to 64 bytes
+    InvalidSeedLength(usize),
+    // serialized keys are 78 bytes
+    InvalidLength(usize),
+    // the version bytes aren't xprv/xpub or one of their SLIP-132 variants
+    UnknownVersion([u8; 4]),
+    // a private key version on a public key or the other way round
+    WrongKeyType,
+    // the key data isn't a secret in 1..n-1 after 0x00, or a valid SEC point
+    InvalidKey,
+    // a key at depth 0 has a parent fingerprint or child number
+    InvalidMaster,
+    // hardened children can't be derived from a public key
+    HardenedFromPublic(u32),
+    // this child's key would be invalid, so it must be skipped (about 1 in
+    // 2^127)
+    InvalidChild(u32),
+    // keys go at most 255 levels deep
+    DepthOverflow,
+    // a path segment isn't a number below 2^31, optionally hardened
+    InvalidPath(String),
+}
+
+impl fmt::Display for Bip32Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bip32Error::Base58(err) => write!(f, "base58: {}", err),
+            Bip32Error::InvalidSeedLength(len) => write!(f, "invalid seed length {}", len),
+            Bip32Error::InvalidLength(len) => write!(f, "invalid extended key length {}", len),
+            Bip32Error::UnknownVersion(version) => write!(f, "unknown version {:02x?}", version),
+            Bip32Error::WrongKeyType => write!(f, "version is for the other key type"),
+            Bip32Error::InvalidKey => write!(f, "invalid key data"),
+            Bip32Error::InvalidMaster => write!(f, "master key with a parent fingerprint or child number"),
+            Bip32Error::HardenedFromPublic(index) => {
+                write!(f, "can't derive hardened child {} from a public key", index)
+            }
+            Bip32Error::InvalidChild(index) => write!(f, "child {} is invalid", index),
+            Bip32Error::DepthOverflow => write!(f, "maximum depth reached"),
+            Bip32Error::InvalidPath(segment) => write!(f, "invalid path segment {:?}", segment),
+        }
+    }
+}
+
+impl std::error::Error for Bip32Error {}
+
+// The script a key is meant for, which SLIP-132 encodes in the version bytes:
+// xpub/tpub, ypub/upub and zpub/vpub
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+}
+
+// (mainnet, script type, private) -> version bytes
+const VERSIONS: [(bool, ScriptType, bool, [u8; 4]); 12] = [
+    (true, ScriptType::P2pkh, true, [0x04, 0x88, 0xad, 0xe4]),
+    (true, ScriptType::P2pkh, false, [0x04, 0x88, 0xb2, 0x1e]),
+    (true, ScriptType::P2shP2wpkh, true, [0x04, 0x9d, 0x78, 0x78]),
+    (true, ScriptType::P2shP2wpkh, false, [0x04, 0x9d, 0x7c, 0xb2]),
+    (true, ScriptType::P2wpkh, true, [0x04, 0xb2, 0x43, 0x0c]),
+    (true, ScriptType::P2wpkh, false, [0x04, 0xb2, 0x47, 0x46]),
+    (false, ScriptType::P2pkh, true, [0x04, 0x35, 0x83, 0x94]),
+    (false, ScriptType::P2pkh, false, [0x04, 0x35, 0x87, 0xcf]),
+    (false, ScriptType::P2shP2wpkh, true, [0x04, 0x4a, 0x4e, 0x28]),
+    (false, ScriptType::P2shP2wpkh, false, [0x04, 0x4a, 0x52, 0x62]),
+    (false, ScriptType::P2wpkh, true, [0x04, 0x5f, 0x18, 0xbc]),
+    (false, ScriptType::P2wpkh, false, [0x04, 0x5f, 0x1c, 0xf6]),
+];
+
+fn version(network: Network, script_type: ScriptType, private: bool) -> [u8; 4] {
+    let mainnet = network == Network::Mainnet;
+    VERSIONS
+        .iter()
+        .find(|(m, t, p, _)| *m == mainnet && *t == script_type && *p == private)
+        .unwrap()
+        .3
+}
+
+// A path of child numbers, written m/84'/0'/0'/0/5 with ' (or h) marking
+// hardened ones
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DerivationPath {
+    children: Vec<u32>,
+}
+
+impl DerivationPath {
+    pub fn new(children: Vec<u32>) -> Self {
+        Self { children }
+    }
+
+    pub fn get_children(&self) -> Vec<u32> {
+        self.children.clone()
+    }
+
+    // the leading m is optional, so origins and suffixes like 0/1 parse too
+    pub fn parse(s: &str) -> Result<Self, Bip32Error> {
+        let rest = match s {
+            "" | "m" => return Ok(Self::default()),
+            s => s.strip_prefix("m/").unwrap_or(s),
+        };
+        rest.split('/').map(parse_child).collect::<Result<Vec<u32>, _>>().map(Self::new)
+    }
+
+    pub fn child(&self, index: u32) -> Self {
+        let mut children = self.children.clone();
+        children.push(index);
+        Self { children }
+    }
+}
+
+// 5, 5' or 5h
+pub fn parse_child(segment: &str) -> Result<u32, Bip32Error> {
+    let (number, hardened) = match segment.strip_suffix(['\'', 'h', 'H']) {
+        Some(number) => (number, true),
+        None => (segment, false),
+    };
+    let invalid = || Bip32Error::InvalidPath(segment.to_string());
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let index: u32 = number.parse().map_err(|_| invalid())?;
+    if index >= HARDENED {
+        return Err(invalid());
+    }
+    Ok(if hardened { index | HARDENED } else { index })
+}
+
+fn format_child(index: u32) -> String {
+    if index >= HARDENED {
+        format!("{}'", index - HARDENED)
+    } else {
+        index.to_string()
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for &index in &self.children {
+            write!(f, "/{}", format_child(index))?;
+        }
+        Ok(())
+    }
+}
+
+// the fields both key types share, and their 78 byte serialization
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    network: Network,
+    script_type: ScriptType,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+}
+
+impl Header {
+    fn child(&self, parent_point: &S256Point, index: u32, chain_code: &[u8]) -> Result<Self, Bip32Error> {
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::DepthOverflow)?;
+        Ok(Self {
+            network: self.network,
+            script_type: self.script_type,
+            depth,
+            parent_fingerprint: fingerprint(parent_point),
+            child_number: index,
+            chain_code: chain_code.try_into().unwrap(),
+        })
+    }
+
+    fn serialize(&self, key: &[u8], private: bool) -> [u8; SERIALIZED_LEN] {
+        let mut result = [0u8; SERIALIZED_LEN];
+        result[..4].copy_from_slice(&version(self.network, self.script_type, private));
+        result[4] = self.depth;
+        result[5..9].copy_from_slice(&self.parent_fingerprint);
+        result[9..13].copy_from_slice(&self.child_number.to_be_bytes());
+        result[13..45].copy_from_slice(&self.chain_code);
+        result[45..].copy_from_slice(key);
+        result
+    }
+
+    // the header and the 33 key bytes
+    fn parse(data: &[u8], private: bool) -> Result<(Self, Vec<u8>), Bip32Error> {
+        if data.len() != SERIALIZED_LEN {
+            return Err(Bip32Error::InvalidLength(data.len()));
+        }
+        let version_bytes: [u8; 4] = data[..4].try_into().unwrap();
+        let &(mainnet, script_type, is_private, _) = VERSIONS
+            .iter()
+            .find(|v| v.3 == version_bytes)
+            .ok_or(Bip32Error::UnknownVersion(version_bytes))?;
+        if is_private != private {
+            return Err(Bip32Error::WrongKeyType);
+        }
+        let header = Self {
+            network: if mainnet { Network::Mainnet } else { Network::Testnet },
+            script_type,
+            depth: data[4],
+            parent_fingerprint: data[5..9].try_into().unwrap(),
+            child_number: u32::from_be_bytes(data[9..13].try_into().unwrap()),
+            chain_code: data[13..45].try_into().unwrap(),
+        };
+        if header.depth == 0 && (header.parent_fingerprint != [0; 4] || header.child_number != 0) {
+            return Err(Bip32Error::InvalidMaster);
+        }
+        Ok((header, data[45..].to_vec()))
+    }
+}
+
+fn fingerprint(point: &S256Point) -> [u8; 4] {
+    hash160(&point.sec(true))[..4].try_into().unwrap()
+}
+
+// I = HMAC-SHA512(c, data), with IL the tweak and IR the child's chain code.
+// IL is rejected if it is not below n.
+fn child_tweak(chain_code: &[u8; 32], data: &[u8], index: u32) -> Result<(BigUint, [u8; 64]), Bip32Error> {
+    let mut msg = data.to_vec();
+    msg.extend_from_slice(&index.to_be_bytes());
+    let i = hmac_sha512(chain_code, &msg);
+    let tweak = BigUint::from_bytes_be(&i[..32]);
+    if tweak >= S256Point::order() {
+        return Err(Bip32Error::InvalidChild(index));
+    }
+    Ok((tweak, i))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPrivKey {
+    header: Header,
+    key: PrivateKey,
+}
+
+impl ExtendedPrivKey {
+    // I = HMAC-SHA512("Bitcoin seed", seed), the secret and chain code of
+    // the root of the tree
+    pub fn new_master(seed: &[u8], network: Network) -> Result<Self, Bip32Error> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(Bip32Error::InvalidSeedLength(seed.len()));
+        }
+        let i = hmac_sha512(b"Bitcoin seed", seed);
+        let secret = BigUint::from_bytes_be(&i[..32]);
+        if secret.is_zero() || secret >= S256Point::order() {
+            return Err(Bip32Error::InvalidKey);
+        }
+        let header = Header {
+            network,
+            script_type: ScriptType::P2pkh,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code: i[32..].try_into().unwrap(),
+        };
+        Ok(Self { header, key: PrivateKey::new(secret) })
+    }
+
+    // the same key, serialized with the SLIP-132 version for script_type
+    pub fn with_script_type(mut self, script_type: ScriptType) -> Self {
+        self.header.script_type = script_type;
+        self
+    }
+
+    pub fn get_network(&self) -> Network {
+        self.header.network
+    }
+
+    pub fn get_script_type(&self) -> ScriptType {
+        self.header.script_type
+    }
+
+    pub fn get_depth(&self) -> u8 {
+        self.header.depth
+    }
+
+    pub fn get_parent_fingerprint(&self) -> [u8; 4] {
+        self.header.parent_fingerprint
+    }
+
+    pub fn get_child_number(&self) -> u32 {
+        self.header.child_number
+    }
+
+    pub fn get_chain_code(&self) -> [u8; 32] {
+        self.header.chain_code
+    }
+
+    pub fn get_private_key(&self) -> PrivateKey {
+        self.key.clone()
+    }
+
+    // the first 4 bytes of hash160 of the public key, which children use to
+    // point at their parent
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.key.get_point())
+    }
+
+    // CKDpriv: hardened children hash the secret, normal ones the public key,
+    // and either way the child secret is IL + k
+    pub fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        let data = if index >= HARDENED {
+            let mut data = vec![0x00];
+            data.extend_from_slice(&scalar::to_bytes(&self.key.get_secret()));
+            data
+        } else {
+            self.key.get_point().sec(true)
+        };
+        let (tweak, i) = child_tweak(&self.header.chain_code, &data, index)?;
+        let secret = (tweak + self.key.get_secret()) % S256Point::order();
+        if secret.is_zero() {
+            return Err(Bip32Error::InvalidChild(index));
+        }
+        let header = self.header.child(&self.key.get_point(), index, &i[32..])?;
+        Ok(Self { header, key: PrivateKey::new(secret) })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.children.iter().try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+
+    pub fn to_public(&self) -> ExtendedPubKey {
+        ExtendedPubKey { header: self.header.clone(), point: self.key.get_point() }
+    }
+
+    pub fn serialize(&self) -> [u8; SERIALIZED_LEN] {
+        let mut key = vec![0x00];
+        key.extend_from_slice(&scalar::to_bytes(&self.key.get_secret()));
+        self.header.serialize(&key, true)
+    }
+
+    // xprv and its SLIP-132 variants. Test network keys come back as Testnet.
+    pub fn parse(s: &str) -> Result<Self, Bip32Error> {
+        Self::from_bytes(&base58::decode_check(s).map_err(Bip32Error::Base58)?)
+    }
+
+    // the 78 bytes of serialize
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Bip32Error> {
+        let (header, key) = Header::parse(data, true)?;
+        if key[0] != 0x00 {
+            return Err(Bip32Error::InvalidKey);
+        }
+        let secret = BigUint::from_bytes_be(&key[1..]);
+        if secret.is_zero() || secret >= S256Point::order() {
+            return Err(Bip32Error::InvalidKey);
+        }
+        Ok(Self { header, key: PrivateKey::new(secret) })
+    }
+}
+
+impl fmt::Display for ExtendedPrivKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base58::encode_check(&self.serialize()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPubKey {
+    header: Header,
+    point: S256Point,
+}
+
+impl ExtendedPubKey {
+    pub fn with_script_type(mut self, script_type: ScriptType) -> Self {
+        self.header.script_type = script_type;
+        self
+    }
+
+    pub fn get_network(&self) -> Network {
+        self.header.network
+    }
+
+    pub fn get_script_type(&self) -> ScriptType {
+        self.header.script_type
+    }
+
+    pub fn get_depth(&self) -> u8 {
+        self.header.depth
+    }
+
+    pub fn get_parent_fingerprint(&self) -> [u8; 4] {
+        self.header.parent_fingerprint
+    }
+
+    pub fn get_child_number(&self) -> u32 {
+        self.header.child_number
+    }
+
+    pub fn get_chain_code(&self) -> [u8; 32] {
+        self.header.chain_code
+    }
+
+    pub fn get_point(&self) -> S256Point {
+        self.point.clone()
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.point)
+    }
+
+    // CKDpub: K_i = IL G + K, for normal children only
+    pub fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        if index >= HARDENED {
+            return Err(Bip32Error::HardenedFromPublic(index));
+        }
+        let (tweak, i) = child_tweak(&self.header.chain_code, &self.point.sec(true), index)?;
+        let point = S256Point::generator() * tweak + self.point.clone();
+        if point == S256Point::infinity_point() {
+            return Err(Bip32Error::InvalidChild(index));
+        }
+        let header = self.header.child(&self.point, index, &i[32..])?;
+        Ok(Self { header, point })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.children.iter().try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+
+    pub fn serialize(&self) -> [u8; SERIALIZED_LEN] {
+        self.header.serialize(&self.point.sec(true), false)
+    }
+
+    // xpub and its SLIP-132 variants. Test network keys come back as Testnet.
+    pub fn parse(s: &str) -> Result<Self, Bip32Error> {
+        Self::from_bytes(&base58::decode_check(s).map_err(Bip32Error::Base58)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Bip32Error> {
+        let (header, key) = Header::parse(data, false)?;
+        if key[0] != 0x02 && key[0] != 0x03 {
+            return Err(Bip32Error::InvalidKey);
+        }
+        let point = S256Point::parse_sec(&key).map_err(|_| Bip32Error::InvalidKey)?;
+        Ok(Self { header, point })
+    }
+}
+
+impl fmt::Display for ExtendedPubKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", base58::encode_check(&self.serialize()))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // each path with its xpub and xprv, checked both by deriving from the
+    // master key and by parsing
+    fn check_vector(seed: &str, cases: &[(&str, &str, &str)]) {
+        let master = ExtendedPrivKey::new_master(&hex::decode(seed).unwrap(), Network::Mainnet).unwrap();
+        for &(path, xpub, xprv) in cases {
+            let path = DerivationPath::parse(path).unwrap();
+            let key = master.derive_path(&path).unwrap();
+            assert_eq!(key.to_string(), xprv, "{}", path);
+            assert_eq!(key.to_public().to_string(), xpub, "{}", path);
+            assert_eq!(ExtendedPrivKey::parse(xprv).unwrap(), key);
+            assert_eq!(ExtendedPubKey::parse(xpub).unwrap(), key.to_public());
+        }
+    }
+
+    #[test]
+    fn test_vector_1_works() {
+        check_vector(
+            "000102030405060708090a0b0c0d0e0f",
+            &[
+                (
+                    "m",
+                    "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+                    "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+                ),
+                (
+                    "m/0H",
+                    "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+                    "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+                ),
+                (
+                    "m/0H/1",
+                    "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+                    "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+                ),
+                (
+                    "m/0H/1/2H",
+                    "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+                    "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+                ),
+                (
+                    "m/0H/1/2H/2",
+                    "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+                    "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+                ),
+                (
+                    "m/0H/1/2H/2/1000000000",
+                    "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+                    "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_vector_2_works() {
+        check_vector(
+            "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+            &[
+                (
+                    "m",
+                    "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+                    "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U",
+                ),
+                (
+                    "m/0",
+                    "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH",
+                    "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt",
+                ),
+                (
+                    "m/0/2147483647H",
+                    "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a",
+                    "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9",
+                ),
+                (
+                    "m/0/2147483647H/1",
+                    "xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon",
+                    "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef",
+                ),
+                (
+                    "m/0/2147483647H/1/2147483646H",
+                    "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL",
+                    "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc",
+                ),
+                (
+                    "m/0/2147483647H/1/2147483646H/2",
+                    "xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt",
+                    "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j",
+                ),
+            ],
+        );
+    }
+
+    // keys with leading zeros, which must be kept when serializing
+    #[test]
+    fn test_vectors_3_and_4_work() {
+        check_vector(
+            "4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be",
+            &[
+                (
+                    "m",
+                    "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13",
+                    "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6",
+                ),
+                (
+                    "m/0H",
+                    "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y",
+                    "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L",
+                ),
+            ],
+        );
+        check_vector(
+            "3ddd5602285899a946114506157c7997e5444528f3003f6134712147db19b678",
+            &[
+                (
+                    "m",
+                    "xpub661MyMwAqRbcGczjuMoRm6dXaLDEhW1u34gKenbeYqAix21mdUKJyuyu5F1rzYGVxyL6tmgBUAEPrEz92mBXjByMRiJdba9wpnN37RLLAXa",
+                    "xprv9s21ZrQH143K48vGoLGRPxgo2JNkJ3J3fqkirQC2zVdk5Dgd5w14S7fRDyHH4dWNHUgkvsvNDCkvAwcSHNAQwhwgNMgZhLtQC63zxwhQmRv",
+                ),
+                (
+                    "m/0H",
+                    "xpub69AUMk3qDBi3uW1sXgjCmVjJ2G6WQoYSnNHyzkmdCHEhSZ4tBok37xfFEqHd2AddP56Tqp4o56AePAgCjYdvpW2PU2jbUPFKsav5ut6Ch1m",
+                    "xprv9vB7xEWwNp9kh1wQRfCCQMnZUEG21LpbR9NPCNN1dwhiZkjjeGRnaALmPXCX7SgjFTiCTT6bXes17boXtjq3xLpcDjzEuGLQBM5ohqkao9G",
+                ),
+                (
+                    "m/0H/1H",
+                    "xpub6BJA1jSqiukeaesWfxe6sNK9CCGaujFFSJLomWHprUL9DePQ4JDkM5d88n49sMGJxrhpjazuXYWdMf17C9T5XnxkopaeS7jGk1GyyVziaMt",
+                    "xprv9xJocDuwtYCMNAo3Zw76WENQeAS6WGXQ55RCy7tDJ8oALr4FWkuVoHJeHVAcAqiZLE7Je3vZJHxspZdFHfnBEjHqU5hG1Jaj32dVoS6XLT1",
+                ),
+            ],
+        );
+    }
+
+    // test vector 5, keys that must be rejected
+    #[test]
+    fn parse_rejects_invalid_keys() {
+        let public = [
+            ("xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6LBpB85b3D2yc8sfvZU521AAwdZafEz7mnzBBsz4wKY5fTtTQBm", Bip32Error::InvalidKey),
+            ("xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6Txnt3siSujt9RCVYsx4qHZGc62TG4McvMGcAUjeuwZdduYEvFn", Bip32Error::InvalidKey),
+            ("xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6N8ZMMXctdiCjxTNq964yKkwrkBJJwpzZS4HS2fxvyYUA4q2Xe4", Bip32Error::InvalidKey),
+            ("xpub661no6RGEX3uJkY4bNnPcw4URcQTrSibUZ4NqJEw5eBkv7ovTwgiT91XX27VbEXGENhYRCf7hyEbWrR3FewATdCEebj6znwMfQkhRYHRLpJ", Bip32Error::InvalidMaster),
+            ("xpub661MyMwAuDcm6CRQ5N4qiHKrJ39Xe1R1NyfouMKTTWcguwVcfrZJaNvhpebzGerh7gucBvzEQWRugZDuDXjNDRmXzSZe4c7mnTK97pTvGS8", Bip32Error::InvalidMaster),
+            ("xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6Q5JXayek4PRsn35jii4veMimro1xefsM58PgBMrvdYre8QyULY", Bip32Error::InvalidKey),
+        ];
+        for (s, err) in public {
+            assert_eq!(ExtendedPubKey::parse(s), Err(err), "{}", s);
+        }
+
+        let private = [
+            ("xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFGTQQD3dC4H2D5GBj7vWvSQaaBv5cxi9gafk7NF3pnBju6dwKvH", Bip32Error::InvalidKey),
+            ("xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFGpWnsj83BHtEy5Zt8CcDr1UiRXuWCmTQLxEK9vbz5gPstX92JQ", Bip32Error::InvalidKey),
+            ("xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFAzHGBP2UuGCqWLTAPLcMtD9y5gkZ6Eq3Rjuahrv17fEQ3Qen6J", Bip32Error::InvalidKey),
+            ("xprv9s2SPatNQ9Vc6GTbVMFPFo7jsaZySyzk7L8n2uqKXJen3KUmvQNTuLh3fhZMBoG3G4ZW1N2kZuHEPY53qmbZzCHshoQnNf4GvELZfqTUrcv", Bip32Error::InvalidMaster),
+            ("xprv9s21ZrQH4r4TsiLvyLXqM9P7k1K3EYhA1kkD6xuquB5i39AU8KF42acDyL3qsDbU9NmZn6MsGSUYZEsuoePmjzsB3eFKSUEh3Gu1N3cqVUN", Bip32Error::InvalidMaster),
+            ("xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzF93Y5wvzdUayhgkkFoicQZcP3y52uPPxFnfoLZB21Teqt1VvEHx", Bip32Error::InvalidKey),
+            ("xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFAzHGBP2UuGCqWLTAPLcMtD5SDKr24z3aiUvKr9bJpdrcLg1y3G", Bip32Error::InvalidKey),
+        ];
+        for (s, err) in private {
+            assert_eq!(ExtendedPrivKey::parse(s), Err(err), "{}", s);
+        }
+
+        assert!(matches!(
+            ExtendedPubKey::parse("DMwo58pR1QLEFihHiXPVykYB6fJmsTeHvyTp7hRThAtCX8CvYzgPcn8XnmdfHGMQzT7ayAmfo4z3gY5KfbrZWZ6St24UVf2Qgo6oujFktLHdHY4"),
+            Err(Bip32Error::UnknownVersion(_))
+        ));
+        assert!(matches!(
+            ExtendedPrivKey::parse("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHL"),
+            Err(Bip32Error::Base58(Base58Error::InvalidChecksum { .. }))
+        ));
+        assert_eq!(
+            ExtendedPrivKey::parse("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            Err(Bip32Error::WrongKeyType)
+        );
+    }
+
+    #[test]
+    fn public_derivation_works() {
+        let master = ExtendedPrivKey::new_master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap(), Network::Mainnet)
+            .unwrap();
+        let account = master.derive_path(&DerivationPath::parse("m/0H/1").unwrap()).unwrap();
+        let path = DerivationPath::parse("2/1000000000").unwrap();
+        assert_eq!(
+            account.to_public().derive_path(&path).unwrap(),
+            account.derive_path(&path).unwrap().to_public()
+        );
+        assert_eq!(account.get_parent_fingerprint(), master.derive_child(HARDENED).unwrap().fingerprint());
+        assert_eq!(account.get_depth(), 2);
+        assert_eq!(account.get_child_number(), 1);
+        assert_eq!(account.to_public().derive_child(HARDENED), Err(Bip32Error::HardenedFromPublic(HARDENED)));
+    }
+
+    // SLIP-132 versions from BIP84's account key, and testnet's
+    #[test]
+    fn slip132_works() {
+        let master = ExtendedPrivKey::new_master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap(), Network::Mainnet)
+            .unwrap();
+        for (script_type, prefix) in [(ScriptType::P2pkh, "x"), (ScriptType::P2shP2wpkh, "y"), (ScriptType::P2wpkh, "z")] {
+            let key = master.clone().with_script_type(script_type);
+            let (xprv, xpub) = (key.to_string(), key.to_public().to_string());
+            assert!(xprv.starts_with(&format!("{}prv", prefix)));
+            assert!(xpub.starts_with(&format!("{}pub", prefix)));
+            assert_eq!(ExtendedPrivKey::parse(&xprv).unwrap(), key);
+            assert_eq!(ExtendedPubKey::parse(&xpub).unwrap().get_script_type(), script_type);
+            // the child keeps the version
+            assert!(key.derive_child(0).unwrap().to_string().starts_with(&format!("{}prv", prefix)));
+        }
+
+        let testnet = ExtendedPrivKey::new_master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap(), Network::Signet)
+            .unwrap();
+        for (script_type, prefix) in [(ScriptType::P2pkh, "t"), (ScriptType::P2shP2wpkh, "u"), (ScriptType::P2wpkh, "v")] {
+            let key = testnet.clone().with_script_type(script_type);
+            assert!(key.to_string().starts_with(&format!("{}prv", prefix)));
+            assert!(key.to_public().to_string().starts_with(&format!("{}pub", prefix)));
+            assert_eq!(ExtendedPubKey::parse(&key.to_public().to_string()).unwrap().get_network(), Network::Testnet);
+        }
+    }
+
+    #[test]
+    fn derivation_path_works() {
+        let path = DerivationPath::parse("m/84'/0'/0'/0/5").unwrap();
+        assert_eq!(path.get_children(), vec![84 | HARDENED, HARDENED, HARDENED, 0, 5]);
+        assert_eq!(path.to_string(), "m/84'/0'/0'/0/5");
+        assert_eq!(DerivationPath::parse("m/84h/0H/0'/0/5"), Ok(path.clone()));
+        assert_eq!(DerivationPath::parse("84'/0'/0'/0/5"), Ok(path.clone()));
+        assert_eq!(DerivationPath::parse("m/84'/0'/0'/0").unwrap().child(5), path);
+        assert_eq!(DerivationPath::parse("m"), Ok(DerivationPath::default()));
+
+        for bad in ["m/", "m/x", "m/2147483648", "m/1''", "m//1", "m/-1", "m/+1"] {
+            assert!(matches!(DerivationPath::parse(bad), Err(Bip32Error::InvalidPath(_))), "{}", bad);
+        }
+    }
+}
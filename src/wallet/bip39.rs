@@ -0,0 +1,208 @@
+#![allow(unused)]
+
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::hashes::pbkdf2::pbkdf2_hmac_sha512;
+use crate::hashes::sha256::sha256;
+
+use super::bip32::{Bip32Error, ExtendedPrivKey};
+use super::network::Network;
+
+// the 2048 words of the English list, one per line and sorted
+const ENGLISH: &str = include_str!("bip39_english.txt");
+
+// rounds of PBKDF2 used to stretch a mnemonic into a seed
+const SEED_ITERATIONS: u32 = 2048;
+
+// Reasons entropy or a phrase isn't a valid mnemonic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bip39Error {
+    // entropy is 16 to 32 bytes, in steps of 4
+    InvalidEntropyLength(usize),
+    // phrases are 12, 15, 18, 21 or 24 words
+    InvalidWordCount(usize),
+    // the word at this position isn't in the wordlist
+    UnknownWord { word: String, position: usize },
+    // the last bits don't match the hash of the entropy
+    InvalidChecksum,
+}
+
+impl fmt::Display for Bip39Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bip39Error::InvalidEntropyLength(len) => write!(f, "invalid entropy length {}", len),
+            Bip39Error::InvalidWordCount(count) => write!(f, "invalid word count {}", count),
+            Bip39Error::UnknownWord { word, position } => {
+                write!(f, "unknown word {:?} at position {}", word, position)
+            }
+            Bip39Error::InvalidChecksum => write!(f, "invalid checksum"),
+        }
+    }
+}
+
+impl std::error::Error for Bip39Error {}
+
+fn wordlist() -> Vec<&'static str> {
+    ENGLISH.lines().collect()
+}
+
+// A BIP39 mnemonic: the entropy followed by the first ENT / 32 bits of its
+// SHA256 as checksum, split into 11 bit indices into the wordlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+    words: Vec<&'static str>,
+}
+
+impl Mnemonic {
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, Bip39Error> {
+        if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err(Bip39Error::InvalidEntropyLength(entropy.len()));
+        }
+        let checksum_bits = entropy.len() / 4;
+        let word_count = (entropy.len() * 8 + checksum_bits) / 11;
+        let mut bytes = entropy.to_vec();
+        // at most 8 checksum bits, so the first hash byte covers them all
+        bytes.push(sha256(entropy)[0]);
+        let list = wordlist();
+        let words = (0..word_count)
+            .map(|i| list[read_bits(&bytes, i * 11, 11)])
+            .collect();
+        Ok(Self { entropy: entropy.to_vec(), words })
+    }
+
+    // Parse a phrase after NFKD normalization, so words typed with any
+    // unicode form or separated by any whitespace are accepted
+    pub fn parse(phrase: &str) -> Result<Self, Bip39Error> {
+        let normalized: String = phrase.nfkd().collect();
+        let words: Vec<&str> = normalized.split_whitespace().collect();
+        if !(12..=24).contains(&words.len()) || !words.len().is_multiple_of(3) {
+            return Err(Bip39Error::InvalidWordCount(words.len()));
+        }
+        let list = wordlist();
+        let mut bytes = vec![0u8; (words.len() * 11).div_ceil(8)];
+        for (position, word) in words.iter().enumerate() {
+            let index = list.binary_search(word).map_err(|_| Bip39Error::UnknownWord {
+                word: word.to_string(),
+                position,
+            })?;
+            write_bits(&mut bytes, position * 11, 11, index);
+        }
+        // 11 bits per word, of which 1 in 33 are checksum
+        let entropy_len = words.len() * 11 * 32 / 33 / 8;
+        let mnemonic = Self::from_entropy(&bytes[..entropy_len])?;
+        if mnemonic.words != words {
+            return Err(Bip39Error::InvalidChecksum);
+        }
+        Ok(mnemonic)
+    }
+
+    pub fn get_entropy(&self) -> Vec<u8> {
+        self.entropy.clone()
+    }
+
+    pub fn get_words(&self) -> Vec<&'static str> {
+        self.words.clone()
+    }
+
+    // PBKDF2-HMAC-SHA512 of the phrase, salted with "mnemonic" and the
+    // passphrase, both NFKD normalized
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let phrase: String = self.to_string().nfkd().collect();
+        let salt: String = format!("mnemonic{}", passphrase).nfkd().collect();
+        pbkdf2_hmac_sha512(phrase.as_bytes(), salt.as_bytes(), SEED_ITERATIONS)
+    }
+
+    pub fn to_master_key(&self, passphrase: &str, network: Network) -> Result<ExtendedPrivKey, Bip32Error> {
+        ExtendedPrivKey::new_master(&self.to_seed(passphrase), network)
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.words.join(" "))
+    }
+}
+
+// count bits starting at bit offset, most significant first
+fn read_bits(bytes: &[u8], offset: usize, count: usize) -> usize {
+    (offset..offset + count).fold(0, |acc, bit| {
+        (acc << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as usize
+    })
+}
+
+fn write_bits(bytes: &mut [u8], offset: usize, count: usize, value: usize) {
+    for i in 0..count {
+        if (value >> (count - 1 - i)) & 1 == 1 {
+            let bit = offset + i;
+            bytes[bit / 8] |= 1 << (7 - bit % 8);
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const TEST_VECTORS: &str = include_str!("bip39_test_vectors.csv");
+
+    #[test]
+    fn wordlist_is_sorted() {
+        let list = wordlist();
+        assert_eq!(list.len(), 2048);
+        assert!(list.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn bip39_vectors_work() {
+        for line in TEST_VECTORS.lines().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            let entropy = hex::decode(fields[0]).unwrap();
+            let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+            assert_eq!(mnemonic.to_string(), fields[1]);
+            assert_eq!(Mnemonic::parse(fields[1]), Ok(mnemonic.clone()));
+            assert_eq!(mnemonic.get_entropy(), entropy);
+            assert_eq!(hex::encode(mnemonic.to_seed("TREZOR")), fields[2]);
+            let master = mnemonic.to_master_key("TREZOR", Network::Mainnet).unwrap();
+            assert_eq!(master.to_string(), fields[3]);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_phrases() {
+        let valid = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        assert_eq!(
+            Mnemonic::parse("legal winner thank year wave sausage worth useful legal winner thank"),
+            Err(Bip39Error::InvalidWordCount(11))
+        );
+        assert_eq!(
+            Mnemonic::parse(&valid.replace("yellow", "yellow yellow")),
+            Err(Bip39Error::InvalidWordCount(13))
+        );
+        assert_eq!(
+            Mnemonic::parse(&valid.replace("wave", "wavy")),
+            Err(Bip39Error::UnknownWord { word: "wavy".to_string(), position: 4 })
+        );
+        assert_eq!(
+            Mnemonic::parse(&valid.replace("yellow", "year")),
+            Err(Bip39Error::InvalidChecksum)
+        );
+        assert_eq!(Mnemonic::from_entropy(&[0; 15]), Err(Bip39Error::InvalidEntropyLength(15)));
+        assert_eq!(Mnemonic::from_entropy(&[0; 18]), Err(Bip39Error::InvalidEntropyLength(18)));
+        assert_eq!(Mnemonic::from_entropy(&[0; 36]), Err(Bip39Error::InvalidEntropyLength(36)));
+
+        // extra whitespace and ideographic spaces are separators after NFKD
+        let spaced = valid.replacen(' ', "\u{3000}", 3).replacen(' ', "  \t", 2);
+        assert_eq!(Mnemonic::parse(&spaced).unwrap().to_string(), valid);
+    }
+
+    #[test]
+    fn passphrase_is_normalized() {
+        let mnemonic = Mnemonic::from_entropy(&[0; 16]).unwrap();
+        // precomposed and combining forms of the same passphrase
+        assert_eq!(mnemonic.to_seed("caf\u{e9}"), mnemonic.to_seed("cafe\u{301}"));
+        assert_ne!(mnemonic.to_seed("cafe"), mnemonic.to_seed("cafe\u{301}"));
+    }
+}
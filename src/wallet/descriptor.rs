@@ -0,0 +1,821 @@
+#![allow(unused)]
+
+use std::fmt;
+
+use crate::encoding::hex::{from_hex, to_hex};
+use crate::finite_field::{private_key::PrivateKey, secp_ec::S256Point};
+use crate::hashes::ripemd160::hash160;
+use crate::hashes::sha256::sha256;
+use crate::schnorr::bip340::XOnlyPublicKey;
+use crate::taproot::tree::{TapTree, TaprootError, TAPROOT_CONTROL_MAX_NODE_COUNT, TAPSCRIPT_LEAF_VERSION};
+use crate::taproot::tweak::tap_tweak;
+
+use super::address::{p2pkh_script, p2sh_script, witness_script, Address, AddressError};
+use super::bip32::{parse_child, Bip32Error, DerivationPath, ExtendedPrivKey, ExtendedPubKey, HARDENED};
+use super::network::Network;
+
+// the characters a descriptor can contain, ordered so that the checksum sees
+// each as its position mod 32 plus a group of 32 it belongs to
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// generator of the BCH code behind the checksum
+const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+
+// OP_CHECKMULTISIG takes at most 20 keys
+const MAX_MULTISIG_KEYS: usize = 20;
+
+// a redeem script is pushed in the scriptSig, and pushes are at most 520 bytes
+const MAX_REDEEM_SCRIPT_SIZE: usize = 520;
+
+// Reasons a string isn't a valid descriptor or can't be expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorError {
+    // a character outside the descriptor character set
+    InvalidCharacter(char),
+    // the checksum after # doesn't match the descriptor
+    InvalidChecksum { expected: String, found: String },
+    // unbalanced brackets or an expression that isn't name(arguments)
+    InvalidSyntax(String),
+    UnknownFunction(String),
+    // the function takes a different number of arguments
+    InvalidArgumentCount { function: String, count: usize },
+    // not a hex public key, WIF key or extended key with a valid path
+    InvalidKey(String),
+    Bip32(Bip32Error),
+    Address(AddressError),
+    Taproot(TaprootError),
+    // an expression or key used where it isn't allowed, like sh() inside
+    // wsh() or an uncompressed key in a segwit script
+    InvalidContext(String),
+    // multi() needs 1 <= k <= n <= 20
+    InvalidThreshold { k: usize, n: usize },
+    // a redeem script over 520 bytes can't be spent
+    ScriptTooLarge(usize),
+    // ranged descriptors are expanded at indices below 2^31
+    InvalidIndex(u32),
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorError::InvalidCharacter(c) => write!(f, "invalid character {:?}", c),
+            DescriptorError::InvalidChecksum { expected, found } => {
+                write!(f, "invalid checksum {}, expected {}", found, expected)
+            }
+            DescriptorError::InvalidSyntax(s) => write!(f, "invalid expression {:?}", s),
+            DescriptorError::UnknownFunction(name) => write!(f, "unknown function {}()", name),
+            DescriptorError::InvalidArgumentCount { function, count } => {
+                write!(f, "{}() can't take {} arguments", function, count)
+            }
+            DescriptorError::InvalidKey(s) => write!(f, "invalid key {:?}", s),
+            DescriptorError::Bip32(err) => write!(f, "bip32: {}", err),
+            DescriptorError::Address(err) => write!(f, "address: {}", err),
+            DescriptorError::Taproot(err) => write!(f, "taproot: {}", err),
+            DescriptorError::InvalidContext(s) => write!(f, "{} not allowed here", s),
+            DescriptorError::InvalidThreshold { k, n } => write!(f, "invalid threshold {} of {}", k, n),
+            DescriptorError::ScriptTooLarge(len) => write!(f, "redeem script of {} bytes is too large", len),
+            DescriptorError::InvalidIndex(index) => write!(f, "invalid index {}", index),
+        }
+    }
+}
+
+impl std::error::Error for DescriptorError {}
+
+// BIP380: each character becomes its position mod 32, and every three also
+// add a symbol for the groups they were in, so a typo that swaps a character
+// for one with the same low bits is still caught
+fn polymod(symbols: &[u64]) -> u64 {
+    let mut chk = 1u64;
+    for value in symbols {
+        let top = chk >> 35;
+        chk = ((chk & 0x7ffffffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+// the 8 character checksum of a descriptor without its #
+pub fn checksum(descriptor: &str) -> Result<String, DescriptorError> {
+    let mut symbols = vec![];
+    let mut groups = vec![];
+    for c in descriptor.chars() {
+        let position = INPUT_CHARSET.find(c).ok_or(DescriptorError::InvalidCharacter(c))? as u64;
+        symbols.push(position & 31);
+        groups.push(position >> 5);
+        if groups.len() == 3 {
+            symbols.push(groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups[..] {
+        [a] => symbols.push(a),
+        [a, b] => symbols.push(a * 3 + b),
+        _ => {}
+    }
+    symbols.extend([0; 8]);
+    let chk = polymod(&symbols) ^ 1;
+    Ok((0..8).map(|i| CHECKSUM_CHARSET[((chk >> (5 * (7 - i))) & 31) as usize] as char).collect())
+}
+
+// The script a key or expression ends up in, which decides what is allowed:
+// segwit scripts need compressed keys and tapscript uses x-only ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Top,
+    Sh,
+    Wsh,
+    Tap,
+}
+
+// the last step of a ranged key, replaced by the index being expanded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wildcard {
+    None,
+    Unhardened,
+    Hardened,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum KeySource {
+    // hex SEC, and whether it was the 33 byte compressed form
+    Sec(S256Point, bool),
+    // 64 hex characters, only in tr()
+    XOnly(XOnlyPublicKey),
+    // WIF, kept with its network and compression flag to print it back
+    Wif(PrivateKey, Network, bool),
+    Xpub(ExtendedPubKey, DerivationPath, Wildcard),
+    Xprv(ExtendedPrivKey, DerivationPath, Wildcard),
+}
+
+// A KEY expression: an optional [fingerprint/path] origin saying where the
+// key comes from, then the key itself, and for extended keys a path to
+// derive with an optional /* or /*' at the end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorKey {
+    origin: Option<([u8; 4], DerivationPath)>,
+    source: KeySource,
+}
+
+impl DescriptorKey {
+    pub fn parse(s: &str) -> Result<Self, DescriptorError> {
+        let invalid = || DescriptorError::InvalidKey(s.to_string());
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest.split_once(']').ok_or_else(invalid)?;
+                let mut steps = origin.split('/');
+                let fingerprint = steps.next().unwrap();
+                if fingerprint.len() != 8 {
+                    return Err(invalid());
+                }
+                let fingerprint: [u8; 4] = from_hex(fingerprint).ok_or_else(invalid)?.try_into().unwrap();
+                let path = steps.map(parse_child).collect::<Result<Vec<u32>, _>>().map_err(DescriptorError::Bip32)?;
+                (Some((fingerprint, DerivationPath::new(path))), key)
+            }
+            None => (None, s),
+        };
+
+        let mut steps = key.split('/');
+        let key = steps.next().unwrap();
+        let mut steps: Vec<&str> = steps.collect();
+        let wildcard = match steps.last() {
+            Some(&"*") => Wildcard::Unhardened,
+            Some(&"*'") | Some(&"*h") | Some(&"*H") => Wildcard::Hardened,
+            _ => Wildcard::None,
+        };
+        if wildcard != Wildcard::None {
+            steps.pop();
+        }
+        let path = steps.into_iter().map(parse_child).collect::<Result<Vec<u32>, _>>();
+        let path = DerivationPath::new(path.map_err(DescriptorError::Bip32)?);
+        let extended = wildcard != Wildcard::None || !path.get_children().is_empty();
+
+        let is_hex = key.bytes().all(|b| b.is_ascii_hexdigit());
+        let source = match key.len() {
+            66 | 130 if is_hex && !extended => {
+                let sec = from_hex(key).ok_or_else(invalid)?;
+                let point = S256Point::parse_sec(&sec).map_err(|_| invalid())?;
+                KeySource::Sec(point, sec.len() == 33)
+            }
+            64 if is_hex && !extended => {
+                KeySource::XOnly(XOnlyPublicKey::from_bytes(&from_hex(key).ok_or_else(invalid)?).map_err(|_| invalid())?)
+            }
+            _ => match ExtendedPubKey::parse(key) {
+                Ok(xpub) => {
+                    // a public key can only derive unhardened children
+                    let hardened = path.get_children().into_iter().find(|&index| index >= HARDENED);
+                    if let Some(index) = hardened {
+                        return Err(DescriptorError::Bip32(Bip32Error::HardenedFromPublic(index)));
+                    }
+                    if wildcard == Wildcard::Hardened {
+                        return Err(DescriptorError::Bip32(Bip32Error::HardenedFromPublic(HARDENED)));
+                    }
+                    KeySource::Xpub(xpub, path, wildcard)
+                }
+                Err(Bip32Error::WrongKeyType) => {
+                    KeySource::Xprv(ExtendedPrivKey::parse(key).map_err(DescriptorError::Bip32)?, path, wildcard)
+                }
+                // not Base58 of 78 bytes, so a WIF key or nothing at all
+                Err(Bip32Error::Base58(_) | Bip32Error::InvalidLength(_)) => match PrivateKey::from_wif(key) {
+                    Ok((private_key, network, compressed)) if !extended => {
+                        KeySource::Wif(private_key, network, compressed)
+                    }
+                    _ => return Err(invalid()),
+                },
+                Err(err) => return Err(DescriptorError::Bip32(err)),
+            },
+        };
+        Ok(Self { origin, source })
+    }
+
+    pub fn get_origin(&self) -> Option<([u8; 4], DerivationPath)> {
+        self.origin.clone()
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        match &self.source {
+            KeySource::Xpub(_, _, wildcard) | KeySource::Xprv(_, _, wildcard) => *wildcard != Wildcard::None,
+            _ => false,
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        !matches!(self.source, KeySource::Sec(_, false) | KeySource::Wif(_, _, false))
+    }
+
+    fn is_x_only(&self) -> bool {
+        matches!(self.source, KeySource::XOnly(_))
+    }
+
+    // the public key, with index in place of the wildcard if there is one
+    pub fn derive(&self, index: u32) -> Result<S256Point, DescriptorError> {
+        let child = |path: &DerivationPath, wildcard: &Wildcard| match wildcard {
+            Wildcard::None => path.clone(),
+            Wildcard::Unhardened => path.child(index),
+            Wildcard::Hardened => path.child(index | HARDENED),
+        };
+        match &self.source {
+            KeySource::Sec(point, _) => Ok(point.clone()),
+            KeySource::XOnly(key) => Ok(key.get_point()),
+            KeySource::Wif(key, _, _) => Ok(key.get_point()),
+            KeySource::Xpub(xpub, path, wildcard) => xpub
+                .derive_path(&child(path, wildcard))
+                .map(|key| key.get_point())
+                .map_err(DescriptorError::Bip32),
+            KeySource::Xprv(xprv, path, wildcard) => xprv
+                .derive_path(&child(path, wildcard))
+                .map(|key| key.get_private_key().get_point())
+                .map_err(DescriptorError::Bip32),
+        }
+    }
+
+    // the key as it goes in a script: 33 or 65 byte SEC, or x-only in
+    // tapscript
+    fn script_bytes(&self, index: u32, context: Context) -> Result<Vec<u8>, DescriptorError> {
+        let point = self.derive(index)?;
+        Ok(match context {
+            Context::Tap => XOnlyPublicKey::from_point(&point).to_bytes().to_vec(),
+            _ => point.sec(self.is_compressed()),
+        })
+    }
+}
+
+// a path without the m, as it follows a fingerprint or key
+fn format_steps(path: &DerivationPath) -> String {
+    path.to_string()[1..].to_string()
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((fingerprint, path)) = &self.origin {
+            write!(f, "[{}{}]", to_hex(fingerprint), format_steps(path))?;
+        }
+        let (path, wildcard) = match &self.source {
+            KeySource::Sec(point, compressed) => return write!(f, "{}", to_hex(&point.sec(*compressed))),
+            KeySource::XOnly(key) => return write!(f, "{}", to_hex(&key.to_bytes())),
+            KeySource::Wif(key, network, compressed) => return write!(f, "{}", key.to_wif(*network, *compressed)),
+            KeySource::Xpub(xpub, path, wildcard) => {
+                write!(f, "{}", xpub)?;
+                (path, wildcard)
+            }
+            KeySource::Xprv(xprv, path, wildcard) => {
+                write!(f, "{}", xprv)?;
+                (path, wildcard)
+            }
+        };
+        write!(f, "{}", format_steps(path))?;
+        match wildcard {
+            Wildcard::None => Ok(()),
+            Wildcard::Unhardened => write!(f, "/*"),
+            Wildcard::Hardened => write!(f, "/*'"),
+        }
+    }
+}
+
+// The script tree of a tr() descriptor: {left,right} pairs down to leaves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeDescriptor {
+    Leaf(Box<Descriptor>),
+    Branch(Box<TreeDescriptor>, Box<TreeDescriptor>),
+}
+
+impl TreeDescriptor {
+    fn parse(s: &str, depth: usize) -> Result<Self, DescriptorError> {
+        if depth > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(DescriptorError::Taproot(TaprootError::TreeTooDeep(depth)));
+        }
+        match s.strip_prefix('{').and_then(|inner| inner.strip_suffix('}')) {
+            Some(inner) => match split_args(inner)?[..] {
+                [left, right] => Ok(TreeDescriptor::Branch(
+                    Box::new(Self::parse(left, depth + 1)?),
+                    Box::new(Self::parse(right, depth + 1)?),
+                )),
+                _ => Err(DescriptorError::InvalidSyntax(s.to_string())),
+            },
+            None => Ok(TreeDescriptor::Leaf(Box::new(Descriptor::parse_expr(s, Context::Tap)?))),
+        }
+    }
+
+    fn is_ranged(&self) -> bool {
+        match self {
+            TreeDescriptor::Leaf(leaf) => leaf.is_ranged(),
+            TreeDescriptor::Branch(left, right) => left.is_ranged() || right.is_ranged(),
+        }
+    }
+
+    fn to_tap_tree(&self, index: u32) -> Result<TapTree, DescriptorError> {
+        match self {
+            TreeDescriptor::Leaf(leaf) => Ok(TapTree::leaf(leaf.script(index, Context::Tap)?, TAPSCRIPT_LEAF_VERSION)),
+            TreeDescriptor::Branch(left, right) => {
+                Ok(TapTree::branch(left.to_tap_tree(index)?, right.to_tap_tree(index)?))
+            }
+        }
+    }
+}
+
+impl fmt::Display for TreeDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeDescriptor::Leaf(leaf) => write!(f, "{}", leaf.body()),
+            TreeDescriptor::Branch(left, right) => write!(f, "{{{},{}}}", left, right),
+        }
+    }
+}
+
+// An output script descriptor (BIP380-386). Parsing checks where each
+// expression may appear, so every descriptor built here can be expanded.
+// Printing gives the canonical form, with ' for hardened steps, lowercase
+// hex and the checksum appended. combo() isn't supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor {
+    Pk(DescriptorKey),
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    Sh(Box<Descriptor>),
+    Wsh(Box<Descriptor>),
+    // multi() or, with sorted, sortedmulti() whose keys go in the script in
+    // lexicographic order of their SEC bytes
+    Multi { threshold: usize, keys: Vec<DescriptorKey>, sorted: bool },
+    Tr(DescriptorKey, Option<TreeDescriptor>),
+    Addr(Address),
+    Raw(Vec<u8>),
+}
+
+// splits at the commas that aren't inside brackets
+fn split_args(s: &str) -> Result<Vec<&str>, DescriptorError> {
+    let mut args = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth = depth.checked_sub(1).ok_or_else(|| DescriptorError::InvalidSyntax(s.to_string()))?
+            }
+            ',' if depth == 0 => {
+                args.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(DescriptorError::InvalidSyntax(s.to_string()));
+    }
+    args.push(&s[start..]);
+    Ok(args)
+}
+
+// name(arg,...) into the name and its arguments
+fn split_call(s: &str) -> Result<(&str, Vec<&str>), DescriptorError> {
+    let invalid = || DescriptorError::InvalidSyntax(s.to_string());
+    let (name, rest) = s.split_once('(').ok_or_else(invalid)?;
+    let inner = rest.strip_suffix(')').ok_or_else(invalid)?;
+    Ok((name, split_args(inner)?))
+}
+
+// a key checked against what the script it goes in allows
+fn parse_key(s: &str, context: Context) -> Result<DescriptorKey, DescriptorError> {
+    let key = DescriptorKey::parse(s)?;
+    let segwit = context == Context::Wsh || context == Context::Tap;
+    if (segwit && !key.is_compressed()) || (context != Context::Tap && key.is_x_only()) {
+        return Err(DescriptorError::InvalidContext(s.to_string()));
+    }
+    Ok(key)
+}
+
+// pushes a number up to 20 the way script does, OP_1 to OP_16 or one byte
+fn push_number(script: &mut Vec<u8>, n: usize) {
+    match n {
+        1..=16 => script.push(0x50 + n as u8),
+        _ => script.extend_from_slice(&[0x01, n as u8]),
+    }
+}
+
+impl Descriptor {
+    // with or without a checksum, which must match if it's there
+    pub fn parse(s: &str) -> Result<Self, DescriptorError> {
+        let body = match s.split_once('#') {
+            Some((body, found)) => {
+                let expected = checksum(body)?;
+                if found != expected {
+                    return Err(DescriptorError::InvalidChecksum { expected, found: found.to_string() });
+                }
+                body
+            }
+            None => {
+                checksum(s)?;
+                s
+            }
+        };
+        Self::parse_expr(body, Context::Top)
+    }
+
+    fn parse_expr(s: &str, context: Context) -> Result<Self, DescriptorError> {
+        let (name, args) = split_call(s)?;
+        let arg_count = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(DescriptorError::InvalidArgumentCount { function: name.to_string(), count: args.len() })
+            }
+        };
+        let descriptor = match (name, context) {
+            ("pk", _) => {
+                arg_count(1)?;
+                Descriptor::Pk(parse_key(args[0], context)?)
+            }
+            ("pkh", Context::Top | Context::Sh | Context::Wsh) => {
+                arg_count(1)?;
+                Descriptor::Pkh(parse_key(args[0], context)?)
+            }
+            ("wpkh", Context::Top | Context::Sh) => {
+                arg_count(1)?;
+                // the key goes in a witness, with the same rules as in wsh()
+                Descriptor::Wpkh(parse_key(args[0], Context::Wsh)?)
+            }
+            ("sh", Context::Top) => {
+                arg_count(1)?;
+                let inner = Self::parse_expr(args[0], Context::Sh)?;
+                let len = inner.script(0, Context::Sh)?.len();
+                if len > MAX_REDEEM_SCRIPT_SIZE {
+                    return Err(DescriptorError::ScriptTooLarge(len));
+                }
+                Descriptor::Sh(Box::new(inner))
+            }
+            ("wsh", Context::Top | Context::Sh) => {
+                arg_count(1)?;
+                Descriptor::Wsh(Box::new(Self::parse_expr(args[0], Context::Wsh)?))
+            }
+            ("multi" | "sortedmulti", Context::Top | Context::Sh | Context::Wsh) => {
+                let (k, keys) = args.split_first().ok_or_else(|| DescriptorError::InvalidSyntax(s.to_string()))?;
+                if !k.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(DescriptorError::InvalidSyntax(k.to_string()));
+                }
+                let threshold = k.parse().map_err(|_| DescriptorError::InvalidSyntax(k.to_string()))?;
+                if threshold == 0 || threshold > keys.len() || keys.len() > MAX_MULTISIG_KEYS {
+                    return Err(DescriptorError::InvalidThreshold { k: threshold, n: keys.len() });
+                }
+                let keys = keys.iter().map(|key| parse_key(key, context)).collect::<Result<_, _>>()?;
+                Descriptor::Multi { threshold, keys, sorted: name == "sortedmulti" }
+            }
+            ("tr", Context::Top) => {
+                if args.len() != 1 && args.len() != 2 {
+                    return Err(DescriptorError::InvalidArgumentCount { function: name.to_string(), count: args.len() });
+                }
+                let internal_key = parse_key(args[0], Context::Tap)?;
+                let tree = args.get(1).map(|tree| TreeDescriptor::parse(tree, 0)).transpose()?;
+                Descriptor::Tr(internal_key, tree)
+            }
+            ("addr", Context::Top) => {
+                arg_count(1)?;
+                Descriptor::Addr(Address::parse(args[0]).map_err(DescriptorError::Address)?)
+            }
+            ("raw", Context::Top) => {
+                arg_count(1)?;
+                Descriptor::Raw(from_hex(args[0]).ok_or_else(|| DescriptorError::InvalidSyntax(args[0].to_string()))?)
+            }
+            ("pkh" | "wpkh" | "sh" | "wsh" | "multi" | "sortedmulti" | "tr" | "addr" | "raw", _) => {
+                return Err(DescriptorError::InvalidContext(format!("{}()", name)))
+            }
+            _ => return Err(DescriptorError::UnknownFunction(name.to_string())),
+        };
+        Ok(descriptor)
+    }
+
+    // whether any key ends in a wildcard, so each index gives another script
+    pub fn is_ranged(&self) -> bool {
+        match self {
+            Descriptor::Pk(key) | Descriptor::Pkh(key) | Descriptor::Wpkh(key) => key.is_ranged(),
+            Descriptor::Sh(inner) | Descriptor::Wsh(inner) => inner.is_ranged(),
+            Descriptor::Multi { keys, .. } => keys.iter().any(|key| key.is_ranged()),
+            Descriptor::Tr(key, tree) => key.is_ranged() || tree.as_ref().is_some_and(|tree| tree.is_ranged()),
+            Descriptor::Addr(_) | Descriptor::Raw(_) => false,
+        }
+    }
+
+    // the script this expression stands for, which is the scriptPubKey at the
+    // top and the redeem, witness or leaf script below sh(), wsh() and tr()
+    fn script(&self, index: u32, context: Context) -> Result<Vec<u8>, DescriptorError> {
+        let script = match self {
+            Descriptor::Pk(key) => {
+                let key = key.script_bytes(index, context)?;
+                let mut script = vec![key.len() as u8];
+                script.extend(key);
+                script.push(0xac);
+                script
+            }
+            Descriptor::Pkh(key) => p2pkh_script(&hash160(&key.script_bytes(index, context)?)),
+            Descriptor::Wpkh(key) => witness_script(0, &hash160(&key.script_bytes(index, context)?)),
+            Descriptor::Sh(inner) => p2sh_script(&hash160(&inner.script(index, Context::Sh)?)),
+            Descriptor::Wsh(inner) => witness_script(0, &sha256(&inner.script(index, Context::Wsh)?)),
+            Descriptor::Multi { threshold, keys, sorted } => {
+                let mut keys = keys
+                    .iter()
+                    .map(|key| key.script_bytes(index, context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if *sorted {
+                    keys.sort();
+                }
+                // OP_k <key>... OP_n OP_CHECKMULTISIG
+                let mut script = vec![];
+                push_number(&mut script, *threshold);
+                for key in &keys {
+                    script.push(key.len() as u8);
+                    script.extend_from_slice(key);
+                }
+                push_number(&mut script, keys.len());
+                script.push(0xae);
+                script
+            }
+            Descriptor::Tr(key, tree) => {
+                let internal_key = XOnlyPublicKey::from_point(&key.derive(index)?);
+                let merkle_root = tree.as_ref().map(|tree| tree.to_tap_tree(index)).transpose()?.map(|tree| tree.node_hash());
+                let (output_key, _) = tap_tweak(&internal_key, merkle_root.as_ref());
+                witness_script(1, &XOnlyPublicKey::from_point(&output_key).to_bytes())
+            }
+            Descriptor::Addr(address) => address.get_script_pubkey(),
+            Descriptor::Raw(script) => script.clone(),
+        };
+        Ok(script)
+    }
+
+    // the output script at index, which is ignored unless the descriptor is
+    // ranged
+    pub fn script_pubkey(&self, index: u32) -> Result<Vec<u8>, DescriptorError> {
+        if index >= HARDENED {
+            return Err(DescriptorError::InvalidIndex(index));
+        }
+        self.script(index, Context::Top)
+    }
+
+    // fails for bare pk() and multi() and for raw() scripts with no address
+    pub fn address(&self, index: u32, network: Network) -> Result<Address, DescriptorError> {
+        Address::from_script_pubkey(&self.script_pubkey(index)?, network).map_err(DescriptorError::Address)
+    }
+
+    // the descriptor without its checksum
+    fn body(&self) -> String {
+        let key_list = |keys: &[DescriptorKey]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(",");
+        match self {
+            Descriptor::Pk(key) => format!("pk({})", key),
+            Descriptor::Pkh(key) => format!("pkh({})", key),
+            Descriptor::Wpkh(key) => format!("wpkh({})", key),
+            Descriptor::Sh(inner) => format!("sh({})", inner.body()),
+            Descriptor::Wsh(inner) => format!("wsh({})", inner.body()),
+            Descriptor::Multi { threshold, keys, sorted } => {
+                let name = if *sorted { "sortedmulti" } else { "multi" };
+                format!("{}({},{})", name, threshold, key_list(keys))
+            }
+            Descriptor::Tr(key, None) => format!("tr({})", key),
+            Descriptor::Tr(key, Some(tree)) => format!("tr({},{})", key, tree),
+            Descriptor::Addr(address) => format!("addr({})", address),
+            Descriptor::Raw(script) => format!("raw({})", to_hex(script)),
+        }
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let body = self.body();
+        write!(f, "{}#{}", body, checksum(&body).unwrap())
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const A: &str = "022f01e5e15cca351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a01";
+    const B: &str = "03acd484e2f0c7f65309ad178a9f559abde09796974c57e714c35f110dfc27ccbe";
+    const C: &str = "03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556";
+    const XPUB: &str = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+    const XPRV: &str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+
+    #[test]
+    fn checksum_works() {
+        assert_eq!(checksum("raw(deadbeef)"), Ok("89f8spxm".to_string()));
+        assert!(Descriptor::parse("raw(deadbeef)#89f8spxm").is_ok());
+        assert_eq!(
+            Descriptor::parse("raw(deadbeef)#89f8spxn"),
+            Err(DescriptorError::InvalidChecksum { expected: "89f8spxm".to_string(), found: "89f8spxn".to_string() })
+        );
+        assert_eq!(
+            Descriptor::parse("raw(deadbeef)#"),
+            Err(DescriptorError::InvalidChecksum { expected: "89f8spxm".to_string(), found: "".to_string() })
+        );
+        assert_eq!(Descriptor::parse("raw(deadbeef)é"), Err(DescriptorError::InvalidCharacter('é')));
+    }
+
+    #[test]
+    fn script_pubkey_works() {
+        let g = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let cases = [
+            (format!("pk({})", g), format!("21{}ac", g)),
+            (
+                "pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)".to_string(),
+                "76a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac".to_string(),
+            ),
+            (format!("sh(wpkh({}))", C), "a914cc6ffbc0bf31af759451068f90ba7a0272b6b33287".to_string()),
+            (format!("sh(multi(2,{},{}))", A, B), "a914a6a8b030a38762f4c1f5cbe387b61a3c5da5cd2687".to_string()),
+            (
+                format!("wsh(sortedmulti(1,{},{}))", B, A),
+                "00201d908c517d7b8dd9849a9e802d04a995b2037812a94a9a38de74bd808bd2e50b".to_string(),
+            ),
+            (format!("sh(wsh(pkh({})))", C), "a9146cd4169f930dfd6489ffc398db8bf544e8c680f087".to_string()),
+            // from BIP383
+            (
+                "sh(wsh(multi(1,03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8,\
+                 03499fdf9e895e719cfd64e67f07d38e3226aa7b63678949e6e49b241a60e823e4,\
+                 02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e)))"
+                    .to_string(),
+                "a914aec509e284f909f769bb7dda299a717c87cc97ac87".to_string(),
+            ),
+            (
+                "tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)".to_string(),
+                "512077aab6e066f8a7419c5ab714c12c67d25007ed55a43cadcacb4d7a970a093f11".to_string(),
+            ),
+            (
+                format!("tr({},{{pk({}),pk({})}})", &g[2..], A, B),
+                "5120425f8eef9f447316015b8e8b2667e14ed2a9d3107db905a8412b5799c658ce36".to_string(),
+            ),
+            (
+                format!("tr({},{{pk({}),{{pk({}),pk({})}}}})", g, A, B, C),
+                "5120612410559eb8a376696177d019250db11edb177f4c1758bedf8d88568465b4b1".to_string(),
+            ),
+            ("raw(deadbeef)".to_string(), "deadbeef".to_string()),
+        ];
+        for (s, expected) in cases {
+            let descriptor = Descriptor::parse(&s).unwrap();
+            assert!(!descriptor.is_ranged());
+            assert_eq!(to_hex(&descriptor.script_pubkey(0).unwrap()), expected);
+            assert_eq!(descriptor.to_string(), format!("{}#{}", s, checksum(&s).unwrap()));
+            assert_eq!(Descriptor::parse(&descriptor.to_string()), Ok(descriptor));
+        }
+    }
+
+    #[test]
+    fn ranged_descriptors_work() {
+        let descriptor = Descriptor::parse(&format!("wpkh([d34db33f/84h/0h/0h]{}/0/*)", XPUB)).unwrap();
+        assert!(descriptor.is_ranged());
+        let addresses = [
+            (0, "bc1qwlvfdv8ctae2ureaqjrugv4j8s5tw9yn78cggy"),
+            (1, "bc1qdhrn4uwfdlmga8daant52wadtxlseqayxnjrpj"),
+            (7, "bc1q6rv06kau7zwg272hukecmkhe2h7mesy94d8r75"),
+        ];
+        for (index, expected) in addresses {
+            assert_eq!(descriptor.address(index, Network::Mainnet).unwrap().to_string(), expected);
+        }
+
+        // hardened derivation all the way down needs the xprv
+        let descriptor = Descriptor::parse(&format!("pkh({}/0'/*')", XPRV)).unwrap();
+        assert_eq!(descriptor.address(0, Network::Mainnet).unwrap().to_string(), "1NnzqqJHuFuh7rJiZ7WK1SeKkgTisMyDZX");
+        assert_eq!(descriptor.address(3, Network::Mainnet).unwrap().to_string(), "1EgvwXScw5uqcnYecw9BSVw4cEG8MW5yAq");
+
+        let descriptor = Descriptor::parse(&format!("tr({}/0/*)", XPUB)).unwrap();
+        assert_eq!(
+            descriptor.address(2, Network::Mainnet).unwrap().to_string(),
+            "bc1p9swkaz3f6gqf529sgn4h93ttmnvzp4ef8waex94c39p89k2h0z8sg8y2fy"
+        );
+        assert_eq!(descriptor.script_pubkey(HARDENED), Err(DescriptorError::InvalidIndex(HARDENED)));
+    }
+
+    #[test]
+    fn to_string_is_canonical() {
+        let g = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+        let cases = [
+            (format!("pkh({})", g), format!("pkh({})#zvxck6mv", g)),
+            (
+                format!("wpkh([d34db33f/84h/0H/0']{}/0/*)", XPUB),
+                format!("wpkh([d34db33f/84'/0'/0']{}/0/*)#jwwsj09z", XPUB),
+            ),
+            (format!("pkh({}/0h/*h)", XPRV), format!("pkh({}/0'/*')#qckv79kt", XPRV)),
+            (format!("sh(wsh(pkh({})))", C), format!("sh(wsh(pkh({})))#zx2k5nk2", C)),
+            (
+                format!("tr(79BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798,{{pk({}),pk({})}})", A, B),
+                format!(
+                    "tr(79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798,{{pk({}),pk({})}})#q4x0fz8d",
+                    A, B
+                ),
+            ),
+            (
+                "addr(BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4)".to_string(),
+                "addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)#uyjndxcw".to_string(),
+            ),
+        ];
+        for (s, expected) in cases {
+            let descriptor = Descriptor::parse(&s).unwrap();
+            assert_eq!(descriptor.to_string(), expected);
+            assert_eq!(Descriptor::parse(&expected), Ok(descriptor));
+        }
+
+        // a WIF key expands to the same script as its public key
+        let wif = "pkh(KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn)#yj0ctua6";
+        let descriptor = Descriptor::parse(wif).unwrap();
+        assert_eq!(descriptor.to_string(), wif);
+        let public = Descriptor::parse("pkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)");
+        assert_eq!(descriptor.script_pubkey(0), public.unwrap().script_pubkey(0));
+
+        let key = DescriptorKey::parse(&format!("[d34db33f/84'/0'/0']{}/0/*", XPUB)).unwrap();
+        let origin = DerivationPath::new(vec![84 | HARDENED, HARDENED, HARDENED]);
+        assert_eq!(key.get_origin(), Some(([0xd3, 0x4d, 0xb3, 0x3f], origin)));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_descriptors() {
+        let uncompressed = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+        let x_only = "a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+        let cases = [
+            (format!("sh(sh(pk({})))", A), DescriptorError::InvalidContext("sh()".to_string())),
+            (format!("wsh(wpkh({}))", A), DescriptorError::InvalidContext("wpkh()".to_string())),
+            (format!("tr({},pkh({}))", x_only, A), DescriptorError::InvalidContext("pkh()".to_string())),
+            (format!("wpkh({})", uncompressed), DescriptorError::InvalidContext(uncompressed.to_string())),
+            (format!("wsh(pk({}))", uncompressed), DescriptorError::InvalidContext(uncompressed.to_string())),
+            (format!("pk({})", x_only), DescriptorError::InvalidContext(x_only.to_string())),
+            (format!("multi(3,{},{})", A, B), DescriptorError::InvalidThreshold { k: 3, n: 2 }),
+            (format!("multi(0,{})", A), DescriptorError::InvalidThreshold { k: 0, n: 1 }),
+            (
+                format!("wpkh({},{})", A, B),
+                DescriptorError::InvalidArgumentCount { function: "wpkh".to_string(), count: 2 },
+            ),
+            (format!("combo({})", A), DescriptorError::UnknownFunction("combo".to_string())),
+            (format!("pk({}", A), DescriptorError::InvalidSyntax(format!("pk({}", A))),
+            ("pk(02aa)".to_string(), DescriptorError::InvalidKey("02aa".to_string())),
+            (format!("pk([d34db33f]{}/0)", A), DescriptorError::InvalidKey(format!("[d34db33f]{}/0", A))),
+            (
+                format!("wpkh({}/1h/*)", XPUB),
+                DescriptorError::Bip32(Bip32Error::HardenedFromPublic(HARDENED + 1)),
+            ),
+            (format!("wpkh({}/*')", XPUB), DescriptorError::Bip32(Bip32Error::HardenedFromPublic(HARDENED))),
+            (format!("wpkh({}/x)", XPUB), DescriptorError::Bip32(Bip32Error::InvalidPath("x".to_string()))),
+        ];
+        for (s, err) in cases {
+            assert_eq!(Descriptor::parse(&s), Err(err));
+        }
+
+        // 15 compressed keys make a 513 byte redeem script, while 16 make 547,
+        // over the 520 byte limit
+        let keys = |n: usize| vec![A; n].join(",");
+        assert!(Descriptor::parse(&format!("sh(multi(1,{}))", keys(15))).is_ok());
+        assert_eq!(
+            Descriptor::parse(&format!("sh(multi(1,{}))", keys(16))),
+            Err(DescriptorError::ScriptTooLarge(547))
+        );
+        assert!(Descriptor::parse(&format!("wsh(multi(1,{}))", keys(20))).is_ok());
+        assert_eq!(
+            Descriptor::parse(&format!("wsh(multi(1,{}))", keys(21))),
+            Err(DescriptorError::InvalidThreshold { k: 1, n: 21 })
+        );
+
+        // bare multisig has no address
+        let bare = Descriptor::parse(&format!("multi(1,{},{})", A, B)).unwrap();
+        assert_eq!(bare.address(0, Network::Mainnet), Err(DescriptorError::Address(AddressError::UnsupportedScript)));
+    }
+}
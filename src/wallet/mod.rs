@@ -0,0 +1,6 @@
+pub mod address;
+pub mod bip32;
+pub mod bip39;
+pub mod descriptor;
+pub mod network;
+pub mod wif;
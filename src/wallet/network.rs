@@ -0,0 +1,56 @@
+#![allow(unused)]
+
+use std::fmt;
+
+// The networks an address or key can be for. Testnet, signet and regtest
+// share their Base58 version bytes, and testnet and signet their Bech32
+// prefix, so those can't always be told apart from an encoding alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    pub fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            _ => 0x6f,
+        }
+    }
+
+    pub fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            _ => 0xc4,
+        }
+    }
+
+    pub fn wif_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x80,
+            _ => 0xef,
+        }
+    }
+
+    pub fn hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet => write!(f, "testnet"),
+            Network::Signet => write!(f, "signet"),
+            Network::Regtest => write!(f, "regtest"),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::encoding::base58::{self, Base58Error};
+use crate::finite_field::{private_key::PrivateKey, scalar, secp_ec::S256Point};
+
+use super::network::Network;
+
+// Reasons a string isn't a valid WIF key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WifError {
+    Base58(Base58Error),
+    // a version byte and 32 byte secret, plus 0x01 for compressed keys
+    InvalidLength(usize),
+    // the version byte isn't 0x80 (mainnet) or 0xef (the test networks)
+    UnknownVersionByte(u8),
+    // the byte after a compressed key's secret must be 0x01
+    InvalidCompressionFlag(u8),
+    // the secret is zero or not below n
+    SecretOutOfRange,
+}
+
+impl fmt::Display for WifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifError::Base58(err) => write!(f, "base58: {}", err),
+            WifError::InvalidLength(len) => write!(f, "invalid WIF payload length {}, expected 33 or 34", len),
+            WifError::UnknownVersionByte(byte) => {
+                write!(f, "unknown version byte {:#04x}, expected 0x80 or 0xef", byte)
+            }
+            WifError::InvalidCompressionFlag(byte) => {
+                write!(f, "invalid compression flag {:#04x}, expected 0x01", byte)
+            }
+            WifError::SecretOutOfRange => write!(f, "secret not in range 1 to n - 1"),
+        }
+    }
+}
+
+impl std::error::Error for WifError {}
+
+impl PrivateKey {
+    // Wallet Import Format: Base58Check of the version byte, the secret and,
+    // if the key's addresses use the compressed public key, 0x01
+    pub fn to_wif(&self, network: Network, compressed: bool) -> String {
+        let mut payload = vec![network.wif_prefix()];
+        payload.extend_from_slice(&scalar::to_bytes(&self.get_secret()));
+        if compressed {
+            payload.push(0x01);
+        }
+        base58::encode_check(&payload)
+    }
+
+    // The key along with its network and whether it is compressed. The test
+    // networks share a version byte, so they all come back as Testnet.
+    pub fn from_wif(s: &str) -> Result<(PrivateKey, Network, bool), WifError> {
+        let payload = base58::decode_check(s).map_err(WifError::Base58)?;
+        let compressed = match payload.len() {
+            33 => false,
+            34 if payload[33] == 0x01 => true,
+            34 => return Err(WifError::InvalidCompressionFlag(payload[33])),
+            len => return Err(WifError::InvalidLength(len)),
+        };
+        let network = match payload[0] {
+            0x80 => Network::Mainnet,
+            0xef => Network::Testnet,
+            byte => return Err(WifError::UnknownVersionByte(byte)),
+        };
+        let secret = BigUint::from_bytes_be(&payload[1..33]);
+        if secret.is_zero() || secret >= S256Point::order() {
+            return Err(WifError::SecretOutOfRange);
+        }
+        Ok((PrivateKey::new(secret), network, compressed))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use num::Num;
+
+    use super::*;
+
+    #[test]
+    fn wif_works() {
+        let one = PrivateKey::new(BigUint::from(1u8));
+        let secret = BigUint::from_str_radix("0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d", 16);
+        let key = PrivateKey::new(secret.unwrap());
+        let cases = [
+            (&one, Network::Mainnet, true, "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn"),
+            (&one, Network::Mainnet, false, "5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf"),
+            (&one, Network::Testnet, true, "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA"),
+            (&key, Network::Mainnet, false, "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ"),
+            (&key, Network::Mainnet, true, "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617"),
+        ];
+        for (key, network, compressed, wif) in cases {
+            assert_eq!(key.to_wif(network, compressed), wif);
+            assert_eq!(PrivateKey::from_wif(wif), Ok((key.clone(), network, compressed)));
+        }
+
+        // signet and regtest keys read back as testnet ones
+        let regtest = one.to_wif(Network::Regtest, true);
+        assert_eq!(PrivateKey::from_wif(&regtest), Ok((one, Network::Testnet, true)));
+    }
+
+    #[test]
+    fn from_wif_rejects_invalid_keys() {
+        // secrets n and 0
+        assert_eq!(
+            PrivateKey::from_wif("L5oLkpV3aqBjhki6LmvChTCV6odsp4SXM6FfU2Gppt5kFqRzExJJ"),
+            Err(WifError::SecretOutOfRange)
+        );
+        assert_eq!(
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73Nd2Mcv1"),
+            Err(WifError::SecretOutOfRange)
+        );
+        assert_eq!(
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sfZr2ym"),
+            Err(WifError::InvalidCompressionFlag(0x02))
+        );
+        assert_eq!(
+            PrivateKey::from_wif("L5oLkpV3aqBjhki6LmvChTCq73v9gyymzzMpBbhDLjDpLCfkwaDM"),
+            Err(WifError::UnknownVersionByte(0x81))
+        );
+        // an address
+        assert_eq!(
+            PrivateKey::from_wif("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"),
+            Err(WifError::InvalidLength(21))
+        );
+        assert!(matches!(
+            PrivateKey::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWo"),
+            Err(WifError::Base58(Base58Error::InvalidChecksum { .. }))
+        ));
+    }
+}
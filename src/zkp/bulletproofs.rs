@@ -0,0 +1,512 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, One, Zero};
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+use super::inner_product::{inner_product, InnerProductProof};
+use super::pedersen::{generator_h, nums_generator, Commitment};
+use super::transcript::Transcript;
+
+// Bulletproofs range proofs (Bünz et al. 2018): a proof that each of m
+// Pedersen commitments vH + rG hides a value in [0, 2^bits). The proof is
+// 2 log2(bits * m) + 4 points and 5 scalars, so aggregating m values costs
+// only 2 log2(m) extra points.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulletproofError {
+    // the bit size must be 8, 16, 32 or 64
+    InvalidBitsize(usize),
+    // the number of values must be a power of two
+    InvalidAggregation(usize),
+    // there must be one blinding factor per value
+    WrongNumBlindings { values: usize, blindings: usize },
+    // the value at this index doesn't fit in the bit size
+    ValueOutOfRange(usize),
+    // the bytes are not a valid proof
+    InvalidProof,
+}
+
+impl fmt::Display for BulletproofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulletproofError::InvalidBitsize(bits) => write!(f, "invalid bit size {}", bits),
+            BulletproofError::InvalidAggregation(m) => write!(f, "can't aggregate {} values", m),
+            BulletproofError::WrongNumBlindings { values, blindings } => {
+                write!(f, "{} values but {} blinding factors", values, blindings)
+            }
+            BulletproofError::ValueOutOfRange(index) => write!(f, "value {} is out of range", index),
+            BulletproofError::InvalidProof => write!(f, "invalid proof"),
+        }
+    }
+}
+
+impl std::error::Error for BulletproofError {}
+
+// a proof's share of the batch equation: its own points, then the scalars
+// for H, G and the vector generators, which all proofs share
+type VerificationTerms = (Vec<(BigUint, S256Point)>, BigUint, BigUint, Vec<BigUint>, Vec<BigUint>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeProof {
+    a: S256Point,
+    s: S256Point,
+    t1: S256Point,
+    t2: S256Point,
+    t_x: BigUint,
+    t_x_blinding: BigUint,
+    e_blinding: BigUint,
+    ipp: InnerProductProof,
+}
+
+// the generators G_i and H_i for the bit vectors, one of each per bit
+fn vector_generators(count: usize) -> (Vec<S256Point>, Vec<S256Point>) {
+    let g = (0..count as u32).map(|i| nums_generator("Bulletproofs/G", i)).collect();
+    let h = (0..count as u32).map(|i| nums_generator("Bulletproofs/H", i)).collect();
+    (g, h)
+}
+
+// 1, x, x^2, ...
+fn powers(x: &BigUint, count: usize) -> Vec<BigUint> {
+    let n = S256Point::order();
+    let mut result = Vec::with_capacity(count);
+    let mut power = BigUint::one();
+    for _ in 0..count {
+        result.push(power.clone());
+        power = power * x % &n;
+    }
+    result
+}
+
+fn check_params(bits: usize, m: usize) -> Result<(), BulletproofError> {
+    if ![8, 16, 32, 64].contains(&bits) {
+        return Err(BulletproofError::InvalidBitsize(bits));
+    }
+    if !m.is_power_of_two() {
+        return Err(BulletproofError::InvalidAggregation(m));
+    }
+    Ok(())
+}
+
+fn start_transcript(bits: usize, commitments: &[Commitment]) -> Transcript {
+    let mut transcript = Transcript::new("Bulletproofs/range");
+    transcript.append_u64("bits", bits as u64);
+    transcript.append_u64("m", commitments.len() as u64);
+    for commitment in commitments {
+        transcript.append_point("V", &commitment.get_point());
+    }
+    transcript
+}
+
+// delta(y, z) = (z - z^2) <1, y^(nm)> - sum_j z^(j+3) <1, 2^n>, the part of
+// t(0) that doesn't depend on the values
+fn delta(bits: usize, m: usize, y: &BigUint, z: &BigUint) -> BigUint {
+    let n = S256Point::order();
+    let sum_y = powers(y, bits * m).iter().fold(BigUint::zero(), |acc, p| (acc + p) % &n);
+    let sum_2 = (BigUint::one() << bits) - 1u8;
+    let z_sq = z * z % &n;
+    let sum_z = powers(z, m).iter().fold(BigUint::zero(), |acc, p| (acc + p) % &n) * &z_sq % &n * z % &n;
+    ((z + scalar::negate(&z_sq)) * sum_y + scalar::negate(&(sum_z * sum_2))) % n
+}
+
+// Proves that every value is below 2^bits, returning the proof and the
+// commitments value H + blinding G it is about. rand must be fresh randomness,
+// it is mixed with the values and blinding factors to get the prover's
+// nonces.
+pub fn prove(
+    values: &[u64],
+    blindings: &[BigUint],
+    bits: usize,
+    rand: &[u8; 32],
+) -> Result<(RangeProof, Vec<Commitment>), BulletproofError> {
+    let order = S256Point::order();
+    let m = values.len();
+    check_params(bits, m)?;
+    if blindings.len() != m {
+        return Err(BulletproofError::WrongNumBlindings { values: m, blindings: blindings.len() });
+    }
+    if let Some(index) = values.iter().position(|v| bits < 64 && *v >> bits != 0) {
+        return Err(BulletproofError::ValueOutOfRange(index));
+    }
+    let nm = bits * m;
+    let (g_vec, h_vec) = vector_generators(nm);
+    let (h, g) = (generator_h(), S256Point::generator());
+
+    let commitments: Vec<Commitment> = values
+        .iter()
+        .zip(blindings)
+        .map(|(v, gamma)| super::pedersen::commit(&BigUint::from(*v), gamma))
+        .collect();
+    let mut transcript = start_transcript(bits, &commitments);
+
+    // the nonces depend on everything secret, so a repeated rand alone
+    // doesn't repeat them
+    let mut seed = rand.to_vec();
+    for (v, gamma) in values.iter().zip(blindings) {
+        seed.extend_from_slice(&v.to_be_bytes());
+        seed.extend_from_slice(&scalar::to_bytes(&(gamma % &order)));
+    }
+    let seed = tagged_hash("Bulletproofs/seed", &seed);
+    let nonce = |label: &str, index: usize| {
+        let mut preimage = seed.to_vec();
+        preimage.extend_from_slice(label.as_bytes());
+        preimage.extend_from_slice(&(index as u64).to_be_bytes());
+        scalar::from_bytes_mod_order(&tagged_hash("Bulletproofs/nonce", &preimage))
+    };
+
+    // a_L holds the bits of all values, a_R = a_L - 1 so that a_L o a_R = 0
+    let a_l: Vec<BigUint> = (0..nm).map(|i| BigUint::from((values[i / bits] >> (i % bits)) & 1)).collect();
+    let a_r: Vec<BigUint> = a_l.iter().map(|bit| (bit + &order - 1u8) % &order).collect();
+    let s_l: Vec<BigUint> = (0..nm).map(|i| nonce("s_L", i)).collect();
+    let s_r: Vec<BigUint> = (0..nm).map(|i| nonce("s_R", i)).collect();
+    let (alpha, rho) = (nonce("alpha", 0), nonce("rho", 0));
+
+    // A = alpha G + <a_L, G_i> + <a_R, H_i>, S the same for the blinding vectors
+    let vector_commit = |blinding: &BigUint, left: &[BigUint], right: &[BigUint]| {
+        let mut terms = vec![(blinding.clone(), g.clone())];
+        terms.extend(left.iter().cloned().zip(g_vec.iter().cloned()));
+        terms.extend(right.iter().cloned().zip(h_vec.iter().cloned()));
+        S256Point::multi_mul(&terms)
+    };
+    let a = vector_commit(&alpha, &a_l, &a_r);
+    let s = vector_commit(&rho, &s_l, &s_r);
+    transcript.append_point("A", &a);
+    transcript.append_point("S", &s);
+    let y = transcript.challenge_scalar("y");
+    let z = transcript.challenge_scalar("z");
+
+    // l(X) = a_L - z + s_L X
+    // r(X) = y^(nm) o (a_R + z + s_R X) + z^(j+2) 2^n for value j
+    let y_powers = powers(&y, nm);
+    let z_powers = powers(&z, m + 2);
+    let two_powers = powers(&BigUint::from(2u8), bits);
+    let l0: Vec<BigUint> = a_l.iter().map(|bit| (bit + scalar::negate(&z)) % &order).collect();
+    let l1 = s_l;
+    let r0: Vec<BigUint> = (0..nm)
+        .map(|i| (&y_powers[i] * (&a_r[i] + &z) + &z_powers[i / bits + 2] * &two_powers[i % bits]) % &order)
+        .collect();
+    let r1: Vec<BigUint> = (0..nm).map(|i| &y_powers[i] * &s_r[i] % &order).collect();
+
+    // t(X) = <l(X), r(X)> = t0 + t1 X + t2 X^2
+    let t1 = (inner_product(&l0, &r1) + inner_product(&l1, &r0)) % &order;
+    let t2 = inner_product(&l1, &r1);
+    let (tau1, tau2) = (nonce("tau1", 0), nonce("tau2", 0));
+    let t1_point = S256Point::multi_mul(&[(t1, h.clone()), (tau1.clone(), g.clone())]);
+    let t2_point = S256Point::multi_mul(&[(t2, h.clone()), (tau2.clone(), g.clone())]);
+    transcript.append_point("T1", &t1_point);
+    transcript.append_point("T2", &t2_point);
+    let x = transcript.challenge_scalar("x");
+
+    let t_x_blinding = blindings.iter().enumerate().fold(
+        (&tau2 * &x % &order * &x + &tau1 * &x) % &order,
+        |acc, (j, gamma)| (acc + &z_powers[j + 2] * gamma) % &order,
+    );
+    let e_blinding = (alpha + rho * &x) % &order;
+    let l_vec: Vec<BigUint> = (0..nm).map(|i| (&l0[i] + &l1[i] * &x) % &order).collect();
+    let r_vec: Vec<BigUint> = (0..nm).map(|i| (&r0[i] + &r1[i] * &x) % &order).collect();
+    let t_x = inner_product(&l_vec, &r_vec);
+    transcript.append_scalar("t_x", &t_x);
+    transcript.append_scalar("t_x_blinding", &t_x_blinding);
+    transcript.append_scalar("e_blinding", &e_blinding);
+
+    // the inner product argument proves <l, r> = t_x for the generators G_i
+    // and y^-i H_i, with w H standing in for Q
+    let w = transcript.challenge_scalar("w");
+    let q = h * w;
+    let y_inv_powers = powers(&scalar::inverse(&y), nm);
+    let ones = vec![BigUint::one(); nm];
+    let ipp = InnerProductProof::prove(&mut transcript, &q, &ones, &y_inv_powers, g_vec, h_vec, l_vec, r_vec);
+
+    let proof = RangeProof { a, s, t1: t1_point, t2: t2_point, t_x, t_x_blinding, e_blinding, ipp };
+    Ok((proof, commitments))
+}
+
+impl RangeProof {
+    // the size of the serialized proof in bytes
+    pub fn size(&self) -> usize {
+        (4 + 2 * self.ipp.get_l_vec().len()) * 33 + 5 * 32
+    }
+
+    // A || S || T1 || T2 || t_x || t_x_blinding || e_blinding || a || b ||
+    // L_0 || R_0 || L_1 || ... with points as compressed SEC
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.size());
+        for point in [&self.a, &self.s, &self.t1, &self.t2] {
+            result.extend(sec_ext(point));
+        }
+        for num in [&self.t_x, &self.t_x_blinding, &self.e_blinding, &self.ipp.get_a(), &self.ipp.get_b()] {
+            result.extend_from_slice(&scalar::to_bytes(num));
+        }
+        for (l, r) in self.ipp.get_l_vec().iter().zip(self.ipp.get_r_vec()) {
+            result.extend(sec_ext(l));
+            result.extend(sec_ext(&r));
+        }
+        result
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, BulletproofError> {
+        let fixed = 4 * 33 + 5 * 32;
+        if bytes.len() < fixed || !(bytes.len() - fixed).is_multiple_of(66) {
+            return Err(BulletproofError::InvalidProof);
+        }
+        let point = |i: usize| parse_sec_ext(&bytes[i * 33..(i + 1) * 33]).ok_or(BulletproofError::InvalidProof);
+        let num = |i: usize| {
+            let start = 4 * 33 + i * 32;
+            let num = BigUint::from_bytes_be(&bytes[start..start + 32]);
+            if num >= S256Point::order() {
+                return Err(BulletproofError::InvalidProof);
+            }
+            Ok(num)
+        };
+
+        let mut l_vec = Vec::new();
+        let mut r_vec = Vec::new();
+        for pair in bytes[fixed..].chunks(66) {
+            l_vec.push(parse_sec_ext(&pair[..33]).ok_or(BulletproofError::InvalidProof)?);
+            r_vec.push(parse_sec_ext(&pair[33..]).ok_or(BulletproofError::InvalidProof)?);
+        }
+        Ok(RangeProof {
+            a: point(0)?,
+            s: point(1)?,
+            t1: point(2)?,
+            t2: point(3)?,
+            t_x: num(0)?,
+            t_x_blinding: num(1)?,
+            e_blinding: num(2)?,
+            ipp: InnerProductProof::new(l_vec, r_vec, num(3)?, num(4)?),
+        })
+    }
+
+    // the terms this proof adds to a batch, each multiplied by weight
+    fn verification_terms(
+        &self,
+        commitments: &[Commitment],
+        bits: usize,
+        weight: &BigUint,
+    ) -> Option<VerificationTerms> {
+        let order = S256Point::order();
+        let m = commitments.len();
+        check_params(bits, m).ok()?;
+        let nm = bits * m;
+        if self.t_x >= order || self.t_x_blinding >= order || self.e_blinding >= order {
+            return None;
+        }
+
+        let mut transcript = start_transcript(bits, commitments);
+        transcript.append_point("A", &self.a);
+        transcript.append_point("S", &self.s);
+        let y = transcript.challenge_scalar("y");
+        let z = transcript.challenge_scalar("z");
+        transcript.append_point("T1", &self.t1);
+        transcript.append_point("T2", &self.t2);
+        let x = transcript.challenge_scalar("x");
+        transcript.append_scalar("t_x", &self.t_x);
+        transcript.append_scalar("t_x_blinding", &self.t_x_blinding);
+        transcript.append_scalar("e_blinding", &self.e_blinding);
+        let w = transcript.challenge_scalar("w");
+        let (u_sq, u_inv_sq, s) = self.ipp.verification_scalars(nm, &mut transcript)?;
+
+        // c ties the polynomial check t_x H + t_x_blinding G = sum z^(j+2) V_j
+        // + delta H + x T1 + x^2 T2 to the inner product check, so both are
+        // done in one multi-scalar multiplication. It comes from the
+        // transcript so the prover can't know it when making the proof.
+        let c = transcript.challenge_scalar("c");
+        let (a, b) = (self.ipp.get_a(), self.ipp.get_b());
+        let z_powers = powers(&z, m + 2);
+        let y_inv_powers = powers(&scalar::inverse(&y), nm);
+        let two_powers = powers(&BigUint::from(2u8), bits);
+        let weigh = |num: BigUint| num * weight % &order;
+
+        let mut terms = vec![
+            (weight.clone(), self.a.clone()),
+            (weigh(x.clone()), self.s.clone()),
+            (weigh(&c * &x % &order), self.t1.clone()),
+            (weigh(&c * &x % &order * &x), self.t2.clone()),
+        ];
+        for (j, commitment) in commitments.iter().enumerate() {
+            terms.push((weigh(&c * &z_powers[j + 2] % &order), commitment.get_point()));
+        }
+        let (l_vec, r_vec) = (self.ipp.get_l_vec(), self.ipp.get_r_vec());
+        for k in 0..u_sq.len() {
+            terms.push((weigh(u_sq[k].clone()), l_vec[k].clone()));
+            terms.push((weigh(u_inv_sq[k].clone()), r_vec[k].clone()));
+        }
+
+        let h_scalar = weigh(
+            (&w * (&self.t_x + scalar::negate(&(&a * &b))) + &c * (delta(bits, m, &y, &z) + scalar::negate(&self.t_x)))
+                % &order,
+        );
+        let g_scalar = weigh(scalar::negate(&(&self.e_blinding + &c * &self.t_x_blinding)));
+        let g_vec_scalars = s.iter().map(|s_i| weigh(scalar::negate(&(&z + &a * s_i)))).collect();
+        let h_vec_scalars = (0..nm)
+            .map(|i| {
+                let s_inv = scalar::inverse(&s[i]);
+                let inner = &z_powers[i / bits + 2] * &two_powers[i % bits] + scalar::negate(&(&b * s_inv));
+                weigh((&z + &y_inv_powers[i] * inner) % &order)
+            })
+            .collect();
+        Some((terms, h_scalar, g_scalar, g_vec_scalars, h_vec_scalars))
+    }
+}
+
+// Checks that every commitment hides a value in [0, 2^bits)
+pub fn verify(proof: &RangeProof, commitments: &[Commitment], bits: usize) -> bool {
+    verify_batch(&[(proof, commitments, bits)])
+}
+
+// Checks many proofs with a single multi-scalar multiplication. Each proof's
+// equation is weighted by a random-looking scalar derived from the whole
+// batch, so they can't cancel each other, and the shared generators are
+// only multiplied once. An empty batch is valid.
+pub fn verify_batch(batch: &[(&RangeProof, &[Commitment], usize)]) -> bool {
+    let order = S256Point::order();
+    let max_nm = batch.iter().map(|(_, commitments, bits)| bits * commitments.len()).max().unwrap_or(0);
+    let (g_vec, h_vec) = vector_generators(max_nm);
+
+    let mut seed = Vec::new();
+    for (proof, commitments, bits) in batch {
+        seed.extend(proof.serialize());
+        for commitment in commitments.iter() {
+            seed.extend(sec_ext(&commitment.get_point()));
+        }
+        seed.extend_from_slice(&(*bits as u64).to_be_bytes());
+    }
+    let seed = tagged_hash("Bulletproofs/batch", &seed);
+
+    let mut terms = Vec::new();
+    let mut h_scalar = BigUint::zero();
+    let mut g_scalar = BigUint::zero();
+    let mut g_vec_scalars = vec![BigUint::zero(); max_nm];
+    let mut h_vec_scalars = vec![BigUint::zero(); max_nm];
+    for (index, (proof, commitments, bits)) in batch.iter().enumerate() {
+        let weight = if index == 0 {
+            BigUint::one()
+        } else {
+            let mut preimage = seed.to_vec();
+            preimage.extend_from_slice(&(index as u64).to_be_bytes());
+            scalar::from_bytes_mod_order(&tagged_hash("Bulletproofs/batch", &preimage))
+        };
+        let (proof_terms, h_part, g_part, g_parts, h_parts) = match proof.verification_terms(commitments, *bits, &weight) {
+            Some(result) => result,
+            None => return false,
+        };
+        terms.extend(proof_terms);
+        h_scalar = (h_scalar + h_part) % &order;
+        g_scalar = (g_scalar + g_part) % &order;
+        for (i, (g_part, h_part)) in g_parts.into_iter().zip(h_parts).enumerate() {
+            g_vec_scalars[i] = (&g_vec_scalars[i] + g_part) % &order;
+            h_vec_scalars[i] = (&h_vec_scalars[i] + h_part) % &order;
+        }
+    }
+
+    terms.push((h_scalar, generator_h()));
+    terms.push((g_scalar, S256Point::generator()));
+    terms.extend(g_vec_scalars.into_iter().zip(g_vec));
+    terms.extend(h_vec_scalars.into_iter().zip(h_vec));
+    S256Point::multi_mul(&terms) == S256Point::infinity_point()
+}
+
+fn sec_ext(point: &S256Point) -> Vec<u8> {
+    if *point == S256Point::infinity_point() {
+        vec![0u8; 33]
+    } else {
+        point.sec(true)
+    }
+}
+
+fn parse_sec_ext(bytes: &[u8]) -> Option<S256Point> {
+    if bytes == [0u8; 33] {
+        return Some(S256Point::infinity_point());
+    }
+    S256Point::parse_sec(bytes).ok()
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn range_proof_works() {
+        let blinding = BigUint::from(0xb11du32);
+        for value in [0u64, 1, 200, 255] {
+            let (proof, commitments) = prove(&[value], std::slice::from_ref(&blinding), 8, &[1; 32]).unwrap();
+            assert!(commitments[0].verify_opening(&BigUint::from(value), &blinding));
+            assert!(verify(&proof, &commitments, 8));
+            assert!(!verify(&proof, &commitments, 16));
+            assert_eq!(RangeProof::parse(&proof.serialize()), Ok(proof));
+        }
+        assert_eq!(prove(&[256], &[blinding], 8, &[1; 32]), Err(BulletproofError::ValueOutOfRange(0)));
+    }
+
+    #[test]
+    fn range_proof_rejects_wrong_commitment() {
+        let (proof, commitments) = prove(&[5, 6], &[BigUint::from(1u8), BigUint::from(2u8)], 8, &[2; 32]).unwrap();
+        assert!(verify(&proof, &commitments, 8));
+
+        // the commitments swapped, and one moved by H
+        assert!(!verify(&proof, &[commitments[1].clone(), commitments[0].clone()], 8));
+        let moved = Commitment::from_point(commitments[0].get_point() + generator_h());
+        assert!(!verify(&proof, &[moved, commitments[1].clone()], 8));
+        assert!(!verify(&proof, &commitments[..1], 8));
+
+        let mut bytes = proof.serialize();
+        bytes[4 * 33 + 31] ^= 1;
+        assert!(!verify(&RangeProof::parse(&bytes).unwrap(), &commitments, 8));
+    }
+
+    // A value of 2^8 or more can't be proven honestly. This builds the proof
+    // for its low bits and swaps in the real commitment.
+    #[test]
+    fn range_proof_rejects_out_of_range_value() {
+        let blinding = BigUint::from(9u8);
+        let (proof, _) = prove(&[4], std::slice::from_ref(&blinding), 8, &[3; 32]).unwrap();
+        let commitment = super::super::pedersen::commit(&BigUint::from(260u32), &blinding);
+        assert!(!verify(&proof, &[commitment], 8));
+    }
+
+    #[test]
+    fn verify_batch_works() {
+        let (single, single_commitments) = prove(&[42], &[BigUint::from(7u8)], 16, &[4; 32]).unwrap();
+        let (pair, pair_commitments) = prove(&[1, 65535], &[BigUint::from(8u8), BigUint::from(9u8)], 16, &[5; 32]).unwrap();
+        let (small, small_commitments) = prove(&[3], &[BigUint::from(10u8)], 8, &[6; 32]).unwrap();
+
+        assert!(verify_batch(&[]));
+        assert!(verify_batch(&[
+            (&single, &single_commitments, 16),
+            (&pair, &pair_commitments, 16),
+            (&small, &small_commitments, 8),
+        ]));
+        assert!(!verify_batch(&[(&single, &single_commitments, 16), (&pair, &single_commitments, 16)]));
+    }
+
+    // Sizes and timings for full 64 bit proofs, one value and two aggregated.
+    // With 33 byte points the sizes are 688 and 754 bytes, against 672 and
+    // 736 for the 32 byte points of the paper and Ristretto implementations.
+    #[test]
+    fn range_proof_64_bit_report() {
+        for m in [1usize, 2] {
+            let values: Vec<u64> = (0..m as u64).map(|i| u64::MAX - i).collect();
+            let blindings: Vec<BigUint> = (0..m as u32).map(|i| BigUint::from(100 + i)).collect();
+
+            let start = Instant::now();
+            let (proof, commitments) = prove(&values, &blindings, 64, &[7; 32]).unwrap();
+            let prove_time = start.elapsed();
+            let start = Instant::now();
+            assert!(verify(&proof, &commitments, 64));
+            let verify_time = start.elapsed();
+
+            let expected = if m == 1 { 688 } else { 754 };
+            assert_eq!(proof.size(), expected);
+            assert_eq!(proof.serialize().len(), expected);
+            println!(
+                "64 bit range proof for {} value(s): {} bytes, proved in {:?}, verified in {:?}",
+                m, expected, prove_time, verify_time
+            );
+        }
+    }
+}
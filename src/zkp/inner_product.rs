@@ -0,0 +1,254 @@
+#![allow(unused)]
+
+use num::{BigUint, One, Zero};
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+
+use super::transcript::Transcript;
+
+// Proof that P = <a, G> + <b, H> + <a, b> Q for vectors a and b of length n,
+// using 2 log2(n) points instead of the 2n scalars of a and b. Each round
+// halves the vectors, and L and R carry the cross terms the verifier needs
+// to halve the statement along with them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InnerProductProof {
+    l_vec: Vec<S256Point>,
+    r_vec: Vec<S256Point>,
+    a: BigUint,
+    b: BigUint,
+}
+
+pub fn inner_product(a: &[BigUint], b: &[BigUint]) -> BigUint {
+    let n = S256Point::order();
+    a.iter().zip(b).fold(BigUint::zero(), |acc, (x, y)| (acc + x * y) % &n)
+}
+
+impl InnerProductProof {
+    pub fn new(l_vec: Vec<S256Point>, r_vec: Vec<S256Point>, a: BigUint, b: BigUint) -> Self {
+        Self { l_vec, r_vec, a, b }
+    }
+
+    pub fn get_l_vec(&self) -> Vec<S256Point> {
+        self.l_vec.clone()
+    }
+
+    pub fn get_r_vec(&self) -> Vec<S256Point> {
+        self.r_vec.clone()
+    }
+
+    pub fn get_a(&self) -> BigUint {
+        self.a.clone()
+    }
+
+    pub fn get_b(&self) -> BigUint {
+        self.b.clone()
+    }
+
+    // Proves the statement for the generators g_factors[i] G[i] and
+    // h_factors[i] H[i]. Taking the factors separately saves the range proof
+    // from multiplying out its H[i] y^-i up front: they are folded into the
+    // generators during the first round. n must be a power of two.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prove(
+        transcript: &mut Transcript,
+        q: &S256Point,
+        g_factors: &[BigUint],
+        h_factors: &[BigUint],
+        mut g: Vec<S256Point>,
+        mut h: Vec<S256Point>,
+        mut a: Vec<BigUint>,
+        mut b: Vec<BigUint>,
+    ) -> Self {
+        let order = S256Point::order();
+        let mut n = g.len();
+        if !n.is_power_of_two() || h.len() != n || a.len() != n || b.len() != n {
+            panic!("Vectors must have the same power of two length");
+        }
+        transcript.append_u64("n", n as u64);
+
+        let mut g_factors = g_factors.to_vec();
+        let mut h_factors = h_factors.to_vec();
+        let mut l_vec = Vec::new();
+        let mut r_vec = Vec::new();
+        while n > 1 {
+            n /= 2;
+            let (a_lo, a_hi) = a.split_at(n);
+            let (b_lo, b_hi) = b.split_at(n);
+            let (g_lo, g_hi) = g.split_at(n);
+            let (h_lo, h_hi) = h.split_at(n);
+
+            // L = <a_lo, G_hi> + <b_hi, H_lo> + <a_lo, b_hi> Q, R the mirror image
+            let cross = |a_part: &[BigUint], g_part: &[S256Point], g_offset: usize, b_part: &[BigUint], h_part: &[S256Point], h_offset: usize| {
+                let mut terms = Vec::with_capacity(2 * n + 1);
+                for i in 0..n {
+                    terms.push((&a_part[i] * &g_factors[g_offset + i] % &order, g_part[i].clone()));
+                    terms.push((&b_part[i] * &h_factors[h_offset + i] % &order, h_part[i].clone()));
+                }
+                terms.push((inner_product(a_part, b_part), q.clone()));
+                S256Point::multi_mul(&terms)
+            };
+            let l = cross(a_lo, g_hi, n, b_hi, h_lo, 0);
+            let r = cross(a_hi, g_lo, 0, b_lo, h_hi, n);
+            transcript.append_point("L", &l);
+            transcript.append_point("R", &r);
+            l_vec.push(l);
+            r_vec.push(r);
+
+            let u = transcript.challenge_scalar("u");
+            let u_inv = scalar::inverse(&u);
+
+            // a' = u a_lo + u^-1 a_hi, b' = u^-1 b_lo + u b_hi and the
+            // generators the other way around, so <a', G'> keeps its form
+            let mut next_a = Vec::with_capacity(n);
+            let mut next_b = Vec::with_capacity(n);
+            let mut next_g = Vec::with_capacity(n);
+            let mut next_h = Vec::with_capacity(n);
+            for i in 0..n {
+                next_a.push((&a_lo[i] * &u + &a_hi[i] * &u_inv) % &order);
+                next_b.push((&b_lo[i] * &u_inv + &b_hi[i] * &u) % &order);
+                next_g.push(S256Point::multi_mul(&[
+                    (&u_inv * &g_factors[i] % &order, g_lo[i].clone()),
+                    (&u * &g_factors[n + i] % &order, g_hi[i].clone()),
+                ]));
+                next_h.push(S256Point::multi_mul(&[
+                    (&u * &h_factors[i] % &order, h_lo[i].clone()),
+                    (&u_inv * &h_factors[n + i] % &order, h_hi[i].clone()),
+                ]));
+            }
+            a = next_a;
+            b = next_b;
+            g = next_g;
+            h = next_h;
+            g_factors = vec![BigUint::one(); n];
+            h_factors = vec![BigUint::one(); n];
+        }
+
+        Self { l_vec, r_vec, a: a.remove(0), b: b.remove(0) }
+    }
+
+    // Replays the transcript and returns the challenges u_k^2, u_k^-2 and the
+    // weights s_i with which G_i ends up in the final generator (H_i gets
+    // s_i^-1). None if the proof doesn't fit n.
+    pub fn verification_scalars(
+        &self,
+        n: usize,
+        transcript: &mut Transcript,
+    ) -> Option<(Vec<BigUint>, Vec<BigUint>, Vec<BigUint>)> {
+        let order = S256Point::order();
+        let rounds = self.l_vec.len();
+        if !n.is_power_of_two() || n != 1 << rounds || self.r_vec.len() != rounds {
+            return None;
+        }
+        if self.a >= order || self.b >= order {
+            return None;
+        }
+        transcript.append_u64("n", n as u64);
+
+        let mut challenges = Vec::with_capacity(rounds);
+        for (l, r) in self.l_vec.iter().zip(&self.r_vec) {
+            transcript.append_point("L", l);
+            transcript.append_point("R", r);
+            challenges.push(transcript.challenge_scalar("u"));
+        }
+        let inverses: Vec<BigUint> = challenges.iter().map(scalar::inverse).collect();
+        let u_sq: Vec<BigUint> = challenges.iter().map(|u| u * u % &order).collect();
+        let u_inv_sq: Vec<BigUint> = inverses.iter().map(|u| u * u % &order).collect();
+
+        // G_lo is multiplied by u^-1 and G_hi by u in each round, and which
+        // half G_i lands in is given by its bits, highest first
+        let s = (0..n)
+            .map(|i| {
+                (0..rounds).fold(BigUint::one(), |acc, k| {
+                    let factor = if (i >> (rounds - 1 - k)) & 1 == 1 { &challenges[k] } else { &inverses[k] };
+                    acc * factor % &order
+                })
+            })
+            .collect();
+        Some((u_sq, u_inv_sq, s))
+    }
+
+    // P + sum(u_k^2 L_k + u_k^-2 R_k) = a sum(s_i G_i) + b sum(s_i^-1 H_i) + ab Q
+    pub fn verify(
+        &self,
+        transcript: &mut Transcript,
+        p: &S256Point,
+        q: &S256Point,
+        g: &[S256Point],
+        h: &[S256Point],
+    ) -> bool {
+        let order = S256Point::order();
+        let (u_sq, u_inv_sq, s) = match self.verification_scalars(g.len(), transcript) {
+            Some(scalars) => scalars,
+            None => return false,
+        };
+        if h.len() != g.len() {
+            return false;
+        }
+
+        let mut terms = vec![(BigUint::one(), p.clone())];
+        for k in 0..u_sq.len() {
+            terms.push((u_sq[k].clone(), self.l_vec[k].clone()));
+            terms.push((u_inv_sq[k].clone(), self.r_vec[k].clone()));
+        }
+        for i in 0..g.len() {
+            terms.push((scalar::negate(&(&self.a * &s[i])), g[i].clone()));
+            terms.push((scalar::negate(&(&self.b * scalar::inverse(&s[i]))), h[i].clone()));
+        }
+        terms.push((scalar::negate(&(&self.a * &self.b)), q.clone()));
+        S256Point::multi_mul(&terms) == S256Point::infinity_point()
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::zkp::pedersen::nums_generator;
+
+    use super::*;
+
+    #[test]
+    fn inner_product_proof_works() {
+        let n = 8;
+        let g: Vec<S256Point> = (0..n).map(|i| nums_generator("test/G", i)).collect();
+        let h: Vec<S256Point> = (0..n).map(|i| nums_generator("test/H", i)).collect();
+        let q = nums_generator("test/Q", 0);
+        let a: Vec<BigUint> = (0..n).map(|i| BigUint::from(i * 3 + 1)).collect();
+        let b: Vec<BigUint> = (0..n).map(|i| BigUint::from(i * 5 + 2)).collect();
+
+        let mut terms: Vec<(BigUint, S256Point)> =
+            a.iter().cloned().zip(g.clone()).chain(b.iter().cloned().zip(h.clone())).collect();
+        terms.push((inner_product(&a, &b), q.clone()));
+        let p = S256Point::multi_mul(&terms);
+
+        let ones = vec![BigUint::one(); n as usize];
+        let proof = InnerProductProof::prove(
+            &mut Transcript::new("test"), &q, &ones, &ones, g.clone(), h.clone(), a.clone(), b.clone(),
+        );
+        assert_eq!(proof.get_l_vec().len(), 3);
+        assert!(proof.verify(&mut Transcript::new("test"), &p, &q, &g, &h));
+        assert!(!proof.verify(&mut Transcript::new("other"), &p, &q, &g, &h));
+        assert!(!proof.verify(&mut Transcript::new("test"), &(p.clone() + q.clone()), &q, &g, &h));
+    }
+
+    #[test]
+    fn inner_product_proof_applies_factors() {
+        let n = 4;
+        let g: Vec<S256Point> = (0..n).map(|i| nums_generator("test/G", i)).collect();
+        let h: Vec<S256Point> = (0..n).map(|i| nums_generator("test/H", i)).collect();
+        let q = nums_generator("test/Q", 0);
+        let a: Vec<BigUint> = (1..=n).map(BigUint::from).collect();
+        let b: Vec<BigUint> = (5..5 + n).map(BigUint::from).collect();
+        let factors: Vec<BigUint> = (2..2 + n).map(BigUint::from).collect();
+        let ones = vec![BigUint::one(); n as usize];
+
+        // the statement is about h_factors[i] H[i]
+        let scaled_h: Vec<S256Point> = h.iter().zip(&factors).map(|(point, f)| point.clone() * f.clone()).collect();
+        let mut terms: Vec<(BigUint, S256Point)> =
+            a.iter().cloned().zip(g.clone()).chain(b.iter().cloned().zip(scaled_h.clone())).collect();
+        terms.push((inner_product(&a, &b), q.clone()));
+        let p = S256Point::multi_mul(&terms);
+
+        let proof = InnerProductProof::prove(&mut Transcript::new("test"), &q, &ones, &factors, g.clone(), h, a, b);
+        assert!(proof.verify(&mut Transcript::new("test"), &p, &q, &g, &scaled_h));
+    }
+}
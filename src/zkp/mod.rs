@@ -0,0 +1,5 @@
+pub mod pedersen;
+pub mod transcript;
+pub mod inner_product;
+pub mod bulletproofs;
+pub mod sigma;
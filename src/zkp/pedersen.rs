@@ -0,0 +1,125 @@
+#![allow(unused)]
+
+use std::ops::{Add, Neg, Sub};
+use num::BigUint;
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+use crate::hashes::sha256::{sha256, tagged_hash};
+
+// H = lift_x(sha256(uncompressed G)). Nobody knows log_G(H), which is what
+// keeps a committer from opening a commitment to two different values. It
+// is the same point BIP341 uses as its unspendable internal key.
+pub fn generator_h() -> S256Point {
+    let x = BigUint::from_bytes_be(&sha256(&S256Point::generator().sec(false)));
+    S256Point::lift_x(&x).expect("sha256(G) is on the curve")
+}
+
+// A further nothing-up-my-sleeve generator for the given tag and index, by
+// hashing to an x coordinate until one is on the curve
+pub fn nums_generator(tag: &str, index: u32) -> S256Point {
+    let mut counter = 0u32;
+    loop {
+        let mut preimage = index.to_be_bytes().to_vec();
+        preimage.extend_from_slice(&counter.to_be_bytes());
+        if let Some(point) = S256Point::lift_x(&BigUint::from_bytes_be(&tagged_hash(tag, &preimage))) {
+            return point;
+        }
+        counter += 1;
+    }
+}
+
+// vH + rG for a value v and a blinding factor r. It hides v as long as r is
+// random, and binds the committer to v as long as log_G(H) is unknown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitment {
+    point: S256Point,
+}
+
+impl Commitment {
+    pub fn from_point(point: S256Point) -> Self {
+        Self { point }
+    }
+
+    pub fn get_point(&self) -> S256Point {
+        self.point.clone()
+    }
+
+    // opening the commitment means revealing both v and r
+    pub fn verify_opening(&self, value: &BigUint, blinding: &BigUint) -> bool {
+        commit(value, blinding) == *self
+    }
+}
+
+pub fn commit(value: &BigUint, blinding: &BigUint) -> Commitment {
+    let n = S256Point::order();
+    let point = S256Point::multi_mul(&[(value % &n, generator_h()), (blinding % &n, S256Point::generator())]);
+    Commitment { point }
+}
+
+// The commitments add up like the values they hide: commit(a, r) + commit(b, s)
+// = commit(a + b, r + s). That lets anyone check that inputs and outputs
+// balance without seeing a single amount.
+impl Add for Commitment {
+    type Output = Commitment;
+
+    fn add(self, other: Commitment) -> Commitment {
+        Commitment { point: self.point + other.point }
+    }
+}
+
+impl Sub for Commitment {
+    type Output = Commitment;
+
+    fn sub(self, other: Commitment) -> Commitment {
+        Commitment { point: self.point - other.point }
+    }
+}
+
+impl Neg for Commitment {
+    type Output = Commitment;
+
+    fn neg(self) -> Commitment {
+        Commitment { point: -self.point }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn generator_h_works() {
+        let h = generator_h();
+        assert_eq!(
+            hex::encode(h.get_x().unwrap().to_bytes()),
+            "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0"
+        );
+        assert_ne!(nums_generator("test", 0), nums_generator("test", 1));
+        assert_ne!(nums_generator("test", 0), h);
+    }
+
+    #[test]
+    fn verify_opening_works() {
+        let c = commit(&BigUint::from(1000u32), &BigUint::from(0xb11du32));
+        assert!(c.verify_opening(&BigUint::from(1000u32), &BigUint::from(0xb11du32)));
+        assert!(!c.verify_opening(&BigUint::from(1001u32), &BigUint::from(0xb11du32)));
+        assert!(!c.verify_opening(&BigUint::from(1000u32), &BigUint::from(0xb11eu32)));
+    }
+
+    // 30 + 12 in, 40 + 2 out: the difference commits to zero with the
+    // leftover blinding factor, which the sender can sign for
+    #[test]
+    fn homomorphism_works() {
+        let v = |value: u32| BigUint::from(value);
+        let inputs = commit(&v(30), &v(111)) + commit(&v(12), &v(222));
+        let outputs = commit(&v(40), &v(300)) + commit(&v(2), &v(3));
+        assert!((inputs.clone() - outputs.clone()).verify_opening(&v(0), &v(30)));
+        assert_eq!(inputs.clone() - outputs.clone(), -(outputs - inputs));
+
+        // negative amounts wrap around mod n
+        let diff = commit(&v(5), &v(5)) - commit(&v(7), &v(6));
+        let n = S256Point::order();
+        assert!(diff.verify_opening(&(&n - 2u8), &(&n - 1u8)));
+    }
+}
@@ -0,0 +1,335 @@
+#![allow(unused)]
+
+use std::fmt;
+use num::{BigUint, Zero};
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+
+use super::transcript::Transcript;
+
+// Sigma protocols made non-interactive with a transcript. Each proof is a
+// commitment R, a challenge c and a response s with sG = R + cP. Proofs are
+// stored as (c, s): R can be recomputed from them, and that form is what
+// OR-composition needs. The statement is appended to the transcript before
+// hashing, so a proof only verifies for the same statement and context.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigmaError {
+    // the proof has the wrong length
+    InvalidLength(usize),
+    // a challenge or response is not below n
+    InvalidScalar,
+    // the index of the known secret is out of range
+    InvalidIndex(usize),
+    // the secret doesn't belong to the statement
+    WrongSecret,
+}
+
+impl fmt::Display for SigmaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigmaError::InvalidLength(len) => write!(f, "invalid proof length {}", len),
+            SigmaError::InvalidScalar => write!(f, "scalar is not below the curve order"),
+            SigmaError::InvalidIndex(index) => write!(f, "invalid index {}", index),
+            SigmaError::WrongSecret => write!(f, "secret doesn't match the statement"),
+        }
+    }
+}
+
+impl std::error::Error for SigmaError {}
+
+// s B - c P, the commitment a verifier recomputes
+fn commitment(base: &S256Point, point: &S256Point, c: &BigUint, s: &BigUint) -> S256Point {
+    S256Point::multi_mul(&[(s.clone(), base.clone()), (scalar::negate(c), point.clone())])
+}
+
+fn parse_scalars(bytes: &[u8]) -> Result<Vec<BigUint>, SigmaError> {
+    let nums: Vec<BigUint> = bytes.chunks(32).map(BigUint::from_bytes_be).collect();
+    if nums.iter().any(|num| *num >= S256Point::order()) {
+        return Err(SigmaError::InvalidScalar);
+    }
+    Ok(nums)
+}
+
+// Knowledge of x with P = xB, the Schnorr identification protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DlogProof {
+    c: BigUint,
+    s: BigUint,
+}
+
+impl DlogProof {
+    pub fn prove(transcript: &mut Transcript, base: &S256Point, secret: &BigUint, rand: &[u8; 32]) -> Self {
+        let point = base.clone() * secret.clone();
+        transcript.append_message("protocol", b"dlog");
+        transcript.append_point("B", base);
+        transcript.append_point("P", &point);
+        let k = transcript.witness_scalar("k", secret, rand);
+        let r = base.clone() * k.clone();
+        transcript.append_point("R", &r);
+        let c = transcript.challenge_scalar("c");
+        let s = (k + &c * secret) % S256Point::order();
+        Self { c, s }
+    }
+
+    pub fn verify(&self, transcript: &mut Transcript, base: &S256Point, point: &S256Point) -> bool {
+        let r = commitment(base, point, &self.c, &self.s);
+        transcript.append_message("protocol", b"dlog");
+        transcript.append_point("B", base);
+        transcript.append_point("P", point);
+        transcript.append_point("R", &r);
+        transcript.challenge_scalar("c") == self.c
+    }
+
+    // c || s
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut result = [0u8; 64];
+        result[..32].copy_from_slice(&scalar::to_bytes(&self.c));
+        result[32..].copy_from_slice(&scalar::to_bytes(&self.s));
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SigmaError> {
+        if bytes.len() != 64 {
+            return Err(SigmaError::InvalidLength(bytes.len()));
+        }
+        let mut nums = parse_scalars(bytes)?;
+        let s = nums.pop().unwrap();
+        Ok(Self { c: nums.pop().unwrap(), s })
+    }
+}
+
+// The same x in P1 = x B1 and P2 = x B2 (Chaum-Pedersen). Both commitments use
+// one nonce and one response, so the response only works for both at once.
+// It shows a blind signer used their real key, or that an ECDH result was
+// computed with the key behind a public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DleqProof {
+    c: BigUint,
+    s: BigUint,
+}
+
+impl DleqProof {
+    pub fn prove(
+        transcript: &mut Transcript,
+        base1: &S256Point,
+        base2: &S256Point,
+        secret: &BigUint,
+        rand: &[u8; 32],
+    ) -> Self {
+        let (point1, point2) = (base1.clone() * secret.clone(), base2.clone() * secret.clone());
+        append_dleq_statement(transcript, [base1, &point1, base2, &point2]);
+        let k = transcript.witness_scalar("k", secret, rand);
+        let (r1, r2) = (base1.clone() * k.clone(), base2.clone() * k.clone());
+        transcript.append_point("R1", &r1);
+        transcript.append_point("R2", &r2);
+        let c = transcript.challenge_scalar("c");
+        let s = (k + &c * secret) % S256Point::order();
+        Self { c, s }
+    }
+
+    pub fn verify(
+        &self,
+        transcript: &mut Transcript,
+        base1: &S256Point,
+        point1: &S256Point,
+        base2: &S256Point,
+        point2: &S256Point,
+    ) -> bool {
+        let r1 = commitment(base1, point1, &self.c, &self.s);
+        let r2 = commitment(base2, point2, &self.c, &self.s);
+        append_dleq_statement(transcript, [base1, point1, base2, point2]);
+        transcript.append_point("R1", &r1);
+        transcript.append_point("R2", &r2);
+        transcript.challenge_scalar("c") == self.c
+    }
+
+    // c || s
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut result = [0u8; 64];
+        result[..32].copy_from_slice(&scalar::to_bytes(&self.c));
+        result[32..].copy_from_slice(&scalar::to_bytes(&self.s));
+        result
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SigmaError> {
+        if bytes.len() != 64 {
+            return Err(SigmaError::InvalidLength(bytes.len()));
+        }
+        let mut nums = parse_scalars(bytes)?;
+        let s = nums.pop().unwrap();
+        Ok(Self { c: nums.pop().unwrap(), s })
+    }
+}
+
+fn append_dleq_statement(transcript: &mut Transcript, points: [&S256Point; 4]) {
+    transcript.append_message("protocol", b"dleq");
+    for (label, point) in ["B1", "P1", "B2", "P2"].iter().zip(points) {
+        transcript.append_point(label, point);
+    }
+}
+
+// Knowledge of the discrete log of one of P_1..P_k (base B) without saying
+// which (Cramer, Damgård and Schoenmakers). The prover simulates a proof for
+// every other key by picking its challenge and response first, and the
+// challenges must add up to the one from the transcript, which leaves
+// exactly one of them free to answer honestly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrProof {
+    challenges: Vec<BigUint>,
+    responses: Vec<BigUint>,
+}
+
+impl OrProof {
+    pub fn prove(
+        transcript: &mut Transcript,
+        base: &S256Point,
+        points: &[S256Point],
+        index: usize,
+        secret: &BigUint,
+        rand: &[u8; 32],
+    ) -> Result<Self, SigmaError> {
+        let n = S256Point::order();
+        if index >= points.len() {
+            return Err(SigmaError::InvalidIndex(index));
+        }
+        if base.clone() * secret.clone() != points[index] {
+            return Err(SigmaError::WrongSecret);
+        }
+
+        append_or_statement(transcript, base, points);
+        let k = transcript.witness_scalar("k", secret, rand);
+        let mut challenges = vec![BigUint::zero(); points.len()];
+        let mut responses = vec![BigUint::zero(); points.len()];
+        let mut commitments = Vec::with_capacity(points.len());
+        for (j, point) in points.iter().enumerate() {
+            if j == index {
+                commitments.push(base.clone() * k.clone());
+            } else {
+                challenges[j] = transcript.witness_scalar(&format!("c{}", j), secret, rand);
+                responses[j] = transcript.witness_scalar(&format!("s{}", j), secret, rand);
+                commitments.push(commitment(base, point, &challenges[j], &responses[j]));
+            }
+        }
+
+        for r in &commitments {
+            transcript.append_point("R", r);
+        }
+        let c = transcript.challenge_scalar("c");
+        let others = challenges.iter().fold(BigUint::zero(), |acc, c_j| (acc + c_j) % &n);
+        challenges[index] = (c + scalar::negate(&others)) % &n;
+        responses[index] = (k + &challenges[index] * secret) % &n;
+        Ok(Self { challenges, responses })
+    }
+
+    pub fn verify(&self, transcript: &mut Transcript, base: &S256Point, points: &[S256Point]) -> bool {
+        let n = S256Point::order();
+        if self.challenges.len() != points.len() || self.responses.len() != points.len() {
+            return false;
+        }
+        let commitments: Vec<S256Point> = points
+            .iter()
+            .zip(self.challenges.iter().zip(&self.responses))
+            .map(|(point, (c, s))| commitment(base, point, c, s))
+            .collect();
+
+        append_or_statement(transcript, base, points);
+        for r in &commitments {
+            transcript.append_point("R", r);
+        }
+        let sum = self.challenges.iter().fold(BigUint::zero(), |acc, c_j| (acc + c_j) % &n);
+        transcript.challenge_scalar("c") == sum
+    }
+
+    // c_1 || s_1 || c_2 || s_2 || ...
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.challenges
+            .iter()
+            .zip(&self.responses)
+            .flat_map(|(c, s)| [scalar::to_bytes(c), scalar::to_bytes(s)].concat())
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SigmaError> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(64) {
+            return Err(SigmaError::InvalidLength(bytes.len()));
+        }
+        let nums = parse_scalars(bytes)?;
+        let challenges = nums.iter().step_by(2).cloned().collect();
+        let responses = nums.iter().skip(1).step_by(2).cloned().collect();
+        Ok(Self { challenges, responses })
+    }
+}
+
+fn append_or_statement(transcript: &mut Transcript, base: &S256Point, points: &[S256Point]) {
+    transcript.append_message("protocol", b"or");
+    transcript.append_point("B", base);
+    transcript.append_u64("k", points.len() as u64);
+    for point in points {
+        transcript.append_point("P", point);
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use crate::finite_field::private_key::PrivateKey;
+
+    use super::*;
+
+    #[test]
+    fn dlog_proof_works() {
+        let g = S256Point::generator();
+        let x = BigUint::from(0x5ec2e7u64);
+        let p = g.clone() * x.clone();
+
+        let proof = DlogProof::prove(&mut Transcript::new("test"), &g, &x, &[1; 32]);
+        assert!(proof.verify(&mut Transcript::new("test"), &g, &p));
+        assert!(!proof.verify(&mut Transcript::new("other"), &g, &p));
+        assert!(!proof.verify(&mut Transcript::new("test"), &g, &(p.clone() + g.clone())));
+        assert_eq!(DlogProof::from_bytes(&proof.to_bytes()), Ok(proof));
+        assert_eq!(DlogProof::from_bytes(&[0xff; 64]), Err(SigmaError::InvalidScalar));
+    }
+
+    // Alice shows the ECDH secret she hands out was computed with the key
+    // behind her public key: log_G(A) = log_B(S)
+    #[test]
+    fn dleq_proves_ecdh() {
+        let g = S256Point::generator();
+        let alice = PrivateKey::new(BigUint::from(0xa11ceu64));
+        let bob = PrivateKey::new(BigUint::from(0xb0bu64));
+        let shared = bob.get_point() * alice.get_secret();
+
+        let proof = DleqProof::prove(&mut Transcript::new("ecdh"), &g, &bob.get_point(), &alice.get_secret(), &[2; 32]);
+        assert!(proof.verify(&mut Transcript::new("ecdh"), &g, &alice.get_point(), &bob.get_point(), &shared));
+
+        // a different point in place of the real shared secret
+        let wrong = shared.clone() + g.clone();
+        assert!(!proof.verify(&mut Transcript::new("ecdh"), &g, &alice.get_point(), &bob.get_point(), &wrong));
+        assert_eq!(DleqProof::from_bytes(&proof.to_bytes()), Ok(proof));
+    }
+
+    #[test]
+    fn or_proof_works() {
+        let g = S256Point::generator();
+        let x = BigUint::from(77u8);
+        let points = vec![
+            g.clone() * BigUint::from(1000u32),
+            g.clone() * x.clone(),
+            g.clone() * BigUint::from(2000u32),
+        ];
+
+        let proof = OrProof::prove(&mut Transcript::new("test"), &g, &points, 1, &x, &[3; 32]).unwrap();
+        assert!(proof.verify(&mut Transcript::new("test"), &g, &points));
+        assert!(!proof.verify(&mut Transcript::new("test"), &g, &points[..2]));
+
+        // the proof doesn't carry over to keys the prover knows nothing about
+        let mut others = points.clone();
+        others[1] = g.clone() * BigUint::from(3000u32);
+        assert!(!proof.verify(&mut Transcript::new("test"), &g, &others));
+
+        assert_eq!(OrProof::from_bytes(&proof.to_bytes()), Ok(proof));
+        assert_eq!(OrProof::prove(&mut Transcript::new("test"), &g, &points, 0, &x, &[3; 32]), Err(SigmaError::WrongSecret));
+        assert_eq!(OrProof::prove(&mut Transcript::new("test"), &g, &points, 3, &x, &[3; 32]), Err(SigmaError::InvalidIndex(3)));
+    }
+}
@@ -0,0 +1,107 @@
+#![allow(unused)]
+
+use num::BigUint;
+
+use crate::finite_field::{scalar, secp_ec::S256Point};
+use crate::hashes::sha256::tagged_hash;
+
+// A Fiat-Shamir transcript: the prover and the verifier feed it the same
+// public data in the same order, and challenges are hashes of everything
+// that came before. That turns an interactive proof into a non-interactive
+// one, as long as every message the verifier would see is appended before
+// the challenge that depends on it. Labels keep different fields, and
+// different protocols, from ever hashing the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    state: [u8; 32],
+}
+
+impl Transcript {
+    // label names the protocol, so proofs for one can't be replayed as another
+    pub fn new(label: &str) -> Self {
+        Self { state: tagged_hash("Transcript/init", label.as_bytes()) }
+    }
+
+    // state = H(state || len(label) || label || len(message) || message)
+    pub fn append_message(&mut self, label: &str, message: &[u8]) {
+        let mut preimage = self.state.to_vec();
+        preimage.extend_from_slice(&(label.len() as u64).to_be_bytes());
+        preimage.extend_from_slice(label.as_bytes());
+        preimage.extend_from_slice(&(message.len() as u64).to_be_bytes());
+        preimage.extend_from_slice(message);
+        self.state = tagged_hash("Transcript/append", &preimage);
+    }
+
+    pub fn append_u64(&mut self, label: &str, value: u64) {
+        self.append_message(label, &value.to_be_bytes());
+    }
+
+    // compressed SEC, or 33 zero bytes for the point at infinity
+    pub fn append_point(&mut self, label: &str, point: &S256Point) {
+        if *point == S256Point::infinity_point() {
+            self.append_message(label, &[0u8; 33]);
+        } else {
+            self.append_message(label, &point.sec(true));
+        }
+    }
+
+    pub fn append_scalar(&mut self, label: &str, num: &BigUint) {
+        self.append_message(label, &scalar::to_bytes(num));
+    }
+
+    // A challenge mod n. It is appended to the transcript itself, so asking
+    // twice in a row gives two different challenges.
+    pub fn challenge_scalar(&mut self, label: &str) -> BigUint {
+        let mut preimage = self.state.to_vec();
+        preimage.extend_from_slice(label.as_bytes());
+        let challenge = scalar::from_bytes_mod_order(&tagged_hash("Transcript/challenge", &preimage));
+        self.append_scalar(label, &challenge);
+        challenge
+    }
+
+    // A prover's secret nonce bound to everything in the transcript so far,
+    // so the same rand in a different context still gives a different nonce.
+    // Doesn't change the transcript.
+    pub fn witness_scalar(&self, label: &str, secret: &BigUint, rand: &[u8; 32]) -> BigUint {
+        let mut preimage = self.state.to_vec();
+        preimage.extend_from_slice(&(label.len() as u64).to_be_bytes());
+        preimage.extend_from_slice(label.as_bytes());
+        preimage.extend_from_slice(&scalar::to_bytes(secret));
+        preimage.extend_from_slice(rand);
+        scalar::from_bytes_mod_order(&tagged_hash("Transcript/witness", &preimage))
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn transcript_works() {
+        let mut a = Transcript::new("test");
+        let mut b = Transcript::new("test");
+        a.append_point("P", &S256Point::generator());
+        b.append_point("P", &S256Point::generator());
+        assert_eq!(a.challenge_scalar("c"), b.challenge_scalar("c"));
+        assert_ne!(a.challenge_scalar("c"), a.challenge_scalar("c"));
+
+        // the label, the protocol and how bytes are split up all matter
+        let challenge = |protocol: &str, label: &str, parts: &[&[u8]]| {
+            let mut t = Transcript::new(protocol);
+            for part in parts {
+                t.append_message(label, part);
+            }
+            t.challenge_scalar("c")
+        };
+        let base = challenge("test", "m", &[b"ab"]);
+        assert_ne!(base, challenge("other", "m", &[b"ab"]));
+        assert_ne!(base, challenge("test", "n", &[b"ab"]));
+        assert_ne!(base, challenge("test", "m", &[b"a", b"b"]));
+
+        let secret = BigUint::from(1u8);
+        let nonce = a.witness_scalar("k", &secret, &[0; 32]);
+        assert_eq!(nonce, a.witness_scalar("k", &secret, &[0; 32]));
+        assert_ne!(nonce, b.witness_scalar("k", &secret, &[0; 32]));
+    }
+}